@@ -1,19 +1,131 @@
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use arboard::Clipboard;
 use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
 use ratatui::{
-    layout::{Constraint, Direction, Layout},
+    layout::{Constraint, Direction, Layout, Rect},
     Frame,
 };
 
-use crate::chess::Game;
+use rhai::Dynamic;
+use serde_json::{json, Value};
+use shakmaty::{
+    fen::Fen, ByColor, CastlingMode, Chess, Color, EnPassantMode, Move, Position, Square,
+};
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crate::chess::{
+    check_tag, chess960_fen, random_chess960, split_games, Arrow, Eval, Game, MarkColor, MoveKind,
+    TimeControl, CHESS960_POSITIONS, SEVEN_TAG_ROSTER,
+};
 use crate::config::Config;
-use crate::engine::{Engine, EngineEvent};
+use crate::control::error_reply;
+use crate::drill::{find_drill, DrillEnd, DrillRecord, DrillSession, DRILLS};
+use crate::engine::{
+    format_cp, format_score, score_to_cp, AnalysisInfo, BestMove, Engine, EngineEvent, StaticEval,
+    MATE_SCORE,
+};
+use crate::events::Waker;
+use crate::explorer::{ExplorerSource, OpeningTree, PositionStats};
+use crate::files::{self, PgnFile};
+use crate::guess::{
+    guess_points, GuessPhase, GuessResult, GuessSession, GUESS_DEPTH, MATCH_POINTS,
+};
+use crate::library::LIBRARY;
+use crate::mates::{pattern_name, MatePhase, MateSession, MATE_DEPTH, MATE_PATTERNS};
+use crate::notify;
+use crate::online::{self, Answers, CloudMove, Outcome, Query, TablebaseEntry};
+use crate::openings;
+use crate::play::{PlaySession, SparringRecord};
+use crate::puzzle::{
+    sample_puzzles, Puzzle, PuzzleFilter, PuzzleRecord, PuzzleSession, PuzzleState, Rush,
+    PUZZLE_BATCH, RUSH_MAX_RATING, RUSH_MINUTES, RUSH_MIN_RATING, RUSH_PUZZLES, RUSH_STRIKES,
+};
+use crate::quiz::{Quiz, QuizItem, QuizPhase, QUIZ_DEPTH};
+use crate::repertoire::{Deviation, Repertoire};
+use crate::review::{
+    blunders, critical_moments, game_evals, summarize, GameSummary, MoveClass, Review, REVIEW_DEPTH,
+};
+use crate::script::{analysis_map, Script, HOOKS};
+use crate::sound::{self, SoundEvent};
+use crate::state;
+use crate::transpositions::{self, Arrivals};
 use crate::ui::{
-    AnalysisState, AnalysisWidget, BoardWidget, DepthPopup, HelpBarWidget, HelpPopup, ImportPopup,
-    InputMode, InputState, InputWidget, MovesWidget, MultiPVPopup, StatusWidget,
+    board_gif, board_svg, format_pv, iterm2_sequence, kitty_delete_sequence, kitty_sequence,
+    sixel_sequence, uci_to_san, AnalysisState, AnalysisWidget, BoardCache, BoardImage,
+    BoardRenderer, BoardSquares, BoardTextPopup, BoardWidget, ClockWidget, ColorDepth, DepthPopup,
+    DrillWidget, EnginePopup, EvalGraphWidget, ExplorerWidget, GamesPopup, GuessWidget,
+    HeadersPopup, HelpBarWidget, HelpPopup, ImportPopup, InputMode, InputState, InputWidget,
+    LibraryPopup, MateWidget, MovesWidget, MultiPVPopup, Panel, PanelLayout, PuzzleWidget,
+    QuizWidget, ReplayOverlay, RushWidget, ScreenLayout, StaticEvalPopup, StatusWidget,
+    StrengthPopup, SummaryPopup, TooSmallWidget, UciLogWidget,
 };
 
+/// Depth cap for the threat (null-move) search
+const THREAT_DEPTH: u32 = 16;
+
+/// Size of a square in exported board images, in pixels
+const IMAGE_SQUARE: usize = 64;
+
+/// Size of a square in exported game GIFs, in pixels
+const GIF_SQUARE: usize = 48;
+/// Time each move of an exported game GIF is shown unless `:export-gif` is
+/// given one
+const GIF_DELAY: Duration = Duration::from_secs(1);
+/// Extra time the final position of a game GIF is shown before it loops
+const GIF_HOLD: Duration = Duration::from_secs(3);
+
+/// Depth of the quick all-moves search for the heatmap
+const HEATMAP_DEPTH: u32 = 8;
+
+/// Plies added to the search by the deepen key
+const DEEPEN_PLIES: u32 = 5;
+/// Most costly moves played during analysis listed in the game summary
+const SUMMARY_DELTAS: usize = 5;
+/// Hash usage (permille) treated as full
+const HASH_FULL: u32 = 950;
+/// How long the hash has to stay full before warning about it
+const HASH_FULL_WARNING: Duration = Duration::from_secs(10);
+/// Smallest terminal the compact layout fits in (columns and rows)
+const MIN_WIDTH: u16 = 32;
+const MIN_HEIGHT: u16 = 17;
+/// Searches taking at least this long send a desktop notification when they
+/// finish in the background
+const NOTIFY_SEARCH_TIME: Duration = Duration::from_secs(20);
+/// Largest hash size the double-hash action goes up to (MB)
+const MAX_HASH_MB: u32 = 32768;
+/// Commands whose argument is a file path, completed with Tab
+const PATH_COMMANDS: &[&str] = &[
+    "open",
+    "export",
+    "export-image",
+    "export-gif",
+    "script",
+    "repertoire white",
+    "repertoire black",
+    "puzzle file",
+];
+/// Path completions listed in the status line at most
+const MAX_COMPLETIONS_SHOWN: usize = 8;
+/// Delays between autoplayed moves that `.` and `,` step through
+const AUTOPLAY_DELAYS: [Duration; 8] = [
+    Duration::from_millis(250),
+    Duration::from_millis(500),
+    Duration::from_secs(1),
+    Duration::from_millis(1500),
+    Duration::from_secs(2),
+    Duration::from_secs(3),
+    Duration::from_secs(5),
+    Duration::from_secs(10),
+];
+/// Delay between autoplayed moves unless `:autoplay` is given one
+const AUTOPLAY_DELAY: Duration = Duration::from_secs(2);
+/// How long autoplay shows a move's class over the board (at most three
+/// quarters of the delay)
+const AUTOPLAY_OVERLAY: Duration = Duration::from_millis(1500);
+
 /// Popup state
 #[derive(Debug, Clone, PartialEq)]
 pub enum Popup {
@@ -22,6 +134,75 @@ pub enum Popup {
     Import,
     Depth(String),
     MultiPV(String),
+    Strength(String),
+    /// Engine picker: candidate binaries and the selected index
+    Engine(Vec<String>, usize),
+    /// Raw UCI log, scrolled up by this many lines
+    UciLog(usize),
+    /// Static evaluation breakdown, scrolled down by this many lines
+    StaticEval(usize),
+    /// Accuracy summary of the reviewed game
+    Summary(GameSummary),
+    /// Position library, with the selected entry
+    Library(usize),
+    /// The board read out rank by rank
+    BoardText,
+    /// PGN files of the games directory, with the selected file
+    Games(Vec<PgnFile>, usize),
+    /// Header editor: the selected row (one past the tags = a new tag) and
+    /// the text typed while editing it
+    Headers(usize, Option<String>),
+    /// Opening explorer, in place of the move list: its games and the
+    /// selected move
+    Explorer(ExplorerSource, usize),
+}
+
+/// Evaluation lost by a played move compared to the engine's best move
+#[derive(Debug, Clone)]
+pub struct MoveDelta {
+    /// Ply index of the move (0 = first move of the game)
+    pub ply: usize,
+    /// Played move in SAN
+    pub played: String,
+    /// Engine's best move in SAN
+    pub best: String,
+    /// Centipawns lost from the mover's perspective (0 = best move)
+    pub loss_cp: i32,
+}
+
+/// A move delta waiting for the engine to evaluate the resulting position
+#[derive(Debug, Clone)]
+struct PendingDelta {
+    ply: usize,
+    played: String,
+    best: String,
+    /// Best score before the move, from the mover's perspective
+    best_cp: i32,
+    /// Depth the best score was measured at
+    depth: u32,
+    /// FEN after the move (to detect navigation away)
+    fen_after: String,
+}
+
+/// A batch of puzzles being drawn from the database in the background, as
+/// reading the whole file takes a while
+struct PuzzleDraw {
+    query: Query<Vec<Puzzle>>,
+    filter: PuzzleFilter,
+    /// Settings of the session to start with the batch: following the
+    /// puzzle rating, the rating and the rush minutes. None refills the
+    /// current session.
+    start: Option<(bool, u32, Option<u32>)>,
+}
+
+/// Stepping through the game on a timer (`:autoplay`)
+#[derive(Debug, Clone)]
+struct Autoplay {
+    /// Time each position is shown
+    delay: Duration,
+    paused: bool,
+    /// When the current position was reached
+    last_step: Instant,
 }
 
 /// Main application state
@@ -34,6 +215,10 @@ pub struct App {
     pub engine: Option<Engine>,
     /// Analysis state
     pub analysis: AnalysisState,
+    /// Second engine for side-by-side comparison
+    pub second_engine: Option<Engine>,
+    /// Analysis state of the second engine
+    pub second_analysis: AnalysisState,
     /// Input state
     pub input: InputState,
     /// Current popup
@@ -42,404 +227,5096 @@ pub struct App {
     pub should_quit: bool,
     /// Move scroll offset
     pub move_scroll: usize,
+    /// Analysis line under the selection cursor
+    pub selected_line: Option<usize>,
+    /// Show the selected analysis line's full PV
+    pub expand_line: bool,
+    /// Show the move list in the compact layout
+    pub show_moves: bool,
+    /// Show nothing but the board
+    pub zen: bool,
+    /// Search for the opponent's threat once the main search finishes
+    pub show_threat: bool,
+    /// Best line for the opponent if they could move again (null-move search)
+    pub threat: Option<AnalysisInfo>,
+    /// Is the engine currently running the threat search?
+    threat_searching: bool,
+    /// Candidate moves (UCI) the analysis is restricted to
+    pub candidates: Vec<String>,
+    /// Position the candidate moves belong to
+    candidates_fen: String,
+    /// Evaluate every legal move and tint their destination squares
+    pub heatmap_mode: bool,
+    /// Scores (side to move, centipawns) of each legal move by UCI
+    heatmap_scores: HashMap<String, i32>,
+    /// Position the heatmap scores belong to
+    heatmap_fen: String,
+    /// Is the engine currently running the heatmap search?
+    heatmap_searching: bool,
+    /// Static evaluation trace of the current position, while shown
+    static_eval: Option<StaticEval>,
+    /// When the hash table filled up during the current run of full reports
+    hash_full_since: Option<Instant>,
+    /// Has the full-hash warning been shown for the current hash size?
+    hash_warned: bool,
+    /// Target depth of a search paused because the terminal lost focus
+    paused_for_focus: Option<u32>,
+    /// Does the terminal have focus?
+    focused: bool,
+    /// Evaluation deltas of moves played during analysis
+    pub move_deltas: Vec<MoveDelta>,
+    /// Move delta waiting for the new position's evaluation
+    pending_delta: Option<PendingDelta>,
+    /// Latest engine evaluation (White's view, centipawns) of analysed positions by FEN
+    position_evals: HashMap<String, i32>,
+    /// Game against the engine, if one is in progress (or just finished)
+    pub play: Option<PlaySession>,
+    /// Endgame drill being played against the engine (or just finished)
+    drill: Option<DrillSession>,
+    /// Background analysis of the whole game, while it runs
+    review: Option<Review>,
+    /// Blunder quiz over the reviewed game, while it runs
+    quiz: Option<Quiz>,
+    /// Guess-the-move session over the loaded game, while it runs
+    guess: Option<GuessSession>,
+    /// Puzzles from the Lichess puzzle database, while being solved
+    puzzle: Option<PuzzleSession>,
+    /// Puzzles being drawn from the database
+    puzzle_draw: Option<PuzzleDraw>,
+    /// Game shown before the puzzles started, put back when they end
+    before_puzzles: Option<Game>,
+    /// Checkmate pattern exercises, while being solved
+    mates: Option<MateSession>,
+    /// Automatic replay of the game, while it runs
+    autoplay: Option<Autoplay>,
+    /// Game as it was before a null move, and the position the null move
+    /// led to, while that position is being analysed
+    before_pass: Option<(Game, Chess)>,
+    /// Games of the last PGN loaded with more than one game
+    pgn_games: Vec<Game>,
+    /// Index of the shown game in `pgn_games`
+    pgn_game: usize,
+    /// Where the positions of `pgn_games` are reached from
+    arrivals: Arrivals,
+    /// Moves played from each position of `pgn_games`, for the explorer
+    opening_tree: OpeningTree,
+    /// Masters explorer answers by FEN
+    masters: Answers<PositionStats>,
+    /// Masters explorer lookup under way
+    masters_query: Option<Query<PositionStats>>,
+    /// Online tablebase answers by FEN
+    tablebase: Answers<TablebaseEntry>,
+    /// Online tablebase lookup under way
+    tablebase_query: Option<Query<TablebaseEntry>>,
+    /// Cloud database answers by FEN
+    cloud: Answers<Vec<CloudMove>>,
+    /// Cloud database lookup under way
+    cloud_query: Option<Query<Vec<CloudMove>>>,
+    /// Upload of the game to Lichess under way, answering with its URL
+    lichess_import: Option<Query<String>>,
+    /// Positions of the game's mainline that another move order reaches
+    /// too, by index
+    transpositions: HashSet<usize>,
+    /// Opening repertoires for each side
+    repertoires: ByColor<Option<Repertoire>>,
+    /// How the board squares are drawn
+    renderer: BoardRenderer,
+    /// Colors the terminal can show
+    color_depth: ColorDepth,
+    /// Squares of the last rendered frame and where they are, when the
+    /// board is drawn as an image
+    board_squares: RefCell<Option<(Rect, BoardSquares)>>,
+    /// Board image currently on screen
+    board_image: Option<(Rect, BoardSquares)>,
+    /// Squares of the last rendered board, redrawn only when they change
+    board_cache: RefCell<BoardCache>,
     /// Last position sent to engine (to detect changes)
     last_fen: String,
+    /// Starting position, moves and position index at the last tick, to
+    /// tell moves played from navigation and games loaded
+    seen_game: (Chess, Vec<Move>, usize),
+    /// When the running analysis started, None once it reached its target
+    /// depth
+    analysis_started: Option<Instant>,
+    /// Wakes the main loop from engine threads
+    waker: Waker,
+    /// User script with hooks on game events
+    script: Option<Script>,
+    /// Aliases being run, outermost first. An alias can run other aliases,
+    /// but not itself: its name then means the built-in command.
+    running_aliases: Vec<String>,
+    /// Is a command from the control socket being run? Those can't start
+    /// programs, write files or change the settings that do.
+    remote: bool,
 }
 
-impl App {
-    /// Create a new application
-    pub fn new(config: Config) -> Result<Self> {
-        let game = Game::new();
-        let analysis = AnalysisState::new(config.engine.depth);
+/// How the game changed since the last tick
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GameChange {
+    None,
+    /// A move (or a move and its reply) was played from the position shown
+    Played,
+    /// A different game or position was loaded
+    Loaded,
+    /// The position shown or the moves changed otherwise, e.g. navigation
+    /// or a takeback
+    Other,
+}
 
+impl App {
+    /// Create a new application; engines wake the main loop through the
+    /// waker when they have output
+    pub fn new(config: Config, waker: Waker) -> Result<Self> {
         // Try to start the engine
         let engine = match config.stockfish_path() {
-            Some(path) => match Engine::new(&path) {
-                Ok(mut e) => {
-                    // Configure the engine
-                    let _ = e.set_option("MultiPV", &config.engine.multipv.to_string());
-                    let _ = e.set_option("Threads", &config.engine.threads.to_string());
-                    let _ = e.set_option("Hash", &config.engine.hash.to_string());
-                    let _ = e.set_option("Contempt", &config.engine.contempt.to_string());
-                    Some(e)
-                }
-                Err(e) => {
-                    eprintln!("Warning: Failed to start Stockfish: {}", e);
-                    None
-                }
-            },
-            None => {
-                eprintln!("Warning: Stockfish not found in PATH");
-                None
-            }
+            Some(path) => Self::spawn_engine(&config, &path, &waker)
+                .map_err(|e| format!("Failed to start Stockfish: {:#}", e)),
+            None => Err("Stockfish not found in PATH".to_string()),
+        };
+        Self::with_engine(config, waker, engine)
+    }
+
+    /// Create an application talking UCI over a backend, e.g. a `MockEngine`
+    #[cfg(test)]
+    pub fn with_backend(
+        config: Config,
+        waker: Waker,
+        backend: Box<dyn crate::engine::EngineBackend>,
+    ) -> Result<Self> {
+        let engine = Self::configure_engine(&config, Engine::with_backend(backend, waker.clone())?);
+        Self::with_engine(config, waker, Ok(engine))
+    }
+
+    /// Create an application around an engine, or the error that kept it
+    /// from starting
+    fn with_engine(config: Config, waker: Waker, engine: Result<Engine, String>) -> Result<Self> {
+        let game = Game::new();
+        let analysis = AnalysisState::new(config.engine.depth);
+        let second_analysis = AnalysisState::new(config.engine.depth);
+        let (engine, engine_error) = match engine {
+            Ok(engine) => (Some(engine), None),
+            Err(error) => (None, Some(error)),
         };
 
         let last_fen = game.to_fen();
+        let seen_game = (
+            game.initial_position().clone(),
+            game.moves().to_vec(),
+            game.current_index(),
+        );
+        let renderer = config.ui.get_renderer();
+        let color_depth = config.ui.get_color_depth();
 
         let mut app = Self {
             game,
             config,
             engine,
             analysis,
+            second_engine: None,
+            second_analysis,
             input: InputState::default(),
             popup: Popup::None,
             should_quit: false,
             move_scroll: 0,
+            selected_line: None,
+            expand_line: false,
+            show_moves: false,
+            zen: false,
+            show_threat: false,
+            threat: None,
+            threat_searching: false,
+            candidates: Vec::new(),
+            candidates_fen: String::new(),
+            heatmap_mode: false,
+            heatmap_scores: HashMap::new(),
+            heatmap_fen: String::new(),
+            heatmap_searching: false,
+            static_eval: None,
+            hash_full_since: None,
+            hash_warned: false,
+            paused_for_focus: None,
+            focused: true,
+            review: None,
+            quiz: None,
+            guess: None,
+            puzzle: None,
+            puzzle_draw: None,
+            before_puzzles: None,
+            mates: None,
+            move_deltas: Vec::new(),
+            pending_delta: None,
+            position_evals: HashMap::new(),
+            play: None,
+            drill: None,
+            pgn_games: Vec::new(),
+            autoplay: None,
+            before_pass: None,
+            pgn_game: 0,
+            arrivals: Arrivals::default(),
+            opening_tree: OpeningTree::default(),
+            masters: HashMap::new(),
+            masters_query: None,
+            tablebase: HashMap::new(),
+            tablebase_query: None,
+            cloud: HashMap::new(),
+            cloud_query: None,
+            lichess_import: None,
+            transpositions: HashSet::new(),
+            repertoires: ByColor::default(),
+            renderer,
+            color_depth,
+            board_squares: RefCell::new(None),
+            board_image: None,
+            board_cache: RefCell::default(),
+            seen_game,
+            analysis_started: None,
+            waker,
+            script: None,
+            running_aliases: Vec::new(),
+            remote: false,
             last_fen,
         };
 
+        if let Some(error) = engine_error {
+            tracing::warn!("{}", error);
+            app.input.set_error(error);
+        }
+
+        for side in [Color::White, Color::Black] {
+            let path = match side {
+                Color::White => app.config.repertoire.white.clone(),
+                Color::Black => app.config.repertoire.black.clone(),
+            };
+            if let Some(path) = path {
+                if let Err(e) = app.load_repertoire(side, &path) {
+                    app.input.set_error(format!("{:#}", e));
+                }
+            }
+        }
+
+        if let Some(path) = app.config.script_path() {
+            app.load_script(&path);
+        }
+
         // Start initial analysis
         app.start_analysis()?;
 
         Ok(app)
     }
 
-    /// Start or restart analysis for the current position
-    pub fn start_analysis(&mut self) -> Result<()> {
-        if let Some(ref mut engine) = self.engine {
-            // Stop any current analysis
-            engine.stop()?;
+    /// Start an engine process and configure it from the config
+    fn spawn_engine(config: &Config, path: &str, waker: &Waker) -> Result<Engine> {
+        Ok(Self::configure_engine(
+            config,
+            Engine::new(path, waker.clone())?,
+        ))
+    }
 
-            // Clear previous analysis
-            self.analysis.clear();
-            self.analysis.is_running = true;
-            self.analysis.is_paused = false;
+    /// Set an engine's options from the config
+    fn configure_engine(config: &Config, mut e: Engine) -> Engine {
+        if config.engine.low_priority {
+            let _ = e.lower_priority();
+        }
+        let _ = e.set_option("MultiPV", &config.engine.multipv.to_string());
+        let _ = e.set_option("Threads", &config.engine.threads().to_string());
+        let _ = e.set_option("Hash", &config.engine.hash.to_string());
+        let _ = e.set_option("Contempt", &config.engine.contempt.to_string());
+        let _ = e.set_option("UCI_ShowWDL", "true");
+        e
+    }
 
-            // Set up the position
-            let fen = self.game.to_fen();
-            engine.set_position(Some(&fen), &[])?;
-            self.last_fen = fen;
+    /// Replace the running engine with a different UCI binary. The old
+    /// process is only shut down once the new one has started.
+    fn switch_engine(&mut self, path: &str) -> Result<()> {
+        if self.is_playing() {
+            self.input
+                .set_error("Not available while playing (:play stop to end)");
+            return Ok(());
+        }
+
+        let engine = match Self::spawn_engine(&self.config, path, &self.waker) {
+            Ok(engine) => engine,
+            Err(e) => {
+                self.input.set_error(format!("{:#}", e));
+                return Ok(());
+            }
+        };
+        let name = engine.name.clone().unwrap_or_else(|| path.to_string());
 
-            // Start analysis
-            engine.go_depth(self.config.engine.depth)?;
+        if let Some(mut old) = self.engine.replace(engine) {
+            let _ = old.quit();
         }
+        self.config.engine.path = Some(path.to_string());
+        self.pending_delta = None;
+        self.start_analysis()?;
+        self.input.set_message(format!("Engine: {}", name));
         Ok(())
     }
 
-    /// Stop analysis
-    pub fn stop_analysis(&mut self) -> Result<()> {
-        if let Some(ref mut engine) = self.engine {
-            engine.stop()?;
-            self.analysis.is_running = false;
+    /// Start a second engine analysing alongside the main one
+    fn start_compare(&mut self, path: &str) -> Result<()> {
+        let path = which::which(path)
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_else(|_| path.to_string());
+        match Self::spawn_engine(&self.config, &path, &self.waker) {
+            Ok(engine) => {
+                let name = engine.name.clone().unwrap_or_else(|| path.clone());
+                if let Some(mut old) = self.second_engine.replace(engine) {
+                    let _ = old.quit();
+                }
+                self.start_analysis()?;
+                self.input.set_message(format!("Comparing with {}", name));
+            }
+            Err(e) => self.input.set_error(format!("{:#}", e)),
         }
         Ok(())
     }
 
-    /// Toggle analysis pause
-    pub fn toggle_pause(&mut self) -> Result<()> {
-        if self.analysis.is_paused {
-            // Resume
-            self.start_analysis()?;
-        } else {
-            // Pause
-            self.stop_analysis()?;
-            self.analysis.is_paused = true;
-        }
-        Ok(())
+    /// Check whether the two engines currently prefer different moves
+    pub fn engines_disagree(&self) -> bool {
+        let best = |state: &AnalysisState| state.lines.first().and_then(|l| l.pv.first().cloned());
+        self.second_engine.is_some()
+            && matches!(
+                (best(&self.analysis), best(&self.second_analysis)),
+                (Some(a), Some(b)) if a != b
+            )
     }
 
-    /// Process engine events
-    pub fn process_engine_events(&mut self) {
-        if let Some(ref mut engine) = self.engine {
-            while let Some(event) = engine.try_recv() {
-                match event {
-                    EngineEvent::Info(info) => {
-                        self.analysis.update(info);
-                    }
-                    EngineEvent::BestMove(_) => {
-                        self.analysis.is_running = false;
-                    }
-                    _ => {}
+    /// Analyse every position of the game in the background with a second
+    /// engine process, filling in the eval graph as results arrive
+    fn start_review(&mut self, depth: u32) -> Result<()> {
+        if self.game.moves().is_empty() {
+            self.input.set_error("No moves to review");
+            return Ok(());
+        }
+        let Some(path) = self.config.stockfish_path() else {
+            self.input.set_error("No engine configured");
+            return Ok(());
+        };
+        let review = Self::spawn_engine(&self.config, &path, &self.waker)
+            .and_then(|engine| Review::start(engine, &self.game, depth));
+        match review {
+            Ok(review) => {
+                let (_, total) = review.progress();
+                if let Some(old) = self.review.replace(review) {
+                    old.quit();
                 }
+                self.input
+                    .set_message(format!("Reviewing {} positions at depth {}", total, depth));
             }
+            Err(e) => self.input.set_error(format!("{:#}", e)),
         }
+        Ok(())
     }
 
-    /// Handle a keyboard event
-    pub fn handle_key_event(&mut self, key: KeyEvent) -> Result<()> {
-        // Handle Ctrl+C globally
-        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
-            self.should_quit = true;
-            return Ok(());
+    /// Stop a running review, keeping the evaluations found so far
+    fn stop_review(&mut self, message: &str) {
+        if let Some(review) = self.review.take() {
+            review.quit();
+            self.input.set_message(message);
         }
+    }
 
-        // Handle popup-specific input
-        match &self.popup {
-            Popup::Help => {
-                match key.code {
-                    KeyCode::Esc | KeyCode::Char('?') | KeyCode::Char('q') => {
-                        self.popup = Popup::None;
-                    }
-                    _ => {}
-                }
-                return Ok(());
-            }
-            Popup::Import => {
-                match key.code {
-                    KeyCode::Esc => {
-                        self.popup = Popup::None;
-                    }
-                    KeyCode::Char('f') => {
-                        self.popup = Popup::None;
-                        self.input.enter_fen_mode();
-                    }
-                    KeyCode::Char('p') => {
-                        self.popup = Popup::None;
-                        self.input.enter_pgn_mode();
-                    }
-                    KeyCode::Char('n') => {
-                        self.popup = Popup::None;
-                        self.game.reset();
-                        self.start_analysis()?;
-                        self.input.set_message("New game started");
-                    }
-                    _ => {}
-                }
-                return Ok(());
-            }
-            Popup::Depth(input) => {
-                let mut input = input.clone();
-                match key.code {
-                    KeyCode::Esc => {
-                        self.popup = Popup::None;
-                    }
-                    KeyCode::Enter => {
-                        if let Ok(depth) = input.parse::<u32>() {
-                            if depth > 0 && depth <= 100 {
-                                self.config.engine.depth = depth;
-                                self.analysis.target_depth = depth;
-                                self.popup = Popup::None;
-                                self.start_analysis()?;
-                                self.input.set_message(format!("Depth set to {}", depth));
-                            } else {
-                                self.input.set_error("Depth must be between 1 and 100");
-                            }
-                        } else {
-                            self.input.set_error("Invalid depth value");
-                        }
-                        self.popup = Popup::None;
-                    }
-                    KeyCode::Char(c) if c.is_ascii_digit() => {
-                        input.push(c);
-                        self.popup = Popup::Depth(input);
-                    }
-                    KeyCode::Backspace => {
-                        input.pop();
-                        self.popup = Popup::Depth(input);
-                    }
-                    _ => {}
-                }
+    /// Store finished review results in the game
+    fn process_review(&mut self) -> Result<()> {
+        let Some(ref mut review) = self.review else {
+            return Ok(());
+        };
+        let results = review.poll()?;
+        let done = review.is_done();
+
+        for result in results {
+            let current = self
+                .game
+                .position_at(result.index)
+                .map(|p| Fen::from_position(p, EnPassantMode::Legal).to_string());
+            if current.as_deref() != Some(result.fen.as_str()) {
+                self.stop_review("Game changed, review stopped");
                 return Ok(());
             }
-            Popup::MultiPV(input) => {
-                let mut input = input.clone();
-                match key.code {
-                    KeyCode::Esc => {
-                        self.popup = Popup::None;
-                    }
-                    KeyCode::Enter => {
-                        if let Ok(multipv) = input.parse::<u32>() {
-                            if multipv > 0 && multipv <= 10 {
-                                self.config.engine.multipv = multipv;
-                                if let Some(ref mut engine) = self.engine {
-                                    let _ = engine.set_option("MultiPV", &multipv.to_string());
-                                }
-                                self.popup = Popup::None;
-                                self.start_analysis()?;
-                                self.input
-                                    .set_message(format!("MultiPV set to {}", multipv));
-                            } else {
-                                self.input.set_error("MultiPV must be between 1 and 10");
-                            }
-                        } else {
-                            self.input.set_error("Invalid MultiPV value");
-                        }
-                        self.popup = Popup::None;
-                    }
-                    KeyCode::Char(c) if c.is_ascii_digit() => {
-                        input.push(c);
-                        self.popup = Popup::MultiPV(input);
-                    }
-                    KeyCode::Backspace => {
-                        input.pop();
-                        self.popup = Popup::MultiPV(input);
-                    }
-                    _ => {}
+            if let Some(eval) = result.eval {
+                self.position_evals.insert(result.fen, eval.to_cp());
+                if let Some(annotation) = self.game.annotation_at_mut(result.index) {
+                    annotation.eval = Some(eval);
                 }
-                return Ok(());
             }
-            Popup::None => {}
         }
 
-        // Handle input mode
-        if self.input.is_input_mode() {
-            match key.code {
-                KeyCode::Esc => {
-                    self.input.exit_mode();
-                }
-                KeyCode::Enter => {
-                    self.handle_input_submit()?;
-                }
-                KeyCode::Backspace => {
-                    self.input.backspace();
-                }
-                KeyCode::Delete => {
-                    self.input.delete();
-                }
-                KeyCode::Left => {
-                    self.input.move_left();
-                }
-                KeyCode::Right => {
-                    self.input.move_right();
-                }
-                KeyCode::Home => {
-                    self.input.move_start();
-                }
-                KeyCode::End => {
-                    self.input.move_end();
-                }
-                KeyCode::Char(c) => {
-                    self.input.insert(c);
-                }
-                _ => {}
+        if done {
+            self.stop_review("Review complete");
+            self.notify("Review complete", "Every position of the game has an eval");
+            if let Some(summary) = self.game_summary() {
+                self.popup = Popup::Summary(summary);
             }
-            return Ok(());
         }
+        Ok(())
+    }
 
-        // Normal mode shortcuts
-        match key.code {
-            KeyCode::Char('q') => {
-                self.should_quit = true;
+    /// Accuracy and ACPL of both players, once every position has an eval
+    fn game_summary(&self) -> Option<GameSummary> {
+        let evals = game_evals(&self.game);
+        summarize(&evals, self.game.initial_position().turn())
+    }
+
+    /// The costliest moves of the game that were played while the engine
+    /// was analysing, as "12. Nf3 -0.85 vs Nc3", in game order
+    fn summary_deltas(&self) -> Vec<String> {
+        let mut deltas: Vec<&MoveDelta> = self
+            .move_deltas
+            .iter()
+            .filter(|d| d.loss_cp > 0 && self.san_at(d.ply).as_deref() == Some(&*d.played))
+            .collect();
+        deltas.sort_by_key(|d| std::cmp::Reverse(d.loss_cp));
+        deltas.truncate(SUMMARY_DELTAS);
+        deltas.sort_by_key(|d| d.ply);
+        deltas
+            .iter()
+            .filter_map(|d| {
+                let san = self.game.numbered_san(d.ply)?;
+                Some(format!(
+                    "{} {:+.2} vs {}",
+                    san,
+                    -(d.loss_cp as f64) / 100.0,
+                    d.best
+                ))
+            })
+            .collect()
+    }
+
+    /// SAN of the game's move at a ply
+    fn san_at(&self, ply: usize) -> Option<String> {
+        let position = self.game.position_at(ply)?;
+        let m = self.game.moves().get(ply)?;
+        Some(shakmaty::san::San::from_move(&position, m).to_string())
+    }
+
+    /// Record the review summary in the PGN headers
+    fn set_summary_headers(&mut self) {
+        let Some(summary) = self.game_summary() else {
+            return;
+        };
+        for (side, player) in [("White", summary.white), ("Black", summary.black)] {
+            self.game.set_header(
+                &format!("{}Accuracy", side),
+                &format!("{:.1}", player.accuracy),
+            );
+            self.game
+                .set_header(&format!("{}ACPL", side), &player.acpl.to_string());
+        }
+    }
+
+    /// Jump to the next (or previous) of the game's biggest eval swings
+    fn goto_critical_moment(&mut self, forward: bool) -> Result<()> {
+        let evals = game_evals(&self.game);
+        let moments = critical_moments(&evals);
+        if moments.is_empty() {
+            self.input
+                .set_error("No critical moments (run :review or load a PGN with evals)");
+            return Ok(());
+        }
+
+        let current = self.game.current_index();
+        let target = if forward {
+            moments.iter().position(|&i| i > current)
+        } else {
+            moments.iter().rposition(|&i| i < current)
+        };
+        let Some(n) = target else {
+            self.input.set_message(if forward {
+                "No later critical moment"
+            } else {
+                "No earlier critical moment"
+            });
+            return Ok(());
+        };
+
+        let index = moments[n];
+        self.game.go_to(index);
+        self.start_analysis()?;
+
+        let format_eval = |eval: Eval| match eval {
+            Eval::Cp(cp) => format_score(Some(cp), None),
+            Eval::Mate(m) => format_score(None, Some(m)),
+        };
+        let swing = match (evals[index - 1], evals[index]) {
+            (Some(before), Some(after)) => {
+                format!(" ({} -> {})", format_eval(before), format_eval(after))
+            }
+            _ => String::new(),
+        };
+        let san = self.game.numbered_san(index - 1).unwrap_or_default();
+        self.input.set_message(format!(
+            "Critical moment {}/{}: {}{}",
+            n + 1,
+            moments.len(),
+            san,
+            swing
+        ));
+        Ok(())
+    }
+
+    /// Jump to the next (or previous) capture, check or pawn break
+    fn goto_move_kind(&mut self, kind: MoveKind, forward: bool) -> Result<()> {
+        let Some(index) = self.game.find_move(kind, forward) else {
+            let direction = if forward { "later" } else { "earlier" };
+            self.input
+                .set_message(format!("No {} {}", direction, kind.name()));
+            return Ok(());
+        };
+        self.game.go_to(index);
+        self.start_analysis()?;
+        let san = self.game.numbered_san(index - 1).unwrap_or_default();
+        self.input.set_message(san);
+        Ok(())
+    }
+
+    /// Load the repertoire for a side from a PGN file
+    fn load_repertoire(&mut self, side: Color, path: &str) -> Result<()> {
+        let repertoire = Repertoire::load(path, side)?;
+        let message = format!(
+            "{} repertoire: {} positions from {}",
+            if side == Color::White {
+                "White"
+            } else {
+                "Black"
+            },
+            repertoire.positions(),
+            path
+        );
+        *self.repertoires.get_mut(side) = Some(repertoire);
+        self.input.set_message(message);
+        Ok(())
+    }
+
+    /// Side the user played in the loaded game: the one whose header matches
+    /// the configured player name, or else the only side with a repertoire
+    fn repertoire_side(&self) -> Option<Color> {
+        if let Some(ref player) = self.config.repertoire.player {
+            for side in [Color::White, Color::Black] {
+                let header = if side == Color::White {
+                    "White"
+                } else {
+                    "Black"
+                };
+                if self
+                    .game
+                    .header(header)
+                    .is_some_and(|name| name.eq_ignore_ascii_case(player))
+                {
+                    return Some(side);
+                }
+            }
+        }
+        match (&self.repertoires.white, &self.repertoires.black) {
+            (Some(_), None) => Some(Color::White),
+            (None, Some(_)) => Some(Color::Black),
+            _ => None,
+        }
+    }
+
+    /// First move where the loaded game leaves the user's repertoire
+    fn repertoire_deviation(&self) -> Option<Deviation> {
+        self.repertoires
+            .get(self.repertoire_side()?)
+            .as_ref()?
+            .deviation(&self.game)
+    }
+
+    /// Describe where the game left the repertoire
+    fn deviation_text(deviation: &Deviation) -> String {
+        format!(
+            "{} left the repertoire at {} (repertoire: {})",
+            if deviation.by_user { "You" } else { "Opponent" },
+            deviation.played,
+            deviation.expected.join(", ")
+        )
+    }
+
+    /// Report where the game leaves the repertoire, optionally jumping there
+    fn check_repertoire(&mut self, jump: bool) -> Result<()> {
+        if self.repertoires.iter().all(Option::is_none) {
+            self.input
+                .set_error("No repertoire loaded (:repertoire white|black <path>)");
+            return Ok(());
+        }
+        if self.repertoire_side().is_none() {
+            self.input
+                .set_error("Set player under [repertoire] to tell which side you played");
+            return Ok(());
+        }
+        let Some(deviation) = self.repertoire_deviation() else {
+            self.input
+                .set_message("The game doesn't leave the repertoire");
+            return Ok(());
+        };
+
+        if jump {
+            self.game.go_to(deviation.index + 1);
+            self.start_analysis()?;
+        }
+        self.input.set_message(Self::deviation_text(&deviation));
+        Ok(())
+    }
+
+    /// Replay the positions where a side blundered in the reviewed game,
+    /// asking for a better move each time
+    fn start_quiz(&mut self, args: &str) -> Result<()> {
+        if self.is_playing() {
+            self.input
+                .set_error("Not available while playing (:play stop to end)");
+            return Ok(());
+        }
+        if let Some(error) = self.training_error() {
+            self.input.set_error(error);
+            return Ok(());
+        }
+        if self.engine.is_none() {
+            self.input.set_error("No engine available to check answers");
+            return Ok(());
+        }
+        let side = match args.to_lowercase().as_str() {
+            "" => None,
+            "white" | "w" => Some(shakmaty::Color::White),
+            "black" | "b" => Some(shakmaty::Color::Black),
+            _ => {
+                self.input
+                    .set_error("Usage: :quiz [white|black] | :quiz skip | :quiz stop");
+                return Ok(());
+            }
+        };
+
+        let evals = game_evals(&self.game);
+        let items: Vec<QuizItem> = blunders(&evals, self.game.initial_position().turn(), side)
+            .into_iter()
+            .filter_map(|index| {
+                let position = self.game.position_at(index)?;
+                let turn = position.turn();
+                let played = self.game.move_to_san(&self.game.moves()[index], &position);
+                Some(QuizItem {
+                    index,
+                    fen: Fen::from_position(position, EnPassantMode::Legal).to_string(),
+                    turn,
+                    played,
+                    before: evals[index]?,
+                    after: evals[index + 1]?,
+                })
+            })
+            .collect();
+        if items.is_empty() {
+            self.input
+                .set_error("No blunders to quiz (run :review first)");
+            return Ok(());
+        }
+
+        self.quiz = Some(Quiz::new(items));
+        self.show_quiz_item()
+    }
+
+    /// Go to the current quiz position and wait for an answer
+    fn show_quiz_item(&mut self) -> Result<()> {
+        let Some(ref quiz) = self.quiz else {
+            return Ok(());
+        };
+        let item = quiz.item();
+        let message = format!("Find a better move than {}", item.played);
+        self.game.go_to(item.index);
+        self.pending_delta = None;
+        self.stop_analysis()?;
+        self.analysis.clear();
+        self.threat = None;
+        self.last_fen = self.game.to_fen();
+        self.input.set_message(message);
+        Ok(())
+    }
+
+    /// Start (or restart) the engine search for the quiz answer being checked:
+    /// first the engine's own best move, then the answer on its own
+    fn search_quiz(&mut self) -> Result<()> {
+        let (Some(quiz), Some(engine)) = (self.quiz.as_mut(), self.engine.as_mut()) else {
+            return Ok(());
+        };
+        let QuizPhase::Checking { answer, best } = &quiz.phase else {
+            return Ok(());
+        };
+        engine.stop()?;
+        engine.set_position(Some(&quiz.item().fen), &[])?;
+        match (answer, best) {
+            (Some((uci, _)), Some(_)) => {
+                engine.go_depth_searchmoves(QUIZ_DEPTH, std::slice::from_ref(uci))?
+            }
+            _ => engine.go_depth(QUIZ_DEPTH)?,
+        }
+        quiz.search = None;
+        Ok(())
+    }
+
+    /// Check a move typed during the quiz against the engine
+    fn answer_quiz(&mut self, input: &str) -> Result<()> {
+        let Some(ref mut quiz) = self.quiz else {
+            return Ok(());
+        };
+        match quiz.phase {
+            QuizPhase::Asking => {}
+            QuizPhase::Checking { .. } => {
+                self.input.set_error("Still checking the last answer");
+                return Ok(());
+            }
+            QuizPhase::Revealed { .. } => {
+                self.input
+                    .set_error("Press n for the next position (:quiz stop to end)");
+                return Ok(());
+            }
+        }
+
+        let answer = match self.game.parse_san(input) {
+            Ok(m) => {
+                let uci = m.to_uci(self.game.castling_mode()).to_string();
+                Some((uci, self.game.move_to_san(&m, self.game.position())))
+            }
+            Err(e) => {
+                self.input.set_error(format!("Invalid move: {}", e));
+                return Ok(());
+            }
+        };
+        quiz.phase = QuizPhase::Checking { answer, best: None };
+        self.search_quiz()
+    }
+
+    /// Give up on the current quiz position and show the engine's move
+    fn skip_quiz(&mut self) -> Result<()> {
+        let Some(ref mut quiz) = self.quiz else {
+            self.input.set_error("No quiz running (use :quiz)");
+            return Ok(());
+        };
+        if quiz.phase != QuizPhase::Asking {
+            return Ok(());
+        }
+        quiz.phase = QuizPhase::Checking {
+            answer: None,
+            best: None,
+        };
+        self.search_quiz()
+    }
+
+    /// Move on to the next quiz position, or finish the quiz after the last
+    fn next_quiz(&mut self) -> Result<()> {
+        let Some(ref mut quiz) = self.quiz else {
+            return Ok(());
+        };
+        if quiz.advance() {
+            return self.show_quiz_item();
+        }
+        let message = format!("Quiz finished: {}/{} correct", quiz.correct, quiz.answered);
+        self.quiz = None;
+        self.start_analysis()?;
+        self.input.set_message(message);
+        Ok(())
+    }
+
+    /// Follow the engine's search while a quiz answer is being checked
+    fn quiz_event(&mut self, event: EngineEvent) -> Result<()> {
+        let Some(ref mut quiz) = self.quiz else {
+            return Ok(());
+        };
+        match event {
+            EngineEvent::Info(info) if info.multipv.unwrap_or(1) == 1 => {
+                let score = score_to_cp(info.score_cp, info.score_mate);
+                if let (Some(first), Some(cp)) = (info.pv.first(), score) {
+                    quiz.search = Some((cp, first.clone()));
+                }
+            }
+            EngineEvent::BestMove(best_move) => {
+                let QuizPhase::Checking { answer, best } = quiz.phase.clone() else {
+                    return Ok(());
+                };
+                // Scores are from the point of view of the quiz's side to move
+                let score = quiz.search.take().map_or(0, |(cp, _)| cp);
+                let best = match best {
+                    Some(best) => best,
+                    None => {
+                        let best = (best_move.best_move, score);
+                        if answer.as_ref().is_some_and(|(uci, _)| *uci != best.0) {
+                            // Now score the answer itself
+                            quiz.phase = QuizPhase::Checking {
+                                answer,
+                                best: Some(best),
+                            };
+                            return self.search_quiz();
+                        }
+                        best
+                    }
+                };
+                let best_san = uci_to_san(self.game.position(), std::slice::from_ref(&best.0))
+                    .pop()
+                    .unwrap_or_else(|| best.0.clone());
+                quiz.reveal(
+                    answer.map(|(_, san)| (san, score)),
+                    (best_san, best.1),
+                    best.0,
+                );
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Hide the loaded game's moves from the current position on and ask
+    /// for each move of one side in turn
+    fn start_guess(&mut self, args: &str) -> Result<()> {
+        if self.is_playing() {
+            self.input
+                .set_error("Not available while playing (:play stop to end)");
+            return Ok(());
+        }
+        if let Some(error) = self.training_error() {
+            self.input.set_error(error);
+            return Ok(());
+        }
+        if self.engine.is_none() {
+            self.input.set_error("No engine available to score guesses");
+            return Ok(());
+        }
+        let side = match args.to_lowercase().as_str() {
+            "" => self.game.turn(),
+            "white" | "w" => shakmaty::Color::White,
+            "black" | "b" => shakmaty::Color::Black,
+            _ => {
+                self.input
+                    .set_error("Usage: :guess [white|black] | :guess stop");
+                return Ok(());
+            }
+        };
+        if self.game.is_at_end() {
+            self.input
+                .set_error("No game moves left to guess (load a game first)");
+            return Ok(());
+        }
+
+        self.guess = Some(GuessSession::new(side));
+        self.pending_delta = None;
+        self.stop_analysis()?;
+        self.analysis.clear();
+        self.threat = None;
+        self.advance_guess()
+    }
+
+    /// Play the game's moves up to the next one to guess, finishing the
+    /// session at the end of the game
+    fn advance_guess(&mut self) -> Result<()> {
+        let Some(ref mut session) = self.guess else {
+            return Ok(());
+        };
+        session.reply = None;
+        while self.game.turn() != session.side && !self.game.is_at_end() {
+            session.reply = self.game.numbered_san(self.game.current_index());
+            self.game.go_forward();
+        }
+
+        if self.game.is_at_end() {
+            let message = format!(
+                "Guess the move finished: {}/{} points, {} of {} moves matched",
+                session.points,
+                session.max_points(),
+                session.matched,
+                session.guesses
+            );
+            self.guess = None;
+            self.start_analysis()?;
+            self.input.set_message(message);
+            return Ok(());
+        }
+
+        session.fen = self.game.to_fen();
+        self.last_fen = session.fen.clone();
+        Ok(())
+    }
+
+    /// Start (or restart) the engine search scoring a guess: first the game
+    /// move on its own, then the guess
+    fn search_guess(&mut self) -> Result<()> {
+        let (Some(session), Some(engine)) = (self.guess.as_mut(), self.engine.as_mut()) else {
+            return Ok(());
+        };
+        let GuessPhase::Checking {
+            guess,
+            actual,
+            actual_cp,
+        } = &session.phase
+        else {
+            return Ok(());
+        };
+        let uci = if actual_cp.is_some() {
+            &guess.0
+        } else {
+            &actual.0
+        };
+        engine.stop()?;
+        engine.set_position(Some(&session.fen), &[])?;
+        engine.go_depth_searchmoves(GUESS_DEPTH, std::slice::from_ref(uci))?;
+        session.search = None;
+        Ok(())
+    }
+
+    /// Compare a guessed move with the game move
+    fn answer_guess(&mut self, input: &str) -> Result<()> {
+        let Some(ref mut session) = self.guess else {
+            return Ok(());
+        };
+        if session.is_checking() {
+            self.input.set_error("Still scoring the last guess");
+            return Ok(());
+        }
+        let guessed = match self.game.parse_san(input) {
+            Ok(m) => m,
+            Err(e) => {
+                self.input.set_error(format!("Invalid move: {}", e));
+                return Ok(());
+            }
+        };
+
+        let index = self.game.current_index();
+        let actual = self.game.moves()[index].clone();
+        let guess_san = self.game.move_to_san(&guessed, self.game.position());
+        let actual_san = self.game.numbered_san(index).unwrap_or_default();
+        if guessed == actual {
+            session.record(GuessResult {
+                guess: guess_san,
+                actual: actual_san,
+                matched: true,
+                scores: None,
+                points: MATCH_POINTS,
+            });
+            self.game.go_forward();
+            return self.advance_guess();
+        }
+
+        let uci = |m: &Move| m.to_uci(self.game.castling_mode()).to_string();
+        session.phase = GuessPhase::Checking {
+            guess: (uci(&guessed), guess_san),
+            actual: (uci(&actual), actual_san),
+            actual_cp: None,
+        };
+        self.search_guess()
+    }
+
+    /// Follow the engine's search while a guess is being scored
+    fn guess_event(&mut self, event: EngineEvent) -> Result<()> {
+        let Some(ref mut session) = self.guess else {
+            return Ok(());
+        };
+        match event {
+            EngineEvent::Info(info) if info.multipv.unwrap_or(1) == 1 => {
+                if let Some(cp) = score_to_cp(info.score_cp, info.score_mate) {
+                    session.search = Some(cp);
+                }
+            }
+            EngineEvent::BestMove(_) => {
+                let GuessPhase::Checking {
+                    guess,
+                    actual,
+                    actual_cp,
+                } = session.phase.clone()
+                else {
+                    return Ok(());
+                };
+                // Scores are from the point of view of the guessing side
+                let score = session.search.take().unwrap_or(0);
+                let Some(actual_cp) = actual_cp else {
+                    // Now score the guess itself
+                    session.phase = GuessPhase::Checking {
+                        guess,
+                        actual,
+                        actual_cp: Some(score),
+                    };
+                    return self.search_guess();
+                };
+                session.record(GuessResult {
+                    guess: guess.1,
+                    actual: actual.1,
+                    matched: false,
+                    scores: Some((score, actual_cp)),
+                    points: guess_points(score, actual_cp),
+                });
+                self.game.go_forward();
+                self.advance_guess()?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Serve puzzles from the Lichess puzzle database, by theme and rating
+    /// range (around the local puzzle rating by default). `rush [minutes]`
+    /// starts a timed puzzle rush instead.
+    fn start_puzzles(&mut self, args: &str) -> Result<()> {
+        if self.is_playing() {
+            self.input
+                .set_error("Not available while playing (:play stop to end)");
+            return Ok(());
+        }
+        // Starting over replaces the current puzzles
+        if let Some(error) = self.training_error().filter(|_| self.puzzle.is_none()) {
+            self.input.set_error(error);
+            return Ok(());
+        }
+        if self.puzzle_draw.is_some() {
+            self.input.set_message("Already drawing puzzles");
+            return Ok(());
+        }
+        let Some(path) = self.config.puzzles.path.clone() else {
+            self.input
+                .set_error("No puzzle database (set puzzles.path or use :puzzle file <path>)");
+            return Ok(());
+        };
+
+        let record = PuzzleRecord::load();
+        let range = self.config.puzzles.rating_range;
+        let mut words = args.split_whitespace().peekable();
+        let mut rush = None;
+        if words.peek() == Some(&"rush") {
+            words.next();
+            rush = Some(RUSH_MINUTES);
+        }
+        let mut filter = if rush.is_some() {
+            PuzzleFilter {
+                theme: None,
+                min_rating: RUSH_MIN_RATING,
+                max_rating: RUSH_MAX_RATING,
+            }
+        } else {
+            PuzzleFilter {
+                theme: None,
+                min_rating: record.rating.saturating_sub(range),
+                max_rating: record.rating + range,
+            }
+        };
+        let mut auto_range = rush.is_none();
+        for arg in words {
+            match arg.parse::<u32>() {
+                Ok(minutes) if rush.is_some() && minutes > 0 => {
+                    rush = Some(minutes);
+                    continue;
+                }
+                _ => {}
+            }
+            let bounds = arg
+                .split_once('-')
+                .and_then(|(min, max)| Some((min.parse::<u32>().ok()?, max.parse::<u32>().ok()?)));
+            match bounds {
+                Some((min, max)) => {
+                    filter.min_rating = min;
+                    filter.max_rating = max;
+                    auto_range = false;
+                }
+                None => filter.theme = Some(arg.to_string()),
+            }
+        }
+
+        let count = if rush.is_some() {
+            RUSH_PUZZLES
+        } else {
+            PUZZLE_BATCH
+        };
+        self.draw_puzzles(
+            path,
+            filter,
+            record.recent,
+            count,
+            Some((auto_range, record.rating, rush)),
+        );
+        Ok(())
+    }
+
+    /// Start drawing a batch of puzzles on a background thread
+    fn draw_puzzles(
+        &mut self,
+        path: String,
+        filter: PuzzleFilter,
+        skip: Vec<String>,
+        count: usize,
+        start: Option<(bool, u32, Option<u32>)>,
+    ) {
+        let query_filter = filter.clone();
+        let query = Query::spawn(&self.game.to_fen(), &self.waker, move || {
+            sample_puzzles(&path, &query_filter, &skip, count)
+        });
+        self.puzzle_draw = Some(PuzzleDraw {
+            query,
+            filter,
+            start,
+        });
+        self.input.set_message("Drawing puzzles...");
+    }
+
+    /// Serve the batch of puzzles drawn in the background once it's ready
+    fn update_puzzle_draw(&mut self) -> Result<()> {
+        let Some(result) = self.puzzle_draw.as_ref().and_then(|d| d.query.poll()) else {
+            return Ok(());
+        };
+        let Some(draw) = self.puzzle_draw.take() else {
+            return Ok(());
+        };
+        let batch = match result {
+            Ok(batch) => batch,
+            Err(e) => {
+                self.input.set_error(format!("{:#}", e));
+                return Ok(());
+            }
+        };
+
+        let Some((auto_range, rating, rush)) = draw.start else {
+            // Puzzles ended while more were being drawn
+            let Some(ref mut session) = self.puzzle else {
+                return Ok(());
+            };
+            if batch.is_empty() {
+                let message = format!("No more puzzles for {}", session.filter.describe());
+                self.end_puzzles()?;
+                self.input.set_message(message);
+                return Ok(());
+            }
+            session.refill(batch);
+            session.advance();
+            return self.show_puzzle();
+        };
+
+        // Something else may have started in the meantime
+        if self.is_playing() || (self.puzzle.is_none() && self.training_error().is_some()) {
+            return Ok(());
+        }
+        if batch.is_empty() {
+            self.input
+                .set_error(format!("No puzzles found for {}", draw.filter.describe()));
+            return Ok(());
+        }
+        let rush = rush.map(|minutes| Rush::new(minutes, PuzzleRecord::load().rush_best(minutes)));
+        if self.puzzle.is_none() {
+            self.before_puzzles = Some(self.game.clone());
+        }
+        self.puzzle = Some(PuzzleSession::new(
+            draw.filter,
+            auto_range,
+            rating,
+            rush,
+            batch,
+        ));
+        self.show_puzzle()
+    }
+
+    /// Leave the puzzles, putting back the game shown before them
+    fn end_puzzles(&mut self) -> Result<()> {
+        self.puzzle = None;
+        self.puzzle_draw = None;
+        if let Some(game) = self.before_puzzles.take() {
+            self.game = game;
+        }
+        self.start_analysis()
+    }
+
+    /// Set up the current puzzle's position and play the opponent's first move
+    fn show_puzzle(&mut self) -> Result<()> {
+        let Some(ref mut session) = self.puzzle else {
+            return Ok(());
+        };
+        let puzzle = &session.puzzle;
+        let first = self
+            .game
+            .load_fen(&puzzle.fen)
+            .ok()
+            .and_then(|_| puzzle.move_at(0, self.game.position()));
+        let Some(first) = first else {
+            let message = format!("Puzzle {} is invalid", puzzle.id);
+            self.end_puzzles()?;
+            self.input.set_error(message);
+            return Ok(());
+        };
+        self.game.make_move(first)?;
+        self.game.set_header(
+            "Site",
+            &format!("https://lichess.org/training/{}", puzzle.id),
+        );
+
+        session.side = self.game.turn();
+        session.fen = self.game.to_fen();
+        let side = if session.side == shakmaty::Color::White {
+            "White"
+        } else {
+            "Black"
+        };
+        let message = if session.rush.is_some() {
+            format!("Find the best move for {}", side)
+        } else {
+            format!(
+                "Puzzle {} ({}): find the best move for {}",
+                puzzle.id, puzzle.rating, side
+            )
+        };
+        self.last_fen = session.fen.clone();
+        self.pending_delta = None;
+        self.stop_analysis()?;
+        self.analysis.clear();
+        self.threat = None;
+        self.input.set_message(message);
+        Ok(())
+    }
+
+    /// Check a move against the puzzle solution, playing the opponent's reply
+    /// when it's right
+    fn answer_puzzle(&mut self, input: &str) -> Result<()> {
+        let Some(ref mut session) = self.puzzle else {
+            return Ok(());
+        };
+        if session.rush.as_ref().is_some_and(|r| r.finished) {
+            self.input
+                .set_error("The rush is over (:puzzle rush to go again, :puzzle stop to end)");
+            return Ok(());
+        }
+        if session.state != PuzzleState::Solving {
+            self.input
+                .set_error("Press n for the next puzzle (:puzzle stop to end)");
+            return Ok(());
+        }
+        let m = match self.game.parse_san(input) {
+            Ok(m) => m,
+            Err(e) => {
+                self.input.set_error(format!("Invalid move: {}", e));
+                return Ok(());
+            }
+        };
+
+        if !session.is_solution(self.game.position(), &m) {
+            return self.fail_puzzle();
+        }
+        self.game.make_move(m)?;
+        session.step += 1;
+        if let Some(reply) = session.puzzle.move_at(session.step, self.game.position()) {
+            self.game.make_move(reply)?;
+            session.step += 1;
+        }
+        session.fen = self.game.to_fen();
+        self.last_fen = session.fen.clone();
+
+        if session.step >= session.puzzle.moves.len() {
+            session.state = PuzzleState::Solved;
+            self.finish_puzzle(true)?;
+        } else {
+            self.input.set_message("Correct, keep going");
+        }
+        Ok(())
+    }
+
+    /// Mark the current puzzle failed and show the expected move
+    fn fail_puzzle(&mut self) -> Result<()> {
+        let Some(ref mut session) = self.puzzle else {
+            return Ok(());
+        };
+        if session.state != PuzzleState::Solving
+            || session.rush.as_ref().is_some_and(|r| r.finished)
+        {
+            return Ok(());
+        }
+        let uci = session.puzzle.moves[session.step].clone();
+        let san = uci_to_san(self.game.position(), std::slice::from_ref(&uci))
+            .pop()
+            .unwrap_or_else(|| uci.clone());
+        session.state = PuzzleState::Failed { uci, san };
+        self.finish_puzzle(false)
+    }
+
+    /// Update and save the puzzle rating once a puzzle is over. During a
+    /// rush the rating is left alone and the next puzzle follows at once.
+    fn finish_puzzle(&mut self, solved: bool) -> Result<()> {
+        let Some(ref mut session) = self.puzzle else {
+            return Ok(());
+        };
+        session.played += 1;
+        if solved {
+            session.solved += 1;
+        }
+        if let Some(ref mut rush) = session.rush {
+            let message = if solved {
+                rush.solved += 1;
+                None
+            } else {
+                rush.strikes += 1;
+                let san = match session.state {
+                    PuzzleState::Failed { ref san, .. } => san.as_str(),
+                    _ => "",
+                };
+                Some(format!(
+                    "Wrong: the solution was {} (strike {}/{})",
+                    san, rush.strikes, RUSH_STRIKES
+                ))
+            };
+            if rush.strikes >= RUSH_STRIKES {
+                self.end_rush("Three strikes");
+                return Ok(());
+            }
+            self.next_puzzle()?;
+            if let Some(message) = message {
+                self.input.set_error(message);
+            }
+            return Ok(());
+        }
+
+        let mut record = PuzzleRecord::load();
+        let before = record.rating;
+        record.record(&session.puzzle, solved);
+        session.rating = record.rating;
+        session.rating_change = Some((before, record.rating));
+        match record.save() {
+            Ok(()) if solved => self.input.set_message("Puzzle solved!"),
+            Ok(()) => self.input.set_error("Wrong move"),
+            Err(e) => self.input.set_error(format!("{:#}", e)),
+        }
+        Ok(())
+    }
+
+    /// End a puzzle rush and save the score if it's a new high score
+    fn end_rush(&mut self, reason: &str) {
+        let Some(rush) = self.puzzle.as_mut().and_then(|s| s.rush.as_mut()) else {
+            return;
+        };
+        if rush.finished {
+            return;
+        }
+        rush.finished = true;
+        let mut record = PuzzleRecord::load();
+        let message = if record.record_rush(rush.minutes, rush.solved) {
+            format!("{}! {} solved, a new high score", reason, rush.solved)
+        } else {
+            format!("{}! {} solved (best {})", reason, rush.solved, rush.best)
+        };
+        match record.save() {
+            Ok(()) => self.input.set_message(message),
+            Err(e) => self.input.set_error(format!("{:#}", e)),
+        }
+    }
+
+    /// End the puzzle rush once its time runs out
+    fn check_puzzle_rush(&mut self) {
+        let expired = self
+            .puzzle
+            .as_ref()
+            .and_then(|s| s.rush.as_ref())
+            .is_some_and(|r| !r.finished && r.remaining().is_zero());
+        if expired {
+            self.end_rush("Time's up");
+        }
+    }
+
+    /// Serve the next puzzle, drawing a new batch when the current one runs out
+    fn next_puzzle(&mut self) -> Result<()> {
+        let Some(ref mut session) = self.puzzle else {
+            return Ok(());
+        };
+        if session.rush.as_ref().is_some_and(|r| r.finished) {
+            return Ok(());
+        }
+        if self.puzzle_draw.is_some() {
+            return Ok(());
+        }
+        if !session.advance() {
+            let record = PuzzleRecord::load();
+            if session.auto_range {
+                let range = self.config.puzzles.rating_range;
+                session.filter.min_rating = record.rating.saturating_sub(range);
+                session.filter.max_rating = record.rating + range;
+            }
+            let path = self.config.puzzles.path.clone().unwrap_or_default();
+            let count = if session.rush.is_some() {
+                RUSH_PUZZLES
+            } else {
+                PUZZLE_BATCH
+            };
+            let filter = session.filter.clone();
+            self.draw_puzzles(path, filter, record.recent, count, None);
+            return Ok(());
+        }
+        self.show_puzzle()
+    }
+
+    /// Green arrow for the expected move after a failed puzzle
+    fn puzzle_arrow(&self) -> Option<Arrow> {
+        let PuzzleState::Failed { ref uci, .. } = self.puzzle.as_ref()?.state else {
+            return None;
+        };
+        let uci = uci.parse::<shakmaty::uci::UciMove>().ok()?;
+        let m = uci.to_move(self.game.position()).ok()?;
+        Some(Arrow {
+            from: m.from()?,
+            to: m.to(),
+            color: MarkColor::Green,
+        })
+    }
+
+    /// Serve the built-in mate-in-N exercises, for one pattern or all
+    fn start_mates(&mut self, args: &str) -> Result<()> {
+        if self.is_playing() {
+            self.input
+                .set_error("Not available while playing (:play stop to end)");
+            return Ok(());
+        }
+        if let Some(error) = self.training_error() {
+            self.input.set_error(error);
+            return Ok(());
+        }
+        if self.engine.is_none() {
+            self.input.set_error("No engine available to verify mates");
+            return Ok(());
+        }
+        let pattern = if args.is_empty() {
+            None
+        } else {
+            match MATE_PATTERNS
+                .iter()
+                .find(|(key, _)| key.eq_ignore_ascii_case(args))
+            {
+                Some((key, _)) => Some(*key),
+                None => {
+                    let keys: Vec<&str> = MATE_PATTERNS.iter().map(|(key, _)| *key).collect();
+                    self.input.set_error(format!(
+                        "Unknown pattern '{}' (patterns: {})",
+                        args,
+                        keys.join(", ")
+                    ));
+                    return Ok(());
+                }
+            }
+        };
+
+        self.mates = MateSession::new(pattern);
+        self.show_mate()
+    }
+
+    /// Set up the current checkmate exercise
+    fn show_mate(&mut self) -> Result<()> {
+        let Some(ref mut session) = self.mates else {
+            return Ok(());
+        };
+        let exercise = session.exercise;
+        self.game.load_fen(exercise.fen)?;
+        self.game.set_header(
+            "Event",
+            &format!("Checkmate pattern: {}", pattern_name(exercise.pattern)),
+        );
+        session.fen = self.game.to_fen();
+        let side = if self.game.turn() == shakmaty::Color::White {
+            "White"
+        } else {
+            "Black"
+        };
+        let message = format!(
+            "{}: {} to move, mate in {}",
+            pattern_name(exercise.pattern),
+            side,
+            exercise.mate_in
+        );
+        self.last_fen = session.fen.clone();
+        self.pending_delta = None;
+        self.stop_analysis()?;
+        self.analysis.clear();
+        self.threat = None;
+        self.input.set_message(message);
+        Ok(())
+    }
+
+    /// Start (or restart) the engine search verifying a move: first the
+    /// engine's own best move, then the move on its own
+    fn search_mate(&mut self) -> Result<()> {
+        let (Some(session), Some(engine)) = (self.mates.as_mut(), self.engine.as_mut()) else {
+            return Ok(());
+        };
+        let MatePhase::Checking { answer, best } = &session.phase else {
+            return Ok(());
+        };
+        engine.stop()?;
+        engine.set_position(Some(&session.fen), &[])?;
+        match (answer, best) {
+            (Some((uci, _)), Some(_)) => {
+                engine.go_depth_searchmoves(MATE_DEPTH, std::slice::from_ref(uci))?
+            }
+            _ => engine.go_depth(MATE_DEPTH)?,
+        }
+        session.search = None;
+        Ok(())
+    }
+
+    /// Check a move typed during a checkmate exercise: a mate on the board
+    /// solves it, any other move has to keep a forced mate in time
+    fn answer_mate(&mut self, input: &str) -> Result<()> {
+        let Some(ref mut session) = self.mates else {
+            return Ok(());
+        };
+        match session.phase {
+            MatePhase::Asking => {}
+            MatePhase::Checking { .. } => {
+                self.input.set_error("Still checking the last move");
+                return Ok(());
+            }
+            MatePhase::Solved | MatePhase::Failed { .. } => {
+                self.input
+                    .set_error("Press n for the next exercise (:mates stop to end)");
+                return Ok(());
+            }
+        }
+        let m = match self.game.parse_san(input) {
+            Ok(m) => m,
+            Err(e) => {
+                self.input.set_error(format!("Invalid move: {}", e));
+                return Ok(());
+            }
+        };
+
+        let after = self.game.position().clone().play(&m)?;
+        if after.is_checkmate() {
+            self.game.make_move(m)?;
+            session.fen = self.game.to_fen();
+            self.last_fen = session.fen.clone();
+            session.finish(MatePhase::Solved);
+            self.input.set_message("Checkmate!");
+            return Ok(());
+        }
+        if session.moves_left == 1 {
+            let mate = self.game.legal_moves().into_iter().find(|m| {
+                self.game
+                    .position()
+                    .clone()
+                    .play(m)
+                    .is_ok_and(|p| p.is_checkmate())
+            });
+            let uci = mate.map(|m| m.to_uci(self.game.castling_mode()).to_string());
+            return self.fail_mate(uci.unwrap_or_default(), "Not mate");
+        }
+
+        let uci = m.to_uci(self.game.castling_mode()).to_string();
+        let san = self.game.move_to_san(&m, self.game.position());
+        session.phase = MatePhase::Checking {
+            answer: Some((uci, san)),
+            best: None,
+        };
+        self.search_mate()
+    }
+
+    /// Give up on the current exercise and show the engine's mating move
+    fn skip_mate(&mut self) -> Result<()> {
+        let Some(ref mut session) = self.mates else {
+            self.input
+                .set_error("No checkmate exercises running (use :mates)");
+            return Ok(());
+        };
+        if session.phase != MatePhase::Asking {
+            return Ok(());
+        }
+        session.phase = MatePhase::Checking {
+            answer: None,
+            best: None,
+        };
+        self.search_mate()
+    }
+
+    /// End the exercise as failed, showing a mating move
+    fn fail_mate(&mut self, uci: String, reason: &str) -> Result<()> {
+        let Some(ref mut session) = self.mates else {
+            return Ok(());
+        };
+        let san = uci_to_san(self.game.position(), std::slice::from_ref(&uci))
+            .pop()
+            .unwrap_or_else(|| uci.clone());
+        let message = format!("{}: the solution was {}", reason, san);
+        session.finish(MatePhase::Failed { uci, san });
+        self.input.set_error(message);
+        Ok(())
+    }
+
+    /// Follow the engine's search while a move is being verified
+    fn mate_event(&mut self, event: EngineEvent) -> Result<()> {
+        let Some(ref mut session) = self.mates else {
+            return Ok(());
+        };
+        match event {
+            EngineEvent::Info(info) if info.multipv.unwrap_or(1) == 1 && !info.pv.is_empty() => {
+                session.search = Some((info.score_mate, info.pv));
+            }
+            EngineEvent::BestMove(best_move) => {
+                let MatePhase::Checking { answer, best } = session.phase.clone() else {
+                    return Ok(());
+                };
+                let Some((uci, san)) = answer else {
+                    return self.fail_mate(best_move.best_move, "Skipped");
+                };
+                if best.is_none() && best_move.best_move != uci {
+                    // Now check the move itself
+                    session.phase = MatePhase::Checking {
+                        answer: Some((uci, san)),
+                        best: Some(best_move.best_move),
+                    };
+                    return self.search_mate();
+                }
+
+                // Mate scores count the mating side's moves, this one included
+                let (mate, pv) = session.search.take().unwrap_or_default();
+                let in_time = mate.is_some_and(|n| n > 0 && n as u32 <= session.moves_left);
+                if !in_time {
+                    return self.fail_mate(
+                        best.unwrap_or(best_move.best_move),
+                        "No forced mate in time",
+                    );
+                }
+
+                let parse = |uci: &str, position: &Chess| {
+                    uci.parse::<shakmaty::uci::UciMove>()
+                        .ok()
+                        .and_then(|uci| uci.to_move(position).ok())
+                };
+                let Some(m) = parse(&uci, self.game.position()) else {
+                    return Ok(());
+                };
+                self.game.make_move(m)?;
+                // The engine's defence, or any move should its line stop short
+                let reply = pv
+                    .get(1)
+                    .and_then(|r| parse(r, self.game.position()))
+                    .or_else(|| self.game.legal_moves().into_iter().next());
+                let reply_san = reply
+                    .as_ref()
+                    .map(|r| self.game.move_to_san(r, self.game.position()));
+                if let Some(reply) = reply {
+                    self.game.make_move(reply)?;
+                }
+                session.moves_left -= 1;
+                session.phase = MatePhase::Asking;
+                session.fen = self.game.to_fen();
+                self.last_fen = session.fen.clone();
+                self.input.set_message(format!(
+                    "{} {}, now mate in {}",
+                    san,
+                    reply_san.unwrap_or_default(),
+                    session.moves_left
+                ));
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Move on to the next exercise, or finish after the last
+    fn next_mate(&mut self) -> Result<()> {
+        let Some(ref mut session) = self.mates else {
+            return Ok(());
+        };
+        if session.advance() {
+            return self.show_mate();
+        }
+        let message = format!(
+            "Checkmate exercises finished: {}/{} solved",
+            session.solved, session.played
+        );
+        self.mates = None;
+        self.start_analysis()?;
+        self.input.set_message(message);
+        Ok(())
+    }
+
+    /// Green arrow for the mating move after a failed exercise
+    fn mate_arrow(&self) -> Option<Arrow> {
+        let MatePhase::Failed { ref uci, .. } = self.mates.as_ref()?.phase else {
+            return None;
+        };
+        let uci = uci.parse::<shakmaty::uci::UciMove>().ok()?;
+        let m = uci.to_move(self.game.position()).ok()?;
+        Some(Arrow {
+            from: m.from()?,
+            to: m.to(),
+            color: MarkColor::Green,
+        })
+    }
+
+    /// Error for actions that would disturb a running quiz, guessing session
+    /// or puzzle
+    fn training_error(&self) -> Option<&'static str> {
+        if self.quiz.is_some() {
+            Some("Not available during the quiz (:quiz stop to end)")
+        } else if self.guess.is_some() {
+            Some("Not available while guessing moves (:guess stop to end)")
+        } else if self.puzzle.is_some() {
+            Some("Not available while solving puzzles (:puzzle stop to end)")
+        } else if self.mates.is_some() {
+            Some("Not available during checkmate exercises (:mates stop to end)")
+        } else {
+            None
+        }
+    }
+
+    /// Green arrow for the engine's move once a quiz answer is revealed
+    fn quiz_arrow(&self) -> Option<Arrow> {
+        let QuizPhase::Revealed { ref best_uci, .. } = self.quiz.as_ref()?.phase else {
+            return None;
+        };
+        let uci = best_uci.parse::<shakmaty::uci::UciMove>().ok()?;
+        let m = uci.to_move(self.game.position()).ok()?;
+        Some(Arrow {
+            from: m.from()?,
+            to: m.to(),
+            color: MarkColor::Green,
+        })
+    }
+
+    /// Open the engine picker with the current engine preselected
+    fn open_engine_picker(&mut self) {
+        let candidates = self.config.engine_candidates();
+        if candidates.is_empty() {
+            self.input
+                .set_error("No engines found (use :engine <path> or set engine.engines)");
+            return;
+        }
+        self.popup = Popup::Engine(candidates, 0);
+    }
+
+    /// Open the game browser on a directory, or the configured games
+    /// directory
+    fn open_game_browser(&mut self, dir: Option<&str>) {
+        let dir = dir
+            .or(self.config.games.dir.as_deref())
+            .map_or_else(|| ".".to_string(), files::expand_home);
+        match files::pgn_files(Path::new(&dir)) {
+            Ok(pgn_files) if pgn_files.is_empty() => {
+                self.input.set_error(format!("No PGN files in {}", dir));
+            }
+            Ok(pgn_files) => self.popup = Popup::Games(pgn_files, 0),
+            Err(e) => self.input.set_error(format!("{:#}", e)),
+        }
+    }
+
+    /// Open the opening explorer on a source: the games of the loaded PGN
+    /// when there are some, else the masters database
+    fn open_explorer(&mut self, args: &str) {
+        let source = match args {
+            "" if self.opening_tree.games() == 0 => ExplorerSource::Masters,
+            "" => ExplorerSource::Games,
+            name => match ExplorerSource::from_name(name) {
+                Some(source) => source,
+                None => {
+                    self.input.set_error("Usage: :explorer [games|masters]");
+                    return;
+                }
+            },
+        };
+        if let Some(error) = self.training_error() {
+            self.input.set_error(error);
+        } else if source == ExplorerSource::Games && self.opening_tree.games() == 0 {
+            self.input
+                .set_error("No game database loaded (load a PGN with several games)");
+        } else {
+            // Failed lookups are tried again
+            self.masters.retain(|_, stats| stats.is_ok());
+            self.popup = Popup::Explorer(source, 0);
+        }
+    }
+
+    /// What the explorer knows about the current position, or a note on
+    /// why there's nothing to show yet
+    fn explorer_stats(
+        &self,
+        source: ExplorerSource,
+    ) -> std::result::Result<Option<PositionStats>, String> {
+        match source {
+            ExplorerSource::Games => Ok(self.opening_tree.lookup(self.game.position())),
+            ExplorerSource::Masters => match self.masters.get(&self.game.to_fen()) {
+                Some(Ok(stats)) => Ok(Some(stats.clone()).filter(|s| s.results.games() > 0)),
+                Some(Err(e)) => Err(e.clone()),
+                None => Err("Looking up master games...".to_string()),
+            },
+        }
+    }
+
+    /// Collect the masters explorer's answer, and ask about the current
+    /// position while the explorer shows master games
+    fn update_masters(&mut self) {
+        online::collect(&mut self.masters_query, &mut self.masters);
+        if !matches!(self.popup, Popup::Explorer(ExplorerSource::Masters, _))
+            || self.masters_query.is_some()
+        {
+            return;
+        }
+        let fen = self.game.to_fen();
+        if !self.masters.contains_key(&fen) {
+            let config = self.config.online.clone();
+            let query_fen = fen.clone();
+            self.masters_query = Some(Query::spawn(&fen, &self.waker, move || {
+                online::masters(&config, &query_fen)
+            }));
+        }
+    }
+
+    /// Collect the online tablebase's answer, and ask about the current
+    /// position when the tablebase has it
+    fn update_tablebase(&mut self) {
+        online::collect(&mut self.tablebase_query, &mut self.tablebase);
+        if !self.config.online.tablebase
+            || self.tablebase_query.is_some()
+            || !online::in_tablebase(self.game.position())
+        {
+            return;
+        }
+        let fen = self.game.to_fen();
+        if !self.tablebase.contains_key(&fen) {
+            let config = self.config.online.clone();
+            let query_fen = fen.clone();
+            self.tablebase_query = Some(Query::spawn(&fen, &self.waker, move || {
+                online::tablebase(&config, &query_fen)
+            }));
+        }
+    }
+
+    /// Turn online tablebase lookups on or off (toggled without an argument)
+    fn set_tablebase(&mut self, args: &str) {
+        let on = match args {
+            "" => !self.config.online.tablebase,
+            "on" => true,
+            "off" => false,
+            _ => {
+                self.input.set_error("Usage: :tablebase [on|off]");
+                return;
+            }
+        };
+        self.config.online.tablebase = on;
+        // Failed lookups are tried again
+        self.tablebase.retain(|_, entry| entry.is_ok());
+        self.input.set_message(if on {
+            "Online tablebase on (positions with up to 7 pieces)"
+        } else {
+            "Online tablebase off"
+        });
+    }
+
+    /// The tablebase's verdict on the current position and its best moves,
+    /// e.g. "White wins, DTZ 12, mate in 23: Kd6 Kc6"
+    fn tablebase_text(&self) -> Option<String> {
+        if !self.config.online.tablebase || !online::in_tablebase(self.game.position()) {
+            return None;
+        }
+        let entry = match self.tablebase.get(&self.game.to_fen()) {
+            Some(Ok(entry)) => entry,
+            Some(Err(e)) => return Some(e.clone()),
+            None => return Some("looking up...".to_string()),
+        };
+        let turn = self.game.turn();
+        let (side, other) = match turn {
+            Color::White => ("White", "Black"),
+            Color::Black => ("Black", "White"),
+        };
+        let mut text = match entry.outcome {
+            Outcome::Win => format!("{} wins", side),
+            Outcome::Loss => format!("{} wins", other),
+            Outcome::CursedWin => format!("{} wins but for the 50-move rule", side),
+            Outcome::BlessedLoss => format!("{} wins but for the 50-move rule", other),
+            Outcome::Draw => "Draw".to_string(),
+            Outcome::Unknown => "Unknown".to_string(),
+        };
+        if let Some(dtz) = entry.dtz.filter(|&dtz| dtz != 0) {
+            text.push_str(&format!(", DTZ {}", dtz.abs()));
+        }
+        if let Some(dtm) = entry.dtm.filter(|&dtm| dtm != 0) {
+            text.push_str(&format!(", mate in {}", (dtm.abs() + 1) / 2));
+        }
+        // The moves keeping the best outcome, in the tablebase's order
+        if let Some(best) = entry.moves.first() {
+            let moves: Vec<&str> = entry
+                .moves
+                .iter()
+                .take_while(|m| m.outcome == best.outcome)
+                .take(3)
+                .map(|m| m.san.as_str())
+                .collect();
+            text.push_str(&format!(": {}", moves.join(" ")));
+        }
+        Some(text)
+    }
+
+    /// Handle `:lichess [pgn]`: open the position on the Lichess analysis
+    /// board, or upload the game and open it at the current move
+    fn open_on_lichess(&mut self, args: &str) {
+        match args {
+            "" => {
+                let url = online::analysis_url(
+                    &self.config.online,
+                    &self.game.to_fen(),
+                    self.game.castling_mode() == CastlingMode::Chess960,
+                    self.config.ui.flip_board,
+                );
+                self.open_in_browser(&url);
+            }
+            "pgn" => {
+                if self.lichess_import.is_some() {
+                    self.input
+                        .set_message("Already uploading the game to Lichess");
+                    return;
+                }
+                self.set_summary_headers();
+                let config = self.config.online.clone();
+                let pgn = self.game.to_pgn();
+                self.lichess_import =
+                    Some(Query::spawn(&self.game.to_fen(), &self.waker, move || {
+                        online::import_game(&config, &pgn)
+                    }));
+                self.input.set_message("Uploading the game to Lichess...");
+            }
+            _ => self.input.set_error("Usage: :lichess [pgn]"),
+        }
+    }
+
+    /// Open the uploaded game once Lichess has it, at the current move
+    fn update_lichess_import(&mut self) {
+        let Some(result) = self.lichess_import.as_ref().and_then(Query::poll) else {
+            return;
+        };
+        self.lichess_import = None;
+        match result {
+            Ok(url) => {
+                let start = self.game.initial_position();
+                let first_ply = (start.fullmoves().get() as usize - 1) * 2
+                    + usize::from(start.turn() == Color::Black);
+                let side = if self.config.ui.flip_board {
+                    "/black"
+                } else {
+                    ""
+                };
+                let url = format!("{}{}#{}", url, side, first_ply + self.game.current_index());
+                self.open_in_browser(&url);
+            }
+            Err(e) => self.input.set_error(format!("{:#}", e)),
+        }
+    }
+
+    fn open_in_browser(&mut self, url: &str) {
+        match online::open_in_browser(url) {
+            Ok(()) => self.input.set_message(format!("Opened {}", url)),
+            Err(e) => self.input.set_error(format!("{:#}", e)),
+        }
+    }
+
+    /// Collect the cloud database's answer, and ask about the current
+    /// position while cloud lookups are on
+    fn update_cloud(&mut self) {
+        online::collect(&mut self.cloud_query, &mut self.cloud);
+        if !self.config.online.cloud || self.cloud_query.is_some() || self.game.is_game_over() {
+            return;
+        }
+        let fen = self.game.to_fen();
+        if !self.cloud.contains_key(&fen) {
+            let config = self.config.online.clone();
+            let query_fen = fen.clone();
+            self.cloud_query = Some(Query::spawn(&fen, &self.waker, move || {
+                online::cloud(&config, &query_fen)
+            }));
+        }
+    }
+
+    /// Turn cloud database lookups on or off (toggled without an argument)
+    fn set_cloud(&mut self, args: &str) {
+        let on = match args {
+            "" => !self.config.online.cloud,
+            "on" => true,
+            "off" => false,
+            _ => {
+                self.input.set_error("Usage: :cloud [on|off]");
+                return;
+            }
+        };
+        self.config.online.cloud = on;
+        self.cloud.retain(|_, moves| moves.is_ok());
+        self.input.set_message(if on {
+            "Cloud database on (chessdb.cn)"
+        } else {
+            "Cloud database off"
+        });
+    }
+
+    /// The cloud database's best moves with their scores, e.g.
+    /// "e4 +0.40  d4 +0.38  Nf3 +0.35"
+    fn cloud_text(&self) -> Option<String> {
+        if !self.config.online.cloud || self.game.is_game_over() {
+            return None;
+        }
+        let moves = match self.cloud.get(&self.game.to_fen()) {
+            Some(Ok(moves)) if moves.is_empty() => return Some("not in the database".to_string()),
+            Some(Ok(moves)) => moves,
+            Some(Err(e)) => return Some(e.clone()),
+            None => return Some("looking up...".to_string()),
+        };
+        // Scores are for the side to move, like the engine's
+        let negate = self.config.ui.white_perspective && self.game.turn() == shakmaty::Color::Black;
+        let moves: Vec<String> = moves
+            .iter()
+            .map(|m| {
+                let (cp, mate) = if negate {
+                    (m.cp.map(|cp| -cp), m.mate.map(|mate| -mate))
+                } else {
+                    (m.cp, m.mate)
+                };
+                format!("{} {}", m.san, format_score(cp, mate))
+            })
+            .collect();
+        Some(moves.join("  "))
+    }
+
+    /// Play a move from the explorer, following the game's own move when
+    /// it's the same
+    fn play_explorer_move(&mut self, source: ExplorerSource, selected: usize) -> Result<()> {
+        let Some(m) = self
+            .explorer_stats(source)
+            .ok()
+            .flatten()
+            .and_then(|stats| stats.moves.into_iter().nth(selected))
+        else {
+            return Ok(());
+        };
+        let next = self.game.moves().get(self.game.current_index());
+        if next.is_some_and(|next| next.to_uci(self.game.castling_mode()).to_string() == m.uci) {
+            self.game.go_forward();
+            return self.start_analysis();
+        }
+        self.submit_line(&m.san)
+    }
+
+    /// Header tags shown by the header editor: the Seven Tag Roster (empty
+    /// when unset), then the game's other tags
+    fn header_rows(&self) -> Vec<(String, String)> {
+        let mut rows: Vec<(String, String)> = SEVEN_TAG_ROSTER
+            .iter()
+            .map(|tag| {
+                (
+                    tag.to_string(),
+                    self.game.header(tag).unwrap_or("").to_string(),
+                )
+            })
+            .collect();
+        rows.extend(
+            self.game
+                .headers()
+                .iter()
+                .filter(|(k, _)| {
+                    !SEVEN_TAG_ROSTER.contains(&k.as_str()) && k != "FEN" && k != "SetUp"
+                })
+                .cloned(),
+        );
+        rows
+    }
+
+    /// Apply text typed in the header editor: the new value of a tag (empty
+    /// removes it), or `Name value` on the new tag row
+    fn edit_header(&mut self, row: usize, text: &str) -> Result<()> {
+        let rows = self.header_rows();
+        let text = text.trim();
+        let (name, value) = match rows.get(row) {
+            Some((name, _)) => (name.as_str(), text),
+            None => match text.split_once(' ') {
+                Some((name, value)) => (name, value.trim()),
+                None => bail!("Type the tag's name and value, e.g. Annotator Me"),
+            },
+        };
+        if value.is_empty() {
+            self.game.remove_header(name);
+            return Ok(());
+        }
+        check_tag(name, value)?;
+        self.game.set_header(name, value);
+        Ok(())
+    }
+
+    /// Start or restart analysis for the current position
+    pub fn start_analysis(&mut self) -> Result<()> {
+        self.start_analysis_to(self.config.engine.depth)
+    }
+
+    /// Start analysing the current position to a depth
+    fn start_analysis_to(&mut self, depth: u32) -> Result<()> {
+        // While playing, the engine searches for its own moves instead
+        if self.is_playing() {
+            return self.update_play();
+        }
+
+        // A finished drill stays on screen until another game is loaded
+        if let Some(ref session) = self.drill {
+            let start =
+                Fen::from_position(self.game.initial_position().clone(), EnPassantMode::Legal);
+            if start.to_string() != session.drill.fen {
+                self.drill = None;
+            }
+        }
+
+        // The engine is kept for checking answers while a quiz is on its position
+        if let Some(ref quiz) = self.quiz {
+            if quiz.item().fen == self.game.to_fen() {
+                return self.search_quiz();
+            }
+            self.quiz = None;
+        }
+        if let Some(ref session) = self.guess {
+            if session.fen == self.game.to_fen() {
+                return self.search_guess();
+            }
+            self.guess = None;
+        }
+        // Analysis would give the solution away
+        if let Some(ref session) = self.puzzle {
+            if session.fen == self.game.to_fen() {
+                return Ok(());
+            }
+            // Something else was loaded, which is kept
+            self.puzzle = None;
+            self.before_puzzles = None;
+        }
+        if let Some(ref session) = self.mates {
+            if session.fen == self.game.to_fen() {
+                return self.search_mate();
+            }
+            self.mates = None;
+        }
+
+        self.threat = None;
+        self.threat_searching = false;
+        if !self.candidates.is_empty() && self.candidates_fen != self.game.to_fen() {
+            self.set_candidates(Vec::new())?;
+        }
+
+        let multipv = self.analysis_multipv();
+        if let Some(ref mut engine) = self.engine {
+            // Stop any current analysis
+            engine.stop()?;
+
+            // Clear previous analysis (kept dimmed until the new search warms up)
+            self.analysis.restart(self.game.position().clone());
+            self.analysis.target_depth = depth;
+            self.analysis.is_running = true;
+            self.analysis.is_paused = false;
+
+            // Set up the position
+            engine.set_game(&self.game)?;
+            self.last_fen = self.game.to_fen();
+
+            // Start analysis, first scoring every move when the heatmap is on
+            let legal_moves = self.game.position().legal_moves().len();
+            if self.heatmap_mode && self.heatmap_fen != self.last_fen && legal_moves > 0 {
+                engine.set_option("MultiPV", &legal_moves.to_string())?;
+                engine.go_depth(HEATMAP_DEPTH)?;
+                self.heatmap_scores.clear();
+                self.heatmap_fen = self.last_fen.clone();
+                self.heatmap_searching = true;
+            } else {
+                if self.heatmap_mode {
+                    engine.set_option("MultiPV", &multipv.to_string())?;
+                }
+                self.heatmap_searching = false;
+                if self.candidates.is_empty() {
+                    engine.go_depth(depth)?;
+                } else {
+                    engine.go_depth_searchmoves(depth, &self.candidates)?;
+                }
+            }
+        }
+
+        if let Some(ref mut engine) = self.second_engine {
+            engine.stop()?;
+            self.second_analysis.restart(self.game.position().clone());
+            self.second_analysis.target_depth = depth;
+            self.second_analysis.is_running = true;
+            self.second_analysis.is_paused = false;
+            engine.set_game(&self.game)?;
+            engine.go_depth(depth)?;
+        }
+
+        // A finished game won't produce a usable score, so settle any
+        // pending move delta from the outcome instead
+        if self.game.is_game_over() {
+            let score = if self.game.position().is_checkmate() {
+                -crate::engine::MATE_SCORE
+            } else {
+                0
+            };
+            self.resolve_move_delta(score);
+        }
+        Ok(())
+    }
+
+    /// Stop analysis
+    pub fn stop_analysis(&mut self) -> Result<()> {
+        if let Some(ref mut engine) = self.engine {
+            engine.stop()?;
+            self.analysis.is_running = false;
+        }
+        self.threat_searching = false;
+        if self.heatmap_searching {
+            // Scores are incomplete, so redo them on resume
+            self.heatmap_searching = false;
+            self.heatmap_fen.clear();
+        }
+        if let Some(ref mut engine) = self.second_engine {
+            engine.stop()?;
+            self.second_analysis.is_running = false;
+        }
+        Ok(())
+    }
+
+    /// Handle `:autoplay [seconds|stop]`: step through the game from the
+    /// current position (the start, if at the end) one move per delay
+    fn autoplay_command(&mut self, args: &str) -> Result<()> {
+        if args == "stop" {
+            if self.autoplay.take().is_some() {
+                self.input.set_message("Autoplay stopped");
+            }
+            return Ok(());
+        }
+        if let Some(error) = self.training_error() {
+            self.input.set_error(error);
+            return Ok(());
+        }
+        if self.is_playing() {
+            self.input
+                .set_error("Not available while playing (:play stop to end)");
+            return Ok(());
+        }
+        let delay = match args {
+            "" => AUTOPLAY_DELAY,
+            secs => match secs.parse::<f64>() {
+                Ok(secs) if (0.1..=60.0).contains(&secs) => Duration::from_secs_f64(secs),
+                _ => {
+                    self.input
+                        .set_error("Usage: :autoplay [seconds (0.1-60)|stop]");
+                    return Ok(());
+                }
+            },
+        };
+        if self.game.moves().is_empty() {
+            self.input.set_error("No moves to play through");
+            return Ok(());
+        }
+        if self.game.is_at_end() {
+            self.game.go_to_start();
+            self.start_analysis()?;
+        }
+        self.autoplay = Some(Autoplay {
+            delay,
+            paused: false,
+            last_step: Instant::now(),
+        });
+        self.input.set_message(format!(
+            "Autoplay every {:.2}s: Space pauses, ,/. change the speed, Esc stops",
+            delay.as_secs_f64()
+        ));
+        Ok(())
+    }
+
+    /// Keys that control a running autoplay; returns whether the key was
+    /// one of them. None of them start a move, and Esc clears a selected
+    /// analysis line before it stops autoplay.
+    fn autoplay_key(&mut self, code: KeyCode) -> bool {
+        let Some(ref mut autoplay) = self.autoplay else {
+            return false;
+        };
+        let message = match code {
+            KeyCode::Char(' ') => {
+                autoplay.paused = !autoplay.paused;
+                autoplay.last_step = Instant::now();
+                if autoplay.paused {
+                    "Autoplay paused".to_string()
+                } else {
+                    "Autoplay resumed".to_string()
+                }
+            }
+            KeyCode::Char('.') => {
+                let delay = autoplay.delay;
+                autoplay.delay = AUTOPLAY_DELAYS
+                    .iter()
+                    .rev()
+                    .find(|d| **d < delay)
+                    .copied()
+                    .unwrap_or(delay);
+                format!("Autoplay every {:.2}s", autoplay.delay.as_secs_f64())
+            }
+            KeyCode::Char(',') => {
+                let delay = autoplay.delay;
+                autoplay.delay = AUTOPLAY_DELAYS
+                    .iter()
+                    .find(|d| **d > delay)
+                    .copied()
+                    .unwrap_or(delay);
+                format!("Autoplay every {:.2}s", autoplay.delay.as_secs_f64())
+            }
+            KeyCode::Esc if self.selected_line.is_none() => {
+                self.autoplay = None;
+                "Autoplay stopped".to_string()
+            }
+            _ => return false,
+        };
+        self.input.set_message(message);
+        true
+    }
+
+    /// Show the next move once the autoplay delay has passed, stopping at
+    /// the end of the game
+    fn step_autoplay(&mut self) -> Result<()> {
+        let Some(ref mut autoplay) = self.autoplay else {
+            return Ok(());
+        };
+        if autoplay.paused || autoplay.last_step.elapsed() < autoplay.delay {
+            return Ok(());
+        }
+        autoplay.last_step = Instant::now();
+        if self.game.go_forward() {
+            self.start_analysis()?;
+        } else {
+            self.autoplay = None;
+            self.input.set_message("Autoplay finished");
+        }
+        Ok(())
+    }
+
+    /// The move autoplay has just shown, its class and the evals before and
+    /// after it, while they're overlaid on the board. Needs the evals of
+    /// both positions, from a review, the PGN or earlier analysis.
+    fn autoplay_overlay(&self) -> Option<(String, MoveClass, String, String)> {
+        let autoplay = self.autoplay.as_ref()?;
+        if autoplay.last_step.elapsed() >= AUTOPLAY_OVERLAY.min(autoplay.delay * 3 / 4) {
+            return None;
+        }
+        let index = self.game.current_index().checked_sub(1)?;
+        let (before, after) = (self.eval_at(index)?, self.eval_at(index + 1)?);
+        let san = self.game.numbered_san(index)?;
+        let mover = self.game.position_at(index)?.turn();
+        let class = MoveClass::of(before, after, mover);
+        Some((san, class, format_cp(before), format_cp(after)))
+    }
+
+    /// Continue a finished search some plies deeper, keeping its lines
+    fn deepen(&mut self, plies: u32) -> Result<()> {
+        if self.is_playing() {
+            self.input
+                .set_error("Not available while playing (:play stop to end)");
+            return Ok(());
+        }
+        if !self.analysis.is_done() || self.heatmap_searching {
+            self.input
+                .set_error("Analysis hasn't reached its target depth yet");
+            return Ok(());
+        }
+
+        let depth = (self.analysis.target_depth + plies).min(100);
+        if let Some(ref mut engine) = self.engine {
+            // A threat search may be running on the null-move position
+            engine.stop()?;
+            self.threat_searching = false;
+            engine.set_game(&self.game)?;
+            if self.candidates.is_empty() {
+                engine.go_depth(depth)?;
+            } else {
+                engine.go_depth_searchmoves(depth, &self.candidates)?;
+            }
+            self.analysis.target_depth = depth;
+            self.analysis.is_running = true;
+        }
+        if let Some(ref mut engine) = self.second_engine {
+            if self.second_analysis.is_done() {
+                engine.set_game(&self.game)?;
+                engine.go_depth(depth)?;
+                self.second_analysis.target_depth = depth;
+                self.second_analysis.is_running = true;
+            }
+        }
+        self.input
+            .set_message(format!("Searching to depth {}", depth));
+        Ok(())
+    }
+
+    /// Toggle analysis pause
+    pub fn toggle_pause(&mut self) -> Result<()> {
+        if let Some(error) = self.training_error() {
+            self.input.set_error(error);
+            return Ok(());
+        }
+        if self.analysis.is_paused {
+            // Resume
+            self.start_analysis()?;
+        } else {
+            // Pause
+            self.stop_analysis()?;
+            self.analysis.is_paused = true;
+            self.second_analysis.is_paused = true;
+        }
+        Ok(())
+    }
+
+    /// Pause a running search when the terminal loses focus (if enabled)
+    pub fn focus_lost(&mut self) -> Result<()> {
+        self.focused = false;
+        if self.config.ui.pause_on_unfocus
+            && self.analysis.is_running
+            && !self.analysis.is_paused
+            && !self.heatmap_searching
+            && !self.is_playing()
+            && self.training_error().is_none()
+            && self.engine.is_some()
+        {
+            let depth = self.analysis.target_depth;
+            self.toggle_pause()?;
+            self.paused_for_focus = Some(depth);
+        }
+        Ok(())
+    }
+
+    /// Resume a search paused by [`App::focus_lost`], to the depth it was
+    /// going to
+    pub fn focus_gained(&mut self) -> Result<()> {
+        self.focused = true;
+        if let Some(depth) = self.paused_for_focus.take() {
+            if self.analysis.is_paused {
+                self.start_analysis_to(depth)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Process engine events
+    pub fn process_engine_events(&mut self) -> Result<()> {
+        let playing = self.is_playing();
+        let mut best_moves = Vec::new();
+        let mut heatmap_done = false;
+        let mut hashfull = None;
+        let checking_quiz = self.quiz.as_ref().is_some_and(Quiz::is_checking);
+        let checking_guess = self.guess.as_ref().is_some_and(GuessSession::is_checking);
+        let checking_mate = self.mates.as_ref().is_some_and(MateSession::is_checking);
+        let mut training_events = Vec::new();
+
+        if let Some(ref mut engine) = self.engine {
+            while let Some(event) = engine.try_recv() {
+                if let EngineEvent::Text(line) = event {
+                    if let Some(eval) = self.static_eval.as_mut().filter(|e| !e.complete) {
+                        eval.push(&line);
+                    }
+                    continue;
+                }
+                if checking_quiz || checking_guess || checking_mate {
+                    training_events.push(event);
+                    continue;
+                }
+                if self.heatmap_searching {
+                    match event {
+                        EngineEvent::Info(info) => {
+                            let score = score_to_cp(info.score_cp, info.score_mate);
+                            if let (Some(first), Some(cp)) = (info.pv.first(), score) {
+                                self.heatmap_scores.insert(first.clone(), cp);
+                            }
+                        }
+                        EngineEvent::BestMove(_) => {
+                            self.heatmap_searching = false;
+                            heatmap_done = true;
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+                if self.threat_searching {
+                    match event {
+                        EngineEvent::Info(info)
+                            if info.multipv.unwrap_or(1) == 1 && !info.pv.is_empty() =>
+                        {
+                            self.threat = Some(info);
+                        }
+                        EngineEvent::BestMove(_) => self.threat_searching = false,
+                        _ => {}
+                    }
+                    continue;
+                }
+                match event {
+                    // Keep the engine's thoughts hidden during a game against it
+                    EngineEvent::Info(_) if playing => {}
+                    EngineEvent::Info(info) => {
+                        hashfull = info.hashfull.or(hashfull);
+                        self.analysis.update(info);
+                        let score = self
+                            .analysis
+                            .lines
+                            .first()
+                            .and_then(|l| score_to_cp(l.score_cp, l.score_mate));
+                        if let Some(cp) = score {
+                            let white_cp = match self.game.turn() {
+                                shakmaty::Color::White => cp,
+                                shakmaty::Color::Black => -cp,
+                            };
+                            self.position_evals.insert(self.last_fen.clone(), white_cp);
+                        }
+                    }
+                    EngineEvent::BestMove(best) => {
+                        self.analysis.is_running = false;
+                        best_moves.push(best);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        if let Some(ref mut engine) = self.second_engine {
+            while let Some(event) = engine.try_recv() {
+                match event {
+                    EngineEvent::Info(info) => self.second_analysis.update(info),
+                    EngineEvent::BestMove(_) => self.second_analysis.is_running = false,
+                    _ => {}
+                }
+            }
+        }
+
+        for event in training_events {
+            if checking_quiz {
+                self.quiz_event(event)?;
+            } else if checking_guess {
+                self.guess_event(event)?;
+            } else {
+                self.mate_event(event)?;
+            }
+        }
+
+        if let Some(hashfull) = hashfull {
+            self.track_hash_usage(hashfull);
+        }
+
+        if heatmap_done && !playing {
+            // Now run the normal search for this position
+            self.start_analysis()?;
+        }
+
+        if playing {
+            for best in best_moves {
+                self.play_engine_move(best)?;
+            }
+        } else if !best_moves.is_empty() && self.show_threat && self.threat.is_none() {
+            self.start_threat_search()?;
+        }
+
+        self.check_pending_delta();
+        Ok(())
+    }
+
+    /// Evaluation (White's view, centipawns) of the position at an index,
+    /// from live analysis or the game's annotations
+    fn eval_at(&self, index: usize) -> Option<i32> {
+        let position = self.game.position_at(index)?;
+        let fen = Fen::from_position(position, EnPassantMode::Legal).to_string();
+        self.position_evals.get(&fen).copied().or_else(|| {
+            self.game
+                .annotations()
+                .get(index)
+                .and_then(|a| a.eval)
+                .map(|e| e.to_cp())
+        })
+    }
+
+    /// Change in evaluation caused by the move leading to the current
+    /// position, from the point of view of the side that played it
+    pub fn eval_delta(&self) -> Option<i32> {
+        let index = self.game.current_index();
+        if index == 0 {
+            return None;
+        }
+        let before = self.eval_at(index - 1)?;
+        let after = self.eval_at(index)?;
+        // Swings into or out of forced mates aren't meaningful as pawns
+        if before.abs() >= MATE_SCORE / 2 || after.abs() >= MATE_SCORE / 2 {
+            return None;
+        }
+        Some(match self.game.turn() {
+            // Black is to move, so White played the move
+            shakmaty::Color::Black => after - before,
+            shakmaty::Color::White => before - after,
+        })
+    }
+
+    /// Announcement line for screen readers, when the mode is on
+    fn announcement(&self) -> Option<String> {
+        self.config
+            .ui
+            .screen_reader
+            .then(|| self.announcement_text())
+    }
+
+    /// Describe the last move and the position in words, e.g.
+    /// "Black played Nf6; eval +0.4; White to move"
+    fn announcement_text(&self) -> String {
+        let mut parts = Vec::new();
+        let index = self.game.current_index();
+        if let (Some(m), Some(before)) = (self.game.last_move(), index.checked_sub(1)) {
+            let position = self.game.position_at(before).unwrap_or_default();
+            let mover = match position.turn() {
+                shakmaty::Color::White => "White",
+                shakmaty::Color::Black => "Black",
+            };
+            parts.push(format!(
+                "{} played {}",
+                mover,
+                self.game.move_to_san(m, &position)
+            ));
+        }
+
+        let position = self.game.position();
+        let turn = match position.turn() {
+            shakmaty::Color::White => "White",
+            shakmaty::Color::Black => "Black",
+        };
+        match self.game.outcome() {
+            Some(shakmaty::Outcome::Decisive { winner }) => {
+                if position.is_checkmate() {
+                    parts.push("checkmate".to_string());
+                }
+                parts.push(match winner {
+                    shakmaty::Color::White => "White wins".to_string(),
+                    shakmaty::Color::Black => "Black wins".to_string(),
+                });
+            }
+            Some(shakmaty::Outcome::Draw) => parts.push("draw".to_string()),
+            None => {
+                if position.is_check() {
+                    parts.push("check".to_string());
+                }
+                // No hints while playing the engine
+                let eval = if self.is_playing() {
+                    None
+                } else {
+                    self.eval_at(index)
+                };
+                if let Some(eval) = eval {
+                    parts.push(if eval.abs() >= MATE_SCORE / 2 {
+                        let side = if eval > 0 { "White" } else { "Black" };
+                        format!("{} mates in {}", side, MATE_SCORE - eval.abs())
+                    } else {
+                        format!("eval {:+.1}", eval as f64 / 100.0)
+                    });
+                }
+                if let Some(delta) = eval.and(self.eval_delta()).filter(|d| *d <= -30) {
+                    parts.push(format!("the move lost {:.1}", -delta as f64 / 100.0));
+                }
+                parts.push(format!("{} to move", turn));
+            }
+        }
+
+        if self.config.ui.show_hanging {
+            let hanging = self.game.describe_squares(self.game.hanging_pieces());
+            if !hanging.is_empty() {
+                parts.push(format!("hanging: {}", hanging));
+            }
+        }
+        parts.join("; ")
+    }
+
+    /// Turn mouse capture on or off (toggled without an argument)
+    fn set_mouse_capture(&mut self, args: &str) {
+        let on = match args {
+            "" => !self.config.ui.mouse_capture,
+            "on" => true,
+            "off" => false,
+            _ => {
+                self.input.set_error("Usage: :mouse [on|off]");
+                return;
+            }
+        };
+        self.config.ui.mouse_capture = on;
+        self.input.set_message(if on {
+            "Mouse capture on"
+        } else {
+            "Mouse capture off: select text with the mouse"
+        });
+    }
+
+    /// Show or hide the FEN line (toggled without an argument)
+    fn set_show_fen(&mut self, args: &str) {
+        let on = match args {
+            "" => !self.config.ui.show_fen,
+            "on" => true,
+            "off" => false,
+            _ => {
+                self.input.set_error("Usage: :show-fen [on|off]");
+                return;
+            }
+        };
+        self.config.ui.show_fen = on;
+        self.input
+            .set_message(if on { "FEN line on" } else { "FEN line off" });
+    }
+
+    /// Show or hide variations, comments or NAGs in the Moves panel
+    /// (toggled without an argument)
+    fn set_move_notes(&mut self, what: &str, args: &str) {
+        let ui = &mut self.config.ui;
+        let (shown, name) = match what {
+            "variations" => (&mut ui.show_variations, "Variations"),
+            "comments" => (&mut ui.show_comments, "Comments"),
+            _ => (&mut ui.show_nags, "NAGs"),
+        };
+        let on = match args {
+            "" => !*shown,
+            "on" => true,
+            "off" => false,
+            _ => {
+                self.input
+                    .set_error(format!("Usage: :show-{} [on|off]", what));
+                return;
+            }
+        };
+        *shown = on;
+        self.input
+            .set_message(format!("{} {}", name, if on { "shown" } else { "hidden" }));
+    }
+
+    /// Switch the Moves panel between the bare mainline and the full view
+    /// with variations, comments and NAGs
+    fn toggle_move_notes(&mut self) {
+        let ui = &mut self.config.ui;
+        let on = !(ui.show_variations || ui.show_comments || ui.show_nags);
+        ui.show_variations = on;
+        ui.show_comments = on;
+        ui.show_nags = on;
+        self.input.set_message(if on {
+            "Moves: variations, comments and NAGs"
+        } else {
+            "Moves: mainline only"
+        });
+    }
+
+    /// Whether the mouse should be captured
+    pub fn mouse_capture(&self) -> bool {
+        self.config.ui.mouse_capture
+    }
+
+    /// Turn screen reader mode on or off (toggled without an argument)
+    fn set_screen_reader(&mut self, args: &str) {
+        let on = match args {
+            "" => !self.config.ui.screen_reader,
+            "on" => true,
+            "off" => false,
+            _ => {
+                self.input.set_error("Usage: :screen-reader [on|off]");
+                return;
+            }
+        };
+        self.config.ui.screen_reader = on;
+        self.input.set_message(if on {
+            "Screen reader mode on (:board reads the board)"
+        } else {
+            "Screen reader mode off"
+        });
+    }
+
+    /// Show or change a config setting: `:set engine.hash 1024`. With `save`
+    /// the config file is written right away instead of on exit.
+    fn set_command(&mut self, args: &str, save: bool) -> Result<()> {
+        let (key, value) = match args.split_once(char::is_whitespace) {
+            Some((key, value)) => (key, value.trim()),
+            None => (args, ""),
+        };
+        if key.is_empty() {
+            self.input.set_error("Usage: :set <section.key> [value]");
+            return Ok(());
+        }
+        if value.is_empty() {
+            match self.config.get_key(key) {
+                Ok(Some(value)) => self.input.set_message(format!("{} = {}", key, value)),
+                Ok(None) => self.input.set_message(format!("{} is not set", key)),
+                Err(e) => self.input.set_error(format!("{:#}", e)),
+            }
+            return Ok(());
+        }
+
+        // The engine is busy with its own moves, except for its strength
+        let strength = matches!(key, "engine.skill_level" | "engine.elo");
+        if self.is_playing() && key.starts_with("engine.") && !strength {
+            self.input
+                .set_error("Not available while playing (:play stop to end)");
+            return Ok(());
+        }
+
+        self.input.clear_messages();
+        let old = self.config.clone();
+        if let Err(e) = self.config.set_key(key, value) {
+            self.input.set_error(format!("{:#}", e));
+            return Ok(());
+        }
+        let shown = match self.config.get_key(key)? {
+            Some(value) => format!("{} = {}", key, value),
+            None => format!("{} unset", key),
+        };
+        self.apply_config(&old)?;
+        if save {
+            if let Err(e) = self.config.save() {
+                self.input.set_error(format!("{:#}", e));
+                return Ok(());
+            }
+        }
+        // Applying may have reported a problem of its own
+        if self.input.error.is_none() {
+            self.input.set_message(if save {
+                format!("{} (saved)", shown)
+            } else {
+                shown
+            });
+        }
+        Ok(())
+    }
+
+    /// Carry config changes over to the engine and the screen
+    fn apply_config(&mut self, old: &Config) -> Result<()> {
+        if self.config.ui.renderer != old.ui.renderer {
+            self.renderer = self.config.ui.get_renderer();
+        }
+        if self.config.ui.colors != old.ui.colors {
+            self.color_depth = self.config.ui.get_color_depth();
+        }
+
+        let repertoires = [
+            (
+                Color::White,
+                &self.config.repertoire.white,
+                &old.repertoire.white,
+            ),
+            (
+                Color::Black,
+                &self.config.repertoire.black,
+                &old.repertoire.black,
+            ),
+        ];
+        let changed: Vec<(Color, Option<String>)> = repertoires
+            .into_iter()
+            .filter(|(_, path, was)| path != was)
+            .map(|(side, path, _)| (side, path.clone()))
+            .collect();
+        for (side, path) in changed {
+            match path {
+                Some(path) => {
+                    if let Err(e) = self.load_repertoire(side, &path) {
+                        self.input.set_error(format!("{:#}", e));
+                    }
+                }
+                None => *self.repertoires.get_mut(side) = None,
+            }
+        }
+        if self.config.script.path != old.script.path {
+            match self.config.script_path() {
+                Some(path) => {
+                    self.load_script(&path);
+                }
+                None => self.script = None,
+            }
+        }
+        if self.config.control.listen != old.control.listen {
+            self.input
+                .set_error("control.listen takes effect on the next start");
+        }
+
+        let (new, was) = (&self.config.engine, &old.engine);
+        if new.path != was.path {
+            if let Some(path) = self.config.stockfish_path() {
+                // Kept if the new engine fails to start; starting it sends
+                // the other engine options too
+                self.config.engine.path = old.engine.path.clone();
+                return self.switch_engine(&path);
+            }
+        }
+        if self.engine.is_none() {
+            return Ok(());
+        }
+        let mut options = Vec::new();
+        if new.threads() != was.threads() {
+            options.push(("Threads", new.threads().to_string()));
+        }
+        if new.hash != was.hash {
+            options.push(("Hash", new.hash.to_string()));
+        }
+        if new.contempt != was.contempt {
+            options.push(("Contempt", new.contempt.to_string()));
+        }
+        let restart = !options.is_empty() || new.multipv != was.multipv || new.depth != was.depth;
+        let strength = new.skill_level != was.skill_level || new.elo != was.elo;
+        let low_priority = new.low_priority && !was.low_priority;
+
+        if let Some(ref mut engine) = self.engine {
+            if low_priority {
+                let _ = engine.lower_priority();
+            }
+            if restart {
+                engine.stop()?;
+                for (name, value) in options {
+                    engine.set_option(name, &value)?;
+                }
+            }
+        }
+        if strength && self.is_playing() {
+            self.apply_play_strength()?;
+        }
+        if restart {
+            let multipv = self.analysis_multipv();
+            if let Some(ref mut engine) = self.engine {
+                engine.set_option("MultiPV", &multipv.to_string())?;
+            }
+            self.hash_full_since = None;
+            self.hash_warned = false;
+            self.start_analysis()?;
+        }
+        Ok(())
+    }
+
+    /// Restrict analysis to the given moves (UCI), one line per candidate.
+    /// An empty list restores normal analysis.
+    fn set_candidates(&mut self, candidates: Vec<String>) -> Result<()> {
+        self.candidates = candidates;
+        self.candidates_fen = self.game.to_fen();
+        let multipv = self.analysis_multipv();
+        if let Some(ref mut engine) = self.engine {
+            engine.stop()?;
+            engine.set_option("MultiPV", &multipv.to_string())?;
+        }
+        Ok(())
+    }
+
+    /// Number of lines the normal analysis shows
+    fn analysis_multipv(&self) -> u32 {
+        if self.candidates.is_empty() {
+            self.config.engine.multipv
+        } else {
+            self.candidates.len() as u32
+        }
+    }
+
+    /// Toggle the move heatmap
+    fn toggle_heatmap(&mut self) -> Result<()> {
+        self.heatmap_mode = !self.heatmap_mode;
+        self.heatmap_fen.clear();
+        self.heatmap_scores.clear();
+        if !self.heatmap_mode {
+            self.heatmap_searching = false;
+            let multipv = self.analysis_multipv();
+            if let Some(ref mut engine) = self.engine {
+                engine.stop()?;
+                engine.set_option("MultiPV", &multipv.to_string())?;
+            }
+            self.input.set_message("Move heatmap disabled");
+        } else {
+            self.input.set_message("Move heatmap enabled");
+        }
+        self.start_analysis()
+    }
+
+    /// Centipawns each destination square's best move loses against the best move
+    fn heatmap_losses(&self) -> [Option<i32>; 64] {
+        let mut losses = [None; 64];
+        // The heatmap would give training answers away
+        if !self.heatmap_mode
+            || self.heatmap_fen != self.game.to_fen()
+            || self.training_error().is_some()
+        {
+            return losses;
+        }
+        let Some(&best) = self.heatmap_scores.values().max() else {
+            return losses;
+        };
+        for (uci, &cp) in &self.heatmap_scores {
+            if let Ok(shakmaty::uci::UciMove::Normal { to, .. }) =
+                uci.parse::<shakmaty::uci::UciMove>()
+            {
+                let loss = best - cp;
+                let slot = &mut losses[to as usize];
+                *slot = Some(slot.map_or(loss, |l: i32| l.min(loss)));
+            }
+        }
+        losses
+    }
+
+    /// Ask the engine for its static evaluation breakdown of the position
+    fn open_static_eval(&mut self) -> Result<()> {
+        let Some(ref mut engine) = self.engine else {
+            self.input.set_message("No engine running");
+            return Ok(());
+        };
+        if self.heatmap_searching || self.threat_searching {
+            // The engine is set up on another position for these searches
+            engine.set_game(&self.game)?;
+        }
+        engine.static_eval()?;
+        self.static_eval = Some(StaticEval::default());
+        self.popup = Popup::StaticEval(0);
+        Ok(())
+    }
+
+    /// Handle `:candidates <SAN moves>` / `:candidates clear`
+    fn candidates_command(&mut self, args: &str) -> Result<()> {
+        if args.is_empty() || args == "clear" {
+            self.set_candidates(Vec::new())?;
+            self.input.set_message("Analysing all moves");
+            return self.start_analysis();
+        }
+
+        let mut moves = Vec::new();
+        for word in args.split_whitespace() {
+            let m = word
+                .parse::<shakmaty::san::San>()
+                .ok()
+                .and_then(|san| san.to_move(self.game.position()).ok());
+            match m {
+                Some(m) => {
+                    let uci = m.to_uci(self.game.castling_mode()).to_string();
+                    if !moves.contains(&uci) {
+                        moves.push(uci);
+                    }
+                }
+                None => {
+                    self.input.set_error(format!("Illegal move: {}", word));
+                    return Ok(());
+                }
+            }
+        }
+
+        let count = moves.len();
+        self.set_candidates(moves)?;
+        self.input
+            .set_message(format!("Analysing {} candidate move(s)", count));
+        self.start_analysis()
+    }
+
+    /// Position with the side to move passed over to the opponent, if legal
+    fn null_move_position(&self) -> Option<Chess> {
+        self.game.position().clone().swap_turn().ok()
+    }
+
+    /// Ask the engine what the opponent would play if it were their move
+    fn start_threat_search(&mut self) -> Result<()> {
+        let Some(position) = self.null_move_position() else {
+            return Ok(());
+        };
+        if let Some(ref mut engine) = self.engine {
+            let fen = Fen::from_position(position, EnPassantMode::Legal).to_string();
+            engine.set_position(Some(&fen), &[])?;
+            engine.go_depth(self.config.engine.depth.min(THREAT_DEPTH))?;
+            self.threat_searching = true;
+        }
+        Ok(())
+    }
+
+    /// Warn once when the hash has been full for a while
+    fn track_hash_usage(&mut self, hashfull: u32) {
+        if hashfull < HASH_FULL {
+            self.hash_full_since = None;
+            return;
+        }
+        let since = *self.hash_full_since.get_or_insert_with(Instant::now);
+        if !self.hash_warned && since.elapsed() >= HASH_FULL_WARNING {
+            self.hash_warned = true;
+            self.input.set_error(format!(
+                "Hash table full ({} MB), press D to double it",
+                self.config.engine.hash
+            ));
+        }
+    }
+
+    /// Clear the engine's hash table and restart analysis
+    fn clear_hash(&mut self) -> Result<()> {
+        if self.is_playing() {
+            self.input
+                .set_error("Not available while playing (:play stop to end)");
+            return Ok(());
+        }
+        if let Some(ref mut engine) = self.engine {
+            engine.stop()?;
+            engine.clear_hash()?;
+        }
+        self.hash_full_since = None;
+        self.input.set_message("Hash cleared");
+        self.start_analysis()
+    }
+
+    /// Double the engine's hash size and restart analysis
+    fn double_hash(&mut self) -> Result<()> {
+        if self.is_playing() {
+            self.input
+                .set_error("Not available while playing (:play stop to end)");
+            return Ok(());
+        }
+        if self.config.engine.hash >= MAX_HASH_MB {
+            self.input
+                .set_error(format!("Hash is already {} MB", self.config.engine.hash));
+            return Ok(());
+        }
+        self.config.engine.hash = (self.config.engine.hash * 2).min(MAX_HASH_MB);
+        if let Some(ref mut engine) = self.engine {
+            engine.stop()?;
+            engine.set_option("Hash", &self.config.engine.hash.to_string())?;
+        }
+        self.hash_full_since = None;
+        self.hash_warned = false;
+        self.input
+            .set_message(format!("Hash set to {} MB", self.config.engine.hash));
+        self.start_analysis()
+    }
+
+    /// Pass: let the other side move in the current position, as a new
+    /// game to analyse, or go back to the game from before the pass
+    fn toggle_pass(&mut self) -> Result<()> {
+        if let Some((game, _)) = self.before_pass.take() {
+            self.game = game;
+            self.input.set_message("Null move undone");
+            return self.start_analysis();
+        }
+        if self.play.is_some() {
+            self.input
+                .set_error("Not available while playing (:play stop to end)");
+            return Ok(());
+        }
+        if let Some(error) = self.training_error() {
+            self.input.set_error(error);
+            return Ok(());
+        }
+        let Some(position) = self.null_move_position() else {
+            self.input.set_error("Can't pass while in check");
+            return Ok(());
+        };
+        let fen = Fen::from_position(position.clone(), EnPassantMode::Legal).to_string();
+        let game = Game::from_fen(&fen)?;
+        let start = game.initial_position().clone();
+        self.before_pass = Some((std::mem::replace(&mut self.game, game), start));
+        self.input
+            .set_message("Null move: analysis only, :pass again to undo");
+        self.start_analysis()
+    }
+
+    /// Toggle the threat search for the current position
+    fn toggle_threat(&mut self) -> Result<()> {
+        self.show_threat = !self.show_threat;
+        if !self.show_threat {
+            self.input.set_message("Threat display disabled");
+        } else if self.game.position().is_check() {
+            self.input
+                .set_message("Threat display enabled (no threat while in check)");
+        } else {
+            self.input.set_message("Threat display enabled");
+        }
+        self.start_analysis()
+    }
+
+    /// Describe the current threat for the analysis panel
+    fn threat_text(&self) -> Option<String> {
+        let threat = self.threat.as_ref()?;
+        let position = self.null_move_position()?;
+        let pv = self.analysis.san(&position, &threat.pv);
+        let moves: Vec<&str> = pv.iter().take(4).map(|s| s.as_str()).collect();
+        Some(format!(
+            "{} {}",
+            format_score(threat.score_cp, threat.score_mate),
+            moves.join(" ")
+        ))
+    }
+
+    /// Arrow for the threatened move
+    fn threat_arrow(&self) -> Option<Arrow> {
+        let threat = self.threat.as_ref()?;
+        let uci = threat.pv.first()?.parse::<shakmaty::uci::UciMove>().ok()?;
+        let m = uci.to_move(&self.null_move_position()?).ok()?;
+        Some(Arrow {
+            from: m.from()?,
+            to: m.to(),
+            color: MarkColor::Red,
+        })
+    }
+
+    /// Check if a game against the engine is in progress
+    pub fn is_playing(&self) -> bool {
+        self.play.as_ref().is_some_and(|p| p.result.is_none())
+    }
+
+    /// Start a game against the engine from the current position
+    fn start_play(&mut self, args: &str) -> Result<()> {
+        if self.engine.is_none() {
+            self.input.set_error("No engine available to play against");
+            return Ok(());
+        }
+        if let Some(error) = self.training_error() {
+            self.input.set_error(error);
+            return Ok(());
+        }
+
+        let mut user_color = self.game.turn();
+        let mut time_control = TimeControl::parse(&self.config.play.time_control).ok();
+        for arg in args.split_whitespace() {
+            match arg.to_lowercase().as_str() {
+                "white" | "w" => user_color = shakmaty::Color::White,
+                "black" | "b" => user_color = shakmaty::Color::Black,
+                "untimed" | "none" => time_control = None,
+                tc => match TimeControl::parse(tc) {
+                    Ok(tc) => time_control = Some(tc),
+                    Err(e) => {
+                        self.input
+                            .set_error(format!("Invalid argument '{}': {}", arg, e));
+                        return Ok(());
+                    }
+                },
+            }
+        }
+
+        // Play continues from the latest position
+        self.game.go_to_end();
+        self.pending_delta = None;
+        self.analysis.clear();
+        self.analysis.is_running = false;
+        if let Some(ref mut engine) = self.second_engine {
+            engine.stop()?;
+            self.second_analysis.clear();
+            self.second_analysis.is_running = false;
+        }
+        if let Some(ref mut engine) = self.engine {
+            engine.stop()?;
+            engine.new_game()?;
+            engine.set_option("Ponder", &self.config.play.ponder.to_string())?;
+        }
+        self.apply_play_strength()?;
+
+        let mut session = PlaySession::new(user_color, time_control, &self.game);
+        if let Some(ref mut clock) = session.clock {
+            clock.start(self.game.turn());
+        }
+        let mut message = session.describe();
+        if self.config.play.adaptive && self.drill.is_none() {
+            message = format!("{}, adaptive {} Elo", message, SparringRecord::load().elo);
+        }
+        self.input.set_message(message);
+        self.play = Some(session);
+
+        self.update_play()
+    }
+
+    /// Set up a Chess960 start position, random or by number, and analyse
+    /// it or (with `play`) play it against the engine
+    fn start_chess960(&mut self, args: &str) -> Result<()> {
+        let mut words = args.split_whitespace().peekable();
+        let number = match words.peek().map(|w| w.parse::<u32>()) {
+            Some(Ok(n)) if n < CHESS960_POSITIONS => {
+                words.next();
+                n
+            }
+            Some(Ok(_)) => {
+                self.input
+                    .set_error("Chess960 positions are numbered 0-959");
+                return Ok(());
+            }
+            _ => random_chess960(),
+        };
+        let play = match words.next() {
+            Some("play") => Some(words.collect::<Vec<_>>().join(" ")),
+            None => None,
+            Some(_) => {
+                self.input
+                    .set_error("Usage: :chess960 [0-959] [play [white|black] [5+3|untimed]]");
+                return Ok(());
+            }
+        };
+        if self.is_playing() {
+            self.input
+                .set_error("Not available while playing (:play stop to end)");
+            return Ok(());
+        }
+        if let Some(error) = self.training_error() {
+            self.input.set_error(error);
+            return Ok(());
+        }
+
+        self.game.load_fen(&chess960_fen(number))?;
+        self.game.set_header("Variant", "Chess960");
+        let message = format!("Chess960 position #{}", number);
+        match play {
+            Some(args) => {
+                self.start_play(&args)?;
+                if self.is_playing() {
+                    self.input.set_message(message);
+                }
+            }
+            None => {
+                self.input.set_message(message);
+                self.start_analysis()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Practise a theoretical endgame against the engine at full strength
+    fn start_drill(&mut self, args: &str) -> Result<()> {
+        let key = match args {
+            "" => {
+                let names: Vec<&str> = DRILLS.iter().map(|d| d.key).collect();
+                self.input
+                    .set_message(format!("Drills: {}", names.join(", ")));
+                return Ok(());
+            }
+            "retry" => match self.drill {
+                Some(ref session) => session.drill.key,
+                None => {
+                    self.input.set_error("No drill to retry");
+                    return Ok(());
+                }
+            },
+            key => key,
+        };
+        let Some(drill) = find_drill(key) else {
+            self.input
+                .set_error(format!("Unknown drill '{}' (:drill lists them)", key));
+            return Ok(());
+        };
+        if self.engine.is_none() {
+            self.input.set_error("No engine available to play against");
+            return Ok(());
+        }
+        if self.is_playing() && self.drill.is_none() {
+            self.input
+                .set_error("Not available while playing (:play stop to end)");
+            return Ok(());
+        }
+        if let Some(error) = self.training_error() {
+            self.input.set_error(error);
+            return Ok(());
+        }
+
+        self.game.load_fen(drill.fen)?;
+        self.game
+            .set_header("Event", &format!("Endgame drill: {}", drill.name));
+        let streak = DrillRecord::load().streak(drill.key);
+        self.drill = Some(DrillSession::new(drill, self.game.turn(), streak));
+        self.start_play("untimed")?;
+        if self.is_playing() {
+            self.input.set_message(format!("Drill: {}", drill.name));
+        }
+        Ok(())
+    }
+
+    /// End the drill, recording the result for the streak
+    fn finish_drill(&mut self, end: DrillEnd) -> Result<()> {
+        let Some(ref mut session) = self.drill else {
+            return Ok(());
+        };
+        let mut record = DrillRecord::load();
+        session.streak = record.record(session.drill.key, end.success);
+        let streak = &session.streak;
+        let message = if end.success {
+            format!(
+                "Success: {}! Streak {} (best {})",
+                end.reason, streak.current, streak.best
+            )
+        } else {
+            format!(
+                "Failed: {}. Streak reset (best {})",
+                end.reason, streak.best
+            )
+        };
+        let result = end.result;
+        session.end = Some(end);
+        let saved = record.save();
+
+        self.finish_play(result.to_string(), String::new())?;
+        match saved {
+            Ok(()) => self.input.set_message(message),
+            Err(e) => self.input.set_error(format!("{:#}", e)),
+        }
+        Ok(())
+    }
+
+    /// Send the configured strength limits to the engine for a game against it
+    fn apply_play_strength(&mut self) -> Result<()> {
+        // Drills are played against full strength
+        if self.drill.is_some() {
+            return self.reset_strength();
+        }
+        let Some(ref mut engine) = self.engine else {
+            return Ok(());
+        };
+        let strength = &self.config.engine;
+        // The adaptive sparring Elo takes precedence over a fixed limit
+        let elo = if self.config.play.adaptive {
+            Some(SparringRecord::load().elo)
+        } else {
+            strength.elo
+        };
+        engine.set_option(
+            "Skill Level",
+            &strength.skill_level.unwrap_or(20).to_string(),
+        )?;
+        engine.set_option("UCI_LimitStrength", &elo.is_some().to_string())?;
+        if let Some(elo) = elo {
+            engine.set_option("UCI_Elo", &elo.to_string())?;
+        }
+        Ok(())
+    }
+
+    /// Restore full strength for analysis
+    fn reset_strength(&mut self) -> Result<()> {
+        if let Some(ref mut engine) = self.engine {
+            engine.set_option("Skill Level", "20")?;
+            engine.set_option("UCI_LimitStrength", "false")?;
+        }
+        Ok(())
+    }
+
+    /// React to a position change during a game against the engine: press the
+    /// clock for a newly played move and let the engine reply on its turn
+    fn update_play(&mut self) -> Result<()> {
+        let Some(ref mut session) = self.play else {
+            return Ok(());
+        };
+
+        let ply = self.game.moves().len();
+        if ply > session.last_ply {
+            let mover = !self.game.turn();
+            if let Some(ref mut clock) = session.clock {
+                let left = clock.press(mover);
+                if let Some(annotation) = self.game.annotation_at_mut(ply) {
+                    annotation.clock = Some(left);
+                }
+            }
+        }
+        session.last_ply = ply;
+
+        if let Some(end) = self.drill.as_ref().and_then(|d| d.judge(&self.game)) {
+            return self.finish_drill(end);
+        }
+        if let Some(outcome) = self.game.outcome() {
+            let message = match outcome {
+                shakmaty::Outcome::Decisive { winner } if winner == session.user_color => {
+                    "You win!"
+                }
+                shakmaty::Outcome::Decisive { .. } => "Engine wins",
+                shakmaty::Outcome::Draw if self.game.position().is_stalemate() => {
+                    "Draw by stalemate"
+                }
+                shakmaty::Outcome::Draw => "Draw by insufficient material",
+            };
+            return self.finish_play(outcome.to_string(), message.to_string());
+        }
+
+        // Draws an arbiter declares without a claim
+        let automatic = if self.game.repetitions() >= 5 {
+            Some("fivefold repetition")
+        } else if self.game.halfmoves() >= 150 {
+            Some("seventy-five-move rule")
+        } else {
+            None
+        };
+        if let Some(reason) = automatic {
+            return self.finish_play("1/2-1/2".to_string(), format!("Draw by {}", reason));
+        }
+        if let Some(claim) = self.game.draw_claim() {
+            if !session.is_engine_turn(&self.game) {
+                self.input.set_message(format!(
+                    "You can claim a draw by {} with :claim",
+                    claim.name()
+                ));
+            }
+        }
+
+        if session.is_engine_turn(&self.game) && !session.engine_thinking {
+            if let Some(ref mut engine) = self.engine {
+                // A correctly predicted reply lets the ponder search carry on;
+                // on a miss it is stopped below and its bestmove discarded
+                if let Some(expected) = session.ponder_move.take() {
+                    let played = self
+                        .game
+                        .moves()
+                        .last()
+                        .map(|m| m.to_uci(self.game.castling_mode()).to_string());
+                    if engine.is_pondering() && played.as_deref() == Some(expected.as_str()) {
+                        engine.ponderhit()?;
+                        self.last_fen = self.game.to_fen();
+                        session.engine_thinking = true;
+                        return Ok(());
+                    }
+                }
+
+                engine.stop()?;
+                engine.set_game(&self.game)?;
+                self.last_fen = self.game.to_fen();
+
+                match session.clock {
+                    Some(ref clock) => {
+                        let ms = |c| clock.remaining(c).as_millis() as u64;
+                        let inc = clock.increment().as_millis() as u64;
+                        engine.go_clock(
+                            ms(shakmaty::Color::White),
+                            ms(shakmaty::Color::Black),
+                            inc,
+                            inc,
+                        )?;
+                    }
+                    None => engine.go_movetime(self.config.play.move_time_ms)?,
+                }
+                session.engine_thinking = true;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Play the engine's chosen move in a game against it
+    fn play_engine_move(&mut self, best: BestMove) -> Result<()> {
+        let best_move = best.best_move.as_str();
+        let Some(ref mut session) = self.play else {
+            return Ok(());
+        };
+        if !session.engine_thinking || !session.is_engine_turn(&self.game) {
+            return Ok(());
+        }
+        session.engine_thinking = false;
+
+        let m = best_move
+            .parse::<shakmaty::uci::UciMove>()
+            .ok()
+            .and_then(|uci| uci.to_move(self.game.position()).ok());
+        match m {
+            Some(m) => {
+                self.game.make_move(m)?;
+                self.update_play()?;
+                if let Some(ponder) = best.ponder {
+                    self.start_ponder(ponder)?;
+                }
+            }
+            None => {
+                self.input
+                    .set_error(format!("Engine played an illegal move: {}", best_move));
+            }
+        }
+        Ok(())
+    }
+
+    /// Let the engine think on the user's time, assuming they reply with
+    /// `ponder_move`
+    fn start_ponder(&mut self, ponder_move: String) -> Result<()> {
+        if !self.config.play.ponder || !self.is_playing() {
+            return Ok(());
+        }
+        let Some(ref mut session) = self.play else {
+            return Ok(());
+        };
+        let Some(ref mut engine) = self.engine else {
+            return Ok(());
+        };
+        if session.is_engine_turn(&self.game) || self.game.is_game_over() {
+            return Ok(());
+        }
+        let legal = ponder_move
+            .parse::<shakmaty::uci::UciMove>()
+            .is_ok_and(|uci| uci.to_move(self.game.position()).is_ok());
+        if !legal {
+            return Ok(());
+        }
+
+        let (start, mut moves) = self.game.uci_position();
+        moves.push(ponder_move.clone());
+        engine.set_position(start.as_deref(), &moves)?;
+        match session.clock {
+            Some(ref clock) => {
+                let ms = |c| clock.remaining(c).as_millis() as u64;
+                let inc = clock.increment().as_millis() as u64;
+                engine.go_ponder_clock(
+                    ms(shakmaty::Color::White),
+                    ms(shakmaty::Color::Black),
+                    inc,
+                    inc,
+                )?;
+            }
+            None => engine.go_ponder_movetime(self.config.play.move_time_ms)?,
+        }
+        session.ponder_move = Some(ponder_move);
+        Ok(())
+    }
+
+    /// End the current game against the engine with a PGN result
+    fn finish_play(&mut self, result: String, message: String) -> Result<()> {
+        let Some(ref mut session) = self.play else {
+            return Ok(());
+        };
+        if let Some(ref mut clock) = session.clock {
+            clock.stop();
+        }
+        session.engine_thinking = false;
+        session.result = Some(result.clone());
+        let user_color = session.user_color;
+        if let Some(ref mut engine) = self.engine {
+            engine.stop()?;
+        }
+        self.reset_strength()?;
+
+        self.game.set_header("Result", &result);
+        self.input.set_message(format!("{} ({})", message, result));
+
+        if self.config.play.adaptive && self.drill.is_none() {
+            let user_score = match (result.as_str(), user_color) {
+                ("1-0", shakmaty::Color::White) | ("0-1", shakmaty::Color::Black) => 1.0,
+                ("1/2-1/2", _) => 0.5,
+                _ => 0.0,
+            };
+            let mut record = SparringRecord::load();
+            let old_elo = record.elo;
+            record.record(user_score);
+            if let Err(e) = record.save() {
+                self.input
+                    .set_error(format!("Failed to save sparring record: {}", e));
+            } else {
+                self.input.set_message(format!(
+                    "{} ({}) - engine {} -> {} Elo",
+                    message, result, old_elo, record.elo
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Claim a draw by threefold repetition or the fifty-move rule in a game
+    /// against the engine
+    fn claim_draw(&mut self) -> Result<()> {
+        if !self.game.is_at_end() {
+            self.input.set_error("Go to the last move to claim a draw");
+            return Ok(());
+        }
+        match self.game.draw_claim() {
+            Some(claim) => {
+                self.finish_play("1/2-1/2".to_string(), format!("Draw by {}", claim.name()))
+            }
+            None => {
+                self.input.set_error(format!(
+                    "No draw to claim: position seen {}×, {} plies without a capture or pawn move",
+                    self.game.repetitions(),
+                    self.game.halfmoves()
+                ));
+                Ok(())
+            }
+        }
+    }
+
+    /// Check the game clock for a flag fall
+    fn check_play_clock(&mut self) -> Result<()> {
+        let Some(ref mut session) = self.play else {
+            return Ok(());
+        };
+        if session.result.is_some() {
+            return Ok(());
+        }
+        let Some(flagged) = session.clock.as_mut().and_then(|c| c.check_flag()) else {
+            return Ok(());
+        };
+
+        let (result, loser) = match flagged {
+            shakmaty::Color::White => ("0-1", "White"),
+            shakmaty::Color::Black => ("1-0", "Black"),
+        };
+        self.finish_play(result.to_string(), format!("{} lost on time", loser))
+    }
+
+    /// Remember the engine's verdict on the position before a move is played,
+    /// so the played move can be compared against the best move
+    fn begin_move_delta(&mut self, before: &Chess, played: &Move) {
+        self.pending_delta = None;
+
+        let Some(best_line) = self.analysis.lines.first() else {
+            return;
+        };
+        let Some(best_cp) = score_to_cp(best_line.score_cp, best_line.score_mate) else {
+            return;
+        };
+        let Some(best) = uci_to_san(before, &best_line.pv[..1]).pop() else {
+            return;
+        };
+
+        let ply = self.game.current_index() - 1;
+        let played_uci = played.to_uci(self.game.castling_mode()).to_string();
+        let played_san = shakmaty::san::San::from_move(before, played).to_string();
+
+        // If the move is one of the analysed lines, its score is already known
+        let known = self
+            .analysis
+            .lines
+            .iter()
+            .find(|l| l.pv.first() == Some(&played_uci))
+            .and_then(|l| score_to_cp(l.score_cp, l.score_mate));
+        if let Some(played_cp) = known {
+            self.record_move_delta(MoveDelta {
+                ply,
+                played: played_san,
+                best,
+                loss_cp: (best_cp - played_cp).max(0),
+            });
+            return;
+        }
+
+        self.pending_delta = Some(PendingDelta {
+            ply,
+            played: played_san,
+            best,
+            best_cp,
+            depth: best_line.depth.unwrap_or(1),
+            fen_after: self.game.to_fen(),
+        });
+    }
+
+    /// Resolve the pending delta once the new position has been searched
+    /// about as deeply as the position before the move
+    fn check_pending_delta(&mut self) {
+        let Some(ref pending) = self.pending_delta else {
+            return;
+        };
+        if pending.fen_after != self.last_fen {
+            self.pending_delta = None;
+            return;
+        }
+
+        let Some(line) = self.analysis.lines.first() else {
+            return;
+        };
+        let reached = line.depth.unwrap_or(0) >= pending.depth;
+        if !reached && self.analysis.is_running {
+            return;
+        }
+        if let Some(score) = score_to_cp(line.score_cp, line.score_mate) {
+            self.resolve_move_delta(score);
+        }
+    }
+
+    /// Finish the pending delta given the score of the position after the
+    /// move, from the perspective of the side now to move
+    fn resolve_move_delta(&mut self, score_after: i32) {
+        let Some(pending) = self.pending_delta.take() else {
+            return;
+        };
+        self.record_move_delta(MoveDelta {
+            ply: pending.ply,
+            played: pending.played,
+            best: pending.best,
+            loss_cp: (pending.best_cp + score_after).max(0),
+        });
+    }
+
+    /// Store a move delta and report it in the input area
+    fn record_move_delta(&mut self, delta: MoveDelta) {
+        if delta.played == delta.best || delta.loss_cp == 0 {
+            self.input
+                .set_message(format!("{} matches the engine's best move", delta.played));
+        } else {
+            self.input.set_message(format!(
+                "{:+.2} vs {}",
+                -(delta.loss_cp as f64) / 100.0,
+                delta.best
+            ));
+        }
+
+        // A new move at this ply replaces any line that continued from here
+        self.move_deltas.retain(|d| d.ply < delta.ply);
+        self.move_deltas.push(delta);
+    }
+
+    /// Step one move back or forward, analysing the position reached; does
+    /// nothing at either end of the game
+    fn step(&mut self, forward: bool) -> Result<()> {
+        let moved = if forward {
+            self.game.go_forward()
+        } else {
+            self.game.go_back()
+        };
+        if moved {
+            self.start_analysis()?;
+        }
+        Ok(())
+    }
+
+    /// Handle a keyboard event
+    pub fn handle_key_event(&mut self, key: KeyEvent) -> Result<()> {
+        // Handle Ctrl+C globally
+        if key.modifiers.contains(KeyModifiers::CONTROL) && key.code == KeyCode::Char('c') {
+            self.should_quit = true;
+            return Ok(());
+        }
+
+        // Handle popup-specific input
+        match &self.popup {
+            Popup::Help => {
+                match key.code {
+                    KeyCode::Esc | KeyCode::Char('?') | KeyCode::Char('q') => {
+                        self.popup = Popup::None;
+                    }
+                    _ => {}
+                }
+                return Ok(());
+            }
+            Popup::Import => {
+                match key.code {
+                    KeyCode::Esc => {
+                        self.popup = Popup::None;
+                    }
+                    KeyCode::Char('f') => {
+                        self.popup = Popup::None;
+                        self.input.enter_fen_mode();
+                    }
+                    KeyCode::Char('p') => {
+                        self.popup = Popup::None;
+                        self.input.enter_pgn_mode();
+                    }
+                    KeyCode::Char('n') => {
+                        self.popup = Popup::None;
+                        self.game.reset();
+                        self.start_analysis()?;
+                        self.input.set_message("New game started");
+                    }
+                    _ => {}
+                }
+                return Ok(());
+            }
+            Popup::Depth(input) => {
+                let mut input = input.clone();
+                match key.code {
+                    KeyCode::Esc => {
+                        self.popup = Popup::None;
+                    }
+                    KeyCode::Enter => {
+                        if let Ok(depth) = input.parse::<u32>() {
+                            if depth > 0 && depth <= 100 {
+                                self.config.engine.depth = depth;
+                                self.analysis.target_depth = depth;
+                                self.second_analysis.target_depth = depth;
+                                self.popup = Popup::None;
+                                self.start_analysis()?;
+                                self.input.set_message(format!("Depth set to {}", depth));
+                            } else {
+                                self.input.set_error("Depth must be between 1 and 100");
+                            }
+                        } else {
+                            self.input.set_error("Invalid depth value");
+                        }
+                        self.popup = Popup::None;
+                    }
+                    KeyCode::Char(c) if c.is_ascii_digit() => {
+                        input.push(c);
+                        self.popup = Popup::Depth(input);
+                    }
+                    KeyCode::Backspace => {
+                        input.pop();
+                        self.popup = Popup::Depth(input);
+                    }
+                    _ => {}
+                }
+                return Ok(());
+            }
+            Popup::MultiPV(input) => {
+                let mut input = input.clone();
+                match key.code {
+                    KeyCode::Esc => {
+                        self.popup = Popup::None;
+                    }
+                    KeyCode::Enter => {
+                        if let Ok(multipv) = input.parse::<u32>() {
+                            if multipv > 0 && multipv <= 10 {
+                                self.config.engine.multipv = multipv;
+                                for engine in
+                                    self.engine.iter_mut().chain(self.second_engine.iter_mut())
+                                {
+                                    let _ = engine.set_option("MultiPV", &multipv.to_string());
+                                }
+                                self.popup = Popup::None;
+                                self.start_analysis()?;
+                                self.input
+                                    .set_message(format!("MultiPV set to {}", multipv));
+                            } else {
+                                self.input.set_error("MultiPV must be between 1 and 10");
+                            }
+                        } else {
+                            self.input.set_error("Invalid MultiPV value");
+                        }
+                        self.popup = Popup::None;
+                    }
+                    KeyCode::Char(c) if c.is_ascii_digit() => {
+                        input.push(c);
+                        self.popup = Popup::MultiPV(input);
+                    }
+                    KeyCode::Backspace => {
+                        input.pop();
+                        self.popup = Popup::MultiPV(input);
+                    }
+                    _ => {}
+                }
+                return Ok(());
+            }
+            Popup::Strength(input) => {
+                let mut input = input.clone();
+                match key.code {
+                    KeyCode::Esc => {
+                        self.popup = Popup::None;
+                    }
+                    KeyCode::Enter => {
+                        self.popup = Popup::None;
+                        match input.parse::<u32>() {
+                            Err(_) if input.is_empty() => {
+                                self.config.engine.skill_level = None;
+                                self.config.engine.elo = None;
+                            }
+                            Ok(level) if level <= 20 => {
+                                self.config.engine.skill_level = Some(level);
+                                self.config.engine.elo = None;
+                            }
+                            Ok(elo) if (500..=4000).contains(&elo) => {
+                                self.config.engine.elo = Some(elo);
+                            }
+                            _ => {
+                                self.input
+                                    .set_error("Enter a skill level (0-20) or an Elo (500-4000)");
+                                return Ok(());
+                            }
+                        }
+                        if self.is_playing() {
+                            self.apply_play_strength()?;
+                        }
+                        self.input.set_message(format!(
+                            "Play strength: {}",
+                            self.config.engine.strength_label()
+                        ));
+                    }
+                    KeyCode::Char(c) if c.is_ascii_digit() => {
+                        input.push(c);
+                        self.popup = Popup::Strength(input);
+                    }
+                    KeyCode::Backspace => {
+                        input.pop();
+                        self.popup = Popup::Strength(input);
+                    }
+                    _ => {}
+                }
+                return Ok(());
+            }
+            Popup::Engine(candidates, selected) => {
+                let (candidates, mut selected) = (candidates.clone(), *selected);
+                match key.code {
+                    KeyCode::Esc | KeyCode::Char('q') => {
+                        self.popup = Popup::None;
+                    }
+                    KeyCode::Enter => {
+                        self.popup = Popup::None;
+                        if let Some(path) = candidates.get(selected) {
+                            self.switch_engine(path)?;
+                        }
+                    }
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        selected = selected.saturating_sub(1);
+                        self.popup = Popup::Engine(candidates, selected);
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        selected = (selected + 1).min(candidates.len().saturating_sub(1));
+                        self.popup = Popup::Engine(candidates, selected);
+                    }
+                    _ => {}
+                }
+                return Ok(());
+            }
+            Popup::Library(selected) => {
+                let selected = *selected;
+                match key.code {
+                    KeyCode::Esc | KeyCode::Char('q') => {
+                        self.popup = Popup::None;
+                    }
+                    KeyCode::Enter => {
+                        self.popup = Popup::None;
+                        self.load_library_entry(selected)?;
+                    }
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        self.popup = Popup::Library(selected.saturating_sub(1));
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        self.popup = Popup::Library((selected + 1).min(LIBRARY.len() - 1));
+                    }
+                    _ => {}
+                }
+                return Ok(());
+            }
+            Popup::Games(pgn_files, selected) => {
+                let last = pgn_files.len().saturating_sub(1);
+                let selected = match key.code {
+                    KeyCode::Esc | KeyCode::Char('q') => {
+                        self.popup = Popup::None;
+                        return Ok(());
+                    }
+                    KeyCode::Enter => {
+                        let path = pgn_files[*selected].path.to_string_lossy().to_string();
+                        self.popup = Popup::None;
+                        return self.load_pgn_file(&path);
+                    }
+                    KeyCode::Up | KeyCode::Char('k') => selected.saturating_sub(1),
+                    KeyCode::Down | KeyCode::Char('j') => (*selected + 1).min(last),
+                    KeyCode::PageUp => selected.saturating_sub(10),
+                    KeyCode::PageDown => (*selected + 10).min(last),
+                    KeyCode::Home | KeyCode::Char('g') => 0,
+                    KeyCode::End | KeyCode::Char('G') => last,
+                    _ => *selected,
+                };
+                if let Popup::Games(_, current) = &mut self.popup {
+                    *current = selected;
+                }
+                return Ok(());
+            }
+            Popup::Explorer(source, selected) => {
+                let (source, selected) = (*source, *selected);
+                let last = self
+                    .explorer_stats(source)
+                    .ok()
+                    .flatten()
+                    .map_or(0, |stats| stats.moves.len().saturating_sub(1));
+                match key.code {
+                    KeyCode::Esc | KeyCode::Char('q' | 'X') => self.popup = Popup::None,
+                    KeyCode::Tab => {
+                        let other = source.other();
+                        if other == ExplorerSource::Games && self.opening_tree.games() == 0 {
+                            self.input.set_error("No game database loaded");
+                        } else {
+                            self.popup = Popup::Explorer(other, 0);
+                        }
+                    }
+                    KeyCode::Enter | KeyCode::Right | KeyCode::Char('l') => {
+                        self.play_explorer_move(source, selected)?;
+                        self.popup = Popup::Explorer(source, 0);
+                    }
+                    KeyCode::Left | KeyCode::Backspace | KeyCode::Char('h') => {
+                        // Select the move taken back, to try its neighbours
+                        let index = self.game.current_index();
+                        let undone = index
+                            .checked_sub(1)
+                            .map(|i| self.game.moves()[i].to_uci(self.game.castling_mode()));
+                        if self.game.go_back() {
+                            self.start_analysis()?;
+                        }
+                        let selected = undone
+                            .and_then(|uci| {
+                                let uci = uci.to_string();
+                                self.explorer_stats(source)
+                                    .ok()??
+                                    .moves
+                                    .iter()
+                                    .position(|m| m.uci == uci)
+                            })
+                            .unwrap_or(0);
+                        self.popup = Popup::Explorer(source, selected);
+                    }
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        self.popup = Popup::Explorer(source, selected.saturating_sub(1));
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        self.popup = Popup::Explorer(source, (selected + 1).min(last));
+                    }
+                    _ => {}
+                }
+                return Ok(());
+            }
+            Popup::Headers(selected, input) => {
+                let (selected, input) = (*selected, input.clone());
+                let rows = self.header_rows();
+                match (input, key.code) {
+                    (Some(_), KeyCode::Esc) => self.popup = Popup::Headers(selected, None),
+                    (Some(text), KeyCode::Enter) => match self.edit_header(selected, &text) {
+                        Ok(()) => {
+                            // A new tag goes above the new tag row, which stays selected
+                            let selected = if selected >= rows.len() {
+                                self.header_rows().len()
+                            } else {
+                                selected
+                            };
+                            self.popup = Popup::Headers(selected, None);
+                        }
+                        Err(e) => {
+                            self.input.set_error(format!("{:#}", e));
+                            self.popup = Popup::Headers(selected, Some(text));
+                        }
+                    },
+                    (Some(mut text), KeyCode::Backspace) => {
+                        text.pop();
+                        self.popup = Popup::Headers(selected, Some(text));
+                    }
+                    (Some(mut text), KeyCode::Char(c)) => {
+                        text.push(c);
+                        self.popup = Popup::Headers(selected, Some(text));
+                    }
+                    (Some(_), _) => {}
+                    (None, KeyCode::Esc | KeyCode::Char('q')) => self.popup = Popup::None,
+                    (None, KeyCode::Enter) => {
+                        let value = rows.get(selected).map_or("", |(_, v)| v.as_str());
+                        self.popup = Popup::Headers(selected, Some(value.to_string()));
+                    }
+                    (None, KeyCode::Up | KeyCode::Char('k')) => {
+                        self.popup = Popup::Headers(selected.saturating_sub(1), None);
+                    }
+                    (None, KeyCode::Down | KeyCode::Char('j')) => {
+                        self.popup = Popup::Headers((selected + 1).min(rows.len()), None);
+                    }
+                    (None, KeyCode::Delete | KeyCode::Char('d')) => {
+                        if let Some((name, _)) = rows.get(selected) {
+                            self.game.remove_header(name);
+                        }
+                        let rows = self.header_rows().len();
+                        self.popup = Popup::Headers(selected.min(rows), None);
+                    }
+                    (None, _) => {}
+                }
+                return Ok(());
+            }
+            Popup::UciLog(scroll) => {
+                let scroll = *scroll;
+                let max = self
+                    .engine
+                    .as_ref()
+                    .map(|e| e.log().entries().len())
+                    .unwrap_or(0);
+                match key.code {
+                    KeyCode::Esc | KeyCode::Char('q' | 'U') => {
+                        self.popup = Popup::None;
+                    }
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        self.popup = Popup::UciLog((scroll + 1).min(max));
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        self.popup = Popup::UciLog(scroll.saturating_sub(1));
+                    }
+                    KeyCode::PageUp => {
+                        self.popup = Popup::UciLog((scroll + 20).min(max));
+                    }
+                    KeyCode::PageDown => {
+                        self.popup = Popup::UciLog(scroll.saturating_sub(20));
+                    }
+                    KeyCode::Home => {
+                        self.popup = Popup::UciLog(max);
+                    }
+                    KeyCode::End => {
+                        self.popup = Popup::UciLog(0);
+                    }
+                    _ => {}
+                }
+                return Ok(());
+            }
+            Popup::Summary(_) | Popup::BoardText => {
+                if matches!(key.code, KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q')) {
+                    self.popup = Popup::None;
+                }
+                return Ok(());
+            }
+            Popup::StaticEval(scroll) => {
+                let scroll = *scroll;
+                match key.code {
+                    KeyCode::Esc | KeyCode::Char('q' | 'v') => {
+                        self.popup = Popup::None;
+                        self.static_eval = None;
+                    }
+                    KeyCode::Down | KeyCode::Char('j') => {
+                        self.popup = Popup::StaticEval(scroll + 1);
+                    }
+                    KeyCode::Up | KeyCode::Char('k') => {
+                        self.popup = Popup::StaticEval(scroll.saturating_sub(1));
+                    }
+                    _ => {}
+                }
+                return Ok(());
+            }
+            Popup::None => {}
+        }
+
+        // Handle input mode
+        if self.input.is_input_mode() {
+            match key.code {
+                KeyCode::Esc => {
+                    self.input.exit_mode();
+                }
+                KeyCode::Enter => {
+                    self.handle_input_submit()?;
+                }
+                KeyCode::Backspace => {
+                    self.input.backspace();
+                }
+                KeyCode::Delete => {
+                    self.input.delete();
+                }
+                KeyCode::Left => {
+                    self.input.move_left();
+                }
+                KeyCode::Right => {
+                    self.input.move_right();
+                }
+                KeyCode::Home => {
+                    self.input.move_start();
+                }
+                KeyCode::End => {
+                    self.input.move_end();
+                }
+                KeyCode::Tab => self.complete_input(),
+                KeyCode::Char(c) => {
+                    self.input.insert(c);
+                }
+                _ => {}
+            }
+            return Ok(());
+        }
+
+        if self.autoplay_key(key.code) {
+            return Ok(());
+        }
+
+        // Normal mode shortcuts
+        match key.code {
+            // Ctrl, as O starts castling
+            KeyCode::Char('o') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                self.open_game_browser(None)
+            }
+            KeyCode::Char('q') => {
+                self.should_quit = true;
+            }
+            KeyCode::Char('?') => {
+                self.popup = Popup::Help;
+            }
+            KeyCode::Char('i') => {
+                self.popup = Popup::Import;
+            }
+            KeyCode::Char('f') => {
+                self.config.ui.flip_board = !self.config.ui.flip_board;
+            }
+            KeyCode::Char('e') => {
+                self.config.ui.analysis_eval_only = !self.config.ui.analysis_eval_only;
+                let mode = if self.config.ui.analysis_eval_only {
+                    "Analysis eval-only enabled"
+                } else {
+                    "Analysis eval-only disabled"
+                };
+                self.input.set_message(mode);
+            }
+            KeyCode::Char('x') => {
+                self.config.ui.show_hanging = !self.config.ui.show_hanging;
+                let mode = if self.config.ui.show_hanging {
+                    "Hanging piece overlay enabled"
+                } else {
+                    "Hanging piece overlay disabled"
+                };
+                self.input.set_message(mode);
+            }
+            KeyCode::Char('w') => {
+                self.config.ui.white_perspective = !self.config.ui.white_perspective;
+                let mode = if self.config.ui.white_perspective {
+                    "Scores from White's point of view"
+                } else {
+                    "Scores from the side to move's point of view"
+                };
+                self.input.set_message(mode);
+            }
+            KeyCode::Char('p') => {
+                self.toggle_pause()?;
+            }
+            KeyCode::Char('d') => {
+                self.popup = Popup::Depth(String::new());
+            }
+            KeyCode::Char('m') => {
+                self.popup = Popup::MultiPV(String::new());
+            }
+            KeyCode::Char('S') => {
+                self.popup = Popup::Strength(String::new());
+            }
+            KeyCode::Char('E') => {
+                self.open_engine_picker();
+            }
+            KeyCode::Char('U') => {
+                self.popup = Popup::UciLog(0);
+            }
+            KeyCode::Char('L') => {
+                self.popup = Popup::Library(0);
+            }
+            KeyCode::Char('X') => self.open_explorer(""),
+            KeyCode::Char('W') => self.open_on_lichess(""),
+            KeyCode::Char(' ') => self.autoplay_command("")?,
+            KeyCode::Char('G') => {
+                self.popup = Popup::Headers(0, None);
+            }
+            KeyCode::Char('V') => self.cycle_layout(),
+            KeyCode::Char('z') => {
+                self.zen = !self.zen;
+            }
+            KeyCode::Char('T') => self.set_mouse_capture(""),
+            KeyCode::Char('F') => self.set_show_fen(""),
+            KeyCode::Char('A') => self.toggle_move_notes(),
+            KeyCode::Char('P') => {
+                let style = self.config.ui.get_piece_style().next();
+                self.config.ui.piece_style = style.name().to_string();
+                self.input
+                    .set_message(format!("Piece style: {}", style.name()));
+            }
+            KeyCode::Char('M') => {
+                self.show_moves = !self.show_moves;
+                self.input.set_message(if self.show_moves {
+                    "Move list shown in the compact layout"
+                } else {
+                    "Move list hidden in the compact layout"
+                });
+            }
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.selected_line = Some(self.selected_line.map_or(0, |i| i.saturating_sub(1)));
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                let last = (self.config.engine.multipv as usize).saturating_sub(1);
+                self.selected_line = Some(self.selected_line.map_or(0, |i| (i + 1).min(last)));
+            }
+            KeyCode::Esc if self.selected_line.is_some() => {
+                self.selected_line = None;
+                self.expand_line = false;
+            }
+            KeyCode::Char('o') if self.selected_line.is_some() => {
+                self.expand_line = !self.expand_line;
+            }
+            KeyCode::Char('C') => {
+                self.copy_analysis_line();
+            }
+            KeyCode::Char('t') => {
+                self.toggle_threat()?;
+            }
+            KeyCode::Char('H') => {
+                self.toggle_heatmap()?;
+            }
+            KeyCode::Char('v') => {
+                self.open_static_eval()?;
+            }
+            KeyCode::Char('D') => {
+                self.double_hash()?;
+            }
+            KeyCode::Char('+') => {
+                self.deepen(DEEPEN_PLIES)?;
+            }
+            KeyCode::Char('y') => {
+                self.copy_fen_to_clipboard();
+            }
+            KeyCode::Char('Y') => {
+                self.copy_pgn_to_clipboard();
+            }
+            KeyCode::Left
+            | KeyCode::Right
+            | KeyCode::Home
+            | KeyCode::End
+            | KeyCode::Char(
+                'h' | 'l' | '1'..='9' | '[' | ']' | '{' | '}' | '(' | ')' | '<' | '>',
+            ) if self.is_playing() => {
+                self.input
+                    .set_error("Not available while playing (:play stop to end)");
+            }
+            KeyCode::Char('n') if self.mates.as_ref().is_some_and(MateSession::is_done) => {
+                self.next_mate()?;
+            }
+            KeyCode::Char('n')
+                if self
+                    .quiz
+                    .as_ref()
+                    .is_some_and(|q| matches!(q.phase, QuizPhase::Revealed { .. })) =>
+            {
+                self.next_quiz()?;
+            }
+            KeyCode::Char('n')
+                if self
+                    .puzzle
+                    .as_ref()
+                    .is_some_and(|p| p.state != PuzzleState::Solving) =>
+            {
+                self.next_puzzle()?;
+            }
+            KeyCode::Left
+            | KeyCode::Right
+            | KeyCode::Home
+            | KeyCode::End
+            | KeyCode::Char(
+                'h' | 'l' | '1'..='9' | '[' | ']' | '{' | '}' | '(' | ')' | '<' | '>',
+            ) if self.training_error().is_some() => {
+                let error = self.training_error().unwrap_or_default();
+                self.input.set_error(error);
+            }
+            KeyCode::Left | KeyCode::Char('h') => self.step(false)?,
+            KeyCode::Right | KeyCode::Char('l') => self.step(true)?,
+            KeyCode::Home => {
+                self.game.go_to_start();
+                self.start_analysis()?;
+            }
+            KeyCode::End => {
+                self.game.go_to_end();
+                self.start_analysis()?;
+            }
+            KeyCode::Char(']') => {
+                self.goto_critical_moment(true)?;
+            }
+            KeyCode::Char('[') => {
+                self.goto_critical_moment(false)?;
+            }
+            KeyCode::Char('}') => self.goto_move_kind(MoveKind::Capture, true)?,
+            KeyCode::Char('{') => self.goto_move_kind(MoveKind::Capture, false)?,
+            KeyCode::Char(')') => self.goto_move_kind(MoveKind::Check, true)?,
+            KeyCode::Char('(') => self.goto_move_kind(MoveKind::Check, false)?,
+            KeyCode::Char('>') => self.goto_move_kind(MoveKind::PawnBreak, true)?,
+            KeyCode::Char('<') => self.goto_move_kind(MoveKind::PawnBreak, false)?,
+            KeyCode::Enter if self.is_playing() && self.selected_line.is_some() => {
+                self.input
+                    .set_error("Not available while playing (:play stop to end)");
+            }
+            KeyCode::Enter if self.training_error().is_some() && self.selected_line.is_some() => {
+                let error = self.training_error().unwrap_or_default();
+                self.input.set_error(error);
+            }
+            KeyCode::Enter if self.selected_line.is_some() => {
+                let line = self.selected_line.unwrap_or(0);
+                self.play_analysis_line(line)?;
+            }
+            KeyCode::Enter | KeyCode::Char(':') => {
+                self.input.enter_command_mode();
+            }
+            KeyCode::Char(c @ '1'..='9') => {
+                // Play the best move from the selected analysis line
+                let line_idx = (c as usize) - ('1' as usize);
+                self.play_analysis_line(line_idx)?;
+            }
+            KeyCode::Char(c) if c.is_alphabetic() => {
+                // Start typing a move
+                self.input.enter_command_mode();
+                self.input.insert(c);
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Handle input submission
+    fn handle_input_submit(&mut self) -> Result<()> {
+        match self.input.mode {
+            InputMode::Command | InputMode::Normal => {
+                let input = self.input.take();
+                self.input.exit_mode();
+
+                if input.is_empty() {
+                    return Ok(());
+                }
+                self.submit_line(&input)?;
+            }
+            InputMode::Fen => {
+                let input = self.input.take();
+                self.input.exit_mode();
+
+                let fen = input.strip_prefix(":fen ").unwrap_or(&input).trim();
+                if !fen.is_empty() {
+                    self.load_typed_fen(fen)?;
+                }
+            }
+            InputMode::Pgn => {
+                let line = self.input.take();
+
+                // Empty line or double-Enter ends PGN input
+                if line.is_empty() {
+                    self.finish_pgn_input()?;
+                } else {
+                    self.input.pgn_buffer.push(line);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Complete the path argument of a command with Tab: the whole name when
+    /// only one entry matches, otherwise as far as the matches agree, listing
+    /// them
+    fn complete_input(&mut self) {
+        if self.input.mode != InputMode::Command || self.input.cursor != self.input.buffer.len() {
+            return;
+        }
+        let buffer = self.input.buffer.clone();
+        // Commands are typed with or without the colon
+        let command = buffer.strip_prefix(':').unwrap_or(&buffer);
+        if let Some(args) = command.strip_prefix("play ") {
+            self.complete_play(&buffer, args);
+            return;
+        }
+        let Some(partial) = PATH_COMMANDS.iter().find_map(|name| {
+            command
+                .strip_prefix(name)
+                .and_then(|rest| rest.strip_prefix(' '))
+                .map(str::trim_start)
+        }) else {
+            return;
+        };
+
+        let matches = files::complete_path(partial);
+        let completed = files::common_prefix(&matches);
+        if completed.len() > partial.len() {
+            let start = buffer.len() - partial.len();
+            let text = format!("{}{}", &buffer[..start], completed);
+            self.input.set_buffer(text);
+        }
+        match matches.len() {
+            0 => self.input.set_error("No matching files"),
+            1 => self.input.clear_messages(),
+            n => {
+                // Just the names, as the directory was typed already
+                let start = partial.rfind('/').map_or(0, |i| i + 1);
+                let names: Vec<&str> = matches
+                    .iter()
+                    .take(MAX_COMPLETIONS_SHOWN)
+                    .map(|m| &m[start..])
+                    .collect();
+                let more = if n > MAX_COMPLETIONS_SHOWN {
+                    " ..."
+                } else {
+                    ""
+                };
+                self.input
+                    .set_message(format!("{}{}", names.join("  "), more));
+            }
+        }
+    }
+
+    /// Complete the last word of `:play` with a side or one of the
+    /// configured time-control presets
+    fn complete_play(&mut self, buffer: &str, args: &str) {
+        let partial = args.rsplit(' ').next().unwrap_or("");
+        let matches: Vec<String> = ["white", "black", "untimed"]
+            .iter()
+            .map(|s| s.to_string())
+            .chain(self.config.play.time_controls.iter().cloned())
+            .filter(|option| option.starts_with(partial))
+            .collect();
+        let completed = files::common_prefix(&matches);
+        if completed.len() > partial.len() {
+            let start = buffer.len() - partial.len();
+            self.input
+                .set_buffer(format!("{}{}", &buffer[..start], completed));
+        }
+        match matches.len() {
+            0 => self.input.set_error("No matching options"),
+            1 => self.input.clear_messages(),
+            _ => self.input.set_message(matches.join("  ")),
+        }
+    }
+
+    /// Load a PGN or FEN file
+    fn open_file(&mut self, path: &str) -> Result<()> {
+        let path = files::expand_home(path);
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) => {
+                self.input
+                    .set_error(format!("Failed to read {}: {}", path, e));
+                return Ok(());
+            }
+        };
+        // A FEN file holds one position per line; the first is shown
+        let first_line = text.lines().map(str::trim).find(|l| !l.is_empty());
+        if let Some(fen) = first_line.filter(|l| !l.starts_with('[') && Game::from_fen(l).is_ok()) {
+            self.game.load_fen(fen)?;
+            self.input
+                .set_message(format!("Position loaded from {}", path));
+            return self.start_analysis();
+        }
+        self.load_pgn_str(&text)
+    }
+
+    /// Act on a line entered in the input bar: a `:command`, or a move
+    /// (answering the current exercise, if any)
+    fn submit_line(&mut self, input: &str) -> Result<()> {
+        if let Some(command) = input.strip_prefix(':') {
+            self.run_command(command)?;
+        } else if self
+            .play
+            .as_ref()
+            .is_some_and(|p| p.result.is_none() && p.is_engine_turn(&self.game))
+        {
+            self.input.set_error("Wait for the engine to move");
+        } else if self.quiz.is_some() {
+            self.answer_quiz(input)?;
+        } else if self.guess.is_some() {
+            self.answer_guess(input)?;
+        } else if self.puzzle.is_some() {
+            self.answer_puzzle(input)?;
+        } else if self.mates.is_some() {
+            self.answer_mate(input)?;
+        } else {
+            // Try to parse as a move
+            let before = self.game.position().clone();
+            match self.game.make_move_san(input) {
+                Ok(m) => {
+                    self.begin_move_delta(&before, &m);
+                    self.start_analysis()?;
+                }
+                Err(e) => {
+                    self.input.set_error(format!("Invalid move: {}", e));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Run the lines of an alias in turn, stopping at the first error
+    fn run_alias(&mut self, name: &str, lines: &[String], args: &str) -> Result<()> {
+        for line in lines {
+            self.input.clear_messages();
+            self.submit_line(line.replace("$*", args).trim())?;
+            if let Some(error) = self.input.error.take() {
+                self.input.set_error(format!(":{}: {}", name, error));
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    /// Show a position from the `--stdin-fen` stream, or note that it ended
+    pub fn stdin_fen(&mut self, fen: Option<&str>) -> Result<()> {
+        let Some(fen) = fen else {
+            self.input.set_message("FEN stream ended");
+            return Ok(());
+        };
+        // Producers often repeat the position; restarting would lose depth
+        if fen == self.game.to_fen() {
+            return Ok(());
+        }
+        match self.game.load_fen(fen) {
+            Ok(_) => {
+                self.input.clear_messages();
+                self.start_analysis()?;
+            }
+            Err(e) => self
+                .input
+                .set_error(format!("Invalid FEN from stdin: {}", e)),
+        }
+        Ok(())
+    }
+
+    /// Answer a JSON command from the control socket
+    pub fn control(&mut self, command: &Value) -> Result<Value> {
+        let arg = |name: &str| {
+            command
+                .get(name)
+                .and_then(Value::as_str)
+                .map(str::to_string)
+        };
+        let line = match command.get("cmd").and_then(Value::as_str) {
+            Some("load_fen") => match arg("fen") {
+                Some(fen) => format!(":fen {}", fen),
+                None => return Ok(error_reply("load_fen needs a \"fen\"")),
+            },
+            Some("move") => match arg("move") {
+                Some(m) => m,
+                None => return Ok(error_reply("move needs a \"move\" in SAN")),
+            },
+            Some("command") => match arg("command") {
+                Some(c) => format!(":{}", c.trim_start_matches(':')),
+                None => return Ok(error_reply("command needs a \"command\"")),
+            },
+            Some("position") => {
+                let moves: Vec<String> = (0..self.game.moves().len())
+                    .filter_map(|i| self.game.numbered_san(i))
+                    .collect();
+                return Ok(json!({
+                    "ok": true,
+                    "fen": self.game.to_fen(),
+                    "turn": if self.game.turn() == Color::White { "white" } else { "black" },
+                    "ply": self.game.current_index(),
+                    "moves": moves,
+                }));
             }
-            KeyCode::Char('?') => {
-                self.popup = Popup::Help;
+            Some("eval") => {
+                let position = self.game.position();
+                let lines: Vec<Value> = self
+                    .analysis
+                    .lines
+                    .iter()
+                    .map(|line| {
+                        json!({
+                            "depth": line.depth,
+                            "score_cp": line.score_cp,
+                            "score_mate": line.score_mate,
+                            "pv": line.pv,
+                            "san": &*self.analysis.san(position, &line.pv),
+                        })
+                    })
+                    .collect();
+                return Ok(json!({
+                    "ok": true,
+                    "fen": self.game.to_fen(),
+                    "running": self.analysis.is_running,
+                    "done": self.analysis.is_done(),
+                    "lines": lines,
+                }));
             }
-            KeyCode::Char('i') => {
-                self.popup = Popup::Import;
+            Some("pgn") => return Ok(json!({ "ok": true, "pgn": self.game.to_pgn() })),
+            Some(other) => return Ok(error_reply(format!("Unknown command: {}", other))),
+            None => return Ok(error_reply("Missing \"cmd\"")),
+        };
+
+        // Everything else goes through the input bar, as if typed
+        self.input.clear_messages();
+        self.remote = true;
+        let result = self.submit_line(&line);
+        self.remote = false;
+        result?;
+        Ok(match self.input.error.clone() {
+            Some(error) => error_reply(error),
+            None => json!({
+                "ok": true,
+                "message": self.input.message,
+                "fen": self.game.to_fen(),
+            }),
+        })
+    }
+
+    /// Check if a command may come from the control socket. Commands that
+    /// start programs or write files are refused, and so are settings that
+    /// name programs, change who may connect or hold tokens.
+    fn allowed_remotely(name: &str, args: &str) -> bool {
+        match name {
+            "engine" | "compare" | "script" | "export" | "export-image" | "export-gif" => false,
+            "set" | "set!" => {
+                let key = args.split_whitespace().next().unwrap_or_default();
+                let section = key.split('.').next().unwrap_or_default();
+                !matches!(
+                    section,
+                    "engine" | "script" | "sounds" | "control" | "online" | "aliases"
+                )
             }
-            KeyCode::Char('f') => {
-                self.config.ui.flip_board = !self.config.ui.flip_board;
+            _ => true,
+        }
+    }
+
+    /// Run a `:command` entered in the input bar (without the leading colon)
+    fn run_command(&mut self, command: &str) -> Result<()> {
+        let command = command.trim();
+        let (name, args) = match command.split_once(char::is_whitespace) {
+            Some((name, args)) => (name, args.trim()),
+            None => (command, ""),
+        };
+
+        if !self.running_aliases.iter().any(|alias| alias == name) {
+            if let Some(lines) = self.config.aliases.get(name).cloned() {
+                self.running_aliases.push(name.to_string());
+                let result = self.run_alias(name, &lines, args);
+                self.running_aliases.pop();
+                return result;
             }
-            KeyCode::Char('e') => {
-                self.config.ui.analysis_eval_only = !self.config.ui.analysis_eval_only;
-                let mode = if self.config.ui.analysis_eval_only {
-                    "Analysis eval-only enabled"
-                } else {
-                    "Analysis eval-only disabled"
-                };
-                self.input.set_message(mode);
+        }
+
+        // Engines, scripts and sounds run as this user, and exports write
+        // where they're told, for whoever reached the socket
+        if self.remote && !Self::allowed_remotely(name, args) {
+            self.input.set_error(format!(
+                ":{} is not available over the control socket",
+                command
+            ));
+            return Ok(());
+        }
+
+        // The position after a null move didn't arise in a game
+        let uses_game = matches!(
+            name,
+            "play"
+                | "drill"
+                | "export"
+                | "export-gif"
+                | "export-image"
+                | "lichess"
+                | "review"
+                | "quiz"
+                | "guess"
+                | "headers"
+        );
+        if uses_game && self.before_pass.is_some() {
+            self.input
+                .set_error("Not available after a null move (:pass to undo)");
+            return Ok(());
+        }
+
+        match name {
+            "fen" => self.load_typed_fen(args)?,
+            "diagram" => self.copy_diagram(args),
+            "export-image" => self.export_image(args),
+            "export-gif" => self.export_gif(args),
+            "lichess" => self.open_on_lichess(args),
+            "pgn" => {
+                self.input.enter_pgn_mode();
             }
-            KeyCode::Char('p') => {
-                self.toggle_pause()?;
+            "game" => self.switch_pgn_game(args)?,
+            "open" if args.is_empty() => self.input.set_error("Usage: :open <path>"),
+            "open" => self.open_file(args)?,
+            "play" if args == "stop" => {
+                if self.is_playing() {
+                    self.reset_strength()?;
+                }
+                self.play = None;
+                self.drill = None;
+                self.start_analysis()?;
+                self.input.set_message("Play mode ended");
             }
-            KeyCode::Char('d') => {
-                self.popup = Popup::Depth(String::new());
+            "play" => {
+                self.drill = None;
+                self.start_play(args)?;
             }
-            KeyCode::Char('m') => {
-                self.popup = Popup::MultiPV(String::new());
+            "drill" if args == "stop" => {
+                if self.drill.take().is_some() {
+                    if self.is_playing() {
+                        self.reset_strength()?;
+                    }
+                    self.play = None;
+                    self.start_analysis()?;
+                    self.input.set_message("Drill ended");
+                }
             }
-            KeyCode::Char('y') => {
-                self.copy_fen_to_clipboard();
+            "drill" => self.start_drill(args)?,
+            "chess960" | "960" => self.start_chess960(args)?,
+            "strength" => {
+                self.popup = Popup::Strength(String::new());
+            }
+            "compare" if args.is_empty() || args == "stop" => {
+                if let Some(mut engine) = self.second_engine.take() {
+                    let _ = engine.quit();
+                }
+                self.second_analysis.clear();
+                self.input.set_message("Comparison engine stopped");
             }
-            KeyCode::Left | KeyCode::Char('h') => {
-                if self.game.go_back() {
+            "compare" => self.start_compare(args)?,
+            "quiz" if args == "skip" => self.skip_quiz()?,
+            "quiz" if args == "stop" => {
+                if self.quiz.take().is_some() {
                     self.start_analysis()?;
+                    self.input.set_message("Quiz ended");
                 }
             }
-            KeyCode::Right | KeyCode::Char('l') => {
-                if self.game.go_forward() {
+            "quiz" => self.start_quiz(args)?,
+            "guess" if args == "stop" => {
+                if self.guess.take().is_some() {
                     self.start_analysis()?;
+                    self.input.set_message("Guess the move ended");
                 }
             }
-            KeyCode::Home => {
-                self.game.go_to_start();
-                self.start_analysis()?;
+            "guess" => self.start_guess(args)?,
+            "puzzle" if args == "stop" => {
+                if let Some(session) = self.puzzle.take() {
+                    let message = match session.rush {
+                        Some(rush) => format!("Puzzle rush ended: {} solved", rush.solved),
+                        None => format!(
+                            "Puzzles ended: {}/{} solved, rating {}",
+                            session.solved, session.played, session.rating
+                        ),
+                    };
+                    self.end_puzzles()?;
+                    self.input.set_message(message);
+                } else if self.puzzle_draw.take().is_some() {
+                    self.input.set_message("Puzzles cancelled");
+                }
             }
-            KeyCode::End => {
-                self.game.go_to_end();
+            "puzzle" if args == "skip" => self.fail_puzzle()?,
+            "puzzle" if args.starts_with("file") => {
+                let path = args["file".len()..].trim();
+                if path.is_empty() {
+                    self.input.set_error("Usage: :puzzle file <path>");
+                } else {
+                    let path = files::expand_home(path);
+                    self.input.set_message(format!("Puzzle database: {}", path));
+                    self.config.puzzles.path = Some(path);
+                }
+            }
+            "puzzle" => self.start_puzzles(args)?,
+            "mates" if args == "stop" => {
+                if let Some(session) = self.mates.take() {
+                    self.start_analysis()?;
+                    self.input.set_message(format!(
+                        "Checkmate exercises ended: {}/{} solved",
+                        session.solved, session.played
+                    ));
+                }
+            }
+            "mates" if args == "skip" => self.skip_mate()?,
+            "mates" => self.start_mates(args)?,
+            "repertoire" if args.is_empty() => self.check_repertoire(false)?,
+            "repertoire" if args == "jump" => self.check_repertoire(true)?,
+            "repertoire" => {
+                let (side, path) = args.split_once(char::is_whitespace).unwrap_or((args, ""));
+                let side = match side {
+                    "white" => Some(Color::White),
+                    "black" => Some(Color::Black),
+                    _ => None,
+                };
+                match side {
+                    Some(side) if !path.trim().is_empty() => {
+                        let path = files::expand_home(path.trim());
+                        match self.load_repertoire(side, &path) {
+                            Ok(()) => match side {
+                                Color::White => self.config.repertoire.white = Some(path),
+                                Color::Black => self.config.repertoire.black = Some(path),
+                            },
+                            Err(e) => self.input.set_error(format!("{:#}", e)),
+                        }
+                    }
+                    _ => self
+                        .input
+                        .set_error("Usage: :repertoire [jump|white <path>|black <path>]"),
+                }
+            }
+            "review" if args == "stop" => self.stop_review("Review stopped"),
+            "summary" => match self.game_summary() {
+                Some(summary) => self.popup = Popup::Summary(summary),
+                None => self
+                    .input
+                    .set_error("Every position needs an eval first (run :review)"),
+            },
+            "review" => match args.parse::<u32>() {
+                Ok(depth) if depth > 0 => self.start_review(depth)?,
+                _ if args.is_empty() => self.start_review(REVIEW_DEPTH)?,
+                _ => self
+                    .input
+                    .set_error("Usage: :review [depth] | :review stop"),
+            },
+            "threat" => self.toggle_threat()?,
+            "pass" => self.toggle_pass()?,
+            "layout" if args.is_empty() => self.input.set_message(format!(
+                "Layout: {} (:layout {})",
+                self.config.ui.layout,
+                self.config.ui.layout_names().join("|")
+            )),
+            "layout" => self.set_layout(args),
+            "heatmap" => self.toggle_heatmap()?,
+            "screen-reader" => self.set_screen_reader(args),
+            "set" => self.set_command(args, false)?,
+            "flip" => self.config.ui.flip_board = !self.config.ui.flip_board,
+            "reset" if self.is_playing() => self
+                .input
+                .set_error("Not available while playing (:play stop to end)"),
+            "reset" => {
+                self.game.reset();
                 self.start_analysis()?;
+                self.input.set_message("New game started");
             }
-            KeyCode::Enter | KeyCode::Char(':') => {
-                self.input.enter_command_mode();
+            "set!" => self.set_command(args, true)?,
+            "mouse" => self.set_mouse_capture(args),
+            "show-fen" => self.set_show_fen(args),
+            "tablebase" | "tb" => self.set_tablebase(args),
+            "cloud" => self.set_cloud(args),
+            "show-variations" => self.set_move_notes("variations", args),
+            "show-comments" => self.set_move_notes("comments", args),
+            "show-nags" => self.set_move_notes("nags", args),
+            "board" => {
+                self.popup = Popup::BoardText;
             }
-            KeyCode::Char(c @ '1'..='9') => {
-                // Play the best move from the selected analysis line
-                let line_idx = (c as usize) - ('1' as usize);
-                self.play_analysis_line(line_idx)?;
+            "static-eval" => self.open_static_eval()?,
+            "clear-hash" => self.clear_hash()?,
+            "deepen" => match args.parse::<u32>() {
+                Ok(plies) if plies > 0 => self.deepen(plies)?,
+                _ if args.is_empty() => self.deepen(DEEPEN_PLIES)?,
+                _ => self.input.set_error("Usage: :deepen [plies]"),
+            },
+            "double-hash" => self.double_hash()?,
+            "candidates" | "cand" => self.candidates_command(args)?,
+            "headers" => {
+                self.popup = Popup::Headers(0, None);
             }
-            KeyCode::Char(c) if c.is_alphabetic() => {
-                // Start typing a move
-                self.input.enter_command_mode();
-                self.input.insert(c);
+            "autoplay" => self.autoplay_command(args)?,
+            "library" => {
+                self.popup = Popup::Library(0);
             }
-            _ => {}
-        }
-
-        Ok(())
-    }
-
-    /// Handle input submission
-    fn handle_input_submit(&mut self) -> Result<()> {
-        match self.input.mode {
-            InputMode::Command | InputMode::Normal => {
-                let input = self.input.take();
-                self.input.exit_mode();
-
-                if input.is_empty() {
-                    return Ok(());
+            "browse" => self.open_game_browser(Some(args).filter(|a| !a.is_empty())),
+            "explorer" | "tree" => self.open_explorer(args),
+            "script" if args.is_empty() => match self.script {
+                Some(ref script) => {
+                    let hooks: Vec<&str> = HOOKS
+                        .iter()
+                        .copied()
+                        .filter(|hook| script.has_hook(hook))
+                        .collect();
+                    let hooks = if hooks.is_empty() {
+                        "no hooks".to_string()
+                    } else {
+                        hooks.join(", ")
+                    };
+                    self.input
+                        .set_message(format!("Script {}: {}", script.path(), hooks));
                 }
-
-                // Check for commands
-                if input.starts_with(":fen ") {
-                    let fen = input.strip_prefix(":fen ").unwrap().trim();
-                    match self.game.load_fen(fen) {
-                        Ok(_) => {
-                            self.input.set_message("Position loaded from FEN");
-                            self.start_analysis()?;
-                        }
-                        Err(e) => {
-                            self.input.set_error(format!("Invalid FEN: {}", e));
+                None => self.input.set_message("No script loaded"),
+            },
+            "script" if args == "off" => {
+                self.script = None;
+                self.input.set_message("Script unloaded");
+            }
+            "script" => {
+                let path = match args {
+                    "reload" => self
+                        .script
+                        .as_ref()
+                        .map(|script| script.path().to_string())
+                        .or_else(|| self.config.script_path()),
+                    path => Some(files::expand_home(path)),
+                };
+                match path {
+                    Some(path) => {
+                        if self.load_script(&path) {
+                            self.input.set_message(format!("Script loaded: {}", path));
                         }
                     }
-                } else if input == ":pgn" {
-                    self.input.enter_pgn_mode();
+                    None => self.input.set_error("No script to reload"),
+                }
+            }
+            "state-dir" => match state::dir() {
+                Some(dir) => self
+                    .input
+                    .set_message(format!("State directory: {}", dir.display())),
+                None => self.input.set_error("Could not determine state directory"),
+            },
+            "uci-log" => {
+                self.popup = Popup::UciLog(0);
+            }
+            "engine" if args.is_empty() => self.open_engine_picker(),
+            "engine" => {
+                let path = files::expand_home(args);
+                let path = which::which(&path)
+                    .map(|p| p.to_string_lossy().to_string())
+                    .unwrap_or(path);
+                self.switch_engine(&path)?;
+            }
+            "claim" if self.is_playing() => self.claim_draw()?,
+            "resign" if self.is_playing() => {
+                let result = match self.play.as_ref().map(|p| p.user_color) {
+                    Some(shakmaty::Color::White) => "0-1",
+                    _ => "1-0",
+                };
+                if self.drill.is_some() {
+                    self.finish_drill(DrillEnd {
+                        success: false,
+                        reason: "Resigned",
+                        result,
+                    })?;
                 } else {
-                    // Try to parse as a move
-                    match self.game.make_move_san(&input) {
-                        Ok(_) => {
-                            self.start_analysis()?;
-                        }
-                        Err(e) => {
-                            self.input.set_error(format!("Invalid move: {}", e));
-                        }
-                    }
+                    self.finish_play(result.to_string(), "You resigned".to_string())?;
                 }
             }
-            InputMode::Fen => {
-                let input = self.input.take();
-                self.input.exit_mode();
-
-                let fen = input.strip_prefix(":fen ").unwrap_or(&input).trim();
-                if !fen.is_empty() {
-                    match self.game.load_fen(fen) {
-                        Ok(_) => {
-                            self.input.set_message("Position loaded from FEN");
-                            self.start_analysis()?;
-                        }
-                        Err(e) => {
-                            self.input.set_error(format!("Invalid FEN: {}", e));
-                        }
+            "arrow" => self.mark_command(args, true),
+            "circle" => self.mark_command(args, false),
+            "clear-marks" => {
+                let annotation = self.game.annotation_mut();
+                annotation.arrows.clear();
+                annotation.circles.clear();
+                self.input.set_message("Marks cleared");
+            }
+            "export" => {
+                if args.is_empty() {
+                    self.input.set_error("Usage: :export <path>");
+                } else {
+                    self.set_summary_headers();
+                    let path = files::expand_home(args);
+                    match std::fs::write(&path, self.game.to_pgn()) {
+                        Ok(_) => self.input.set_message(format!("PGN saved to {}", path)),
+                        Err(e) => self.input.set_error(format!("Failed to save PGN: {}", e)),
                     }
                 }
             }
-            InputMode::Pgn => {
-                let line = self.input.take();
-
-                // Empty line or double-Enter ends PGN input
-                if line.is_empty() {
-                    self.finish_pgn_input()?;
+            _ => {
+                if self.running_aliases.iter().any(|alias| alias == name) {
+                    self.input.set_error(format!(
+                        "Alias {} runs itself ({})",
+                        name,
+                        self.running_aliases.join(" -> ")
+                    ));
                 } else {
-                    self.input.pgn_buffer.push(line);
+                    self.input.set_error(format!("Unknown command: {}", name));
                 }
             }
         }
@@ -447,6 +5324,59 @@ impl App {
         Ok(())
     }
 
+    /// Handle `:arrow <from><to> [color]` and `:circle <square> [color]`
+    fn mark_command(&mut self, args: &str, arrow: bool) {
+        let mut parts = args.split_whitespace();
+        let target = parts.next().unwrap_or("");
+        let color = match parts.next() {
+            Some(name) => match MarkColor::from_name(name) {
+                Some(color) => color,
+                None => {
+                    self.input.set_error(format!("Unknown color: {}", name));
+                    return;
+                }
+            },
+            None => MarkColor::Green,
+        };
+
+        if arrow {
+            let from = target.get(0..2).and_then(|s| s.parse::<Square>().ok());
+            let to = target.get(2..4).and_then(|s| s.parse::<Square>().ok());
+            match (from, to) {
+                (Some(from), Some(to)) if from != to && target.len() == 4 => {
+                    self.game.toggle_arrow(from, to, color);
+                }
+                _ => self
+                    .input
+                    .set_error("Usage: :arrow <from><to> [color], e.g. :arrow e2e4"),
+            }
+        } else {
+            match target.parse::<Square>() {
+                Ok(square) => self.game.toggle_circle(square, color),
+                Err(_) => self
+                    .input
+                    .set_error("Usage: :circle <square> [color], e.g. :circle e4"),
+            }
+        }
+    }
+
+    /// Load a position from the built-in library
+    fn load_library_entry(&mut self, index: usize) -> Result<()> {
+        let Some(entry) = LIBRARY.get(index) else {
+            return Ok(());
+        };
+        if self.is_playing() {
+            self.input
+                .set_error("Not available while playing (:play stop to end)");
+            return Ok(());
+        }
+        self.game.load_fen(entry.fen)?;
+        self.game.set_header("Event", entry.name);
+        self.input
+            .set_message(format!("{}: {}", entry.name, entry.note));
+        self.start_analysis()
+    }
+
     /// Finish PGN input and parse the game
     fn finish_pgn_input(&mut self) -> Result<()> {
         let pgn_text = self.input.pgn_buffer.join("\n");
@@ -455,91 +5385,130 @@ impl App {
         if pgn_text.trim().is_empty() {
             return Ok(());
         }
+        self.load_pgn_str(&pgn_text)
+    }
 
-        match self.parse_pgn(&pgn_text) {
-            Ok(_) => {
-                self.input.set_message("Game loaded from PGN");
-                self.start_analysis()?;
-            }
+    /// Read a PGN file and load its games, showing the first
+    pub fn load_pgn_file(&mut self, path: &str) -> Result<()> {
+        match std::fs::read_to_string(path) {
+            Ok(pgn) => self.load_pgn_str(&pgn),
             Err(e) => {
-                self.input.set_error(format!("Invalid PGN: {}", e));
+                self.input
+                    .set_error(format!("Failed to read {}: {}", path, e));
+                Ok(())
             }
         }
-
-        Ok(())
     }
 
-    /// Parse PGN and load the game (simple parser)
-    fn parse_pgn(&mut self, pgn: &str) -> Result<()> {
-        // Simple PGN parser - extract moves from the movetext
-        // Skip headers (lines starting with '[')
-        // Parse moves like "1. e4 e5 2. Nf3 Nc6"
-
-        self.game.reset();
-
-        let mut in_comment = false;
-        let mut in_variation = 0;
-
-        for token in pgn.split_whitespace() {
-            // Skip headers
-            if token.starts_with('[') {
-                continue;
-            }
-
-            // Handle comments
-            if token.starts_with('{') {
-                in_comment = true;
-            }
-            if token.ends_with('}') {
-                in_comment = false;
-                continue;
-            }
-            if in_comment {
-                continue;
-            }
-
-            // Handle variations
-            if token.starts_with('(') {
-                in_variation += 1;
-            }
-            if token.ends_with(')') {
-                in_variation -= 1;
-                continue;
-            }
-            if in_variation > 0 {
-                continue;
-            }
-
-            // Skip move numbers (e.g., "1.", "12.")
-            if token.ends_with('.') {
-                continue;
-            }
-            // Skip "..." for black moves
-            if token == "..." {
-                continue;
+    /// Load whatever the clipboard holds: a FEN, or else a PGN
+    pub fn load_clipboard(&mut self) -> Result<()> {
+        let text = match Clipboard::new().and_then(|mut clipboard| clipboard.get_text()) {
+            Ok(text) => text,
+            Err(e) => {
+                self.input.set_error(format!("Clipboard error: {}", e));
+                return Ok(());
             }
+        };
+        let text = text.trim();
+        if text.is_empty() {
+            self.input.set_error("The clipboard has no text");
+            Ok(())
+        } else if text.parse::<Fen>().is_ok() {
+            self.load_typed_fen(text)
+        } else {
+            self.load_pgn_str(text)
+        }
+    }
 
-            // Skip result markers
-            if token == "1-0" || token == "0-1" || token == "1/2-1/2" || token == "*" {
-                continue;
+    /// Load the games of a PGN text, showing the first. Games that don't
+    /// parse are skipped and reported; with more than one game left,
+    /// `:game` switches between them.
+    pub fn load_pgn_str(&mut self, pgn: &str) -> Result<()> {
+        let mut games = Vec::new();
+        let mut errors = Vec::new();
+        for (i, text) in split_games(pgn).into_iter().enumerate() {
+            match Game::from_pgn(text) {
+                Ok(game) => games.push(game),
+                Err(e) => errors.push(format!("game {}: {:#}", i + 1, e)),
             }
+        }
+        if games.is_empty() {
+            let error = errors
+                .into_iter()
+                .next()
+                .unwrap_or_else(|| "no games".into());
+            self.input.set_error(format!("Invalid PGN: {}", error));
+            return Ok(());
+        }
 
-            // Skip annotations like "!" "?" "!!" etc.
-            let clean_move = token
-                .trim_end_matches('!')
-                .trim_end_matches('?')
-                .trim_end_matches('+')
-                .trim_end_matches('#');
+        self.game = games[0].clone();
+        self.pgn_game = 0;
+        let loaded = match games.len() {
+            1 => "Game loaded from PGN".to_string(),
+            n => format!("Game 1 of {} loaded from PGN (:game <n> for the others)", n),
+        };
+        self.pgn_games = if games.len() > 1 { games } else { Vec::new() };
+        self.arrivals = Arrivals::new(&self.pgn_games);
+        self.opening_tree = OpeningTree::new(&self.pgn_games);
+        self.show_loaded_game(loaded)?;
+        if !errors.is_empty() {
+            tracing::warn!("Skipped PGN games: {}", errors.join("; "));
+            self.input.set_error(format!(
+                "Skipped {} invalid game{} ({})",
+                errors.len(),
+                if errors.len() == 1 { "" } else { "s" },
+                errors[0]
+            ));
+        }
+        Ok(())
+    }
 
-            if clean_move.is_empty() {
-                continue;
+    /// Switch to another game of the last multi-game PGN loaded
+    fn switch_pgn_game(&mut self, args: &str) -> Result<()> {
+        let count = self.pgn_games.len();
+        if count == 0 {
+            self.input.set_error("Only one game is loaded");
+            return Ok(());
+        }
+        let index = match args {
+            "" => {
+                self.input.set_message(format!(
+                    "Game {} of {}: {}",
+                    self.pgn_game + 1,
+                    count,
+                    self.pgn_games[self.pgn_game].title()
+                ));
+                return Ok(());
             }
+            "next" => (self.pgn_game + 1).min(count - 1),
+            "prev" => self.pgn_game.saturating_sub(1),
+            n => match n.parse::<usize>() {
+                Ok(n) if (1..=count).contains(&n) => n - 1,
+                _ => {
+                    self.input
+                        .set_error(format!("Usage: :game [1-{}|next|prev]", count));
+                    return Ok(());
+                }
+            },
+        };
+        self.pgn_game = index;
+        self.game = self.pgn_games[index].clone();
+        let loaded = format!("Game {} of {}: {}", index + 1, count, self.game.title());
+        self.show_loaded_game(loaded)
+    }
 
-            // Try to make the move
-            self.game.make_move_san(clean_move)?;
+    /// Report a newly loaded game, with where it leaves the repertoire, and
+    /// analyse it
+    fn show_loaded_game(&mut self, loaded: String) -> Result<()> {
+        match self.repertoire_deviation() {
+            Some(deviation) => self.input.set_message(format!(
+                "{}. {} (R to jump there)",
+                loaded,
+                Self::deviation_text(&deviation)
+            )),
+            None => self.input.set_message(loaded),
         }
-
-        Ok(())
+        self.start_analysis()
     }
 
     /// Play the first move from an analysis line
@@ -551,9 +5520,11 @@ impl App {
                 if let Ok(uci) = uci_move.parse::<shakmaty::uci::UciMove>() {
                     if let Ok(m) = uci.to_move(self.game.position()) {
                         // Convert to SAN for make_move_san
-                        let san = shakmaty::san::San::from_move(self.game.position(), &m);
+                        let before = self.game.position().clone();
+                        let san = shakmaty::san::San::from_move(&before, &m);
                         match self.game.make_move_san(&san.to_string()) {
-                            Ok(_) => {
+                            Ok(m) => {
+                                self.begin_move_delta(&before, &m);
                                 self.start_analysis()?;
                             }
                             Err(e) => {
@@ -570,84 +5541,330 @@ impl App {
                 self.input
                     .set_error(format!("No moves in line {}", line_idx + 1));
             }
-        } else {
-            self.input
-                .set_error(format!("No analysis line {}", line_idx + 1));
+        } else {
+            self.input
+                .set_error(format!("No analysis line {}", line_idx + 1));
+        }
+        Ok(())
+    }
+
+    /// Copy text to the clipboard, reporting `what` was copied
+    fn copy_to_clipboard(&mut self, text: &str, what: &str) {
+        match Clipboard::new() {
+            Ok(mut clipboard) => match clipboard.set_text(text) {
+                Ok(_) => {
+                    self.input
+                        .set_message(format!("{} copied to clipboard", what));
+                }
+                Err(e) => {
+                    self.input.set_error(format!("Failed to copy: {}", e));
+                }
+            },
+            Err(e) => {
+                self.input.set_error(format!("Clipboard error: {}", e));
+            }
+        }
+    }
+
+    /// Load a FEN typed by the user, saying which fields were dropped
+    /// because they can't apply to the position
+    fn load_typed_fen(&mut self, fen: &str) -> Result<()> {
+        match self.game.load_fen(fen) {
+            Ok(dropped) if dropped.is_empty() => {
+                self.input.set_message("Position loaded from FEN");
+                self.start_analysis()
+            }
+            Ok(dropped) => {
+                self.input.set_message(format!(
+                    "Position loaded from FEN, without the {} it can't have",
+                    dropped.join(" and ")
+                ));
+                self.start_analysis()
+            }
+            Err(e) => {
+                self.input.set_error(format!("Invalid FEN: {:#}", e));
+                Ok(())
+            }
+        }
+    }
+
+    /// Copy current FEN to clipboard
+    fn copy_fen_to_clipboard(&mut self) {
+        let fen = self.game.to_fen();
+        self.copy_to_clipboard(&fen, "FEN");
+    }
+
+    /// Copy a text diagram of the board, Unicode unless asked for ASCII
+    fn copy_diagram(&mut self, args: &str) {
+        let ascii = match args {
+            "" | "unicode" => false,
+            "ascii" => true,
+            _ => {
+                self.input.set_error("Usage: :diagram [unicode|ascii]");
+                return;
+            }
+        };
+        let diagram = self.game.diagram(self.config.ui.flip_board, ascii);
+        self.copy_to_clipboard(&diagram, "Board diagram");
+    }
+
+    /// Handle `:export-image <path> [plain]`: save the board as shown, with
+    /// its highlights and arrows unless `plain` is given, as PNG or SVG by
+    /// the file's extension
+    fn export_image(&mut self, args: &str) {
+        let (path, plain) = match args.strip_suffix(" plain") {
+            Some(path) => (path.trim_end(), true),
+            None => (args, false),
+        };
+        if path.is_empty() {
+            self.input
+                .set_error("Usage: :export-image <path.png|path.svg> [plain]");
+            return;
+        }
+        let path = files::expand_home(path);
+        let extension = Path::new(&path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_lowercase);
+        let (squares, arrows) = if plain {
+            let board = BoardWidget::new(&self.game, &self.config.ui);
+            (board.plain_squares(), Vec::new())
+        } else {
+            let board = self.board_widget();
+            (board.squares(), board.image_arrows())
+        };
+        let data = match extension.as_deref() {
+            Some("svg") => Ok(board_svg(&squares, &arrows, IMAGE_SQUARE).into_bytes()),
+            Some("png") => {
+                let mut image = BoardImage::with_square_size(&squares, IMAGE_SQUARE);
+                image.draw_arrows(&arrows, IMAGE_SQUARE);
+                image.to_png()
+            }
+            _ => {
+                self.input.set_error("Image path must end in .png or .svg");
+                return;
+            }
+        };
+        match data.and_then(|data| {
+            std::fs::write(&path, data).with_context(|| format!("Failed to write {}", path))
+        }) {
+            Ok(()) => self
+                .input
+                .set_message(format!("Board image saved to {}", path)),
+            Err(e) => self.input.set_error(format!("{:#}", e)),
+        }
+    }
+
+    /// Handle `:export-gif <path> [first-last] [white|black] [seconds]`:
+    /// save the game, or the moves numbered in a range, as an animated GIF
+    fn export_gif(&mut self, args: &str) {
+        const USAGE: &str = "Usage: :export-gif <path.gif> [first-last] [white|black] [seconds]";
+        let mut words = args.split_whitespace();
+        let Some(path) = words.next() else {
+            self.input.set_error(USAGE);
+            return;
+        };
+        let mut range = None;
+        let mut flipped = self.config.ui.flip_board;
+        let mut delay = GIF_DELAY;
+        for word in words {
+            if let Some((first, last)) = word.split_once('-') {
+                match (first.parse::<u32>(), last.parse::<u32>()) {
+                    (Ok(first), Ok(last)) if first <= last => range = Some(first..=last),
+                    _ => {
+                        self.input.set_error(USAGE);
+                        return;
+                    }
+                }
+                continue;
+            }
+            match word {
+                "white" => flipped = false,
+                "black" => flipped = true,
+                secs => match secs.parse::<f64>() {
+                    Ok(secs) if (0.1..=60.0).contains(&secs) => {
+                        delay = Duration::from_secs_f64(secs)
+                    }
+                    _ => {
+                        self.input.set_error(USAGE);
+                        return;
+                    }
+                },
+            }
+        }
+        let path = files::expand_home(path);
+        if !path.to_lowercase().ends_with(".gif") {
+            self.input.set_error("GIF path must end in .gif");
+            return;
+        }
+
+        // Plies of the moves in the range, by their move numbers
+        let start = self.game.initial_position();
+        let first_number = start.fullmoves().get();
+        let offset = u32::from(start.turn() == Color::Black);
+        let plies: Vec<usize> = (0..self.game.moves().len())
+            .filter(|&ply| {
+                let number = first_number + (ply as u32 + offset) / 2;
+                range.as_ref().is_none_or(|range| range.contains(&number))
+            })
+            .collect();
+        let (Some(&first), Some(&last)) = (plies.first(), plies.last()) else {
+            self.input.set_error(match range {
+                Some(range) => format!("No moves numbered {}-{}", range.start(), range.end()),
+                None => "No moves to export".to_string(),
+            });
+            return;
+        };
+
+        // The position before the first move, then after each move
+        let mut ui = self.config.ui.clone();
+        ui.flip_board = flipped;
+        let mut game = self.game.clone();
+        let frames: Vec<(BoardImage, Duration)> = (first..=last + 1)
+            .map(|index| {
+                game.go_to(index);
+                let board = BoardWidget::new(&game, &ui);
+                let mut image = BoardImage::with_square_size(&board.squares(), GIF_SQUARE);
+                image.draw_arrows(&board.image_arrows(), GIF_SQUARE);
+                let duration = if index == last + 1 {
+                    delay + GIF_HOLD
+                } else {
+                    delay
+                };
+                (image, duration)
+            })
+            .collect();
+        match board_gif(&frames).and_then(|data| {
+            std::fs::write(&path, data).with_context(|| format!("Failed to write {}", path))
+        }) {
+            Ok(()) => self.input.set_message(format!(
+                "Game GIF saved to {} ({} moves)",
+                path,
+                plies.len()
+            )),
+            Err(e) => self.input.set_error(format!("{:#}", e)),
         }
-        Ok(())
     }
 
-    /// Copy current FEN to clipboard
-    fn copy_fen_to_clipboard(&mut self) {
-        let fen = self.game.to_fen();
-        match Clipboard::new() {
-            Ok(mut clipboard) => match clipboard.set_text(&fen) {
-                Ok(_) => {
-                    self.input.set_message("FEN copied to clipboard");
-                }
-                Err(e) => {
-                    self.input.set_error(format!("Failed to copy: {}", e));
-                }
-            },
-            Err(e) => {
-                self.input.set_error(format!("Clipboard error: {}", e));
-            }
+    /// Copy the game as PGN to clipboard
+    fn copy_pgn_to_clipboard(&mut self) {
+        if self.before_pass.is_some() {
+            self.input
+                .set_error("Not available after a null move (:pass to undo)");
+            return;
         }
+        let pgn = self.game.to_pgn();
+        self.copy_to_clipboard(&pgn, "PGN");
+    }
+
+    /// Copy the selected (or best) analysis line to clipboard as SAN
+    fn copy_analysis_line(&mut self) {
+        let idx = self.selected_line.unwrap_or(0);
+        let Some(info) = self.analysis.lines.get(idx) else {
+            self.input
+                .set_error(format!("No analysis line {}", idx + 1));
+            return;
+        };
+        let pv = format_pv(self.game.position(), &info.pv);
+        let text = match score_to_cp(info.score_cp, info.score_mate) {
+            Some(_) => format!("({}) {}", format_score(info.score_cp, info.score_mate), pv),
+            None => pv,
+        };
+        self.copy_to_clipboard(&text, "Analysis line");
     }
 
     /// Render the UI
     pub fn render(&self, frame: &mut Frame) {
         let size = frame.area();
+        if size.width < MIN_WIDTH || size.height < MIN_HEIGHT {
+            frame.render_widget(TooSmallWidget::new(MIN_WIDTH, MIN_HEIGHT), size);
+            return;
+        }
+        if self.zen {
+            self.render_zen(frame);
+            self.render_popup(frame);
+            self.color_depth.apply(frame.buffer_mut());
+            return;
+        }
+
+        // Small terminals get a compact layout: no title bar or eval graph,
+        // smaller board cells, a one-line analysis and the move list behind `M`
+        let compact = size.width < 62 || size.height < 27;
 
         // Main layout: vertical split
+        let fen_line = u16::from(self.config.ui.show_fen);
         let main_chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
-                Constraint::Length(1), // Title bar
-                Constraint::Min(10),   // Main content
-                Constraint::Length(3), // Input bar
-                Constraint::Length(1), // Help bar
+                Constraint::Length(if compact { 0 } else { 1 }), // Title bar
+                Constraint::Min(10),                             // Main content
+                Constraint::Length(fen_line),                    // FEN line
+                Constraint::Length(3),                           // Input bar
+                Constraint::Length(1),                           // Help bar
             ])
             .split(size);
 
         // Title bar
+        let profile = match self.config.profile {
+            Some(ref profile) => format!("[{}] ", profile),
+            None => String::new(),
+        };
+        // The opening reached so far, e.g. "B90 Sicilian Defense: Najdorf Variation"
+        let opening = match openings::opening(&self.game) {
+            Some(opening) => format!("· {} {}", opening.eco, opening.name),
+            None => String::new(),
+        };
         let title = format!(
-            " lazychess {}",
+            " lazychess {}{}{}",
+            profile,
             if self.engine.is_some() {
                 ""
             } else {
-                "(no engine)"
-            }
+                "(no engine) "
+            },
+            opening
         );
-        let title_widget = ratatui::widgets::Paragraph::new(title)
-            .style(ratatui::style::Style::default().fg(ratatui::style::Color::Cyan));
+        // Flag positions reached by a null move
+        let pass_flag = if self.before_pass.is_some() {
+            " NULL MOVE: analysis only "
+        } else {
+            ""
+        };
+        let title_width = title.chars().count() + pass_flag.chars().count() + 1;
+        let title_widget = ratatui::widgets::Paragraph::new(ratatui::text::Line::from(vec![
+            ratatui::text::Span::styled(
+                title,
+                ratatui::style::Style::default().fg(ratatui::style::Color::Cyan),
+            ),
+            ratatui::text::Span::raw(" "),
+            ratatui::text::Span::styled(
+                pass_flag,
+                ratatui::style::Style::default()
+                    .fg(ratatui::style::Color::Black)
+                    .bg(ratatui::style::Color::Yellow)
+                    .add_modifier(ratatui::style::Modifier::BOLD),
+            ),
+        ]));
         frame.render_widget(title_widget, main_chunks[0]);
 
-        // Main content: horizontal split into (board + analysis) | moves
-        let content_chunks = Layout::default()
-            .direction(Direction::Horizontal)
-            .constraints([
-                Constraint::Min(40),    // Left side (board + analysis)
-                Constraint::Length(22), // Move history
-            ])
-            .split(main_chunks[1]);
+        // Players, event and result of a loaded game on the right
+        if let Some(info) = self.game.info() {
+            let room = (main_chunks[0].width as usize).saturating_sub(title_width + 3);
+            let info = if info.chars().count() > room {
+                let cut: String = info.chars().take(room.saturating_sub(1)).collect();
+                format!("{}…", cut)
+            } else {
+                info
+            };
+            let info_widget = ratatui::widgets::Paragraph::new(format!("{} ", info))
+                .alignment(ratatui::layout::Alignment::Right)
+                .style(ratatui::style::Style::default().fg(ratatui::style::Color::Gray));
+            frame.render_widget(info_widget, main_chunks[0]);
+        }
 
-        // Left panel: board on top, analysis below
-        // Calculate board height based on piece style
-        // Block mode: 8*4 cells + 2 border + 1 coords + 2 captured + 1 status = 38
-        // Normal mode: 8*2 cells + 2 border + 1 coords + 2 captured + 1 status = 22
-        let board_height = if self.config.ui.get_piece_style() == crate::chess::PieceStyle::Blocks {
-            38
-        } else {
-            22
-        };
-        let left_chunks = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([
-                Constraint::Length(board_height),
-                Constraint::Min(8), // Analysis
-            ])
-            .split(content_chunks[0]);
+        let board_widget = self.board_widget().show_captured(!compact);
+        let (board_area, analysis_area, graph_area, moves_area) =
+            self.panel_areas(main_chunks[1], &board_widget, compact);
 
         // Board area (board + status)
         let board_chunks = Layout::default()
@@ -656,38 +5873,474 @@ impl App {
                 Constraint::Min(10),   // Board with captured pieces inside
                 Constraint::Length(1), // Status
             ])
-            .split(left_chunks[0]);
+            .split(board_area);
 
         // Render board (captured pieces are now inside the board pane)
-        let board_widget = BoardWidget::new(&self.game, &self.config.ui);
-        frame.render_widget(board_widget, board_chunks[0]);
+        self.render_board(frame, board_widget, board_chunks[0]);
+        if let Some((san, class, before, after)) = self.autoplay_overlay() {
+            frame.render_widget(
+                ReplayOverlay::new(&san, class, &before, &after)
+                    .palette(self.config.ui.get_palette()),
+                board_chunks[0],
+            );
+        }
 
-        // Render status
-        let status_widget = StatusWidget::new(&self.game);
-        frame.render_widget(status_widget, board_chunks[1]);
+        // Render status, with the game clocks on the right while playing
+        // (or the countdown during a puzzle rush)
+        let clock = self.play.as_ref().and_then(|p| p.clock.as_ref());
+        let rush = self.puzzle.as_ref().and_then(|s| s.rush.as_ref());
+        let status_chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([
+                Constraint::Min(10),
+                Constraint::Length(if clock.is_some() || rush.is_some() {
+                    30
+                } else {
+                    0
+                }),
+            ])
+            .split(board_chunks[1]);
+        let eval_delta = if self.is_playing() {
+            None
+        } else {
+            self.eval_delta()
+        };
+        let status_widget = StatusWidget::new(&self.game)
+            .eval_delta(eval_delta)
+            .palette(self.config.ui.get_palette())
+            .announcement(self.announcement());
+        frame.render_widget(status_widget, status_chunks[0]);
+        if let Some(clock) = clock {
+            frame.render_widget(ClockWidget::new(clock), status_chunks[1]);
+        } else if let Some(rush) = rush {
+            frame.render_widget(
+                RushWidget::new(rush).palette(self.config.ui.get_palette()),
+                status_chunks[1],
+            );
+        }
 
         // Render analysis panel
-        let analysis_widget = AnalysisWidget::new(
-            &self.analysis,
-            self.game.position(),
-            self.config.engine.multipv,
-            self.config.ui.analysis_eval_only,
-        );
-        frame.render_widget(analysis_widget, left_chunks[1]);
+        if let Some(analysis_area) = analysis_area {
+            self.render_analysis(frame, analysis_area, compact);
+        }
+
+        // Render eval graph
+        if let Some(graph_area) = graph_area {
+            let black_up =
+                !self.config.ui.white_perspective && self.game.turn() == shakmaty::Color::Black;
+            frame.render_widget(
+                EvalGraphWidget::new(&self.game)
+                    .black_up(black_up)
+                    .hide_future(self.guess.is_some())
+                    .progress(self.review.as_ref().map(Review::progress)),
+                graph_area,
+            );
+        }
+
+        // Render move history, or the explorer in its place
+        if let (Some(moves_area), Popup::Explorer(source, selected)) = (moves_area, &self.popup) {
+            self.render_explorer(frame, moves_area, *source, *selected);
+        } else if let Some(moves_area) = moves_area {
+            let moves_widget = MovesWidget::new(&self.game, self.move_scroll)
+                .hide_future(self.guess.is_some())
+                .flag(self.repertoire_deviation().map(|d| d.index))
+                .variations(self.config.ui.show_variations)
+                .comments(self.config.ui.show_comments)
+                .nags(self.config.ui.show_nags)
+                .transpositions(&self.transpositions);
+            frame.render_widget(moves_widget, moves_area);
+        }
 
-        // Render move history
-        let moves_widget = MovesWidget::new(&self.game, self.move_scroll);
-        frame.render_widget(moves_widget, content_chunks[1]);
+        // Render the FEN line, cut on the left when it doesn't fit so the
+        // side to move, castling rights and counters stay in view
+        if self.config.ui.show_fen {
+            let fen = self.game.to_fen();
+            let width = main_chunks[2].width.saturating_sub(1) as usize;
+            let len = fen.chars().count();
+            let fen = if len > width {
+                let cut: String = fen.chars().skip(len + 1 - width).collect();
+                format!("…{}", cut)
+            } else {
+                fen
+            };
+            let fen_widget = ratatui::widgets::Paragraph::new(format!(" {}", fen))
+                .style(ratatui::style::Style::default().fg(ratatui::style::Color::Gray));
+            frame.render_widget(fen_widget, main_chunks[2]);
+        }
 
         // Render input bar
         let input_widget = InputWidget::new(&self.input);
-        frame.render_widget(input_widget, main_chunks[2]);
+        frame.render_widget(input_widget, main_chunks[3]);
 
         // Render help bar
         let help_widget = HelpBarWidget::new(self.input.is_input_mode());
-        frame.render_widget(help_widget, main_chunks[3]);
+        frame.render_widget(help_widget, main_chunks[4]);
 
         // Render popup if any
+        self.render_popup(frame);
+
+        // Terminals without true color get the nearest palette colors
+        self.color_depth.apply(frame.buffer_mut());
+    }
+
+    /// Draw the analysis panel (or the training panel in its place), split in
+    /// two when comparing engines
+    fn render_analysis(&self, frame: &mut Frame, analysis_area: Rect, compact: bool) {
+        let palette = self.config.ui.get_palette();
+        if let Some(ref quiz) = self.quiz {
+            frame.render_widget(QuizWidget::new(quiz).palette(palette), analysis_area);
+        } else if let Some(ref session) = self.guess {
+            frame.render_widget(GuessWidget::new(session).palette(palette), analysis_area);
+        } else if let Some(ref session) = self.puzzle {
+            frame.render_widget(PuzzleWidget::new(session).palette(palette), analysis_area);
+        } else if let Some(ref session) = self.mates {
+            frame.render_widget(MateWidget::new(session).palette(palette), analysis_area);
+        } else if let Some(ref session) = self.drill {
+            frame.render_widget(
+                DrillWidget::new(session, &self.game).palette(palette),
+                analysis_area,
+            );
+        } else if let Some(ref second) = self.second_engine {
+            let compare_chunks = Layout::default()
+                .direction(Direction::Horizontal)
+                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .split(analysis_area);
+            let disagree = self.engines_disagree();
+            let panes = [
+                (&self.analysis, self.engine.as_ref(), compare_chunks[0]),
+                (&self.second_analysis, Some(second), compare_chunks[1]),
+            ];
+            for (i, (state, engine, area)) in panes.into_iter().enumerate() {
+                // Selection applies to the main engine's lines
+                let selected = if i == 0 { self.selected_line } else { None };
+                let analysis_widget = AnalysisWidget::new(
+                    state,
+                    self.game.position(),
+                    self.config.engine.multipv,
+                    self.config.ui.analysis_eval_only,
+                )
+                .white_perspective(self.config.ui.white_perspective)
+                .selected(selected, self.expand_line)
+                .threat(if i == 0 { self.threat_text() } else { None })
+                .title(engine.and_then(|e| e.name.clone()))
+                .disagree(disagree)
+                .palette(self.config.ui.get_palette())
+                .compact(compact);
+                frame.render_widget(analysis_widget, area);
+            }
+        } else {
+            let multipv = self.analysis_multipv();
+            let analysis_widget = AnalysisWidget::new(
+                &self.analysis,
+                self.game.position(),
+                multipv,
+                self.config.ui.analysis_eval_only,
+            )
+            .candidates(!self.candidates.is_empty())
+            .white_perspective(self.config.ui.white_perspective)
+            .selected(self.selected_line, self.expand_line)
+            .threat(self.threat_text())
+            .tablebase(self.tablebase_text())
+            .cloud(self.cloud_text())
+            .palette(self.config.ui.get_palette())
+            .compact(compact);
+            frame.render_widget(analysis_widget, analysis_area);
+        }
+    }
+
+    /// Switch to a built-in layout or a preset from the config
+    fn set_layout(&mut self, name: &str) {
+        let name = match ScreenLayout::from_name(name) {
+            Some(layout) => layout.name(),
+            None => match self.config.ui.layouts.iter().find(|p| p.name == name) {
+                Some(preset) => {
+                    if let Err(e) = PanelLayout::parse(&preset.arrangement, &preset.panels) {
+                        self.input.set_error(format!("Layout {}: {:#}", name, e));
+                        return;
+                    }
+                    name
+                }
+                None => {
+                    self.input.set_error(format!(
+                        "Unknown layout {} ({})",
+                        name,
+                        self.config.ui.layout_names().join(", ")
+                    ));
+                    return;
+                }
+            },
+        };
+        self.config.ui.layout = name.to_string();
+        self.input.set_message(format!("Layout: {}", name));
+    }
+
+    /// Switch to the next layout: the built-in ones, then the presets
+    /// (skipping broken ones, which `:layout` reports)
+    fn cycle_layout(&mut self) {
+        let ui = &self.config.ui;
+        let names: Vec<String> = ui
+            .layout_names()
+            .into_iter()
+            .filter(|name| {
+                ui.layouts
+                    .iter()
+                    .find(|preset| preset.name == *name)
+                    .is_none_or(|preset| {
+                        PanelLayout::parse(&preset.arrangement, &preset.panels).is_ok()
+                    })
+            })
+            .collect();
+        let current = names.iter().position(|n| *n == self.config.ui.layout);
+        let next = current.map_or(0, |i| (i + 1) % names.len());
+        self.set_layout(&names[next]);
+    }
+
+    /// Split the main content between the board (with its status line)
+    /// and the panels of the current layout, which are None when hidden
+    fn panel_areas(
+        &self,
+        content: Rect,
+        board_widget: &BoardWidget,
+        compact: bool,
+    ) -> (Rect, Option<Rect>, Option<Rect>, Option<Rect>) {
+        let training_panel = self.quiz.is_some()
+            || self.guess.is_some()
+            || self.puzzle.is_some()
+            || self.mates.is_some()
+            || self.drill.is_some();
+
+        let mut layout = if compact {
+            PanelLayout::builtin(ScreenLayout::Stacked)
+        } else {
+            self.config.ui.get_layout()
+        };
+        // The eval graph is only shown once there are evaluations to plot
+        if compact || !(self.game.has_evals() || self.review.is_some()) {
+            layout.panels.retain(|(panel, _)| *panel != Panel::Graph);
+        }
+        if compact && !self.show_moves {
+            layout.panels.retain(|(panel, _)| *panel != Panel::Moves);
+        }
+        // The explorer is drawn in place of the move list, which shows for it
+        let explorer = matches!(self.popup, Popup::Explorer(..));
+        if explorer && layout.panel(Panel::Moves).is_none() {
+            layout.panels.push((Panel::Moves, None));
+        }
+
+        // The move list widens to fit clock times and evals when the game has them
+        let mut moves_width = 22;
+        if self.game.has_clocks() {
+            moves_width += 16;
+        }
+        if self.game.has_evals() {
+            moves_width += 14;
+        }
+        if explorer {
+            moves_width = moves_width.max(40);
+        }
+        let analysis_height = if compact && !training_panel { 1 } else { 8 };
+
+        let mut areas = [None; 3];
+        let area_index = |panel: Panel| match panel {
+            Panel::Analysis => 0,
+            Panel::Graph => 1,
+            Panel::Moves => 2,
+        };
+
+        let board_area = match layout.arrangement {
+            ScreenLayout::Side => {
+                // Board on the left, as large as the height allows, with the
+                // panels in a column on the right
+                let mut board_space = content;
+                if !layout.panels.is_empty() {
+                    board_space.width = board_space.width.saturating_sub(moves_width.max(40));
+                }
+                let board_width = board_widget.pane_width(board_space);
+                let board_height = board_widget.pane_height(board_space);
+                let columns = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints([Constraint::Length(board_width), Constraint::Min(0)])
+                    .split(content);
+                let constraints: Vec<Constraint> = layout
+                    .panels
+                    .iter()
+                    .map(|&(panel, size)| match (panel, size) {
+                        (_, Some(size)) => Constraint::Length(size),
+                        (Panel::Analysis, None) => Constraint::Percentage(45),
+                        (Panel::Moves, None) => Constraint::Min(5),
+                        (Panel::Graph, None) => Constraint::Length(7),
+                    })
+                    .collect();
+                let rows = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints(constraints)
+                    .split(columns[1]);
+                for (&(panel, _), &area) in layout.panels.iter().zip(rows.iter()) {
+                    areas[area_index(panel)] = Some(area);
+                }
+                let mut board_area = columns[0];
+                board_area.height = board_height.min(board_area.height);
+                board_area
+            }
+            ScreenLayout::Stacked => {
+                // Main content: horizontal split into (board + panels) | moves
+                let moves = layout.panel(Panel::Moves);
+                let content_chunks = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints([
+                        Constraint::Min(if compact { 29 } else { 40 }), // Left side (board + panels)
+                        Constraint::Length(match moves {
+                            Some(size) => size.unwrap_or(moves_width),
+                            None => 0,
+                        }), // Move history
+                    ])
+                    .split(content);
+                if moves.is_some() {
+                    areas[area_index(Panel::Moves)] = Some(content_chunks[1]);
+                }
+
+                // Left panel: board on top, the other panels below
+                let below: Vec<(Panel, Constraint)> = layout
+                    .panels
+                    .iter()
+                    .filter(|(panel, _)| *panel != Panel::Moves)
+                    .map(|&(panel, size)| {
+                        let constraint = match (panel, size) {
+                            (_, Some(size)) => Constraint::Length(size),
+                            (Panel::Analysis, None) => Constraint::Min(analysis_height),
+                            (_, None) => Constraint::Length(7),
+                        };
+                        (panel, constraint)
+                    })
+                    .collect();
+                // The board takes what the panels leave, e.g. 8*2 cells + 2
+                // border + 1 coords + 2 captured + 1 status = 22 rows for 4×2 squares
+                let reserved: u16 = below
+                    .iter()
+                    .map(|(_, constraint)| match constraint {
+                        Constraint::Length(n) | Constraint::Min(n) => *n,
+                        _ => 0,
+                    })
+                    .sum();
+                let mut board_space = content_chunks[0];
+                board_space.height = content.height.saturating_sub(reserved);
+                let board_height = board_widget.pane_height(board_space);
+                let constraints: Vec<Constraint> =
+                    std::iter::once(Constraint::Length(board_height))
+                        .chain(below.iter().map(|(_, constraint)| *constraint))
+                        .chain(std::iter::once(Constraint::Min(0)))
+                        .collect();
+                let left_chunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints(constraints)
+                    .split(content_chunks[0]);
+                for (&(panel, _), &area) in below.iter().zip(left_chunks.iter().skip(1)) {
+                    areas[area_index(panel)] = Some(area);
+                }
+                left_chunks[0]
+            }
+        };
+        (board_area, areas[0], areas[1], areas[2])
+    }
+
+    /// The board with every overlay, blank for the image when it's drawn
+    /// with graphics
+    fn board_widget(&self) -> BoardWidget<'_> {
+        let threat_arrows: Vec<Arrow> = self
+            .threat_arrow()
+            .into_iter()
+            .chain(self.quiz_arrow())
+            .chain(self.puzzle_arrow())
+            .chain(self.mate_arrow())
+            .collect();
+        // Popups would be hidden under the board image
+        let graphics = self.renderer.is_graphics() && self.popup == Popup::None;
+        BoardWidget::new(&self.game, &self.config.ui)
+            .glyphs(&self.config.pieces)
+            .extra_arrows(&threat_arrows)
+            .heatmap(self.heatmap_losses())
+            .graphics(graphics)
+            .half_blocks(self.renderer == BoardRenderer::HalfBlock)
+            .cache(&self.board_cache)
+    }
+
+    /// Draw a board pane, noting where the squares are for a board image
+    fn render_board(&self, frame: &mut Frame, board_widget: BoardWidget, area: Rect) {
+        *self.board_squares.borrow_mut() = board_widget
+            .squares_area(area)
+            .filter(|_| self.renderer.is_graphics() && self.popup == Popup::None)
+            .map(|squares| (squares, board_widget.squares()));
+        frame.render_widget(board_widget, area);
+    }
+
+    /// Zen mode: just the board, as large as it fits, in the middle of the
+    /// screen (with the input bar while typing)
+    fn render_zen(&self, frame: &mut Frame) {
+        let mut area = frame.area();
+        if self.input.is_input_mode() {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Min(1), Constraint::Length(3)])
+                .split(area);
+            frame.render_widget(InputWidget::new(&self.input), chunks[1]);
+            area = chunks[0];
+        }
+
+        let board_widget = self.board_widget();
+        let height = board_widget.pane_height(area).min(area.height);
+        let width = board_widget.pane_width(area).min(area.width);
+        let pane = Rect::new(
+            area.x + (area.width - width) / 2,
+            area.y + (area.height - height) / 2,
+            width,
+            height,
+        );
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(1), Constraint::Length(1)])
+            .split(pane);
+        self.render_board(frame, board_widget, chunks[0]);
+        frame.render_widget(
+            StatusWidget::new(&self.game).announcement(self.announcement()),
+            chunks[1],
+        );
+    }
+
+    /// How long the main loop may sleep without events: short while the
+    /// screen changes on its own, long otherwise
+    pub fn tick_interval(&self) -> Duration {
+        if self.animating() {
+            Duration::from_millis(100)
+        } else {
+            Duration::from_secs(1)
+        }
+    }
+
+    /// Check if the screen changes without any event: a clock is counting
+    /// down or autoplay runs
+    pub fn animating(&self) -> bool {
+        let clock_running = self
+            .play
+            .as_ref()
+            .and_then(|p| p.clock.as_ref())
+            .is_some_and(|c| c.running().is_some());
+        let rush_running = self
+            .puzzle
+            .as_ref()
+            .and_then(|s| s.rush.as_ref())
+            .is_some_and(|r| !r.finished);
+        let autoplaying = self.autoplay.as_ref().is_some_and(|a| !a.paused);
+        clock_running || rush_running || autoplaying
+    }
+
+    /// Redraw the board image at its new place after the terminal is resized
+    pub fn resized(&mut self) {
+        self.board_image = None;
+    }
+
+    /// Draw the open popup, if any, over the screen
+    fn render_popup(&self, frame: &mut Frame) {
+        let size = frame.area();
         match &self.popup {
             Popup::Help => {
                 let area = HelpPopup::centered_rect(60, 80, size);
@@ -705,13 +6358,312 @@ impl App {
                 let area = HelpPopup::centered_rect(35, 30, size);
                 frame.render_widget(MultiPVPopup::new(self.config.engine.multipv, input), area);
             }
-            Popup::None => {}
+            Popup::Strength(input) => {
+                let area = HelpPopup::centered_rect(40, 30, size);
+                frame.render_widget(StrengthPopup::new(&self.config.engine, input), area);
+            }
+            Popup::Engine(candidates, selected) => {
+                let area = HelpPopup::centered_rect(60, 40, size);
+                frame.render_widget(EnginePopup::new(candidates, *selected), area);
+            }
+            Popup::Library(selected) => {
+                let area = HelpPopup::centered_rect(50, 70, size);
+                frame.render_widget(LibraryPopup::new(*selected), area);
+            }
+            Popup::Headers(selected, input) => {
+                let area = HelpPopup::centered_rect(60, 60, size);
+                let rows = self.header_rows();
+                frame.render_widget(HeadersPopup::new(&rows, *selected, input.as_deref()), area);
+            }
+            Popup::Games(pgn_files, selected) => {
+                let area = HelpPopup::centered_rect(70, 70, size);
+                frame.render_widget(GamesPopup::new(pgn_files, *selected), area);
+            }
+            Popup::UciLog(scroll) => {
+                let entries = self
+                    .engine
+                    .as_ref()
+                    .map(|e| e.log().entries())
+                    .unwrap_or_default();
+                let area = HelpPopup::centered_rect(90, 85, size);
+                frame.render_widget(UciLogWidget::new(&entries, *scroll), area);
+            }
+            Popup::Summary(summary) => {
+                let deltas = self.summary_deltas();
+                let height = if deltas.is_empty() { 40 } else { 60 };
+                let area = HelpPopup::centered_rect(50, height, size);
+                let white = self.game.header("White").unwrap_or("White");
+                let black = self.game.header("Black").unwrap_or("Black");
+                frame.render_widget(
+                    SummaryPopup::new(summary, white, black).deltas(&deltas),
+                    area,
+                );
+            }
+            Popup::BoardText => {
+                let mut lines = self.game.describe_ranks(self.config.ui.flip_board);
+                lines.push(self.announcement_text());
+                let area = HelpPopup::centered_rect(80, 70, size);
+                frame.render_widget(BoardTextPopup::new(&lines), area);
+            }
+            Popup::StaticEval(scroll) => {
+                let area = HelpPopup::centered_rect(70, 70, size);
+                frame.render_widget(
+                    StaticEvalPopup::new(self.static_eval.as_ref(), *scroll),
+                    area,
+                );
+            }
+            // Drawn in place of the move list, except on the zen screen
+            Popup::Explorer(source, selected) if self.zen => {
+                let area = HelpPopup::centered_rect(50, 70, size);
+                frame.render_widget(ratatui::widgets::Clear, area);
+                self.render_explorer(frame, area, *source, *selected);
+            }
+            Popup::Explorer(..) | Popup::None => {}
+        }
+    }
+
+    /// Draw the opening explorer for the current position
+    fn render_explorer(
+        &self,
+        frame: &mut Frame,
+        area: Rect,
+        source: ExplorerSource,
+        selected: usize,
+    ) {
+        let title = match source {
+            ExplorerSource::Games => format!("Explorer: {} games", self.opening_tree.games()),
+            ExplorerSource::Masters => "Explorer: masters".to_string(),
+        };
+        let (stats, note) = match self.explorer_stats(source) {
+            Ok(stats) => (stats, None),
+            Err(note) => (None, Some(note)),
+        };
+        frame.render_widget(
+            ExplorerWidget::new(&title, stats.as_ref(), self.game.turn(), selected)
+                .note(note.as_deref())
+                .palette(self.config.ui.get_palette()),
+            area,
+        );
+    }
+
+    /// Escape sequence drawing the board image after a frame when the board
+    /// changed since it was drawn (or removing the image while a popup is open)
+    pub fn board_graphics(&mut self) -> Option<String> {
+        let board = self.board_squares.get_mut().take();
+        if board == self.board_image {
+            return None;
+        }
+        self.board_image = board;
+        let Some((area, squares)) = board else {
+            return (self.renderer == BoardRenderer::Kitty).then(kitty_delete_sequence);
+        };
+
+        // Size the image from the terminal's cell size when it reports one
+        let (cell_width, cell_height) = match crossterm::terminal::window_size() {
+            Ok(size) if size.width > 0 && size.columns > 0 && size.rows > 0 => (
+                (size.width / size.columns) as u32,
+                (size.height / size.rows) as u32,
+            ),
+            _ => (8, 16),
+        };
+        let width = area.width as u32 * cell_width;
+        let height = area.height as u32 * cell_height;
+        let image = BoardImage::render(&squares, width.min(height));
+        match self.renderer {
+            BoardRenderer::Kitty => Some(kitty_sequence(&image, area, height < width)),
+            BoardRenderer::Iterm2 => iterm2_sequence(&image, area).ok(),
+            BoardRenderer::Sixel => Some(sixel_sequence(&image, area)),
+            BoardRenderer::Text | BoardRenderer::HalfBlock => None,
         }
     }
 
     /// Main application tick - process events
     pub fn tick(&mut self) -> Result<()> {
-        self.process_engine_events();
+        self.process_engine_events()?;
+        self.process_review()?;
+        self.check_play_clock()?;
+        self.check_puzzle_rush();
+        let analysis_time = self.analysis_finished();
+        self.step_autoplay()?;
+        self.update_masters();
+        self.update_tablebase();
+        self.update_cloud();
+        self.update_lichess_import();
+        self.update_puzzle_draw()?;
+        let change = self.game_change();
+        if change != GameChange::None {
+            let shown = (!self.pgn_games.is_empty()).then_some(self.pgn_game);
+            self.transpositions = transpositions::transpositions(&self.game, &self.arrivals, shown);
+        }
+        if change == GameChange::Loaded {
+            self.autoplay = None;
+            // Loading something else leaves the null move behind
+            let passed = self.before_pass.as_ref().map(|(_, start)| start);
+            if passed.is_some_and(|start| start != self.game.initial_position()) {
+                self.before_pass = None;
+            }
+        }
+        self.check_sounds(change == GameChange::Played, analysis_time.is_some());
+        self.run_script_hooks(change)?;
+        if analysis_time.is_some_and(|t| t >= NOTIFY_SEARCH_TIME) {
+            let eval = self
+                .analysis
+                .lines
+                .first()
+                .map(|info| format_score(info.score_cp, info.score_mate))
+                .unwrap_or_default();
+            let body = format!("Depth {}, eval {}", self.analysis.target_depth, eval);
+            self.notify("Analysis finished", &body);
+        }
+        Ok(())
+    }
+
+    /// How long the analysis took when it has just reached its target depth
+    fn analysis_finished(&mut self) -> Option<Duration> {
+        if !self.analysis.is_done() {
+            self.analysis_started.get_or_insert_with(Instant::now);
+            return None;
+        }
+        self.analysis_started.take().map(|start| start.elapsed())
+    }
+
+    /// Send a desktop notification, if enabled and the user is looking at
+    /// another window
+    fn notify(&self, summary: &str, body: &str) {
+        if self.config.ui.notifications && !self.focused {
+            notify::send(summary, body);
+        }
+    }
+
+    /// Work out how the game changed since the last tick
+    fn game_change(&mut self) -> GameChange {
+        let initial = self.game.initial_position();
+        let moves = self.game.moves();
+        let index = self.game.current_index();
+        let (old_initial, old_moves, old_index) = &self.seen_game;
+        if initial == old_initial && moves == old_moves.as_slice() && index == *old_index {
+            return GameChange::None;
+        }
+
+        let played = initial == old_initial
+            && index == moves.len()
+            && index > *old_index
+            && index - old_index <= 2
+            && moves.get(..*old_index) == old_moves.get(..*old_index);
+        // Moves that aren't a continuation of the old ones (or the old ones
+        // cut short) come from another game
+        let loaded = initial != old_initial
+            || !(moves.starts_with(old_moves) || old_moves.starts_with(moves));
+        let change = if played {
+            GameChange::Played
+        } else if loaded {
+            GameChange::Loaded
+        } else {
+            GameChange::Other
+        };
+        self.seen_game = (initial.clone(), moves.to_vec(), index);
+        change
+    }
+
+    /// Play sounds for a move played and for analysis reaching its depth
+    fn check_sounds(&mut self, played: bool, analysis_done: bool) {
+        let mut events = Vec::new();
+        if played {
+            events.push(if self.game.is_game_over() {
+                SoundEvent::GameOver
+            } else if self.game.position().is_check() {
+                SoundEvent::Check
+            } else {
+                SoundEvent::Move
+            });
+        }
+        if analysis_done {
+            events.push(SoundEvent::AnalysisDone);
+        }
+
+        for event in events {
+            if let Err(e) = sound::play(self.config.sounds.action(event)) {
+                self.input.set_error(e.to_string());
+            }
+        }
+    }
+
+    /// Load the user script, replacing any loaded before. Returns false
+    /// (with the error shown) when it fails to load.
+    fn load_script(&mut self, path: &str) -> bool {
+        match Script::load(path) {
+            Ok(script) => {
+                self.script = Some(script);
+                true
+            }
+            Err(e) => {
+                self.input.set_error(format!("{:#}", e));
+                false
+            }
+        }
+    }
+
+    /// Call the script's hooks for what happened since the last tick, then
+    /// carry out the moves and commands it asked for
+    fn run_script_hooks(&mut self, change: GameChange) -> Result<()> {
+        let Some(mut script) = self.script.take() else {
+            return Ok(());
+        };
+        let index = self.game.current_index();
+        let mut calls: Vec<(&str, Vec<Dynamic>)> = Vec::new();
+        match change {
+            GameChange::Played => {
+                let san = index
+                    .checked_sub(1)
+                    .and_then(|i| Some((self.game.position_at(i)?, self.game.moves().get(i)?)))
+                    .map(|(position, m)| self.game.move_to_san(m, &position))
+                    .unwrap_or_default();
+                calls.push(("on_move", vec![san.into()]));
+            }
+            GameChange::Loaded => calls.push(("on_game_loaded", Vec::new())),
+            GameChange::None | GameChange::Other => {}
+        }
+        let first = self.analysis.lines.first();
+        let depth = first.and_then(|l| l.depth);
+        if depth != script.analysis_depth {
+            script.analysis_depth = depth;
+            if let Some(info) = first {
+                let pv = self.analysis.san(self.game.position(), &info.pv);
+                let map = analysis_map(info.depth, info.score_cp, info.score_mate, &pv);
+                calls.push(("on_analysis", vec![map.into()]));
+            }
+        }
+        calls.retain(|(hook, _)| script.has_hook(hook));
+        if calls.is_empty() {
+            self.script = Some(script);
+            return Ok(());
+        }
+
+        let mut moves = Vec::new();
+        let mut position = self.game.initial_position().clone();
+        for m in self.game.moves() {
+            moves.push(self.game.move_to_san(m, &position));
+            position.play_unchecked(m);
+        }
+        script.set_game(self.game.to_fen(), moves, index);
+
+        let mut outputs = Vec::new();
+        for (hook, args) in calls {
+            match script.call(hook, args) {
+                Ok(output) => outputs.push(output),
+                Err(e) => self.input.set_error(format!("{:#}", e)),
+            }
+        }
+        self.script = Some(script);
+
+        for output in outputs {
+            for message in output.messages {
+                self.input.set_message(message);
+            }
+            for action in output.actions {
+                self.submit_line(&action)?;
+            }
+        }
         Ok(())
     }
 }
@@ -719,6 +6671,287 @@ impl App {
 impl Drop for App {
     fn drop(&mut self) {
         // Save config on exit
-        let _ = self.config.save();
+        if let Err(e) = self.config.save() {
+            tracing::warn!("{:#}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::engine::MockEngine;
+    use std::thread;
+
+    const SEARCH: &[&str] = &[
+        "info depth 20 seldepth 24 multipv 1 score cp 31 nodes 120000 nps 600000 pv e2e4 e7e5 g1f3",
+        "info depth 20 seldepth 22 multipv 2 score cp 25 nodes 120000 nps 600000 pv d2d4 d7d5",
+        "bestmove e2e4 ponder e7e5",
+    ];
+
+    fn app(engine: MockEngine) -> App {
+        app_with(Config::default(), engine)
+    }
+
+    fn app_with(config: Config, engine: MockEngine) -> App {
+        App::with_backend(config, Waker::default(), Box::new(engine)).unwrap()
+    }
+
+    /// Tick the app until the engine's output has been handled
+    fn wait_until(app: &mut App, done: impl Fn(&App) -> bool) {
+        for _ in 0..500 {
+            app.tick().unwrap();
+            if done(app) {
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        panic!("Timed out waiting for the engine");
+    }
+
+    #[test]
+    fn analysis_lines_come_from_the_engine() {
+        let mut app = app(MockEngine::new("Mock").unwrap().search(SEARCH));
+        wait_until(&mut app, |app| app.analysis.is_done());
+
+        let lines = &app.analysis.lines;
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0].score_cp, Some(31));
+        assert_eq!(lines[0].pv, ["e2e4", "e7e5", "g1f3"]);
+        assert_eq!(lines[1].pv, ["d2d4", "d7d5"]);
+        assert_eq!(app.engine.as_ref().unwrap().name.as_deref(), Some("Mock"));
+    }
+
+    #[test]
+    fn best_move_is_played_from_analysis() {
+        let reply = &[
+            "info depth 20 seldepth 20 multipv 1 score cp -28 nodes 90000 nps 600000 pv c7c5 g1f3",
+            "bestmove c7c5 ponder g1f3",
+        ];
+        let engine = MockEngine::new("Mock")
+            .unwrap()
+            .search(SEARCH)
+            .search(reply);
+        let mut app = app(engine);
+        wait_until(&mut app, |app| app.analysis.is_done());
+
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('1'), KeyModifiers::NONE))
+            .unwrap();
+        assert_eq!(app.game.moves().len(), 1);
+        wait_until(&mut app, |app| app.analysis.is_done());
+        assert_eq!(app.analysis.lines[0].pv, ["c7c5", "g1f3"]);
+    }
+
+    #[test]
+    fn summary_lists_moves_that_lost_eval() {
+        let mut app = app(MockEngine::new("Mock").unwrap().search(SEARCH));
+        wait_until(&mut app, |app| app.analysis.is_done());
+
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('2'), KeyModifiers::NONE))
+            .unwrap();
+        assert_eq!(app.summary_deltas(), ["1. d4 -0.06 vs e4"]);
+
+        // Playing the best move instead replaces it
+        app.handle_key_event(KeyEvent::new(KeyCode::Left, KeyModifiers::NONE))
+            .unwrap();
+        wait_until(&mut app, |app| app.analysis.is_done());
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('1'), KeyModifiers::NONE))
+            .unwrap();
+        assert!(app.summary_deltas().is_empty());
+    }
+
+    #[test]
+    fn puzzles_are_drawn_in_the_background_and_give_the_game_back() {
+        let path =
+            std::env::temp_dir().join(format!("lazychess-puzzles-{}.csv", std::process::id()));
+        std::fs::write(
+            &path,
+            "PuzzleId,FEN,Moves,Rating,RatingDeviation,Popularity,NbPlays,Themes,GameUrl,OpeningTags\n\
+             LCTST,r6k/pp2r2p/4Rp1Q/3p4/8/1N1P2R1/PqP2bPP/7K b - - 0 24,f2g3 e6e7 b2b1 b3c1 b1c1 h6c1,1800,75,94,6230,crushing hangingPiece long middlegame,https://lichess.org/787zsVup/black#47,\n",
+        )
+        .unwrap();
+        let mut config = Config::default();
+        config.puzzles.path = Some(path.to_string_lossy().to_string());
+        let mut app = app_with(config, MockEngine::new("Mock").unwrap().search(SEARCH));
+        wait_until(&mut app, |app| app.analysis.is_done());
+        app.handle_key_event(KeyEvent::new(KeyCode::Char('1'), KeyModifiers::NONE))
+            .unwrap();
+
+        app.submit_line(":puzzle 0-3000").unwrap();
+        wait_until(&mut app, |app| app.puzzle.is_some());
+        assert_eq!(app.puzzle.as_ref().unwrap().puzzle.id, "LCTST");
+        assert_eq!(app.game.moves().len(), 1);
+        assert_ne!(app.game.initial_position(), &Chess::default());
+
+        app.submit_line(":puzzle stop").unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert!(app.puzzle.is_none());
+        assert_eq!(app.game.initial_position(), &Chess::default());
+        assert_eq!(app.game.numbered_san(0).as_deref(), Some("1. e4"));
+    }
+
+    #[test]
+    fn aliases_run_other_aliases() {
+        let mut config = Config::default();
+        let alias = |lines: &[&str]| lines.iter().map(|l| l.to_string()).collect();
+        config
+            .aliases
+            .insert("prep".into(), alias(&[":deep", ":flip"]));
+        config
+            .aliases
+            .insert("deep".into(), alias(&[":set engine.depth 30"]));
+        config.aliases.insert("ping".into(), alias(&[":pong"]));
+        config.aliases.insert("pong".into(), alias(&[":ping"]));
+        let mut app = app_with(config, MockEngine::new("Mock").unwrap().search(SEARCH));
+
+        app.submit_line(":prep").unwrap();
+        assert_eq!(app.input.error, None);
+        assert_eq!(app.config.engine.depth, 30);
+        assert!(app.config.ui.flip_board);
+
+        app.submit_line(":ping").unwrap();
+        let error = app.input.error.clone().unwrap();
+        assert!(
+            error.contains("Alias ping runs itself (ping -> pong)"),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn finished_search_is_not_paused_on_focus_loss() {
+        let mut config = Config::default();
+        config.ui.pause_on_unfocus = true;
+        let mut app = app_with(config, MockEngine::new("Mock").unwrap().search(SEARCH));
+        wait_until(&mut app, |app| app.analysis.is_done());
+
+        app.focus_lost().unwrap();
+        assert!(!app.analysis.is_paused);
+        app.focus_gained().unwrap();
+        assert!(app.analysis.is_done());
+        assert_eq!(app.analysis.lines.len(), 2);
+    }
+
+    #[test]
+    fn deepened_search_resumes_to_its_depth() {
+        let mut config = Config::default();
+        config.ui.pause_on_unfocus = true;
+        let deeper = &["info depth 21 seldepth 26 multipv 1 score cp 30 pv e2e4 e7e5"];
+        let engine = MockEngine::new("Mock")
+            .unwrap()
+            .search(SEARCH)
+            .search(deeper);
+        let mut app = app_with(config, engine);
+        wait_until(&mut app, |app| app.analysis.is_done());
+
+        app.deepen(5).unwrap();
+        app.focus_lost().unwrap();
+        assert!(app.analysis.is_paused);
+        app.focus_gained().unwrap();
+        assert!(app.analysis.is_running);
+        assert_eq!(app.analysis.target_depth, 25);
+    }
+
+    #[test]
+    fn control_socket_cannot_start_programs() {
+        let mut app = app(MockEngine::new("Mock").unwrap().search(SEARCH));
+        for command in [
+            json!({ "cmd": "command", "command": "engine /bin/sh" }),
+            json!({ "cmd": "command", "command": ":compare /bin/sh" }),
+            json!({ "cmd": "move", "move": ":script /tmp/evil.rhai" }),
+            json!({ "cmd": "command", "command": "set engine.path /bin/sh" }),
+            json!({ "cmd": "command", "command": "set! script.path /tmp/evil.rhai" }),
+            json!({ "cmd": "command", "command": "set sounds.move_played rm -rf ~" }),
+            json!({ "cmd": "command", "command": "set control.token none" }),
+            json!({ "cmd": "command", "command": "set online.lichess_token" }),
+            json!({ "cmd": "command", "command": "set aliases.x [\":engine /bin/sh\"]" }),
+            json!({ "cmd": "command", "command": "export /tmp/lazychess-remote.pgn" }),
+            json!({ "cmd": "command", "command": "export-image /tmp/lazychess-remote.png" }),
+        ] {
+            let reply = app.control(&command).unwrap();
+            assert_eq!(reply["ok"], false, "{}", command);
+        }
+        assert_eq!(app.engine.as_ref().unwrap().name.as_deref(), Some("Mock"));
+        assert_eq!(app.config.engine.path, None);
+        assert_eq!(
+            app.config.sounds.move_played,
+            Config::default().sounds.move_played
+        );
+        assert!(!Path::new("/tmp/lazychess-remote.pgn").exists());
+
+        // Settings that only change the display are fine
+        let reply = app
+            .control(&json!({ "cmd": "command", "command": "set ui.flip_board true" }))
+            .unwrap();
+        assert_eq!(reply["ok"], true);
+        assert!(app.config.ui.flip_board);
+
+        let reply = app
+            .control(&json!({ "cmd": "move", "move": "e4" }))
+            .unwrap();
+        assert_eq!(reply["ok"], true);
+    }
+
+    #[test]
+    fn commands_are_dispatched_by_name() {
+        let mut app = app(MockEngine::new("Mock").unwrap().search(SEARCH));
+        let fen = "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1";
+        app.submit_line(&format!(":fen {}", fen)).unwrap();
+        assert_eq!(app.game.to_fen(), fen);
+
+        app.submit_line(":bogus 1 2").unwrap();
+        assert_eq!(app.input.error.as_deref(), Some("Unknown command: bogus"));
+        assert_eq!(app.game.to_fen(), fen);
+
+        app.submit_line(":pgn").unwrap();
+        assert_eq!(app.input.mode, InputMode::Pgn);
+    }
+
+    #[test]
+    fn game_commands_wait_for_the_pass_to_be_undone() {
+        let mut app = app(MockEngine::new("Mock").unwrap().search(SEARCH));
+        app.toggle_pass().unwrap();
+        for line in [
+            ":lichess",
+            ":lichess pgn",
+            ":export-image /tmp/lazychess-pass.png",
+        ] {
+            app.input.clear_messages();
+            app.submit_line(line).unwrap();
+            let error = app.input.error.clone().unwrap_or_default();
+            assert!(error.contains("null move"), "{}: {}", line, error);
+        }
+        app.input.clear_messages();
+        app.handle_key_event(KeyEvent::from(KeyCode::Char('Y')))
+            .unwrap();
+        assert!(app.input.error.as_ref().unwrap().contains("null move"));
+        assert!(!Path::new("/tmp/lazychess-pass.png").exists());
+
+        app.toggle_pass().unwrap();
+        assert!(app.before_pass.is_none());
+    }
+
+    #[test]
+    fn static_eval_reads_the_engine_trace() {
+        let trace = &[
+            "     Term    |    White    |    Black    |    Total   ",
+            "    Mobility |  0.12  0.34 | -0.10 -0.20 |  0.02  0.14 ",
+            "NNUE evaluation        +0.21 (white side)",
+            "Final evaluation       +0.18 (white side) [with scaled NNUE, ...]",
+        ];
+        let engine = MockEngine::new("Mock")
+            .unwrap()
+            .search(SEARCH)
+            .reply("eval", trace);
+        let mut app = app(engine);
+        wait_until(&mut app, |app| app.analysis.is_done());
+
+        app.open_static_eval().unwrap();
+        wait_until(&mut app, |app| {
+            app.static_eval.as_ref().is_some_and(|eval| eval.complete)
+        });
+        let eval = app.static_eval.as_ref().unwrap();
+        assert_eq!(eval.terms.len(), 1);
+        assert_eq!(eval.summary.len(), 2);
     }
 }
@@ -0,0 +1,67 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+/// Number of Chess960 start positions
+pub const CHESS960_POSITIONS: u32 = 960;
+
+/// Placements of the two knights among the five files left after the
+/// bishops and queen, indexed as in the standard numbering
+const KNIGHTS: [(usize, usize); 10] = [
+    (0, 1),
+    (0, 2),
+    (0, 3),
+    (0, 4),
+    (1, 2),
+    (1, 3),
+    (1, 4),
+    (2, 3),
+    (2, 4),
+    (3, 4),
+];
+
+/// Pick a random Chess960 start position number
+pub fn random_chess960() -> u32 {
+    (RandomState::new().build_hasher().finish() % CHESS960_POSITIONS as u64) as u32
+}
+
+/// FEN of a Chess960 start position by its standard (Scharnagl) number,
+/// 0-959; number 518 is the regular start position
+pub fn chess960_fen(number: u32) -> String {
+    let mut rank: [Option<char>; 8] = [None; 8];
+    let mut n = (number % CHESS960_POSITIONS) as usize;
+
+    // Light-squared bishop on b, d, f or h, dark-squared on a, c, e or g
+    rank[n % 4 * 2 + 1] = Some('B');
+    n /= 4;
+    rank[n % 4 * 2] = Some('B');
+    n /= 4;
+
+    // The queen, knights, and then rook, king, rook fill the empty files in turn
+    let empty = |rank: &[Option<char>; 8]| -> Vec<usize> {
+        (0..8).filter(|&file| rank[file].is_none()).collect()
+    };
+    rank[empty(&rank)[n % 6]] = Some('Q');
+    n /= 6;
+    let files = empty(&rank);
+    let (first, second) = KNIGHTS[n];
+    rank[files[first]] = Some('N');
+    rank[files[second]] = Some('N');
+    let files = empty(&rank);
+    rank[files[0]] = Some('R');
+    rank[files[1]] = Some('K');
+    rank[files[2]] = Some('R');
+
+    let white: String = rank.iter().map(|piece| piece.unwrap_or('?')).collect();
+    // Shredder-FEN castling rights name the rooks' files
+    let rook_files: String = [files[2], files[0]]
+        .iter()
+        .map(|&file| (b'A' + file as u8) as char)
+        .collect();
+    format!(
+        "{}/pppppppp/8/8/8/8/PPPPPPPP/{} w {}{} - 0 1",
+        white.to_lowercase(),
+        white,
+        rook_files,
+        rook_files.to_lowercase()
+    )
+}
@@ -0,0 +1,165 @@
+use anyhow::{bail, Context, Result};
+use shakmaty::Color;
+use std::time::{Duration, Instant};
+
+/// A time control: base time plus increment per move
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeControl {
+    pub base: Duration,
+    pub increment: Duration,
+}
+
+impl TimeControl {
+    /// Parse a "minutes+increment" preset like "5+3" or "0.5+0"
+    pub fn parse(s: &str) -> Result<Self> {
+        let (base, inc) = s.trim().split_once('+').unwrap_or((s.trim(), "0"));
+        let minutes: f64 = base
+            .trim()
+            .parse()
+            .with_context(|| format!("Invalid base time '{}'", base))?;
+        let increment: u64 = inc
+            .trim()
+            .parse()
+            .with_context(|| format!("Invalid increment '{}'", inc))?;
+        if !minutes.is_finite() || minutes <= 0.0 {
+            bail!("Base time must be positive");
+        }
+        let base = Duration::try_from_secs_f64(minutes * 60.0)
+            .with_context(|| format!("Base time '{}' is too long", base))?;
+
+        Ok(Self {
+            base,
+            increment: Duration::from_secs(increment),
+        })
+    }
+}
+
+impl std::fmt::Display for TimeControl {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let minutes = self.base.as_secs_f64() / 60.0;
+        write!(f, "{}+{}", minutes, self.increment.as_secs())
+    }
+}
+
+/// A two-sided chess clock
+#[derive(Debug, Clone)]
+pub struct Clock {
+    /// Remaining time for [White, Black] as of the last press
+    remaining: [Duration; 2],
+    increment: Duration,
+    /// Side whose clock is running and when it started
+    running: Option<(Color, Instant)>,
+    /// Side that ran out of time
+    flagged: Option<Color>,
+}
+
+impl Clock {
+    /// Create a stopped clock for the given time control
+    pub fn new(tc: TimeControl) -> Self {
+        Self {
+            remaining: [tc.base, tc.base],
+            increment: tc.increment,
+            running: None,
+            flagged: None,
+        }
+    }
+
+    fn idx(color: Color) -> usize {
+        match color {
+            Color::White => 0,
+            Color::Black => 1,
+        }
+    }
+
+    /// Start (or switch) the clock for `color`
+    pub fn start(&mut self, color: Color) {
+        self.stop();
+        if self.flagged.is_none() {
+            self.running = Some((color, Instant::now()));
+        }
+    }
+
+    /// Stop the clock, banking elapsed time
+    pub fn stop(&mut self) {
+        if let Some((color, started)) = self.running.take() {
+            let idx = Self::idx(color);
+            self.remaining[idx] = self.remaining[idx].saturating_sub(started.elapsed());
+        }
+    }
+
+    /// `color` finished a move: stop their clock, add the increment and start
+    /// the opponent's. Returns the mover's remaining time.
+    pub fn press(&mut self, color: Color) -> Duration {
+        self.stop();
+        let idx = Self::idx(color);
+        if self.flagged.is_none() {
+            self.remaining[idx] += self.increment;
+            self.running = Some((!color, Instant::now()));
+        }
+        self.remaining[idx]
+    }
+
+    /// Live remaining time for `color`
+    pub fn remaining(&self, color: Color) -> Duration {
+        let banked = self.remaining[Self::idx(color)];
+        match self.running {
+            Some((running, started)) if running == color => {
+                banked.saturating_sub(started.elapsed())
+            }
+            _ => banked,
+        }
+    }
+
+    /// Side whose clock is currently running
+    pub fn running(&self) -> Option<Color> {
+        self.running.map(|(color, _)| color)
+    }
+
+    /// Get the increment per move
+    pub fn increment(&self) -> Duration {
+        self.increment
+    }
+
+    /// Check whether the running side has run out of time, stopping the clock if so
+    pub fn check_flag(&mut self) -> Option<Color> {
+        if self.flagged.is_none() {
+            if let Some(color) = self.running() {
+                if self.remaining(color).is_zero() {
+                    self.stop();
+                    self.flagged = Some(color);
+                }
+            }
+        }
+        self.flagged
+    }
+
+    /// Side that ran out of time, if any
+    pub fn flagged(&self) -> Option<Color> {
+        self.flagged
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_time_controls() {
+        let tc = TimeControl::parse("5+3").unwrap();
+        assert_eq!(tc.base, Duration::from_secs(300));
+        assert_eq!(tc.increment, Duration::from_secs(3));
+        assert_eq!(
+            TimeControl::parse("0.5").unwrap().base,
+            Duration::from_secs(30)
+        );
+    }
+
+    #[test]
+    fn parse_rejects_invalid_times() {
+        for s in [
+            "inf", "nan+0", "-inf+2", "0+0", "-1+0", "5+-1", "5+nan", "1e300",
+        ] {
+            assert!(TimeControl::parse(s).is_err(), "{} parsed", s);
+        }
+    }
+}
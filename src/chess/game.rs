@@ -2,8 +2,123 @@
 
 use anyhow::{Context, Result};
 use shakmaty::{
-    fen::Fen, san::San, CastlingMode, Chess, Color, Move, Piece, Position, Role, Square,
+    fen::Fen,
+    san::San,
+    zobrist::{Zobrist64, ZobristHash},
+    Bitboard, ByColor, CastlingMode, Chess, Color, EnPassantMode, File, Move, Piece, Position,
+    PositionError, PositionErrorKinds, Rank, Role, Square,
 };
+use std::time::Duration;
+
+/// Color of a board marking, matching the Lichess `%cal`/`%csl` letters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkColor {
+    Green,
+    Red,
+    Yellow,
+    Blue,
+}
+
+impl MarkColor {
+    /// Parse from the single-letter PGN code (G, R, Y, B)
+    pub fn from_code(c: char) -> Option<Self> {
+        match c.to_ascii_uppercase() {
+            'G' => Some(MarkColor::Green),
+            'R' => Some(MarkColor::Red),
+            'Y' => Some(MarkColor::Yellow),
+            'B' => Some(MarkColor::Blue),
+            _ => None,
+        }
+    }
+
+    /// Parse from a color name or single-letter code
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "green" | "g" => Some(MarkColor::Green),
+            "red" | "r" => Some(MarkColor::Red),
+            "yellow" | "y" => Some(MarkColor::Yellow),
+            "blue" | "b" => Some(MarkColor::Blue),
+            _ => None,
+        }
+    }
+
+    /// Single-letter PGN code
+    pub fn code(self) -> char {
+        match self {
+            MarkColor::Green => 'G',
+            MarkColor::Red => 'R',
+            MarkColor::Yellow => 'Y',
+            MarkColor::Blue => 'B',
+        }
+    }
+}
+
+/// An arrow drawn on the board
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Arrow {
+    pub from: Square,
+    pub to: Square,
+    pub color: MarkColor,
+}
+
+/// A circled (highlighted) square
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Circle {
+    pub square: Square,
+    pub color: MarkColor,
+}
+
+/// An evaluation of a position from White's point of view
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Eval {
+    /// Centipawns (positive = White is better)
+    Cp(i32),
+    /// Mate in N moves (positive = White mates)
+    Mate(i32),
+}
+
+impl Eval {
+    /// Centipawn value with mates mapped to large values, for graphs and deltas
+    pub fn to_cp(self) -> i32 {
+        match self {
+            Eval::Cp(cp) => cp,
+            Eval::Mate(m) if m > 0 => 10_000 - m,
+            Eval::Mate(m) => -10_000 - m,
+        }
+    }
+}
+
+/// Annotations attached to a position in the game
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Annotation {
+    /// Free-text comment
+    pub comment: Option<String>,
+    /// Arrows drawn in this position
+    pub arrows: Vec<Arrow>,
+    /// Circled squares in this position
+    pub circles: Vec<Circle>,
+    /// Evaluation of this position (e.g. from a PGN `[%eval]` tag)
+    pub eval: Option<Eval>,
+    /// Clock time left for the side that just moved (PGN `[%clk]`)
+    pub clock: Option<Duration>,
+    /// Numeric annotation glyphs of the move into this position (`$1` = `!`)
+    pub nags: Vec<u8>,
+    /// Alternatives to the move into this position, as numbered PGN movetext
+    pub variations: Vec<String>,
+}
+
+impl Annotation {
+    /// Check if there is nothing to display or export
+    pub fn is_empty(&self) -> bool {
+        self.comment.is_none()
+            && self.arrows.is_empty()
+            && self.circles.is_empty()
+            && self.eval.is_none()
+            && self.clock.is_none()
+            && self.nags.is_empty()
+            && self.variations.is_empty()
+    }
+}
 
 /// Represents the full game state with move history
 #[derive(Debug, Clone)]
@@ -17,6 +132,10 @@ pub struct Game {
     /// Current position index (for navigation). Points to the position AFTER moves[index-1]
     /// 0 = initial position, moves.len() = current/latest position
     current_index: usize,
+    /// Annotations per position, indexed like `current_index` (len = moves.len() + 1)
+    annotations: Vec<Annotation>,
+    /// PGN header tags, in order
+    headers: Vec<(String, String)>,
 }
 
 impl Default for Game {
@@ -33,22 +152,51 @@ impl Game {
             position: Chess::default(),
             moves: Vec::new(),
             current_index: 0,
+            annotations: vec![Annotation::default()],
+            headers: Vec::new(),
         }
     }
 
-    /// Create a game from a FEN string
+    /// Create a game from a FEN string. Castling rights and an en passant
+    /// square that can't apply to the position are dropped rather than
+    /// refused, since other tools write them carelessly and an engine would
+    /// otherwise be told about castling moves that don't exist.
     pub fn from_fen(fen: &str) -> Result<Self> {
+        Self::from_fen_dropping(fen).map(|(game, _)| game)
+    }
+
+    /// Create a game from a FEN string, naming the fields that were dropped
+    /// because they can't apply to the position
+    fn from_fen_dropping(fen: &str) -> Result<(Self, Vec<&'static str>)> {
         let fen: Fen = fen.parse().context("Invalid FEN string")?;
-        let position: Chess = fen
-            .into_position(CastlingMode::Standard)
-            .context("Invalid position")?;
+        // Castling rights that only make sense in Chess960 switch to its rules
+        let mode = CastlingMode::detect(fen.as_setup());
+        let mut dropped = Vec::new();
+        let position: Chess = match fen.into_position(mode) {
+            Ok(position) => position,
+            Err(e) => {
+                let kinds = e.kinds();
+                if kinds.contains(PositionErrorKinds::INVALID_CASTLING_RIGHTS) {
+                    dropped.push("castling rights");
+                }
+                if kinds.contains(PositionErrorKinds::INVALID_EP_SQUARE) {
+                    dropped.push("en passant square");
+                }
+                e.ignore_invalid_castling_rights()
+                    .or_else(PositionError::ignore_invalid_ep_square)
+                    .context("Invalid position")?
+            }
+        };
 
-        Ok(Self {
+        let game = Self {
             initial_position: position.clone(),
             position,
             moves: Vec::new(),
             current_index: 0,
-        })
+            annotations: vec![Annotation::default()],
+            headers: Vec::new(),
+        };
+        Ok((game, dropped))
     }
 
     /// Get the current FEN string
@@ -56,11 +204,42 @@ impl Game {
         Fen::from_position(self.position.clone(), shakmaty::EnPassantMode::Legal).to_string()
     }
 
+    /// The current position for a UCI `position` command: the start FEN
+    /// (None for the standard start position) and the moves played from it
+    /// in UCI, so the engine knows the history behind the position and can
+    /// tell repetitions
+    pub fn uci_position(&self) -> (Option<String>, Vec<String>) {
+        let fen = (!self.is_standard_start()).then(|| {
+            Fen::from_position(self.initial_position.clone(), EnPassantMode::Legal).to_string()
+        });
+        let mode = self.castling_mode();
+        let moves = self.moves[..self.current_index]
+            .iter()
+            .map(|m| m.to_uci(mode).to_string())
+            .collect();
+        (fen, moves)
+    }
+
+    /// Castling rules of the game: Chess960 when the start position needs them
+    pub fn castling_mode(&self) -> CastlingMode {
+        self.initial_position.castles().mode()
+    }
+
     /// Get the current position
     pub fn position(&self) -> &Chess {
         &self.position
     }
 
+    /// Get the position the game started from
+    pub fn initial_position(&self) -> &Chess {
+        &self.initial_position
+    }
+
+    /// Check if the game starts from the standard initial position
+    pub fn is_standard_start(&self) -> bool {
+        self.initial_position == Chess::default()
+    }
+
     /// Get whose turn it is
     pub fn turn(&self) -> Color {
         self.position.turn()
@@ -83,26 +262,31 @@ impl Game {
 
     /// Try to make a move from SAN notation (e.g., "e4", "Nf3", "O-O")
     pub fn make_move_san(&mut self, san_str: &str) -> Result<Move> {
-        let san: San = san_str.parse().context("Invalid move notation")?;
-        let m = san
-            .to_move(&self.position)
-            .context("Illegal move for current position")?;
-
+        let m = self.parse_san(san_str)?;
         self.make_move(m.clone())?;
         Ok(m)
     }
 
+    /// Parse a move in SAN notation for the current position without playing it
+    pub fn parse_san(&self, san_str: &str) -> Result<Move> {
+        let san: San = san_str.parse().context("Invalid move notation")?;
+        san.to_move(&self.position)
+            .context("Illegal move for current position")
+    }
+
     /// Make a move
     pub fn make_move(&mut self, m: Move) -> Result<()> {
         // If we're not at the end, truncate the move list (overwrite mode)
         if self.current_index < self.moves.len() {
             self.moves.truncate(self.current_index);
+            self.annotations.truncate(self.current_index + 1);
         }
 
         // Apply the move
         let new_position = self.position.clone().play(&m).context("Illegal move")?;
         self.position = new_position;
         self.moves.push(m);
+        self.annotations.push(Annotation::default());
         self.current_index = self.moves.len();
 
         Ok(())
@@ -162,6 +346,22 @@ impl Game {
         self.rebuild_position();
     }
 
+    /// Go to the position at an index (clamped to the game length)
+    pub fn go_to(&mut self, index: usize) {
+        self.current_index = index.min(self.moves.len());
+        self.rebuild_position();
+    }
+
+    /// Get the position at an index (0 = initial position)
+    pub fn position_at(&self, index: usize) -> Option<Chess> {
+        let moves = self.moves.get(..index)?;
+        let mut position = self.initial_position.clone();
+        for m in moves {
+            position = position.play(m).ok()?;
+        }
+        Some(position)
+    }
+
     /// Rebuild the position from moves up to current_index
     fn rebuild_position(&mut self) {
         self.position = self.initial_position.clone();
@@ -183,15 +383,143 @@ impl Game {
         }
     }
 
+    /// Get the annotation of the current position
+    pub fn annotation(&self) -> &Annotation {
+        &self.annotations[self.current_index]
+    }
+
+    /// Get the annotation of the current position for editing
+    pub fn annotation_mut(&mut self) -> &mut Annotation {
+        &mut self.annotations[self.current_index]
+    }
+
+    /// Get the annotations of all positions (index 0 = initial position)
+    pub fn annotations(&self) -> &[Annotation] {
+        &self.annotations
+    }
+
+    /// Check if any position carries clock times
+    pub fn has_clocks(&self) -> bool {
+        self.annotations.iter().any(|a| a.clock.is_some())
+    }
+
+    /// Check if any position carries an evaluation
+    pub fn has_evals(&self) -> bool {
+        self.annotations.iter().any(|a| a.eval.is_some())
+    }
+
+    /// Get the annotation of the position at `index` for editing
+    pub fn annotation_at_mut(&mut self, index: usize) -> Option<&mut Annotation> {
+        self.annotations.get_mut(index)
+    }
+
+    /// Add an arrow to the current position, or remove it if already present
+    pub fn toggle_arrow(&mut self, from: Square, to: Square, color: MarkColor) {
+        let arrows = &mut self.annotation_mut().arrows;
+        if let Some(idx) = arrows.iter().position(|a| a.from == from && a.to == to) {
+            if arrows[idx].color == color {
+                arrows.remove(idx);
+                return;
+            }
+            arrows.remove(idx);
+        }
+        arrows.push(Arrow { from, to, color });
+    }
+
+    /// Circle a square in the current position, or remove the circle if already present
+    pub fn toggle_circle(&mut self, square: Square, color: MarkColor) {
+        let circles = &mut self.annotation_mut().circles;
+        if let Some(idx) = circles.iter().position(|c| c.square == square) {
+            if circles[idx].color == color {
+                circles.remove(idx);
+                return;
+            }
+            circles.remove(idx);
+        }
+        circles.push(Circle { square, color });
+    }
+
+    /// Get the PGN header tags
+    pub fn headers(&self) -> &[(String, String)] {
+        &self.headers
+    }
+
+    /// Get a PGN header value by name
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Set a PGN header tag, replacing any existing value
+    pub fn set_header(&mut self, name: &str, value: &str) {
+        if let Some(entry) = self.headers.iter_mut().find(|(k, _)| k == name) {
+            entry.1 = value.to_string();
+        } else {
+            self.headers.push((name.to_string(), value.to_string()));
+        }
+    }
+
+    /// Remove a PGN header tag
+    pub fn remove_header(&mut self, name: &str) {
+        self.headers.retain(|(k, _)| k != name);
+    }
+
+    /// Describe the game from its headers, e.g. "Carlsen - Caruana, 1-0"
+    pub fn title(&self) -> String {
+        let player = |name| self.header(name).filter(|v| !v.is_empty()).unwrap_or("?");
+        format!(
+            "{} - {}, {}",
+            player("White"),
+            player("Black"),
+            self.header("Result").unwrap_or("*")
+        )
+    }
+
+    /// Summarise the game's headers for the title bar, e.g.
+    /// "Carlsen (2830) - Caruana (2805) · Norway Chess · 2024.05.27 · 1-0".
+    /// None when the headers name neither players nor an event.
+    pub fn info(&self) -> Option<String> {
+        let known = |name| {
+            self.header(name)
+                .filter(|v| !v.is_empty() && !v.chars().all(|c| c == '?' || c == '.'))
+        };
+        let player = |name, elo| match (known(name), known(elo)) {
+            (player, Some(elo)) => format!("{} ({})", player.unwrap_or("?"), elo),
+            (player, None) => player.unwrap_or("?").to_string(),
+        };
+
+        let mut parts = Vec::new();
+        if known("White").is_some() || known("Black").is_some() {
+            parts.push(format!(
+                "{} - {}",
+                player("White", "WhiteElo"),
+                player("Black", "BlackElo")
+            ));
+        }
+        parts.extend(known("Event").map(str::to_string));
+        if parts.is_empty() {
+            return None;
+        }
+        // Unknown month and day are dropped, e.g. "2024.??.??" -> "2024"
+        parts.extend(known("Date").map(|d| d.trim_end_matches(['?', '.']).to_string()));
+        parts.extend(known("TimeControl").map(str::to_string));
+        parts.extend(known("Result").filter(|r| *r != "*").map(str::to_string));
+        Some(parts.join(" · "))
+    }
+
     /// Reset to a new game
     pub fn reset(&mut self) {
         *self = Self::new();
     }
 
-    /// Load from FEN, resetting the game
-    pub fn load_fen(&mut self, fen: &str) -> Result<()> {
-        *self = Self::from_fen(fen)?;
-        Ok(())
+    /// Load from FEN, resetting the game. Returns the fields dropped because
+    /// they can't apply to the position (see `from_fen`).
+    pub fn load_fen(&mut self, fen: &str) -> Result<Vec<&'static str>> {
+        let (game, dropped) = Self::from_fen_dropping(fen)?;
+        *self = game;
+        Ok(dropped)
     }
 
     /// Get move as SAN string
@@ -200,6 +528,45 @@ impl Game {
         san.to_string()
     }
 
+    /// Numbered SAN of the move played from the position at an index,
+    /// e.g. "12. Nf3" or "12... Nc6"
+    pub fn numbered_san(&self, index: usize) -> Option<String> {
+        let position = self.position_at(index)?;
+        let m = self.moves.get(index)?;
+        let dots = if position.turn() == Color::White {
+            "."
+        } else {
+            "..."
+        };
+        Some(format!(
+            "{}{} {}",
+            position.fullmoves(),
+            dots,
+            self.move_to_san(m, &position)
+        ))
+    }
+
+    /// Position index after the next (or previous) move of a kind, counting
+    /// from the current position
+    pub fn find_move(&self, kind: MoveKind, forward: bool) -> Option<usize> {
+        let mut position = self.initial_position.clone();
+        let mut found = None;
+        for (i, m) in self.moves.iter().enumerate() {
+            let index = i + 1;
+            if !forward && index >= self.current_index {
+                break;
+            }
+            if kind.matches(&position, m) {
+                found = Some(index);
+                if forward && index > self.current_index {
+                    break;
+                }
+            }
+            position.play_unchecked(m);
+        }
+        found.filter(|&index| (index > self.current_index) == forward)
+    }
+
     /// Get the formatted move list for display
     pub fn formatted_moves(&self) -> Vec<(usize, String, Option<String>)> {
         let mut result = Vec::new();
@@ -246,54 +613,89 @@ impl Game {
         self.position.outcome()
     }
 
-    /// Get captured pieces for each side
-    /// Returns (white_captured, black_captured) where each is a list of roles
-    /// white_captured = pieces that white has captured (black pieces that are gone)
-    /// black_captured = pieces that black has captured (white pieces that are gone)
-    pub fn captured_pieces(&self) -> (Vec<Role>, Vec<Role>) {
-        // Standard starting material for each side
-        let starting_material: [(Role, u8); 5] = [
-            (Role::Queen, 1),
-            (Role::Rook, 2),
-            (Role::Bishop, 2),
-            (Role::Knight, 2),
-            (Role::Pawn, 8),
-        ];
+    /// Key telling positions apart as repetitions and transpositions do: a
+    /// Zobrist hash of everything but the move counters
+    pub fn position_key(position: &Chess) -> u64 {
+        position.zobrist_hash::<Zobrist64>(EnPassantMode::Legal).0
+    }
 
-        let board = self.position.board();
+    /// Number of times the current position has occurred so far in the game,
+    /// counting itself (3 = threefold repetition). Positions are compared by
+    /// `position_key`, and only back to the last capture or pawn move, since
+    /// nothing before one can repeat after it.
+    pub fn repetitions(&self) -> usize {
+        let key = Self::position_key;
+        let current = key(&self.position);
+        let reversible = (self.position.halfmoves() as usize).min(self.current_index);
+        let first = self.current_index - reversible;
+        let mut position = self.initial_position.clone();
+        for m in &self.moves[..first] {
+            position.play_unchecked(m);
+        }
+        let mut count = usize::from(key(&position) == current);
+        for m in &self.moves[first..self.current_index] {
+            position.play_unchecked(m);
+            if key(&position) == current {
+                count += 1;
+            }
+        }
+        count
+    }
 
-        // Count current pieces for each side
-        let mut white_captured: Vec<Role> = Vec::new(); // Black pieces captured by white
-        let mut black_captured: Vec<Role> = Vec::new(); // White pieces captured by black
+    /// Halfmove clock of the current position: plies since the last capture
+    /// or pawn move (100 = fifty-move rule)
+    pub fn halfmoves(&self) -> u32 {
+        self.position.halfmoves()
+    }
 
-        for (role, starting_count) in starting_material {
-            // Count how many of this piece type each side currently has
-            let white_count = board
-                .by_color(Color::White)
-                .intersect(board.by_role(role))
-                .count() as u8;
-            let black_count = board
-                .by_color(Color::Black)
-                .intersect(board.by_role(role))
-                .count() as u8;
+    /// The draw the side to move can claim in the current position, if any
+    pub fn draw_claim(&self) -> Option<DrawClaim> {
+        if self.is_game_over() {
+            None
+        } else if self.repetitions() >= 3 {
+            Some(DrawClaim::Repetition)
+        } else if self.halfmoves() >= 100 {
+            Some(DrawClaim::FiftyMoves)
+        } else {
+            None
+        }
+    }
 
-            // White captured = starting black pieces - current black pieces
-            let white_captures = starting_count.saturating_sub(black_count);
-            for _ in 0..white_captures {
-                white_captured.push(role);
+    /// Get captured pieces for each side, from the moves played up to the
+    /// current position (en passant included), so games set up from a FEN
+    /// and promoted pieces count right
+    /// Returns (white_captured, black_captured) where each is a list of roles
+    /// white_captured = pieces that white has captured (black pieces that are gone)
+    /// black_captured = pieces that black has captured (white pieces that are gone)
+    pub fn captured_pieces(&self) -> (Vec<Role>, Vec<Role>) {
+        let mut captured: ByColor<Vec<Role>> = ByColor::default();
+        let mut position = self.initial_position.clone();
+        for m in &self.moves[..self.current_index] {
+            if let Some(role) = m.capture() {
+                captured.get_mut(position.turn()).push(role);
             }
+            position.play_unchecked(m);
+        }
+        (captured.white, captured.black)
+    }
 
-            // Black captured = starting white pieces - current white pieces
-            let black_captures = starting_count.saturating_sub(white_count);
-            for _ in 0..black_captures {
-                black_captured.push(role);
+    /// Get the pieces each side promoted to on the way to the current
+    /// position, as (white_promoted, black_promoted)
+    pub fn promoted_pieces(&self) -> (Vec<Role>, Vec<Role>) {
+        let mut promoted: ByColor<Vec<Role>> = ByColor::default();
+        let mut position = self.initial_position.clone();
+        for m in &self.moves[..self.current_index] {
+            if let Some(role) = m.promotion() {
+                promoted.get_mut(position.turn()).push(role);
             }
+            position.play_unchecked(m);
         }
-
-        (white_captured, black_captured)
+        (promoted.white, promoted.black)
     }
 
-    /// Get material advantage in centipawns (positive = white advantage)
+    /// Get material advantage in centipawns (positive = white advantage),
+    /// counted from the pieces on the board, so it holds for any start
+    /// position and after promotions
     pub fn material_balance(&self) -> i32 {
         let piece_values: [(Role, i32); 5] = [
             (Role::Queen, 900),
@@ -320,6 +722,148 @@ impl Game {
 
         balance
     }
+
+    /// Get the squares of pieces that are attacked and insufficiently defended.
+    /// A piece counts as hanging when it is attacked and either has no
+    /// defenders or can be taken by a less valuable piece. Kings are ignored.
+    pub fn hanging_pieces(&self) -> Bitboard {
+        let board = self.position.board();
+        let occupied = board.occupied();
+        let mut hanging = Bitboard::EMPTY;
+
+        for sq in occupied & !board.kings() {
+            let Some(piece) = board.piece_at(sq) else {
+                continue;
+            };
+
+            let attackers = board.attacks_to(sq, !piece.color, occupied);
+            if attackers.is_empty() {
+                continue;
+            }
+
+            let defenders = board.attacks_to(sq, piece.color, occupied);
+            let cheapest_attacker = attackers
+                .into_iter()
+                .filter_map(|a| board.role_at(a))
+                .map(role_value)
+                .min()
+                .unwrap_or(i32::MAX);
+
+            if defenders.is_empty() || cheapest_attacker < role_value(piece.role) {
+                hanging.add(sq);
+            }
+        }
+
+        hanging
+    }
+
+    /// Describe the board rank by rank for a screen reader, e.g.
+    /// "Rank 1: white rook a1, white king e1" (top rank first, as seen from
+    /// White unless flipped)
+    pub fn describe_ranks(&self, flipped: bool) -> Vec<String> {
+        let mut ranks: Vec<Rank> = Rank::ALL.into_iter().rev().collect();
+        if flipped {
+            ranks.reverse();
+        }
+        ranks
+            .into_iter()
+            .map(|rank| {
+                let squares = File::ALL
+                    .into_iter()
+                    .map(|file| Square::from_coords(file, rank))
+                    .collect();
+                let pieces = self.describe_squares(squares);
+                let pieces = if pieces.is_empty() {
+                    "empty".to_string()
+                } else {
+                    pieces
+                };
+                format!("Rank {}: {}", rank.char(), pieces)
+            })
+            .collect()
+    }
+
+    /// Text diagram of the board with rank and file labels and the side to
+    /// move, in Unicode symbols and box drawing or in plain ASCII (as seen
+    /// from White unless flipped)
+    pub fn diagram(&self, flipped: bool, ascii: bool) -> String {
+        let (style, empty, [top_left, top_right, bottom_left, bottom_right, side, dash]) = if ascii
+        {
+            (PieceStyle::Ascii, '.', ["+", "+", "+", "+", "|", "-"])
+        } else {
+            (PieceStyle::Unicode, '·', ["┌", "┐", "└", "┘", "│", "─"])
+        };
+        let mut ranks: Vec<Rank> = Rank::ALL.into_iter().rev().collect();
+        let mut files = File::ALL.to_vec();
+        if flipped {
+            ranks.reverse();
+            files.reverse();
+        }
+        let line = dash.repeat(files.len() * 2 + 1);
+
+        let mut lines = vec![format!("  {}{}{}", top_left, line, top_right)];
+        for rank in ranks {
+            let squares: Vec<String> = files
+                .iter()
+                .map(|&file| {
+                    self.piece_at(Square::from_coords(file, rank))
+                        .map_or(empty, |p| piece_to_char(p, style))
+                        .to_string()
+                })
+                .collect();
+            lines.push(format!(
+                "{} {} {} {}",
+                rank.char(),
+                side,
+                squares.join(" "),
+                side
+            ));
+        }
+        lines.push(format!("  {}{}{}", bottom_left, line, bottom_right));
+        let labels: Vec<String> = files.iter().map(|f| f.char().to_string()).collect();
+        lines.push(format!("    {}", labels.join(" ")));
+        let turn = match self.turn() {
+            Color::White => "White",
+            Color::Black => "Black",
+        };
+        lines.push(format!("{} to move", turn));
+        lines.join("\n")
+    }
+
+    /// Describe the pieces on some squares, e.g. "white knight f3, black pawn e5"
+    pub fn describe_squares(&self, squares: Bitboard) -> String {
+        squares
+            .into_iter()
+            .filter_map(|sq| {
+                self.piece_at(sq)
+                    .map(|p| format!("{} {}", piece_name(p), sq))
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
+
+/// Approximate piece value in centipawns (kings are priceless)
+pub fn role_value(role: Role) -> i32 {
+    match role {
+        Role::Pawn => 100,
+        Role::Knight => 320,
+        Role::Bishop => 330,
+        Role::Rook => 500,
+        Role::Queen => 900,
+        Role::King => 20_000,
+    }
+}
+
+/// Format a clock time as h:mm:ss, or m:ss under an hour
+pub fn format_clock(time: Duration) -> String {
+    let secs = time.as_secs();
+    let (h, m, s) = (secs / 3600, (secs % 3600) / 60, secs % 60);
+    if h > 0 {
+        format!("{}:{:02}:{:02}", h, m, s)
+    } else {
+        format!("{}:{:02}", m, s)
+    }
 }
 
 /// Convert a square to algebraic notation
@@ -327,6 +871,66 @@ pub fn square_to_string(sq: Square) -> String {
     sq.to_string()
 }
 
+/// Grounds for claiming a draw
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DrawClaim {
+    /// The position has occurred three times
+    Repetition,
+    /// Fifty moves by each side without a capture or pawn move
+    FiftyMoves,
+}
+
+impl DrawClaim {
+    pub fn name(self) -> &'static str {
+        match self {
+            DrawClaim::Repetition => "threefold repetition",
+            DrawClaim::FiftyMoves => "fifty-move rule",
+        }
+    }
+}
+
+/// Kinds of moves to jump between in the move list
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MoveKind {
+    Capture,
+    Check,
+    /// A pawn move that captures a pawn or attacks one, opening the position
+    PawnBreak,
+}
+
+impl MoveKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            MoveKind::Capture => "capture",
+            MoveKind::Check => "check",
+            MoveKind::PawnBreak => "pawn break",
+        }
+    }
+
+    /// Whether a move played from a position is of this kind
+    pub fn matches(self, position: &Chess, m: &Move) -> bool {
+        match self {
+            MoveKind::Capture => m.is_capture(),
+            MoveKind::Check => {
+                let mut after = position.clone();
+                after.play_unchecked(m);
+                after.is_check()
+            }
+            MoveKind::PawnBreak => {
+                if m.role() != Role::Pawn {
+                    return false;
+                }
+                let their_pawns = position.board().by_piece(Piece {
+                    color: !position.turn(),
+                    role: Role::Pawn,
+                });
+                let attacks = shakmaty::attacks::pawn_attacks(position.turn(), m.to());
+                m.capture() == Some(Role::Pawn) || !(attacks & their_pawns).is_empty()
+            }
+        }
+    }
+}
+
 /// Piece style for rendering
 #[derive(Debug, Clone, Copy, PartialEq, Default)]
 pub enum PieceStyle {
@@ -341,6 +945,28 @@ pub enum PieceStyle {
     Blocks,
 }
 
+impl PieceStyle {
+    /// Name used in the config file
+    pub fn name(self) -> &'static str {
+        match self {
+            PieceStyle::Unicode => "unicode",
+            PieceStyle::NerdFont => "nerd",
+            PieceStyle::Ascii => "ascii",
+            PieceStyle::Blocks => "blocks",
+        }
+    }
+
+    /// Next style in the cycle: Nerd Font, Unicode, ASCII, blocks
+    pub fn next(self) -> Self {
+        match self {
+            PieceStyle::NerdFont => PieceStyle::Unicode,
+            PieceStyle::Unicode => PieceStyle::Ascii,
+            PieceStyle::Ascii => PieceStyle::Blocks,
+            PieceStyle::Blocks => PieceStyle::NerdFont,
+        }
+    }
+}
+
 /// Get the character for a piece based on style
 pub fn piece_to_char(piece: Piece, style: PieceStyle) -> char {
     match style {
@@ -352,6 +978,23 @@ pub fn piece_to_char(piece: Piece, style: PieceStyle) -> char {
     }
 }
 
+/// Spoken name of a piece, e.g. "white knight"
+pub fn piece_name(piece: Piece) -> String {
+    let color = match piece.color {
+        Color::White => "white",
+        Color::Black => "black",
+    };
+    let role = match piece.role {
+        Role::King => "king",
+        Role::Queen => "queen",
+        Role::Rook => "rook",
+        Role::Bishop => "bishop",
+        Role::Knight => "knight",
+        Role::Pawn => "pawn",
+    };
+    format!("{} {}", color, role)
+}
+
 /// Get the Unicode character for a piece
 pub fn piece_to_unicode(piece: Piece) -> char {
     match (piece.color, piece.role) {
@@ -1,3 +1,11 @@
+mod chess960;
+mod clock;
 mod game;
+mod pgn;
 
+pub use chess960::*;
+pub use clock::*;
 pub use game::*;
+pub use pgn::{
+    check_tag, first_headers, nag_glyph, split_games, tree_moves, SEVEN_TAG_ROSTER,
+};
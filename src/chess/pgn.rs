@@ -0,0 +1,837 @@
+use anyhow::{bail, Context, Result};
+use shakmaty::{
+    fen::Fen,
+    san::{San, SanPlus},
+    Chess, Color, EnPassantMode, Move, Position, Square,
+};
+use std::time::Duration;
+
+use super::{Annotation, Arrow, Circle, Eval, Game, MarkColor};
+
+/// A lexical token of PGN text
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// Header tag: `[Name "value"]`
+    Header(String, String),
+    /// Comment text between braces (or after `;`)
+    Comment(String),
+    /// Start of a variation `(`
+    VariationStart,
+    /// End of a variation `)`
+    VariationEnd,
+    /// Numeric annotation glyph `$n`
+    Nag(u8),
+    /// Game termination marker
+    Result(String),
+    /// A move in SAN (move numbers already stripped)
+    Move(String),
+}
+
+/// Split PGN text into tokens
+fn tokenize(pgn: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = pgn.chars().peekable();
+    let mut line_start = true;
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            '\n' => {
+                chars.next();
+                line_start = true;
+                continue;
+            }
+            c if c.is_whitespace() => {
+                chars.next();
+                continue;
+            }
+            // Escape lines are ignored entirely
+            '%' if line_start => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+                continue;
+            }
+            '[' => {
+                chars.next();
+                let mut name = String::new();
+                let mut value = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '"' || c == ']' {
+                        break;
+                    }
+                    name.push(c);
+                    chars.next();
+                }
+                while let Some(&c) = chars.peek() {
+                    chars.next();
+                    if c == '"' {
+                        break;
+                    }
+                    if c == ']' {
+                        break;
+                    }
+                }
+                let mut escaped = false;
+                for c in chars.by_ref() {
+                    if escaped {
+                        value.push(c);
+                        escaped = false;
+                    } else if c == '\\' {
+                        escaped = true;
+                    } else if c == '"' {
+                        break;
+                    } else {
+                        value.push(c);
+                    }
+                }
+                for c in chars.by_ref() {
+                    if c == ']' {
+                        break;
+                    }
+                }
+                tokens.push(Token::Header(name, value));
+            }
+            '{' => {
+                chars.next();
+                let mut text = String::new();
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        break;
+                    }
+                    text.push(c);
+                }
+                tokens.push(Token::Comment(text));
+            }
+            ';' => {
+                chars.next();
+                let mut text = String::new();
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                    text.push(c);
+                }
+                tokens.push(Token::Comment(text));
+                line_start = true;
+                continue;
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::VariationStart);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::VariationEnd);
+            }
+            '$' => {
+                chars.next();
+                let mut digits = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !c.is_ascii_digit() {
+                        break;
+                    }
+                    digits.push(c);
+                    chars.next();
+                }
+                if let Ok(n) = digits.parse() {
+                    tokens.push(Token::Nag(n));
+                }
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || "{}();[$".contains(c) {
+                        break;
+                    }
+                    word.push(c);
+                    chars.next();
+                }
+                if let Some(token) = classify_word(&word) {
+                    let is_move = matches!(token, Token::Move(_));
+                    tokens.push(token);
+                    if let Some(nag) = suffix_nag(&word).filter(|_| is_move) {
+                        tokens.push(Token::Nag(nag));
+                    }
+                }
+            }
+        }
+        line_start = false;
+    }
+
+    tokens
+}
+
+/// Classify a bare movetext word as a result or a move, stripping move numbers
+fn classify_word(word: &str) -> Option<Token> {
+    if matches!(word, "1-0" | "0-1" | "1/2-1/2" | "*") {
+        return Some(Token::Result(word.to_string()));
+    }
+
+    // Strip move numbers like "12." or "12..." (possibly glued to the move)
+    let word = word.trim_start_matches(|c: char| c.is_ascii_digit() && word.contains('.'));
+    let word = word.trim_start_matches('.');
+
+    // Strip annotation glyphs like "!" "?" "!!"
+    let word = word.trim_end_matches(['!', '?']);
+
+    if word.is_empty() {
+        return None;
+    }
+
+    // Some tools write castling with zeros
+    let word = word.replace("0-0-0", "O-O-O").replace("0-0", "O-O");
+    Some(Token::Move(word))
+}
+
+/// The NAG of a move's `!`/`?` suffix
+fn suffix_nag(word: &str) -> Option<u8> {
+    let suffix = &word[word.trim_end_matches(['!', '?']).len()..];
+    SUFFIX_NAGS
+        .iter()
+        .position(|&s| s == suffix)
+        .map(|i| i as u8 + 1)
+}
+
+/// Move suffixes for NAGs 1 to 6
+const SUFFIX_NAGS: [&str; 6] = ["!", "?", "!!", "??", "!?", "?!"];
+
+/// Short form of a NAG: a move suffix or position symbol where one is in
+/// common use, `$n` otherwise
+pub fn nag_glyph(nag: u8) -> String {
+    let glyph = match nag {
+        1..=6 => SUFFIX_NAGS[nag as usize - 1],
+        7 => "□",
+        10 => "=",
+        13 => "∞",
+        14 => "⩲",
+        15 => "⩱",
+        16 => "±",
+        17 => "∓",
+        18 => "+-",
+        19 => "-+",
+        22 | 23 => "⨀",
+        _ => return format!("${}", nag),
+    };
+    glyph.to_string()
+}
+
+/// Extract Lichess-style `[%cal ...]`, `[%csl ...]`, `[%eval ...]` and
+/// `[%clk ...]` tags from a comment, returning the remaining text
+fn extract_marks(comment: &str, annotation: &mut Annotation) -> String {
+    let mut text = String::new();
+    let mut rest = comment;
+
+    while let Some(start) = rest.find("[%") {
+        text.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find(']') else {
+            rest = &rest[start..];
+            break;
+        };
+        let command = &rest[start + 2..start + end];
+        let mut parts = command.splitn(2, char::is_whitespace);
+        let name = parts.next().unwrap_or("");
+        let args = parts.next().unwrap_or("").trim();
+
+        match name {
+            "cal" => {
+                for item in args.split(',') {
+                    if let Some(arrow) = parse_arrow(item.trim()) {
+                        annotation.arrows.push(arrow);
+                    }
+                }
+            }
+            "csl" => {
+                for item in args.split(',') {
+                    if let Some(circle) = parse_circle(item.trim()) {
+                        annotation.circles.push(circle);
+                    }
+                }
+            }
+            "eval" => {
+                if let Some(eval) = parse_eval(args) {
+                    annotation.eval = Some(eval);
+                }
+            }
+            "clk" => {
+                if let Some(clock) = parse_clock(args) {
+                    annotation.clock = Some(clock);
+                }
+            }
+            // Keep unknown commands so they survive a round trip
+            _ => text.push_str(&rest[start..start + end + 1]),
+        }
+        rest = &rest[start + end + 1..];
+    }
+    text.push_str(rest);
+
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Parse an arrow like "Ge2e4"
+fn parse_arrow(item: &str) -> Option<Arrow> {
+    let color = MarkColor::from_code(item.chars().next()?)?;
+    let from: Square = item.get(1..3)?.parse().ok()?;
+    let to: Square = item.get(3..5)?.parse().ok()?;
+    Some(Arrow { from, to, color })
+}
+
+/// Parse a circle like "Rd5"
+fn parse_circle(item: &str) -> Option<Circle> {
+    let color = MarkColor::from_code(item.chars().next()?)?;
+    let square: Square = item.get(1..3)?.parse().ok()?;
+    Some(Circle { square, color })
+}
+
+/// Parse an eval like "0.17", "-1.5", "#3" or "#-2" (optionally followed by ",depth")
+fn parse_eval(args: &str) -> Option<Eval> {
+    let value = args.split(',').next()?.trim();
+    if let Some(mate) = value.strip_prefix('#') {
+        return mate.parse().ok().map(Eval::Mate);
+    }
+    let pawns: f64 = value.parse().ok()?;
+    Some(Eval::Cp((pawns * 100.0).round() as i32))
+}
+
+/// Parse a clock like "0:03:00" or "1:02:03.4"; negative, infinite and NaN
+/// fields make it invalid
+fn parse_clock(args: &str) -> Option<Duration> {
+    let mut secs = 0.0;
+    for part in args.trim().split(':') {
+        let value: f64 = part.parse().ok()?;
+        if !value.is_finite() || value.is_sign_negative() {
+            return None;
+        }
+        secs = secs * 60.0 + value;
+    }
+    Duration::try_from_secs_f64(secs).ok()
+}
+
+/// Format an eval for a PGN `[%eval]` tag
+fn format_eval(eval: Eval) -> String {
+    match eval {
+        Eval::Cp(cp) => format!("{:.2}", cp as f64 / 100.0),
+        Eval::Mate(m) => format!("#{}", m),
+    }
+}
+
+/// Format a clock for a PGN `[%clk]` tag
+fn format_pgn_clock(time: Duration) -> String {
+    let secs = time.as_secs();
+    format!("{}:{:02}:{:02}", secs / 3600, (secs % 3600) / 60, secs % 60)
+}
+
+/// Attach comment text (and any marks in it) to a position's annotation
+fn attach_comment(annotation: &mut Annotation, comment: &str) {
+    let text = extract_marks(comment, annotation);
+    if text.is_empty() {
+        return;
+    }
+    annotation.comment = Some(match annotation.comment.take() {
+        Some(existing) => format!("{} {}", existing, text),
+        None => text,
+    });
+}
+
+/// Tags every PGN game has, in the order they're exported
+pub const SEVEN_TAG_ROSTER: [&str; 7] =
+    ["Event", "Site", "Date", "Round", "White", "Black", "Result"];
+
+/// Check a header tag typed by hand: the name has to be a PGN symbol, and
+/// Date and Result need their PGN formats. FEN and SetUp follow the game's
+/// starting position, so they can't be set.
+pub fn check_tag(name: &str, value: &str) -> Result<()> {
+    let valid_name = name.starts_with(|c: char| c.is_ascii_alphanumeric())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid_name {
+        bail!("Invalid tag name: {}", name);
+    }
+    match name {
+        "FEN" | "SetUp" => bail!("{} follows the starting position", name),
+        "Result" if !["1-0", "0-1", "1/2-1/2", "*"].contains(&value) => {
+            bail!("Result must be 1-0, 0-1, 1/2-1/2 or *")
+        }
+        "Date" => {
+            let parts: Vec<&str> = value.split('.').collect();
+            let valid = parts.len() == 3
+                && parts.iter().zip([4, 2, 2]).all(|(part, len)| {
+                    part.len() == len && part.chars().all(|c| c.is_ascii_digit() || c == '?')
+                });
+            if !valid {
+                bail!("Date must look like 2024.05.17 (with ? for unknown digits)");
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Header tags of the first game of a PGN text
+pub fn first_headers(pgn: &str) -> Vec<(String, String)> {
+    tokenize(pgn)
+        .into_iter()
+        .map_while(|token| match token {
+            Token::Header(name, value) => Some((name, value)),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Split PGN text into its games: a header line after movetext starts the
+/// next one
+pub fn split_games(pgn: &str) -> Vec<&str> {
+    let mut games = Vec::new();
+    let mut start = 0;
+    let mut offset = 0;
+    let mut in_movetext = false;
+    let mut in_comment = false;
+    for line in pgn.split_inclusive('\n') {
+        offset += line.len();
+        let line_text = line.trim();
+        // A comment can run over lines, which aren't headers
+        if !in_comment && line_text.starts_with('[') {
+            if in_movetext {
+                let line_start = offset - line.len();
+                games.push(&pgn[start..line_start]);
+                start = line_start;
+                in_movetext = false;
+            }
+            continue;
+        }
+        if !line_text.is_empty() && !line_text.starts_with('%') {
+            in_movetext = true;
+        }
+        for c in line.chars() {
+            match c {
+                '{' => in_comment = true,
+                '}' => in_comment = false,
+                ';' if !in_comment => break,
+                _ => {}
+            }
+        }
+    }
+    if !pgn[start..].trim().is_empty() {
+        games.push(&pgn[start..]);
+    }
+    games
+}
+
+/// Every move of a PGN text, across all games and variations, with the
+/// position it is played from
+pub fn tree_moves(pgn: &str) -> Result<Vec<(Chess, Move)>> {
+    let mut moves = Vec::new();
+    let mut start = Chess::default();
+    // Position before the last move, and the current position
+    let mut previous = start.clone();
+    let mut current = start.clone();
+    let mut stack: Vec<(Chess, Chess)> = Vec::new();
+    let mut in_movetext = false;
+
+    for token in tokenize(pgn) {
+        match token {
+            Token::Header(name, value) => {
+                if in_movetext {
+                    // Start of the next game
+                    in_movetext = false;
+                    start = Chess::default();
+                    stack.clear();
+                }
+                if name == "FEN" {
+                    start = Game::from_fen(&value)
+                        .context("Invalid FEN header")?
+                        .initial_position()
+                        .clone();
+                }
+                previous = start.clone();
+                current = start.clone();
+            }
+            // A variation replaces the move before it
+            Token::VariationStart => {
+                stack.push((previous.clone(), current.clone()));
+                current = previous.clone();
+            }
+            Token::VariationEnd => {
+                (previous, current) = stack.pop().context("Unbalanced ')' in movetext")?;
+            }
+            Token::Result(_) => {
+                in_movetext = true;
+                start = Chess::default();
+                stack.clear();
+                previous = start.clone();
+                current = start.clone();
+            }
+            Token::Move(san) => {
+                in_movetext = true;
+                let m = san
+                    .parse::<San>()
+                    .ok()
+                    .and_then(|san| san.to_move(&current).ok())
+                    .with_context(|| format!("Illegal move '{}'", san))?;
+                previous = current.clone();
+                current.play_unchecked(&m);
+                moves.push((previous.clone(), m));
+            }
+            Token::Comment(_) | Token::Nag(_) => {}
+        }
+    }
+
+    Ok(moves)
+}
+
+/// A variation being read from PGN, kept as numbered movetext
+struct VariationText {
+    text: String,
+    /// Ply of the next move, counting from White's first move of the game
+    ply: u32,
+    /// Whether a Black move needs its number, as at the start and after a
+    /// comment or sub-variation
+    need_number: bool,
+}
+
+impl VariationText {
+    fn new(ply: u32) -> Self {
+        Self {
+            text: String::new(),
+            ply,
+            need_number: true,
+        }
+    }
+
+    fn push(&mut self, word: &str) {
+        if !self.text.is_empty() {
+            self.text.push(' ');
+        }
+        self.text.push_str(word);
+    }
+
+    fn push_move(&mut self, san: &str) {
+        let number = self.ply / 2 + 1;
+        if self.ply.is_multiple_of(2) {
+            self.push(&format!("{}.", number));
+        } else if self.need_number {
+            self.push(&format!("{}...", number));
+        }
+        self.push(san);
+        self.ply += 1;
+        self.need_number = false;
+    }
+
+    fn push_nag(&mut self, nag: u8) {
+        match nag {
+            1..=6 => self.text.push_str(&nag_glyph(nag)),
+            _ => self.push(&format!("${}", nag)),
+        }
+    }
+
+    fn push_comment(&mut self, comment: &str) {
+        self.push(&format!("{{ {} }}", comment.trim()));
+        self.need_number = true;
+    }
+
+    fn push_variation(&mut self, variation: &str) {
+        self.push(&format!("( {} )", variation));
+        self.need_number = true;
+    }
+}
+
+impl Game {
+    /// Parse the first game of a PGN text: the mainline as moves, and its
+    /// variations as text
+    pub fn from_pgn(pgn: &str) -> Result<Self> {
+        let tokens = tokenize(pgn);
+
+        // Headers come first and may set up a custom start position
+        let headers: Vec<(String, String)> = tokens
+            .iter()
+            .filter_map(|t| match t {
+                Token::Header(k, v) => Some((k.clone(), v.clone())),
+                _ => None,
+            })
+            .collect();
+
+        let mut game = match headers.iter().find(|(k, _)| k == "FEN") {
+            Some((_, fen)) => Game::from_fen(fen).context("Invalid FEN header")?,
+            None => Game::new(),
+        };
+        for (k, v) in &headers {
+            game.set_header(k, v);
+        }
+
+        // Variations being read, innermost last
+        let mut variations: Vec<VariationText> = Vec::new();
+        let start = game.initial_position();
+        let first_ply = (start.fullmoves().get() - 1) * 2 + u32::from(start.turn() == Color::Black);
+        let mut seen_moves = false;
+
+        for token in tokens {
+            match token {
+                Token::Header(..) => {
+                    if seen_moves {
+                        // Start of the next game
+                        break;
+                    }
+                }
+                // A variation replaces the move before it
+                Token::VariationStart => {
+                    let ply = match variations.last() {
+                        Some(parent) => parent.ply,
+                        None => first_ply + game.current_index() as u32,
+                    };
+                    variations.push(VariationText::new(ply.saturating_sub(1)));
+                }
+                Token::VariationEnd => {
+                    let variation = variations.pop().context("Unbalanced ')' in movetext")?;
+                    if variation.text.is_empty() {
+                        continue;
+                    }
+                    match variations.last_mut() {
+                        Some(parent) => parent.push_variation(&variation.text),
+                        None => game.annotation_mut().variations.push(variation.text),
+                    }
+                }
+                Token::Comment(text) => match variations.last_mut() {
+                    Some(variation) => variation.push_comment(&text),
+                    None => attach_comment(game.annotation_mut(), &text),
+                },
+                Token::Nag(nag) => match variations.last_mut() {
+                    Some(variation) => variation.push_nag(nag),
+                    None => game.annotation_mut().nags.push(nag),
+                },
+                Token::Result(_) if !variations.is_empty() => {}
+                Token::Result(result) => {
+                    if !headers.iter().any(|(k, _)| k == "Result") {
+                        game.set_header("Result", &result);
+                    }
+                    break;
+                }
+                Token::Move(san) => match variations.last_mut() {
+                    Some(variation) => variation.push_move(&san),
+                    None => {
+                        game.make_move_san(&san)
+                            .with_context(|| format!("Illegal move '{}'", san))?;
+                        seen_moves = true;
+                    }
+                },
+            }
+        }
+
+        Ok(game)
+    }
+
+    /// Export the game as PGN, including comments, board marks, NAGs and
+    /// variations
+    pub fn to_pgn(&self) -> String {
+        let mut out = String::new();
+
+        // Seven Tag Roster first, then any other tags
+        for tag in SEVEN_TAG_ROSTER {
+            let value = match tag {
+                "Result" => self.result_string(),
+                "Date" => self.header(tag).unwrap_or("????.??.??").to_string(),
+                _ => self.header(tag).unwrap_or("?").to_string(),
+            };
+            out.push_str(&format!("[{} \"{}\"]\n", tag, escape_tag(&value)));
+        }
+        for (k, v) in self.headers() {
+            if SEVEN_TAG_ROSTER.contains(&k.as_str()) || k == "FEN" || k == "SetUp" {
+                continue;
+            }
+            out.push_str(&format!("[{} \"{}\"]\n", k, escape_tag(v)));
+        }
+        if !self.is_standard_start() {
+            let fen = Fen::from_position(self.initial_position().clone(), EnPassantMode::Legal);
+            out.push_str("[SetUp \"1\"]\n");
+            out.push_str(&format!("[FEN \"{}\"]\n", fen));
+        }
+        out.push('\n');
+
+        // Movetext
+        let mut words: Vec<String> = Vec::new();
+        let annotations = self.annotations();
+        if let Some(comment) = format_comment(&annotations[0]) {
+            words.push(comment);
+        }
+
+        let mut pos: Chess = self.initial_position().clone();
+        let mut need_number = true;
+        for (idx, m) in self.moves().iter().enumerate() {
+            let fullmove = pos.fullmoves().get();
+            if pos.turn() == Color::White {
+                words.push(format!("{}.", fullmove));
+            } else if need_number {
+                words.push(format!("{}...", fullmove));
+            }
+            let san = SanPlus::from_move_and_play_unchecked(&mut pos, m);
+            words.push(san.to_string());
+            let annotation = &annotations[idx + 1];
+            words.extend(annotation.nags.iter().map(|nag| format!("${}", nag)));
+
+            need_number = false;
+            if let Some(comment) = format_comment(annotation) {
+                words.push(comment);
+                need_number = true;
+            }
+            // Variations are split into words so they wrap with the rest
+            for variation in &annotation.variations {
+                let text = format!("({})", variation);
+                words.extend(text.split_whitespace().map(String::from));
+                need_number = true;
+            }
+        }
+        words.push(self.result_string());
+
+        // Wrap movetext at 80 columns
+        let mut line_len = 0;
+        for word in words {
+            if line_len > 0 && line_len + 1 + word.len() > 80 {
+                out.push('\n');
+                line_len = 0;
+            } else if line_len > 0 {
+                out.push(' ');
+                line_len += 1;
+            }
+            line_len += word.len();
+            out.push_str(&word);
+        }
+        out.push('\n');
+
+        out
+    }
+
+    /// Get the game result for PGN export
+    fn result_string(&self) -> String {
+        if let Some(result) = self.header("Result") {
+            if result != "*" {
+                return result.to_string();
+            }
+        }
+        if self.is_at_end() {
+            match self.outcome() {
+                Some(outcome) => outcome.to_string(),
+                None => "*".to_string(),
+            }
+        } else {
+            "*".to_string()
+        }
+    }
+}
+
+/// Escape a header value for PGN
+fn escape_tag(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Format an annotation as a PGN comment, or None if it is empty
+fn format_comment(annotation: &Annotation) -> Option<String> {
+    let mut parts: Vec<String> = Vec::new();
+    if let Some(eval) = annotation.eval {
+        parts.push(format!("[%eval {}]", format_eval(eval)));
+    }
+    if let Some(clock) = annotation.clock {
+        parts.push(format!("[%clk {}]", format_pgn_clock(clock)));
+    }
+    if !annotation.circles.is_empty() {
+        let circles: Vec<String> = annotation
+            .circles
+            .iter()
+            .map(|c| format!("{}{}", c.color.code(), c.square))
+            .collect();
+        parts.push(format!("[%csl {}]", circles.join(",")));
+    }
+    if !annotation.arrows.is_empty() {
+        let arrows: Vec<String> = annotation
+            .arrows
+            .iter()
+            .map(|a| format!("{}{}{}", a.color.code(), a.from, a.to))
+            .collect();
+        parts.push(format!("[%cal {}]", arrows.join(",")));
+    }
+    if let Some(ref text) = annotation.comment {
+        parts.push(text.replace('}', ")"));
+    }
+    if parts.is_empty() {
+        return None;
+    }
+
+    Some(format!("{{ {} }}", parts.join(" ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Number of moves in the mainline of a PGN text
+    fn moves(pgn: &str) -> usize {
+        Game::from_pgn(pgn).unwrap().moves().len()
+    }
+
+    #[test]
+    fn reads_what_the_whitespace_parser_did() {
+        // Headers, move numbers, "..." and the result are skipped
+        let pgn = "[Event \"Casual\"]\n[White \"A\"]\n\n1. e4 e5 2. Nf3 ... Nc6 3. Bb5 a6 1-0";
+        assert_eq!(moves(pgn), 6);
+
+        // Annotation glyphs and check/mate marks are dropped from moves
+        let game = Game::from_pgn("1. e4 e5 2. Qh5?! Nc6 3. Bc4 Nf6?? 4. Qxf7# 1-0").unwrap();
+        assert_eq!(game.moves().len(), 7);
+        assert!(game.is_game_over());
+
+        // Comments and variations are left out of the mainline
+        assert_eq!(moves("1. e4 {best} e5 (1... c5 2. Nf3) 2. Nf3 *"), 3);
+        assert_eq!(moves("1. e4 e5 (1... c5 (1... e6) 2. Nf3) 2. Nf3 Nc6 *"), 4);
+
+        // An illegal move is an error
+        assert!(Game::from_pgn("1. e4 e5 2. Ke3").is_err());
+    }
+
+    #[test]
+    fn reads_what_the_whitespace_parser_could_not() {
+        // Comments spanning several words, and brackets inside them
+        assert_eq!(
+            moves("1. e4 { the (main) line } e5 { [%cal Ge2e4] } 2. Nf3"),
+            3
+        );
+        // Move numbers glued to the move, and zeros for castling
+        assert_eq!(moves("1.e4 e5 2.Nf3 Nc6 3.Bc4 Bc5 4.0-0"), 7);
+        // NAGs, ';' comments and a custom start position
+        let pgn = "[FEN \"4k3/8/8/8/8/8/4P3/4K3 w - - 0 1\"]\n1. e4 $1 ; rest of line\nKd7 *";
+        let game = Game::from_pgn(pgn).unwrap();
+        assert_eq!(game.to_fen(), "8/3k4/8/8/4P3/8/8/4K3 w - - 1 2");
+    }
+
+    #[test]
+    fn marks_round_trip() {
+        let pgn = "1. e4 { [%cal Ge2e4,Rd7d5] good [%csl Yd5] } e5 *";
+        let game = Game::from_pgn(pgn).unwrap();
+        let annotation = &game.annotations()[1];
+        assert_eq!(annotation.arrows.len(), 2);
+        assert_eq!(annotation.arrows[1].color, MarkColor::Red);
+        assert_eq!(annotation.circles[0].square, Square::D5);
+        assert_eq!(annotation.comment.as_deref(), Some("good"));
+
+        let exported = game.to_pgn();
+        assert!(exported.contains("1. e4 { [%csl Yd5] [%cal Ge2e4,Rd7d5] good } 1... e5 *"));
+        let again = Game::from_pgn(&exported).unwrap();
+        assert_eq!(again.annotations(), game.annotations());
+    }
+
+    #[test]
+    fn parse_clock_rejects_negative_and_non_finite() {
+        assert_eq!(parse_clock("0:03:00"), Some(Duration::from_secs(180)));
+        assert_eq!(
+            parse_clock("1:02:03.5"),
+            Some(Duration::from_secs_f64(3723.5))
+        );
+        assert_eq!(parse_clock("-0:00:01"), None);
+        assert_eq!(parse_clock("0:00:-01"), None);
+        assert_eq!(parse_clock("nan"), None);
+        assert_eq!(parse_clock("0:inf:00"), None);
+    }
+
+    #[test]
+    fn bad_clock_comment_loads() {
+        let game = Game::from_pgn("1. e4 { [%clk -0:00:01] } e5 { [%clk 0:nan:00] } *").unwrap();
+        assert_eq!(game.moves().len(), 2);
+    }
+}
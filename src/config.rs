@@ -1,12 +1,49 @@
-use anyhow::{Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::PathBuf;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use crate::power;
+
+/// Prefix of environment variables that override settings
+const ENV_PREFIX: &str = "LAZYCHESS_";
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Config {
     pub engine: EngineConfig,
     pub ui: UiConfig,
+    #[serde(default)]
+    pub play: PlayConfig,
+    #[serde(default)]
+    pub puzzles: PuzzleConfig,
+    #[serde(default)]
+    pub repertoire: RepertoireConfig,
+    #[serde(default)]
+    pub pieces: PiecesConfig,
+    #[serde(default)]
+    pub sounds: SoundConfig,
+    #[serde(default)]
+    pub control: ControlConfig,
+    #[serde(default)]
+    pub script: ScriptConfig,
+    #[serde(default)]
+    pub log: LogConfig,
+    #[serde(default)]
+    pub games: GamesConfig,
+    #[serde(default)]
+    pub online: OnlineConfig,
+    /// Custom `:commands`, each running a list of input lines (`:commands`
+    /// and moves) in turn; `$*` stands for the alias's arguments
+    #[serde(default)]
+    pub aliases: BTreeMap<String, Vec<String>>,
+    /// Profile the config was loaded from (None = the default config file)
+    #[serde(skip)]
+    pub profile: Option<String>,
+    /// File values of the settings environment variables override, put
+    /// back when saving so the overrides never reach the file
+    #[serde(skip)]
+    env_overrides: BTreeMap<String, Option<toml::Value>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,12 +54,28 @@ pub struct EngineConfig {
     pub depth: u32,
     /// Number of best lines to show
     pub multipv: u32,
-    /// CPU threads for analysis
-    pub threads: u32,
+    /// CPU threads for analysis (None = physical cores minus one)
+    #[serde(default)]
+    pub threads: Option<u32>,
     /// Hash table size in MB
     pub hash: u32,
     /// Draw avoidance (-100 to 100)
     pub contempt: i32,
+    /// Skill level (0-20) used when playing against the engine (None = full strength)
+    #[serde(default)]
+    pub skill_level: Option<u32>,
+    /// Elo limit used when playing against the engine (None = unlimited)
+    #[serde(default)]
+    pub elo: Option<u32>,
+    /// Extra engine binaries offered in the engine picker
+    #[serde(default)]
+    pub engines: Vec<String>,
+    /// Run the engine at reduced CPU priority
+    #[serde(default)]
+    pub low_priority: bool,
+    /// Thread cap while running on battery (None = no cap)
+    #[serde(default)]
+    pub battery_threads: Option<u32>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -39,17 +92,273 @@ pub struct UiConfig {
     /// Show only the evaluation line in analysis panel
     #[serde(default)]
     pub analysis_eval_only: bool,
+    /// Highlight attacked pieces that are insufficiently defended
+    #[serde(default)]
+    pub show_hanging: bool,
+    /// Show evaluations from White's point of view instead of the side to move
+    #[serde(default)]
+    pub white_perspective: bool,
+    /// Show the current FEN in a line above the input bar
+    #[serde(default)]
+    pub show_fen: bool,
+    /// Show variations in the Moves panel
+    #[serde(default = "default_true")]
+    pub show_variations: bool,
+    /// Show comments in the Moves panel
+    #[serde(default = "default_true")]
+    pub show_comments: bool,
+    /// Show NAGs (`!`, `?!`, `±`...) in the Moves panel
+    #[serde(default = "default_true")]
+    pub show_nags: bool,
+    /// Pause analysis while the terminal window doesn't have focus
+    #[serde(default)]
+    pub pause_on_unfocus: bool,
+    /// Capture the mouse; off leaves it to the terminal for selecting text
+    #[serde(default = "default_true")]
+    pub mouse_capture: bool,
+    /// Send a desktop notification when a review or a long search finishes
+    /// while the terminal doesn't have focus
+    #[serde(default = "default_true")]
+    pub notifications: bool,
+    /// Keep a spoken-style announcement line and avoid color-only cues, for
+    /// screen readers
+    #[serde(default)]
+    pub screen_reader: bool,
+    /// Board renderer: "text", "halfblock", "kitty", "iterm2", "sixel", or
+    /// "auto" to use a graphics protocol when the terminal supports one
+    #[serde(default = "default_renderer")]
+    pub renderer: String,
+    /// Colors to draw with: "truecolor", "256", "16", or "auto" to detect
+    /// what the terminal supports
+    #[serde(default = "default_colors")]
+    pub colors: String,
+    /// Highlight colors: "standard", or "deuteranopia" or "protanopia" for
+    /// schemes that avoid telling red and green apart
+    #[serde(default = "default_palette")]
+    pub palette: String,
+    /// Board square width in character cells (0 = fit the terminal, also
+    /// when only `cell_height` is set)
+    #[serde(default)]
+    pub cell_width: u16,
+    /// Board square height in character cells (0 = fit the terminal, also
+    /// when only `cell_width` is set)
+    #[serde(default)]
+    pub cell_height: u16,
+    /// Screen layout: "stacked" (analysis under the board), "side"
+    /// (analysis and moves in a column right of the board) or the name of a
+    /// preset from `layouts`
+    #[serde(default = "default_layout")]
+    pub layout: String,
+    /// Named layout presets, cycled through after the built-in layouts
+    #[serde(default)]
+    pub layouts: Vec<LayoutPreset>,
 }
 
-fn default_piece_style() -> String {
-    "nerd".to_string()
+/// A named layout: which panels are shown next to the board, in which order
+/// and at what size
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LayoutPreset {
+    pub name: String,
+    /// "stacked" or "side"
+    #[serde(default = "default_layout")]
+    pub arrangement: String,
+    /// Panels in order: "analysis", "moves" or "graph", each with an optional
+    /// size after a colon, e.g. "analysis:12"
+    pub panels: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlayConfig {
+    /// Time control presets as "minutes+increment"
+    pub time_controls: Vec<String>,
+    /// Time control for new games ("" = untimed)
+    pub time_control: String,
+    /// Engine think time per move in untimed games (milliseconds)
+    pub move_time_ms: u64,
+    /// Adjust the engine's Elo automatically from recent results against it
+    pub adaptive: bool,
+    /// Let the engine think on the user's time
+    pub ponder: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PuzzleConfig {
+    /// Lichess puzzle database (CSV, uncompressed), or a subset of it
+    pub path: Option<String>,
+    /// Puzzles are picked within this many points of your puzzle rating
+    pub rating_range: u32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RepertoireConfig {
+    /// Repertoire PGN (with variations) for the White pieces
+    pub white: Option<String>,
+    /// Repertoire PGN (with variations) for the Black pieces
+    pub black: Option<String>,
+    /// Your name in game headers, to tell which side you played
+    pub player: Option<String>,
+}
+
+/// Audio feedback for each event: "bell" for the terminal bell, a shell
+/// command to run (e.g. "paplay ~/move.ogg"), or "" for silence
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SoundConfig {
+    /// A move is played
+    #[serde(rename = "move")]
+    pub move_played: String,
+    /// A move gives check
+    pub check: String,
+    /// A move ends the game
+    pub game_over: String,
+    /// Analysis reaches its target depth
+    pub analysis_done: String,
+}
+
+impl SoundConfig {
+    /// Action configured for an event
+    pub fn action(&self, event: crate::sound::SoundEvent) -> &str {
+        use crate::sound::SoundEvent;
+        match event {
+            SoundEvent::Move => &self.move_played,
+            SoundEvent::Check => &self.check,
+            SoundEvent::GameOver => &self.game_over,
+            SoundEvent::AnalysisDone => &self.analysis_done,
+        }
+    }
+}
+
+/// Remote control of a running instance
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ControlConfig {
+    /// Unix socket path, or `tcp://host:port`, to accept JSON commands on
+    /// (None = no control socket). TCP only listens on this machine.
+    pub listen: Option<String>,
+    /// Shared secret every command must carry as `"token"`; needed to
+    /// listen on TCP
+    pub token: Option<String>,
+}
+
+/// User script with hooks on game events
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ScriptConfig {
+    /// Rhai script to load (None = `init.rhai` in the config directory, if
+    /// it exists)
+    pub path: Option<String>,
 }
 
-impl Default for Config {
+/// PGN files offered by the game browser
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GamesConfig {
+    /// Directory to list PGN files from, sub-directories included (None =
+    /// the current directory)
+    pub dir: Option<String>,
+}
+
+/// Online databases, only queried when asked for
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OnlineConfig {
+    /// Masters endpoint of the Lichess opening explorer (or a self-hosted
+    /// copy of it)
+    pub masters_url: String,
+    /// Lichess API token, sent along when set
+    pub lichess_token: Option<String>,
+    /// Lichess site that positions and games are opened on
+    pub lichess_url: String,
+    /// Look up positions with up to 7 pieces in the Lichess tablebase
+    pub tablebase: bool,
+    /// Endpoint of the Lichess tablebase (or a self-hosted copy of it)
+    pub tablebase_url: String,
+    /// Look up positions in the chessdb.cn cloud database
+    pub cloud: bool,
+    /// Endpoint of the chessdb.cn cloud database
+    pub cloud_url: String,
+}
+
+impl Default for OnlineConfig {
     fn default() -> Self {
         Self {
-            engine: EngineConfig::default(),
-            ui: UiConfig::default(),
+            masters_url: "https://explorer.lichess.ovh/masters".to_string(),
+            lichess_token: None,
+            lichess_url: "https://lichess.org".to_string(),
+            tablebase: false,
+            tablebase_url: "https://tablebase.lichess.ovh/standard".to_string(),
+            cloud: false,
+            cloud_url: "https://www.chessdb.cn/cdb.php".to_string(),
+        }
+    }
+}
+
+/// Log file in the state directory
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogConfig {
+    /// Detail to log: "off", "error", "warn", "info", "debug" or "trace",
+    /// or a filter such as "warn,lazychess::engine=debug"
+    pub level: String,
+}
+
+/// Custom piece glyphs, replacing the piece style's for the pieces set
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PiecesConfig {
+    pub white: PieceGlyphs,
+    pub black: PieceGlyphs,
+}
+
+/// Glyphs for one side's pieces: any string, e.g. an emoji or a Nerd Font icon
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PieceGlyphs {
+    pub king: Option<String>,
+    pub queen: Option<String>,
+    pub rook: Option<String>,
+    pub bishop: Option<String>,
+    pub knight: Option<String>,
+    pub pawn: Option<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_piece_style() -> String {
+    "nerd".to_string()
+}
+
+fn default_renderer() -> String {
+    "text".to_string()
+}
+
+fn default_colors() -> String {
+    "auto".to_string()
+}
+
+fn default_palette() -> String {
+    "standard".to_string()
+}
+
+fn default_layout() -> String {
+    "stacked".to_string()
+}
+
+impl EngineConfig {
+    /// Threads to give the engine, detecting a default from the machine and
+    /// applying the battery cap when unplugged
+    pub fn threads(&self) -> u32 {
+        let threads = self
+            .threads
+            .unwrap_or_else(|| (num_cpus::get_physical() as u32).saturating_sub(1).max(1));
+        match self.battery_threads {
+            Some(cap) if power::on_battery() => threads.min(cap).max(1),
+            _ => threads,
         }
     }
 }
@@ -60,9 +369,14 @@ impl Default for EngineConfig {
             path: None,
             depth: 20,
             multipv: 3,
-            threads: 4,
+            threads: None,
             hash: 256,
             contempt: 0,
+            skill_level: None,
+            elo: None,
+            engines: Vec::new(),
+            low_priority: false,
+            battery_threads: None,
         }
     }
 }
@@ -75,10 +389,78 @@ impl Default for UiConfig {
             highlight_last_move: true,
             piece_style: "nerd".to_string(),
             analysis_eval_only: false,
+            show_hanging: false,
+            white_perspective: false,
+            show_fen: false,
+            show_variations: true,
+            show_comments: true,
+            show_nags: true,
+            pause_on_unfocus: false,
+            mouse_capture: true,
+            notifications: true,
+            screen_reader: false,
+            renderer: default_renderer(),
+            colors: default_colors(),
+            palette: default_palette(),
+            cell_width: 0,
+            cell_height: 0,
+            layout: default_layout(),
+            layouts: Vec::new(),
         }
     }
 }
 
+impl Default for PlayConfig {
+    fn default() -> Self {
+        Self {
+            time_controls: ["1+0", "3+2", "5+3", "10+5", "15+10", "30+0"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            time_control: "5+3".to_string(),
+            move_time_ms: 1000,
+            adaptive: false,
+            ponder: false,
+        }
+    }
+}
+
+impl Default for PuzzleConfig {
+    fn default() -> Self {
+        Self {
+            path: None,
+            rating_range: 200,
+        }
+    }
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            level: "warn".to_string(),
+        }
+    }
+}
+
+impl PiecesConfig {
+    /// Custom glyph for a piece, if one is set
+    pub fn glyph(&self, piece: shakmaty::Piece) -> Option<&str> {
+        let glyphs = match piece.color {
+            shakmaty::Color::White => &self.white,
+            shakmaty::Color::Black => &self.black,
+        };
+        let glyph = match piece.role {
+            shakmaty::Role::King => &glyphs.king,
+            shakmaty::Role::Queen => &glyphs.queen,
+            shakmaty::Role::Rook => &glyphs.rook,
+            shakmaty::Role::Bishop => &glyphs.bishop,
+            shakmaty::Role::Knight => &glyphs.knight,
+            shakmaty::Role::Pawn => &glyphs.pawn,
+        };
+        glyph.as_deref().filter(|g| !g.is_empty())
+    }
+}
+
 impl UiConfig {
     /// Get the piece style enum from the config string
     pub fn get_piece_style(&self) -> crate::chess::PieceStyle {
@@ -90,46 +472,350 @@ impl UiConfig {
             _ => crate::chess::PieceStyle::NerdFont,
         }
     }
+
+    /// Get the board renderer from the config string, detecting the
+    /// terminal's graphics support for "auto"
+    pub fn get_renderer(&self) -> crate::ui::BoardRenderer {
+        match self.renderer.to_lowercase().as_str() {
+            "kitty" => crate::ui::BoardRenderer::Kitty,
+            "iterm2" | "iterm" => crate::ui::BoardRenderer::Iterm2,
+            "sixel" => crate::ui::BoardRenderer::Sixel,
+            "halfblock" | "half-block" | "halfblocks" => crate::ui::BoardRenderer::HalfBlock,
+            "auto" => crate::ui::BoardRenderer::detect(),
+            _ => crate::ui::BoardRenderer::Text,
+        }
+    }
+
+    /// Get the color depth from the config string, detecting the
+    /// terminal's support for "auto" and unknown values
+    pub fn get_color_depth(&self) -> crate::ui::ColorDepth {
+        match self.colors.to_lowercase().as_str() {
+            "truecolor" | "24bit" | "rgb" => crate::ui::ColorDepth::TrueColor,
+            "256" | "ansi256" => crate::ui::ColorDepth::Ansi256,
+            "16" | "ansi16" | "ansi" => crate::ui::ColorDepth::Ansi16,
+            _ => crate::ui::ColorDepth::detect(),
+        }
+    }
+
+    /// Get the highlight palette from the config string
+    pub fn get_palette(&self) -> crate::ui::Palette {
+        crate::ui::Palette::from_name(&self.palette).unwrap_or_default()
+    }
+
+    /// Get the panel layout for the layout name, falling back to the
+    /// stacked layout for unknown names and broken presets
+    pub fn get_layout(&self) -> crate::ui::PanelLayout {
+        use crate::ui::{PanelLayout, ScreenLayout};
+        if let Some(arrangement) = ScreenLayout::from_name(&self.layout) {
+            return PanelLayout::builtin(arrangement);
+        }
+        self.layouts
+            .iter()
+            .find(|preset| preset.name == self.layout)
+            .and_then(|preset| PanelLayout::parse(&preset.arrangement, &preset.panels).ok())
+            .unwrap_or_else(|| PanelLayout::builtin(ScreenLayout::Stacked))
+    }
+
+    /// Names of the layouts to cycle through: the built-in ones, then the
+    /// presets
+    pub fn layout_names(&self) -> Vec<String> {
+        ["stacked", "side"]
+            .into_iter()
+            .map(String::from)
+            .chain(self.layouts.iter().map(|preset| preset.name.clone()))
+            .collect()
+    }
+}
+
+impl EngineConfig {
+    /// Describe the play strength setting for display
+    pub fn strength_label(&self) -> String {
+        match (self.elo, self.skill_level) {
+            (Some(elo), _) => format!("{} Elo", elo),
+            (None, Some(level)) => format!("Skill level {}", level),
+            (None, None) => "Full strength".to_string(),
+        }
+    }
 }
 
 impl Config {
     /// Get the config file path
     pub fn config_path() -> Option<PathBuf> {
+        // Tests save configs too, and must leave the user's alone
+        if cfg!(test) {
+            return Some(crate::state::test_dir().join("config.toml"));
+        }
         dirs::config_dir().map(|p| p.join("lazychess").join("config.toml"))
     }
 
-    /// Load config from file, or create default if not exists
-    pub fn load() -> Result<Self> {
-        let path = Self::config_path();
+    /// Get the config file path of a profile: `config.<name>.toml` next to
+    /// the default config file
+    pub fn profile_path(profile: &str) -> Option<PathBuf> {
+        Self::config_path().map(|p| p.with_file_name(format!("config.{}.toml", profile)))
+    }
+
+    /// Get the file this config is loaded from and saved to
+    fn file_path(&self) -> Option<PathBuf> {
+        match self.profile {
+            Some(ref profile) => Self::profile_path(profile),
+            None => Self::config_path(),
+        }
+    }
+
+    /// Get the user script path: the configured one, or `init.rhai` next to
+    /// the config file when there is one
+    pub fn script_path(&self) -> Option<String> {
+        if let Some(ref path) = self.script.path {
+            return Some(path.clone());
+        }
+        Self::config_path()
+            .map(|p| p.with_file_name("init.rhai"))
+            .filter(|p| p.exists())
+            .map(|p| p.to_string_lossy().to_string())
+    }
 
-        match path {
+    /// Load config from file, or create default if not exists. A profile
+    /// that doesn't exist yet starts as a copy of the default config.
+    pub fn load(profile: Option<&str>) -> Result<Self> {
+        if let Some(profile) = profile {
+            if profile.is_empty() || profile.contains(['/', '\\', '.']) {
+                bail!("Invalid profile name: {:?}", profile);
+            }
+        }
+        let path = match profile {
+            Some(profile) => Self::profile_path(profile).filter(|p| p.exists()),
+            None => None,
+        }
+        .or_else(Self::config_path);
+
+        let mut config = match path {
             Some(p) if p.exists() => {
                 let contents = fs::read_to_string(&p)
                     .with_context(|| format!("Failed to read config from {:?}", p))?;
                 let config: Config = toml::from_str(&contents)
                     .with_context(|| format!("Failed to parse config from {:?}", p))?;
-                Ok(config)
+                config
             }
-            _ => Ok(Config::default()),
-        }
+            _ => Config::default(),
+        };
+        config.profile = profile.map(String::from);
+        Ok(config)
     }
 
     /// Save config to file
     pub fn save(&self) -> Result<()> {
-        let path = Self::config_path().context("Could not determine config directory")?;
+        let path = self
+            .file_path()
+            .context("Could not determine config directory")?;
 
         if let Some(parent) = path.parent() {
             fs::create_dir_all(parent)
                 .with_context(|| format!("Failed to create config directory {:?}", parent))?;
         }
 
-        let contents = toml::to_string_pretty(self).context("Failed to serialize config")?;
-        fs::write(&path, contents)
+        fs::write(&path, self.file_contents()?)
             .with_context(|| format!("Failed to write config to {:?}", path))?;
 
         Ok(())
     }
 
+    /// The config as saved: TOML with the file's own values for the settings
+    /// environment variables override
+    fn file_contents(&self) -> Result<String> {
+        let mut value = toml::Value::try_from(self).context("Failed to serialize config")?;
+        for (key, file_value) in &self.env_overrides {
+            let (table, name) = Self::lookup_mut(&mut value, key)?;
+            match file_value {
+                Some(file_value) => table.insert(name.to_string(), file_value.clone()),
+                None => table.remove(name),
+            };
+        }
+        toml::to_string_pretty(&value).context("Failed to serialize config")
+    }
+
+    /// Get a setting by its dotted key, e.g. `engine.hash` (None when an
+    /// optional setting is unset)
+    pub fn get_key(&self, key: &str) -> Result<Option<toml::Value>> {
+        let value = toml::Value::try_from(self).context("Failed to serialize config")?;
+        let (parent, name) = Self::lookup(&value, key)?;
+        Ok(parent.get(name).cloned())
+    }
+
+    /// Change a setting by its dotted key. The text is read as the type the
+    /// setting already has; "none" unsets an optional setting.
+    pub fn set_key(&mut self, key: &str, text: &str) -> Result<()> {
+        let mut value = toml::Value::try_from(&*self).context("Failed to serialize config")?;
+        let (parent, name) = Self::lookup(&value, key)?;
+        let current = parent.get(name).cloned();
+        if text == "none" && current.is_none() && !Self::is_optional(&value, key) {
+            bail!("Unknown setting: {}", key);
+        }
+
+        let new = match current {
+            _ if text == "none" => None,
+            // Strings are taken as typed, so `:set ui.colors 256` stays text
+            Some(toml::Value::String(_)) => Some(toml::Value::String(text.to_string())),
+            _ => Some(
+                toml::from_str::<toml::Table>(&format!("value = {}", text))
+                    .ok()
+                    .and_then(|mut table| table.remove("value"))
+                    .unwrap_or_else(|| toml::Value::String(text.to_string())),
+            ),
+        };
+
+        let (table, _) = Self::lookup_mut(&mut value, key)?;
+        match new {
+            Some(new) => table.insert(name.to_string(), new),
+            None => table.remove(name),
+        };
+
+        let config: Config = value
+            .try_into()
+            .map_err(|e: toml::de::Error| anyhow!("Invalid value for {}: {}", key, e.message()))?;
+        // Unknown keys are dropped when the config is read back
+        if text != "none" && config.get_key(key)?.is_none() {
+            bail!("Unknown setting: {}", key);
+        }
+        let mut config = config;
+        config.profile = self.profile.take();
+        config.env_overrides = std::mem::take(&mut self.env_overrides);
+        // A setting changed on purpose is saved as it is
+        config.env_overrides.remove(key);
+        *self = config;
+        Ok(())
+    }
+
+    /// Is a key that is missing from the serialized config an optional
+    /// setting that is unset, rather than one that doesn't exist? Optional
+    /// settings are text or numbers, so one of those is kept when read back.
+    fn is_optional(value: &toml::Value, key: &str) -> bool {
+        [toml::Value::String(String::new()), toml::Value::Integer(0)]
+            .into_iter()
+            .any(|probe| {
+                let mut value = value.clone();
+                let Ok((table, name)) = Self::lookup_mut(&mut value, key) else {
+                    return false;
+                };
+                table.insert(name.to_string(), probe);
+                value
+                    .try_into::<Config>()
+                    .is_ok_and(|config| config.get_key(key).is_ok_and(|v| v.is_some()))
+            })
+    }
+
+    /// Override settings from `LAZYCHESS_<SECTION>_<KEY>` environment
+    /// variables, e.g. `LAZYCHESS_ENGINE_DEPTH=30`, read like `set_key` values
+    pub fn apply_env(&mut self) -> Result<()> {
+        self.apply_vars(
+            std::env::vars_os().filter_map(|(name, text)| {
+                Some((name.into_string().ok()?, text.into_string().ok()?))
+            }),
+        )
+    }
+
+    /// Override settings from variables named like environment variables
+    fn apply_vars(&mut self, vars: impl IntoIterator<Item = (String, String)>) -> Result<()> {
+        let value = toml::Value::try_from(&*self).context("Failed to serialize config")?;
+        let sections: Vec<&String> = value
+            .as_table()
+            .into_iter()
+            .flatten()
+            .filter(|(_, v)| v.is_table())
+            .map(|(k, _)| k)
+            .collect();
+
+        let mut vars: Vec<(String, String)> = vars
+            .into_iter()
+            .filter(|(name, _)| name.starts_with(ENV_PREFIX))
+            .collect();
+        vars.sort();
+        for (name, text) in vars {
+            let setting = name[ENV_PREFIX.len()..].to_lowercase();
+            // Other variables may share the prefix
+            let Some((section, key)) = setting.split_once('_') else {
+                continue;
+            };
+            if !sections.iter().any(|s| *s == section) {
+                continue;
+            }
+            let key = format!("{}.{}", section, key);
+            let file_value = self.get_key(&key).ok().flatten();
+            self.set_key(&key, &text)
+                .with_context(|| format!("Invalid {}", name))?;
+            self.env_overrides.entry(key).or_insert(file_value);
+        }
+        Ok(())
+    }
+
+    /// Find the table holding a dotted key for changing, and the key's last
+    /// part
+    fn lookup_mut<'a, 'k>(
+        value: &'a mut toml::Value,
+        key: &'k str,
+    ) -> Result<(&'a mut toml::Table, &'k str)> {
+        let (path, name) = key.rsplit_once('.').unwrap_or(("", key));
+        let mut table = value;
+        for part in path.split('.').filter(|p| !p.is_empty()) {
+            table = table
+                .get_mut(part)
+                .with_context(|| format!("Unknown setting: {}", key))?;
+        }
+        let table = table
+            .as_table_mut()
+            .with_context(|| format!("Unknown setting: {}", key))?;
+        Ok((table, name))
+    }
+
+    /// Find the table holding a dotted key, and the key's last part
+    fn lookup<'a, 'k>(value: &'a toml::Value, key: &'k str) -> Result<(&'a toml::Table, &'k str)> {
+        let (path, name) = key.rsplit_once('.').unwrap_or(("", key));
+        let mut table = value;
+        for part in path.split('.').filter(|p| !p.is_empty()) {
+            table = table
+                .get(part)
+                .with_context(|| format!("Unknown setting: {}", key))?;
+        }
+        let table = table
+            .as_table()
+            .with_context(|| format!("Unknown setting: {}", key))?;
+        if name.is_empty() || table.get(name).is_some_and(toml::Value::is_table) {
+            bail!("{} is a section; name one of its settings", key);
+        }
+        Ok((table, name))
+    }
+
+    /// List engine binaries for the engine picker: the current engine,
+    /// configured extras, and well-known engines found in $PATH
+    pub fn engine_candidates(&self) -> Vec<String> {
+        const KNOWN: [&str; 8] = [
+            "stockfish",
+            "lc0",
+            "berserk",
+            "ethereal",
+            "komodo",
+            "dragon",
+            "rubichess",
+            "koivisto",
+        ];
+
+        let mut candidates: Vec<String> = Vec::new();
+        let found = KNOWN
+            .iter()
+            .filter_map(|name| which::which(name).ok())
+            .map(|p| p.to_string_lossy().to_string());
+        for path in self
+            .stockfish_path()
+            .into_iter()
+            .chain(self.engine.engines.iter().cloned())
+            .chain(found)
+        {
+            if !candidates.contains(&path) {
+                candidates.push(path);
+            }
+        }
+        candidates
+    }
+
     /// Get the Stockfish path, either from config or by searching $PATH
     pub fn stockfish_path(&self) -> Option<String> {
         if let Some(ref path) = self.engine.path {
@@ -142,3 +828,54 @@ impl Config {
             .map(|p| p.to_string_lossy().to_string())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn env_overrides_are_not_saved() {
+        let mut config = Config {
+            profile: Some("box".to_string()),
+            ..Config::default()
+        };
+        config.set_key("ui.flip_board", "true").unwrap();
+        let vars = [
+            ("LAZYCHESS_ENGINE_DEPTH", "31"),
+            ("LAZYCHESS_ONLINE_LICHESS_TOKEN", "secret"),
+            ("LAZYCHESS_STATE_DIR", "/tmp"),
+        ];
+        config
+            .apply_vars(vars.map(|(name, text)| (name.to_string(), text.to_string())))
+            .unwrap();
+        assert_eq!(config.engine.depth, 31);
+        assert_eq!(config.online.lichess_token.as_deref(), Some("secret"));
+
+        let saved: Config = toml::from_str(&config.file_contents().unwrap()).unwrap();
+        assert_eq!(saved.engine.depth, Config::default().engine.depth);
+        assert_eq!(saved.online.lichess_token, None);
+        assert!(saved.ui.flip_board);
+        assert_eq!(config.profile.as_deref(), Some("box"));
+
+        // A setting changed on purpose afterwards is saved
+        config.set_key("engine.depth", "25").unwrap();
+        let saved: Config = toml::from_str(&config.file_contents().unwrap()).unwrap();
+        assert_eq!(saved.engine.depth, 25);
+        assert_eq!(saved.online.lichess_token, None);
+    }
+
+    #[test]
+    fn unset_needs_a_known_setting() {
+        let mut config = Config::default();
+        assert!(config.set_key("engine.nonexistent", "none").is_err());
+        assert!(config.set_key("nosuchsection.key", "none").is_err());
+
+        // Optional settings can be unset whether or not they have a value
+        config.set_key("engine.threads", "none").unwrap();
+        config.set_key("engine.threads", "4").unwrap();
+        assert_eq!(config.engine.threads, Some(4));
+        config.set_key("engine.threads", "none").unwrap();
+        assert_eq!(config.engine.threads, None);
+        config.set_key("online.lichess_token", "none").unwrap();
+    }
+}
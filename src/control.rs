@@ -0,0 +1,200 @@
+//! Remote control: scripts, stream tooling and editors drive a running
+//! instance by sending JSON commands, one per line, to a Unix socket or a TCP
+//! port. Each command is answered with one line of JSON.
+//!
+//! Whoever can reach the socket can play moves and run commands, so TCP only
+//! listens on loopback and needs a shared token, and commands that would
+//! start a program are refused (see `App::control`).
+
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, ToSocketAddrs};
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+
+use crate::config::ControlConfig;
+use crate::events::{AppEvent, Waker};
+
+/// A command from a control connection, to be answered through `reply`
+#[derive(Debug)]
+pub struct ControlRequest {
+    pub command: Value,
+    pub reply: Sender<Value>,
+}
+
+/// Reply for a command that failed
+pub fn error_reply(message: impl Into<String>) -> Value {
+    json!({ "ok": false, "error": message.into() })
+}
+
+/// A control socket being listened on. A Unix socket's file is removed
+/// when it's dropped.
+pub struct ControlSocket {
+    path: Option<String>,
+}
+
+impl Drop for ControlSocket {
+    fn drop(&mut self) {
+        if let Some(ref path) = self.path {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+}
+
+/// Listen for control connections on a Unix socket path, or on
+/// `tcp://host:port` of this machine
+pub fn listen(config: &ControlConfig, address: &str, waker: Waker) -> Result<ControlSocket> {
+    let token = config.token.clone().filter(|token| !token.is_empty());
+    if let Some(addr) = address.strip_prefix("tcp://") {
+        let addrs: Vec<SocketAddr> = addr
+            .to_socket_addrs()
+            .with_context(|| format!("Invalid control address {}", addr))?
+            .collect();
+        if addrs.is_empty() || !addrs.iter().all(|a| a.ip().is_loopback()) {
+            anyhow::bail!(
+                "Control only listens on this machine (127.0.0.1 or ::1), not {}",
+                addr
+            );
+        }
+        let Some(token) = token else {
+            anyhow::bail!("Set token under [control] to listen for control on TCP");
+        };
+        let listener = TcpListener::bind(&addrs[..])
+            .with_context(|| format!("Failed to listen for control on {}", addr))?;
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let Ok(reader) = stream.try_clone() else {
+                    continue;
+                };
+                let waker = waker.clone();
+                let token = token.clone();
+                thread::spawn(move || serve(reader, stream, Some(&token), &waker));
+            }
+        });
+        return Ok(ControlSocket { path: None });
+    }
+    listen_unix(address, token, waker)?;
+    Ok(ControlSocket {
+        path: Some(address.to_string()),
+    })
+}
+
+#[cfg(unix)]
+fn listen_unix(path: &str, token: Option<String>, waker: Waker) -> Result<()> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+    use std::os::unix::net::UnixListener;
+
+    // A socket left behind by an earlier run would stop the bind
+    if std::fs::metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+        let _ = std::fs::remove_file(path);
+    }
+    let listener = UnixListener::bind(path)
+        .with_context(|| format!("Failed to listen for control on {}", path))?;
+    // Only for this user
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+        .with_context(|| format!("Failed to restrict {}", path))?;
+    thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let Ok(reader) = stream.try_clone() else {
+                continue;
+            };
+            let waker = waker.clone();
+            let token = token.clone();
+            thread::spawn(move || serve(reader, stream, token.as_deref(), &waker));
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn listen_unix(_path: &str, _token: Option<String>, _waker: Waker) -> Result<()> {
+    anyhow::bail!("Unix sockets aren't available here; listen on tcp://host:port instead")
+}
+
+/// Answer the commands of one connection until it closes. With a token,
+/// commands without it are refused.
+fn serve(reader: impl Read, mut writer: impl Write, token: Option<&str>, waker: &Waker) {
+    for line in BufReader::new(reader).lines() {
+        let Ok(line) = line else {
+            break;
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match serde_json::from_str::<Value>(&line) {
+            Ok(command)
+                if token
+                    .is_some_and(|t| command.get("token").and_then(Value::as_str) != Some(t)) =>
+            {
+                tracing::warn!("Control command with a wrong or missing token");
+                error_reply("Wrong or missing \"token\"")
+            }
+            Ok(command) => {
+                let (tx, rx) = mpsc::channel();
+                waker.send(AppEvent::Control(ControlRequest { command, reply: tx }));
+                rx.recv()
+                    .unwrap_or_else(|_| error_reply("lazychess is shutting down"))
+            }
+            Err(e) => {
+                tracing::warn!("Invalid control command {:?}: {}", line, e);
+                error_reply(format!("Invalid JSON: {}", e))
+            }
+        };
+        if writeln!(writer, "{}", reply).is_err() {
+            break;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(token: Option<&str>) -> ControlConfig {
+        ControlConfig {
+            listen: None,
+            token: token.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn tcp_needs_loopback_and_a_token() {
+        let waker = Waker::default();
+        let error = listen(&config(Some("secret")), "tcp://0.0.0.0:0", waker.clone());
+        assert!(error.is_err());
+        assert!(listen(&config(None), "tcp://127.0.0.1:0", waker.clone()).is_err());
+        assert!(listen(&config(Some("")), "tcp://127.0.0.1:0", waker.clone()).is_err());
+        assert!(listen(&config(Some("secret")), "tcp://127.0.0.1:0", waker).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn socket_file_is_removed_on_drop() {
+        let path = std::env::temp_dir().join(format!("lazychess-{}.sock", std::process::id()));
+        let path = path.to_string_lossy().to_string();
+        let socket = listen(&config(None), &path, Waker::default()).unwrap();
+        assert!(std::fs::metadata(&path).is_ok());
+        drop(socket);
+        assert!(std::fs::metadata(&path).is_err());
+    }
+
+    #[test]
+    fn commands_without_the_token_are_refused() {
+        let input = "{\"cmd\":\"position\"}\n{\"cmd\":\"pgn\",\"token\":\"wrong\"}\n";
+        let mut output = Vec::new();
+        serve(
+            input.as_bytes(),
+            &mut output,
+            Some("secret"),
+            &Waker::default(),
+        );
+        let replies: Vec<Value> = String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(replies.len(), 2);
+        assert!(replies.iter().all(|reply| reply["ok"] == false));
+    }
+}
@@ -0,0 +1,212 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use shakmaty::{Color, Outcome, Position};
+use std::collections::BTreeMap;
+
+use crate::chess::Game;
+use crate::state;
+
+/// Moves the defender has to survive to hold a drawn ending
+pub const HOLD_MOVES: usize = 40;
+
+/// What the user has to achieve in a drill
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrillGoal {
+    /// Convert the position into checkmate
+    Win,
+    /// Hold the draw
+    Draw,
+}
+
+/// A theoretical endgame to practise against the engine
+#[derive(Debug, Clone, PartialEq)]
+pub struct Drill {
+    /// Short name used with `:drill`
+    pub key: &'static str,
+    pub name: &'static str,
+    /// Starting position; the user plays the side to move
+    pub fen: &'static str,
+    pub goal: DrillGoal,
+}
+
+/// Built-in endgame drills
+pub const DRILLS: &[Drill] = &[
+    Drill {
+        key: "kqk",
+        name: "King and queen vs king",
+        fen: "8/8/8/3k4/8/8/8/4K1Q1 w - - 0 1",
+        goal: DrillGoal::Win,
+    },
+    Drill {
+        key: "krk",
+        name: "King and rook vs king",
+        fen: "8/8/8/4k3/8/8/8/R3K3 w - - 0 1",
+        goal: DrillGoal::Win,
+    },
+    Drill {
+        key: "kbbk",
+        name: "Two bishops vs king",
+        fen: "8/8/8/4k3/8/8/8/2B1KB2 w - - 0 1",
+        goal: DrillGoal::Win,
+    },
+    Drill {
+        key: "kbnk",
+        name: "Bishop and knight vs king",
+        fen: "8/8/8/4k3/8/8/8/1NB1K3 w - - 0 1",
+        goal: DrillGoal::Win,
+    },
+    Drill {
+        key: "kpk",
+        name: "King and pawn vs king",
+        fen: "4k3/8/4K3/4P3/8/8/8/8 w - - 0 1",
+        goal: DrillGoal::Win,
+    },
+    Drill {
+        key: "lucena",
+        name: "Lucena position",
+        fen: "1K1k4/1P6/8/8/8/8/r7/2R5 w - - 0 1",
+        goal: DrillGoal::Win,
+    },
+    Drill {
+        key: "philidor",
+        name: "Philidor position",
+        fen: "4k3/8/r7/4PK2/8/8/8/1R6 b - - 0 1",
+        goal: DrillGoal::Draw,
+    },
+    Drill {
+        key: "wrongbishop",
+        name: "Rook pawn and wrong bishop",
+        fen: "7k/8/6K1/7P/8/8/4B3/8 b - - 0 1",
+        goal: DrillGoal::Draw,
+    },
+];
+
+/// Look up a built-in drill by its short name
+pub fn find_drill(key: &str) -> Option<&'static Drill> {
+    DRILLS.iter().find(|d| d.key.eq_ignore_ascii_case(key))
+}
+
+/// How a drill ended
+#[derive(Debug, Clone, PartialEq)]
+pub struct DrillEnd {
+    pub success: bool,
+    pub reason: &'static str,
+    /// PGN result of the game
+    pub result: &'static str,
+}
+
+/// Results for one drill, persisted in the state dir
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DrillStreak {
+    pub attempts: u32,
+    pub successes: u32,
+    /// Successes in a row
+    pub current: u32,
+    pub best: u32,
+}
+
+/// An endgame drill being played against the engine
+#[derive(Debug, Clone)]
+pub struct DrillSession {
+    pub drill: &'static Drill,
+    /// Side the user plays
+    pub side: Color,
+    /// Results so far for this drill
+    pub streak: DrillStreak,
+    /// Set once the drill is over
+    pub end: Option<DrillEnd>,
+}
+
+impl DrillSession {
+    pub fn new(drill: &'static Drill, side: Color, streak: DrillStreak) -> Self {
+        Self {
+            drill,
+            side,
+            streak,
+            end: None,
+        }
+    }
+
+    /// Number of moves the user has played in the drill
+    pub fn moves_played(&self, game: &Game) -> usize {
+        let plies = game.current_index();
+        if game.initial_position().turn() == self.side {
+            plies.div_ceil(2)
+        } else {
+            plies / 2
+        }
+    }
+
+    /// Check whether the drill is over in the current position. Draws by
+    /// rule end it too, since the engine won't claim them.
+    pub fn judge(&self, game: &Game) -> Option<DrillEnd> {
+        let position = game.position();
+        let won = self.drill.goal == DrillGoal::Win;
+        let draw = |reason| DrillEnd {
+            success: !won,
+            reason,
+            result: "1/2-1/2",
+        };
+
+        match game.outcome() {
+            Some(Outcome::Decisive { winner }) => Some(DrillEnd {
+                success: winner == self.side,
+                reason: if winner == self.side {
+                    "Checkmate"
+                } else {
+                    "You were mated"
+                },
+                result: if winner == Color::White { "1-0" } else { "0-1" },
+            }),
+            Some(Outcome::Draw) if position.is_stalemate() => Some(draw("Stalemate")),
+            Some(Outcome::Draw) => Some(draw("Insufficient material")),
+            None if position.halfmoves() >= 100 => Some(draw("Fifty-move rule")),
+            None if game.repetitions() >= 3 => Some(draw("Threefold repetition")),
+            None if !won && self.moves_played(game) >= HOLD_MOVES => Some(DrillEnd {
+                success: true,
+                reason: "Held",
+                result: "*",
+            }),
+            None => None,
+        }
+    }
+}
+
+/// Drill results by drill name, persisted in the state dir
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DrillRecord {
+    pub drills: BTreeMap<String, DrillStreak>,
+}
+
+impl DrillRecord {
+    /// Load the record, or start a fresh one
+    pub fn load() -> Self {
+        state::load("drills.toml")
+    }
+
+    /// Save the record to the state dir
+    pub fn save(&self) -> Result<()> {
+        state::save("drills.toml", self)
+    }
+
+    /// Results for a drill
+    pub fn streak(&self, key: &str) -> DrillStreak {
+        self.drills.get(key).cloned().unwrap_or_default()
+    }
+
+    /// Record an attempt at a drill and return its updated results
+    pub fn record(&mut self, key: &str, success: bool) -> DrillStreak {
+        let streak = self.drills.entry(key.to_string()).or_default();
+        streak.attempts += 1;
+        if success {
+            streak.successes += 1;
+            streak.current += 1;
+            streak.best = streak.best.max(streak.current);
+        } else {
+            streak.current = 0;
+        }
+        streak.clone()
+    }
+}
@@ -0,0 +1,311 @@
+use anyhow::{bail, Context, Result};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{Shutdown, TcpStream, ToSocketAddrs};
+use std::path::Path;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+/// Time an engine on this machine gets to answer `uci`
+const STARTUP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Time a remote engine gets to answer `uci`, connecting included
+const REMOTE_STARTUP_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Time to wait for a TCP connection to an engine
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Lines of an engine's stderr kept to explain a failed start
+const STDERR_LINES: usize = 10;
+
+/// Time to let an engine that failed to start finish writing stderr
+const STDERR_GRACE: Duration = Duration::from_millis(500);
+
+/// Something that speaks UCI: an engine process, or a stand-in for one
+pub trait EngineBackend: Send {
+    /// Send one line to the engine
+    fn send_line(&mut self, line: &str) -> Result<()>;
+
+    /// Take the engine's output, read line by line on another thread. Only
+    /// called once.
+    fn output(&mut self) -> Result<Box<dyn BufRead + Send>>;
+
+    /// Lower the engine's CPU priority so it yields to other programs
+    fn lower_priority(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// How long to wait for the engine to answer `uci`
+    fn startup_timeout(&self) -> Duration {
+        STARTUP_TIMEOUT
+    }
+
+    /// What the engine wrote to stderr, to explain why it failed to start
+    fn error_output(&mut self) -> Option<String> {
+        None
+    }
+
+    /// Wait for the engine to exit after `quit`
+    fn wait(&mut self) {}
+}
+
+/// Split a command line into words, keeping quoted strings together
+fn split_command(command: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+    for c in command.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => word.push(c),
+            (None, '"' | '\'') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if in_word {
+        words.push(word);
+    }
+    words
+}
+
+/// A UCI engine running as a child process, possibly on another machine
+/// through `ssh`
+pub struct ProcessBackend {
+    process: Child,
+    stdin: ChildStdin,
+    stdout: Option<ChildStdout>,
+    /// Is the engine reached over ssh?
+    remote: bool,
+    /// The last lines of stderr, e.g. ssh failing to log in
+    stderr: Arc<Mutex<Vec<String>>>,
+    stderr_reader: Option<JoinHandle<()>>,
+}
+
+impl ProcessBackend {
+    /// Start the engine at a path, or run a command line such as
+    /// `ssh desktop stockfish` when no file has that name
+    pub fn spawn(path: &str) -> Result<Self> {
+        let mut words = if Path::new(path).exists() {
+            vec![path.to_string()]
+        } else {
+            split_command(path)
+        };
+        if words.is_empty() {
+            bail!("No engine command given");
+        }
+        let program = words.remove(0);
+
+        // A password prompt would draw over the screen, so ssh has to log in
+        // with keys, and without a terminal on the other end
+        let remote = Path::new(&program).file_stem().is_some_and(|s| s == "ssh");
+        if remote {
+            words.splice(0..0, ["-T", "-o", "BatchMode=yes"].map(String::from));
+        }
+
+        let mut process = Command::new(&program)
+            .args(&words)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .with_context(|| format!("Failed to start engine at: {}", path))?;
+
+        let stdin = process.stdin.take().context("Failed to get engine stdin")?;
+        let stdout = process
+            .stdout
+            .take()
+            .context("Failed to get engine stdout")?;
+
+        // Drain stderr so a chatty engine never blocks on a full pipe
+        let stderr = Arc::new(Mutex::new(Vec::new()));
+        let stderr_reader = process.stderr.take().map(|pipe| {
+            let lines = Arc::clone(&stderr);
+            thread::spawn(move || {
+                for line in BufReader::new(pipe).lines().map_while(|l| l.ok()) {
+                    let mut lines = lines.lock().unwrap();
+                    if lines.len() == STDERR_LINES {
+                        lines.remove(0);
+                    }
+                    lines.push(line);
+                }
+            })
+        });
+
+        Ok(Self {
+            process,
+            stdin,
+            stdout: Some(stdout),
+            remote,
+            stderr,
+            stderr_reader,
+        })
+    }
+}
+
+impl EngineBackend for ProcessBackend {
+    fn send_line(&mut self, line: &str) -> Result<()> {
+        writeln!(self.stdin, "{}", line).context("Failed to write to engine")?;
+        self.stdin.flush().context("Failed to flush engine stdin")?;
+        Ok(())
+    }
+
+    fn output(&mut self) -> Result<Box<dyn BufRead + Send>> {
+        let stdout = self.stdout.take().context("Engine output already taken")?;
+        Ok(Box::new(BufReader::new(stdout)))
+    }
+
+    #[cfg(unix)]
+    fn lower_priority(&mut self) -> Result<()> {
+        // SAFETY: setpriority only reads its arguments
+        let ret = unsafe { libc::setpriority(libc::PRIO_PROCESS, self.process.id() as _, 10) };
+        if ret != 0 {
+            bail!(
+                "Failed to lower engine priority: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+        Ok(())
+    }
+
+    #[cfg(windows)]
+    fn lower_priority(&mut self) -> Result<()> {
+        use std::os::windows::io::AsRawHandle;
+        use windows_sys::Win32::System::Threading::{
+            SetPriorityClass, BELOW_NORMAL_PRIORITY_CLASS,
+        };
+
+        // SAFETY: the handle belongs to our child process and stays valid while we own it
+        let ok =
+            unsafe { SetPriorityClass(self.process.as_raw_handle(), BELOW_NORMAL_PRIORITY_CLASS) };
+        if ok == 0 {
+            bail!(
+                "Failed to lower engine priority: {}",
+                std::io::Error::last_os_error()
+            );
+        }
+        Ok(())
+    }
+
+    fn startup_timeout(&self) -> Duration {
+        if self.remote {
+            REMOTE_STARTUP_TIMEOUT
+        } else {
+            STARTUP_TIMEOUT
+        }
+    }
+
+    fn error_output(&mut self) -> Option<String> {
+        // A process that just quit may still have stderr in flight, so give
+        // the reader a moment to catch up before looking
+        if let Some(reader) = self.stderr_reader.take() {
+            let deadline = std::time::Instant::now() + STDERR_GRACE;
+            while !reader.is_finished() && std::time::Instant::now() < deadline {
+                thread::sleep(Duration::from_millis(10));
+            }
+            if reader.is_finished() {
+                let _ = reader.join();
+            } else {
+                self.stderr_reader = Some(reader);
+            }
+        }
+        let lines = self.stderr.lock().unwrap();
+        let text = lines.join(" ").trim().to_string();
+        (!text.is_empty()).then_some(text)
+    }
+
+    fn wait(&mut self) {
+        let _ = self.process.wait();
+    }
+}
+
+/// A UCI engine served over a TCP socket, e.g. from a container
+pub struct TcpBackend {
+    stream: TcpStream,
+    output: Option<TcpStream>,
+}
+
+impl TcpBackend {
+    /// Connect to an engine at `host:port`
+    pub fn connect(address: &str) -> Result<Self> {
+        let addrs = address
+            .to_socket_addrs()
+            .with_context(|| format!("Invalid engine address: {}", address))?;
+        let mut last_error = None;
+        for addr in addrs {
+            match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+                Ok(stream) => {
+                    // UCI commands are short and should go out right away
+                    stream.set_nodelay(true)?;
+                    let output = stream.try_clone()?;
+                    return Ok(Self {
+                        stream,
+                        output: Some(output),
+                    });
+                }
+                Err(e) => last_error = Some(e),
+            }
+        }
+        match last_error {
+            Some(e) => {
+                Err(e).with_context(|| format!("Failed to connect to engine at {}", address))
+            }
+            None => bail!("No addresses found for {}", address),
+        }
+    }
+}
+
+impl EngineBackend for TcpBackend {
+    fn send_line(&mut self, line: &str) -> Result<()> {
+        writeln!(self.stream, "{}", line).context("Failed to write to engine")?;
+        self.stream
+            .flush()
+            .context("Failed to flush engine connection")?;
+        Ok(())
+    }
+
+    fn output(&mut self) -> Result<Box<dyn BufRead + Send>> {
+        let output = self.output.take().context("Engine output already taken")?;
+        Ok(Box::new(BufReader::new(output)))
+    }
+
+    fn startup_timeout(&self) -> Duration {
+        REMOTE_STARTUP_TIMEOUT
+    }
+
+    fn wait(&mut self) {
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::engine::Engine;
+
+    #[cfg(unix)]
+    #[test]
+    fn failed_start_shows_stderr() {
+        let command = "sh -c 'echo Permission denied \\(publickey\\). >&2; exit 255'";
+        let err = Engine::new(command, Default::default())
+            .err()
+            .expect("engine should fail to start");
+        assert!(
+            format!("{:#}", err).contains("Permission denied (publickey)."),
+            "{:#}",
+            err
+        );
+    }
+}
@@ -0,0 +1,65 @@
+/// One row of Stockfish's classical evaluation trace
+#[derive(Debug, Clone)]
+pub struct EvalTerm {
+    /// Term name (e.g. "Mobility", "King safety")
+    pub name: String,
+    /// White's contribution as printed (middlegame and endgame)
+    pub white: String,
+    /// Black's contribution as printed
+    pub black: String,
+    /// Net contribution as printed
+    pub total: String,
+}
+
+/// Output of the engine's `eval` command
+#[derive(Debug, Clone, Default)]
+pub struct StaticEval {
+    /// Lines as printed by the engine, other than the summary
+    pub lines: Vec<String>,
+    /// Classical evaluation terms, if the engine printed a term table
+    pub terms: Vec<EvalTerm>,
+    /// Summary lines such as "NNUE evaluation" and "Final evaluation"
+    pub summary: Vec<String>,
+    /// Has the engine finished printing?
+    pub complete: bool,
+}
+
+impl StaticEval {
+    /// Add a line of engine output
+    pub fn push(&mut self, line: &str) {
+        let trimmed = line.trim();
+        if trimmed.contains("evaluation") && !trimmed.starts_with('|') {
+            self.summary
+                .push(trimmed.split_whitespace().collect::<Vec<_>>().join(" "));
+        } else {
+            self.lines.push(line.to_string());
+            if let Some(term) = Self::parse_term(trimmed) {
+                self.terms.push(term);
+            }
+        }
+
+        // "Final evaluation" is always last; engines without `eval` just complain
+        if trimmed.starts_with("Final evaluation") || trimmed.starts_with("Unknown command") {
+            self.complete = true;
+        }
+    }
+
+    /// Parse a "Mobility |  0.12  0.34 | ... " row of the term table
+    fn parse_term(line: &str) -> Option<EvalTerm> {
+        let cells: Vec<&str> = line.split('|').map(str::trim).collect();
+        if cells.len() != 4 {
+            return None;
+        }
+        let name = cells[0];
+        if name.is_empty() || name == "Term" || !name.chars().all(|c| c.is_alphabetic() || c == ' ')
+        {
+            return None;
+        }
+        Some(EvalTerm {
+            name: name.to_string(),
+            white: cells[1].to_string(),
+            black: cells[2].to_string(),
+            total: cells[3].to_string(),
+        })
+    }
+}
@@ -0,0 +1,95 @@
+use anyhow::{Context, Result};
+use std::collections::VecDeque;
+use std::io::{self, BufRead, BufReader, PipeReader, PipeWriter, Write};
+
+use super::EngineBackend;
+
+/// A scripted stand-in for a UCI engine. Commands are answered with lines set
+/// up in advance, so a run goes the same way every time and needs no engine
+/// binary.
+pub struct MockEngine {
+    name: String,
+    /// Replies to commands, by the start of the command
+    replies: Vec<(String, Vec<String>)>,
+    /// Output of the coming searches, in order
+    searches: VecDeque<Vec<String>>,
+    output: Option<PipeReader>,
+    /// Closed on `quit`, ending the output
+    writer: Option<PipeWriter>,
+}
+
+impl MockEngine {
+    /// A mock that answers the UCI handshake and nothing else
+    pub fn new(name: &str) -> Result<Self> {
+        let (output, writer) = io::pipe().context("Failed to create mock engine pipe")?;
+        Ok(Self {
+            name: name.to_string(),
+            replies: Vec::new(),
+            searches: VecDeque::new(),
+            output: Some(output),
+            writer: Some(writer),
+        })
+    }
+
+    /// Answer commands starting with `command` with some lines, e.g. the
+    /// trace of `eval`. Replies set here come before the built-in ones.
+    pub fn reply(mut self, command: &str, lines: &[&str]) -> Self {
+        let lines = lines.iter().map(|l| l.to_string()).collect();
+        self.replies.push((command.to_string(), lines));
+        self
+    }
+
+    /// Queue the output of a search: its `info` lines and the `bestmove`.
+    /// The last search queued answers every `go` after it.
+    pub fn search(mut self, lines: &[&str]) -> Self {
+        self.searches
+            .push_back(lines.iter().map(|l| l.to_string()).collect());
+        self
+    }
+
+    /// Lines to answer a command with
+    fn answer(&mut self, line: &str) -> Vec<String> {
+        if let Some((_, lines)) = self
+            .replies
+            .iter()
+            .find(|(command, _)| line.starts_with(command.as_str()))
+        {
+            return lines.clone();
+        }
+        match line.split_whitespace().next() {
+            Some("uci") => vec![format!("id name {}", self.name), "uciok".to_string()],
+            Some("isready") => vec!["readyok".to_string()],
+            Some("go") => {
+                if self.searches.len() > 1 {
+                    self.searches.pop_front().unwrap_or_default()
+                } else {
+                    self.searches
+                        .front()
+                        .cloned()
+                        .unwrap_or_else(|| vec!["bestmove (none)".to_string()])
+                }
+            }
+            _ => Vec::new(),
+        }
+    }
+}
+
+impl EngineBackend for MockEngine {
+    fn send_line(&mut self, line: &str) -> Result<()> {
+        if line == "quit" {
+            self.writer = None;
+            return Ok(());
+        }
+        let answer = self.answer(line);
+        let writer = self.writer.as_mut().context("Mock engine has quit")?;
+        for line in answer {
+            writeln!(writer, "{}", line).context("Failed to write mock engine output")?;
+        }
+        Ok(())
+    }
+
+    fn output(&mut self) -> Result<Box<dyn BufRead + Send>> {
+        let output = self.output.take().context("Engine output already taken")?;
+        Ok(Box::new(BufReader::new(output)))
+    }
+}
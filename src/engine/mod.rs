@@ -1,3 +1,11 @@
+mod backend;
+mod eval;
+#[cfg(test)]
+mod mock;
 mod uci;
 
+pub use backend::*;
+pub use eval::*;
+#[cfg(test)]
+pub use mock::*;
 pub use uci::*;
@@ -1,13 +1,19 @@
 #![allow(dead_code)]
 
-use anyhow::{bail, Context, Result};
-use std::io::{BufRead, BufReader, Write};
-use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use anyhow::{bail, Result};
+use shakmaty::{fen::Fen, CastlingMode};
+use std::collections::VecDeque;
+use std::io::BufRead;
 use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use vampirc_uci::{parse_one, UciInfoAttribute, UciMessage, UciOptionConfig};
 
+use super::{EngineBackend, ProcessBackend, TcpBackend};
+use crate::chess::Game;
+use crate::events::Waker;
+
 /// Analysis information from the engine
 #[derive(Debug, Clone, Default)]
 pub struct AnalysisInfo {
@@ -15,9 +21,9 @@ pub struct AnalysisInfo {
     pub depth: Option<u32>,
     /// Selective depth
     pub seldepth: Option<u32>,
-    /// Score in centipawns (positive = white advantage)
+    /// Score in centipawns (positive = side to move is better)
     pub score_cp: Option<i32>,
-    /// Mate in N moves (positive = white mates, negative = black mates)
+    /// Mate in N moves (positive = side to move mates)
     pub score_mate: Option<i32>,
     /// Nodes searched
     pub nodes: Option<u64>,
@@ -31,6 +37,14 @@ pub struct AnalysisInfo {
     pub pv: Vec<String>,
     /// Hash table usage (per mille)
     pub hashfull: Option<u32>,
+    /// Win/draw/loss probabilities in per mille, from the side to move's view
+    pub wdl: Option<(u32, u32, u32)>,
+    /// Tablebase positions probed
+    pub tbhits: Option<u64>,
+    /// Root move currently being searched (UCI notation)
+    pub currmove: Option<String>,
+    /// Index of the root move being searched (1-indexed)
+    pub currmovenumber: Option<u32>,
 }
 
 /// Best move result from engine
@@ -58,15 +72,83 @@ pub enum EngineEvent {
     },
     /// Engine options available
     Option(String),
+    /// Output line that isn't a UCI message (e.g. the `eval` trace)
+    Text(String),
+}
+
+/// Maximum number of lines kept in the UCI log
+const UCI_LOG_CAPACITY: usize = 2000;
+
+/// Direction of a logged UCI line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UciDirection {
+    /// Command sent to the engine
+    Sent,
+    /// Line received from the engine
+    Received,
+}
+
+/// A raw line exchanged with the engine
+#[derive(Debug, Clone)]
+pub struct UciLogEntry {
+    /// Time since the engine was started
+    pub elapsed: Duration,
+    pub direction: UciDirection,
+    pub line: String,
+}
+
+/// Ring buffer of raw UCI traffic, shared with the reader thread
+#[derive(Debug, Clone)]
+pub struct UciLog {
+    started: Instant,
+    entries: Arc<Mutex<VecDeque<UciLogEntry>>>,
+}
+
+impl UciLog {
+    fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            entries: Arc::new(Mutex::new(VecDeque::new())),
+        }
+    }
+
+    fn push(&self, direction: UciDirection, line: &str) {
+        let Ok(mut entries) = self.entries.lock() else {
+            return;
+        };
+        if entries.len() >= UCI_LOG_CAPACITY {
+            entries.pop_front();
+        }
+        entries.push_back(UciLogEntry {
+            elapsed: self.started.elapsed(),
+            direction,
+            line: line.to_string(),
+        });
+    }
+
+    /// Copy out the logged lines, oldest first
+    pub fn entries(&self) -> Vec<UciLogEntry> {
+        self.entries
+            .lock()
+            .map(|entries| entries.iter().cloned().collect())
+            .unwrap_or_default()
+    }
 }
 
 /// UCI Engine wrapper
 pub struct Engine {
-    process: Child,
-    stdin: ChildStdin,
+    backend: Box<dyn EngineBackend>,
     event_rx: Receiver<EngineEvent>,
     /// Is engine currently analyzing?
     is_analyzing: bool,
+    /// Is the current search a ponder search awaiting `ponderhit`?
+    is_pondering: bool,
+    /// Searches that were stopped but whose `bestmove` hasn't arrived yet
+    stale_searches: usize,
+    /// Is `UCI_Chess960` switched on?
+    chess960: bool,
+    /// Raw traffic with the engine
+    log: UciLog,
     /// Engine name
     pub name: Option<String>,
     /// Engine author
@@ -74,62 +156,85 @@ pub struct Engine {
 }
 
 impl Engine {
-    /// Start a new engine process
-    pub fn new(path: &str) -> Result<Self> {
-        let mut process = Command::new(path)
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::null())
-            .spawn()
-            .with_context(|| format!("Failed to start engine at: {}", path))?;
-
-        let stdin = process.stdin.take().context("Failed to get engine stdin")?;
-        let stdout = process
-            .stdout
-            .take()
-            .context("Failed to get engine stdout")?;
+    /// Start a new engine process, or connect to one at `tcp://host:port`.
+    /// The waker is woken whenever the engine has output.
+    pub fn new(path: &str, waker: Waker) -> Result<Self> {
+        tracing::info!("Starting engine {}", path);
+        match path.strip_prefix("tcp://") {
+            Some(address) => Self::with_backend(Box::new(TcpBackend::connect(address)?), waker),
+            None => Self::with_backend(Box::new(ProcessBackend::spawn(path)?), waker),
+        }
+    }
+
+    /// Talk UCI over a backend, e.g. a `MockEngine`
+    pub fn with_backend(mut backend: Box<dyn EngineBackend>, waker: Waker) -> Result<Self> {
+        let output = backend.output()?;
 
         // Create channel for engine events
         let (event_tx, event_rx) = mpsc::channel();
 
         // Spawn reader thread
+        let log = UciLog::new();
+        let reader_log = log.clone();
         thread::spawn(move || {
-            Self::read_output(stdout, event_tx);
+            Self::read_output(output, event_tx, reader_log, waker);
         });
 
         let mut engine = Self {
-            process,
-            stdin,
+            backend,
             event_rx,
             is_analyzing: false,
+            is_pondering: false,
+            stale_searches: 0,
+            chess960: false,
+            log,
             name: None,
             author: None,
         };
 
-        // Initialize UCI
-        engine.send_command("uci")?;
-
-        // Wait for uciok
-        engine.wait_for_ready(Duration::from_secs(5))?;
+        // Initialize UCI and wait for uciok. An engine that quits right away
+        // may already be gone when `uci` is sent.
+        let timeout = engine.backend.startup_timeout();
+        let started = engine
+            .send_command("uci")
+            .and_then(|_| engine.wait_for_ready(timeout));
+        if let Err(e) = started {
+            match engine.backend.error_output() {
+                Some(stderr) => bail!("{}: {}", e, stderr),
+                None => return Err(e),
+            }
+        }
 
         Ok(engine)
     }
 
     /// Read engine output in a separate thread
-    fn read_output(stdout: ChildStdout, tx: Sender<EngineEvent>) {
-        let reader = BufReader::new(stdout);
-
+    fn read_output(
+        reader: Box<dyn BufRead + Send>,
+        tx: Sender<EngineEvent>,
+        log: UciLog,
+        waker: Waker,
+    ) {
         for line in reader.lines() {
             let line = match line {
-                Ok(l) => l,
-                Err(_) => break,
+                // Engines served over a network may end lines with \r\n
+                Ok(l) => l.trim_end_matches('\r').to_string(),
+                Err(e) => {
+                    tracing::warn!("Failed to read engine output: {}", e);
+                    break;
+                }
             };
 
             if line.is_empty() {
                 continue;
             }
+            log.push(UciDirection::Received, &line);
+            tracing::trace!("< {}", line);
 
-            let msg = parse_one(&line);
+            // vampirc-uci doesn't know `wdl` and would swallow the rest of the
+            // line, so pull it out before parsing
+            let (stripped, wdl) = split_wdl(&line);
+            let msg = parse_one(&stripped);
 
             match msg {
                 UciMessage::Id { name, author } => {
@@ -142,7 +247,8 @@ impl Engine {
                     let _ = tx.send(EngineEvent::Ready);
                 }
                 UciMessage::Info(attrs) => {
-                    let info = Self::parse_info(attrs);
+                    let mut info = Self::parse_info(attrs);
+                    info.wdl = wdl;
                     let _ = tx.send(EngineEvent::Info(info));
                 }
                 UciMessage::BestMove { best_move, ponder } => {
@@ -161,9 +267,24 @@ impl Engine {
                     };
                     let _ = tx.send(EngineEvent::Option(opt_name));
                 }
+                // `bestmove (none)` after searching a mate or stalemate still
+                // ends the search
+                UciMessage::Unknown(..) if line.starts_with("bestmove") => {
+                    let best_move = line.split_whitespace().nth(1).unwrap_or("(none)");
+                    let _ = tx.send(EngineEvent::BestMove(BestMove {
+                        best_move: best_move.to_string(),
+                        ponder: None,
+                    }));
+                }
+                UciMessage::Unknown(..) => {
+                    tracing::debug!("Unparsed engine output: {}", line);
+                    let _ = tx.send(EngineEvent::Text(line));
+                }
                 _ => {}
             }
+            waker.wake();
         }
+        tracing::info!("Engine output closed");
     }
 
     /// Parse info attributes into AnalysisInfo
@@ -175,7 +296,7 @@ impl Engine {
                 UciInfoAttribute::Depth(d) => info.depth = Some(d as u32),
                 UciInfoAttribute::SelDepth(d) => info.seldepth = Some(d as u32),
                 UciInfoAttribute::Score { cp, mate, .. } => {
-                    info.score_cp = cp.map(|c| c as i32);
+                    info.score_cp = cp;
                     info.score_mate = mate.map(|m| m as i32);
                 }
                 UciInfoAttribute::Nodes(n) => info.nodes = Some(n),
@@ -186,6 +307,13 @@ impl Engine {
                     info.pv = moves.iter().map(|m| m.to_string()).collect();
                 }
                 UciInfoAttribute::HashFull(h) => info.hashfull = Some(h as u32),
+                UciInfoAttribute::TbHits(n) => info.tbhits = Some(n),
+                UciInfoAttribute::CurrMove(m) => info.currmove = Some(m.to_string()),
+                // Not modelled by vampirc-uci; the value runs to the end of the line
+                UciInfoAttribute::Any(name, value) if name == "currmovenumber" => {
+                    info.currmovenumber =
+                        value.split_whitespace().next().and_then(|n| n.parse().ok());
+                }
                 _ => {}
             }
         }
@@ -195,9 +323,9 @@ impl Engine {
 
     /// Send a raw command to the engine
     pub fn send_command(&mut self, cmd: &str) -> Result<()> {
-        writeln!(self.stdin, "{}", cmd).context("Failed to write to engine")?;
-        self.stdin.flush().context("Failed to flush engine stdin")?;
-        Ok(())
+        self.log.push(UciDirection::Sent, cmd);
+        tracing::trace!("> {}", cmd);
+        self.backend.send_line(cmd)
     }
 
     /// Wait for the engine to be ready
@@ -208,8 +336,9 @@ impl Engine {
             match self.event_rx.recv_timeout(Duration::from_millis(100)) {
                 Ok(EngineEvent::Ready) => return Ok(()),
                 Ok(EngineEvent::Id { name, author }) => {
-                    self.name = name;
-                    self.author = author;
+                    // Name and author arrive on separate lines
+                    self.name = name.or(self.name.take());
+                    self.author = author.or(self.author.take());
                 }
                 Ok(_) => continue,
                 Err(mpsc::RecvTimeoutError::Timeout) => continue,
@@ -227,8 +356,27 @@ impl Engine {
         self.send_command(&format!("setoption name {} value {}", name, value))
     }
 
+    /// Lower the engine's CPU priority so it yields to other programs
+    pub fn lower_priority(&mut self) -> Result<()> {
+        self.backend.lower_priority()
+    }
+
+    /// Clear the engine's hash table (the `Clear Hash` button option)
+    pub fn clear_hash(&mut self) -> Result<()> {
+        self.send_command("setoption name Clear Hash")
+    }
+
     /// Set up the position from FEN or startpos
     pub fn set_position(&mut self, fen: Option<&str>, moves: &[String]) -> Result<()> {
+        // Chess960 castling rights need the engine's Chess960 mode
+        let chess960 = fen
+            .and_then(|f| f.parse::<Fen>().ok())
+            .is_some_and(|f| CastlingMode::detect(f.as_setup()) == CastlingMode::Chess960);
+        if chess960 != self.chess960 {
+            self.set_option("UCI_Chess960", &chess960.to_string())?;
+            self.chess960 = chess960;
+        }
+
         let pos_str = match fen {
             Some(f) => format!("position fen {}", f),
             None => "position startpos".to_string(),
@@ -243,6 +391,13 @@ impl Engine {
         self.send_command(&cmd)
     }
 
+    /// Set up a game's current position as its start and the moves played
+    /// since, rather than a bare FEN, so the engine can tell repetitions
+    pub fn set_game(&mut self, game: &Game) -> Result<()> {
+        let (fen, moves) = game.uci_position();
+        self.set_position(fen.as_deref(), &moves)
+    }
+
     /// Start analysis with infinite time
     pub fn go_infinite(&mut self) -> Result<()> {
         self.is_analyzing = true;
@@ -255,34 +410,116 @@ impl Engine {
         self.send_command(&format!("go depth {}", depth))
     }
 
+    /// Start analysis with depth limit, considering only the given moves
+    pub fn go_depth_searchmoves(&mut self, depth: u32, moves: &[String]) -> Result<()> {
+        self.is_analyzing = true;
+        self.send_command(&format!(
+            "go depth {} searchmoves {}",
+            depth,
+            moves.join(" ")
+        ))
+    }
+
+    /// Search for a fixed time in milliseconds
+    pub fn go_movetime(&mut self, ms: u64) -> Result<()> {
+        self.is_analyzing = true;
+        self.send_command(&format!("go movetime {}", ms))
+    }
+
+    /// Search with game clocks (all times in milliseconds)
+    pub fn go_clock(&mut self, wtime: u64, btime: u64, winc: u64, binc: u64) -> Result<()> {
+        self.is_analyzing = true;
+        self.send_command(&format!(
+            "go wtime {} btime {} winc {} binc {}",
+            wtime, btime, winc, binc
+        ))
+    }
+
+    /// Ponder on the opponent's time with a fixed move time for the real search
+    pub fn go_ponder_movetime(&mut self, ms: u64) -> Result<()> {
+        self.is_analyzing = true;
+        self.is_pondering = true;
+        self.send_command(&format!("go ponder movetime {}", ms))
+    }
+
+    /// Ponder on the opponent's time with game clocks (all times in milliseconds)
+    pub fn go_ponder_clock(&mut self, wtime: u64, btime: u64, winc: u64, binc: u64) -> Result<()> {
+        self.is_analyzing = true;
+        self.is_pondering = true;
+        self.send_command(&format!(
+            "go ponder wtime {} btime {} winc {} binc {}",
+            wtime, btime, winc, binc
+        ))
+    }
+
+    /// The expected move was played: turn the ponder search into a normal one
+    pub fn ponderhit(&mut self) -> Result<()> {
+        if self.is_pondering {
+            self.is_pondering = false;
+            self.send_command("ponderhit")?;
+        }
+        Ok(())
+    }
+
     /// Stop analysis
     pub fn stop(&mut self) -> Result<()> {
         if self.is_analyzing {
             self.send_command("stop")?;
             self.is_analyzing = false;
+            self.is_pondering = false;
+            self.stale_searches += 1;
         }
         Ok(())
     }
 
+    /// Get the raw UCI traffic log
+    pub fn log(&self) -> &UciLog {
+        &self.log
+    }
+
     /// Check if currently analyzing
     pub fn is_analyzing(&self) -> bool {
         self.is_analyzing
     }
 
+    /// Check if a ponder search is running
+    pub fn is_pondering(&self) -> bool {
+        self.is_pondering
+    }
+
     /// Try to receive an event (non-blocking)
+    ///
+    /// Output belonging to a search that was stopped is discarded, so callers
+    /// only ever see info lines for the position they last asked about.
     pub fn try_recv(&mut self) -> Option<EngineEvent> {
-        match self.event_rx.try_recv() {
-            Ok(event) => {
-                // Update analyzing state on bestmove
-                if matches!(event, EngineEvent::BestMove(_)) {
-                    self.is_analyzing = false;
+        loop {
+            let event = self.event_rx.try_recv().ok()?;
+
+            if self.stale_searches > 0 {
+                match event {
+                    EngineEvent::BestMove(_) => {
+                        self.stale_searches -= 1;
+                        continue;
+                    }
+                    EngineEvent::Info(_) => continue,
+                    _ => return Some(event),
                 }
-                Some(event)
             }
-            Err(_) => None,
+
+            // Update analyzing state on bestmove
+            if matches!(event, EngineEvent::BestMove(_)) {
+                self.is_analyzing = false;
+                self.is_pondering = false;
+            }
+            return Some(event);
         }
     }
 
+    /// Ask for the static evaluation trace of the current position
+    pub fn static_eval(&mut self) -> Result<()> {
+        self.send_command("eval")
+    }
+
     /// Send new game notification
     pub fn new_game(&mut self) -> Result<()> {
         self.send_command("ucinewgame")
@@ -291,7 +528,7 @@ impl Engine {
     /// Quit the engine
     pub fn quit(&mut self) -> Result<()> {
         self.send_command("quit")?;
-        let _ = self.process.wait();
+        self.backend.wait();
         Ok(())
     }
 }
@@ -302,6 +539,34 @@ impl Drop for Engine {
     }
 }
 
+/// Remove a `wdl <w> <d> <l>` attribute from an info line, returning the
+/// remaining line and the parsed probabilities
+fn split_wdl(line: &str) -> (String, Option<(u32, u32, u32)>) {
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    let Some(idx) = tokens.iter().position(|t| *t == "wdl") else {
+        return (line.to_string(), None);
+    };
+    let values: Vec<u32> = tokens[idx + 1..]
+        .iter()
+        .take(3)
+        .map_while(|t| t.parse().ok())
+        .collect();
+    let wdl = match values[..] {
+        [w, d, l] => Some((w, d, l)),
+        _ => None,
+    };
+
+    let mut rest = tokens[..idx].to_vec();
+    rest.extend_from_slice(&tokens[(idx + 1 + values.len()).min(tokens.len())..]);
+    (rest.join(" "), wdl)
+}
+
+/// Format win/draw/loss per mille values as percentages
+pub fn format_wdl((win, draw, loss): (u32, u32, u32)) -> String {
+    let pct = |v: u32| (v as f64 / 10.0).round() as u32;
+    format!("W{}% D{}% L{}%", pct(win), pct(draw), pct(loss))
+}
+
 /// Format score for display
 pub fn format_score(cp: Option<i32>, mate: Option<i32>) -> String {
     if let Some(m) = mate {
@@ -322,6 +587,30 @@ pub fn format_score(cp: Option<i32>, mate: Option<i32>) -> String {
     }
 }
 
+/// Collapse a score into centipawns, mapping mates to large values
+/// (closer mates score higher) so scores can be compared and subtracted
+pub fn score_to_cp(cp: Option<i32>, mate: Option<i32>) -> Option<i32> {
+    match (cp, mate) {
+        (_, Some(m)) if m > 0 => Some(MATE_SCORE - m),
+        (_, Some(m)) => Some(-MATE_SCORE - m),
+        (Some(c), None) => Some(c),
+        (None, None) => None,
+    }
+}
+
+/// Centipawn value used for forced mates in [`score_to_cp`]
+pub const MATE_SCORE: i32 = 10_000;
+
+/// Format a score from [`score_to_cp`], showing forced mates as such
+pub fn format_cp(cp: i32) -> String {
+    if cp.abs() >= MATE_SCORE / 2 {
+        let moves = MATE_SCORE - cp.abs();
+        format_score(None, Some(if cp > 0 { moves } else { -moves }))
+    } else {
+        format_score(Some(cp), None)
+    }
+}
+
 /// Format nodes count for display
 pub fn format_nodes(nodes: u64) -> String {
     if nodes >= 1_000_000_000 {
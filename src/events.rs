@@ -0,0 +1,96 @@
+//! Event aggregation for the main loop: terminal input and wake-ups from
+//! background threads arrive on one channel, so the screen is only redrawn
+//! when something happened
+
+use crossterm::event::{self, Event};
+use std::io::BufRead;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+use crate::control::ControlRequest;
+
+/// Something the main loop should react to
+#[derive(Debug)]
+pub enum AppEvent {
+    /// Input from the terminal (keys, focus changes, resizes)
+    Terminal(Event),
+    /// A background thread has news, e.g. engine output
+    Wake,
+    /// A command from the control socket
+    Control(ControlRequest),
+    /// A position read from stdin with `--stdin-fen`; None once stdin closes
+    StdinFen(Option<String>),
+}
+
+/// Lets a background thread wake the main loop. The default one is
+/// detached and does nothing.
+#[derive(Debug, Clone, Default)]
+pub struct Waker(Option<Sender<AppEvent>>);
+
+impl Waker {
+    pub fn wake(&self) {
+        self.send(AppEvent::Wake);
+    }
+
+    /// Hand the main loop an event
+    pub fn send(&self, event: AppEvent) {
+        if let Some(ref tx) = self.0 {
+            let _ = tx.send(event);
+        }
+    }
+}
+
+/// Incoming events, with a thread reading the terminal
+pub struct Events {
+    tx: Sender<AppEvent>,
+    rx: Receiver<AppEvent>,
+}
+
+impl Events {
+    pub fn new() -> Self {
+        let (tx, rx) = mpsc::channel();
+        let input_tx = tx.clone();
+        thread::spawn(move || {
+            while let Ok(event) = event::read() {
+                if input_tx.send(AppEvent::Terminal(event)).is_err() {
+                    break;
+                }
+            }
+        });
+        Self { tx, rx }
+    }
+
+    /// Waker for background threads
+    pub fn waker(&self) -> Waker {
+        Waker(Some(self.tx.clone()))
+    }
+
+    /// Wait for the next event, or None once the timeout passes
+    pub fn next(&self, timeout: Duration) -> Option<AppEvent> {
+        self.rx.recv_timeout(timeout).ok()
+    }
+
+    /// Take an event that is already waiting
+    pub fn try_next(&self) -> Option<AppEvent> {
+        self.rx.try_recv().ok()
+    }
+}
+
+/// Read FEN strings from stdin, one per line, on a background thread. Keys
+/// still come from the terminal, which crossterm opens on its own when stdin
+/// is a pipe.
+pub fn read_stdin_fens(waker: Waker) {
+    thread::spawn(move || {
+        for line in std::io::stdin().lock().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            let fen = line.trim();
+            if !fen.is_empty() {
+                waker.send(AppEvent::StdinFen(Some(fen.to_string())));
+            }
+        }
+        waker.send(AppEvent::StdinFen(None));
+    });
+}
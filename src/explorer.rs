@@ -0,0 +1,200 @@
+//! Opening explorer: the moves played from a position in a database of
+//! games, with how often each was played and how it scored. The database is
+//! the loaded games or, online, the Lichess masters database.
+
+use shakmaty::san::San;
+use shakmaty::{Chess, Color, Move, Position};
+use std::collections::{HashMap, HashSet};
+
+use crate::chess::Game;
+
+/// Results of a set of games
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Results {
+    pub white: usize,
+    pub draws: usize,
+    pub black: usize,
+    /// Games without a result
+    pub unfinished: usize,
+}
+
+impl Results {
+    pub fn new(white: usize, draws: usize, black: usize) -> Self {
+        Self {
+            white,
+            draws,
+            black,
+            unfinished: 0,
+        }
+    }
+
+    fn add(&mut self, result: Option<&str>) {
+        match result {
+            Some("1-0") => self.white += 1,
+            Some("0-1") => self.black += 1,
+            Some("1/2-1/2") => self.draws += 1,
+            _ => self.unfinished += 1,
+        }
+    }
+
+    pub fn games(&self) -> usize {
+        self.white + self.draws + self.black + self.unfinished
+    }
+
+    /// Wins and losses of a side
+    pub fn wins(&self, side: Color) -> usize {
+        side.fold_wb(self.white, self.black)
+    }
+
+    pub fn losses(&self, side: Color) -> usize {
+        side.fold_wb(self.black, self.white)
+    }
+
+    /// Score of a side in percent over the finished games, or None when
+    /// none finished
+    pub fn score(&self, side: Color) -> Option<u32> {
+        let finished = self.white + self.draws + self.black;
+        let points = 2 * self.wins(side) + self.draws;
+        (finished > 0).then(|| (points * 50 / finished) as u32)
+    }
+}
+
+/// Where the explorer's games come from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExplorerSource {
+    /// The games of the loaded PGN
+    Games,
+    /// Master games, from the Lichess opening explorer
+    Masters,
+}
+
+impl ExplorerSource {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "games" | "mine" => Some(Self::Games),
+            "masters" => Some(Self::Masters),
+            _ => None,
+        }
+    }
+
+    pub fn other(self) -> Self {
+        match self {
+            Self::Games => Self::Masters,
+            Self::Masters => Self::Games,
+        }
+    }
+}
+
+/// A move played from the explored position
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExplorerMove {
+    pub san: String,
+    pub uci: String,
+    pub results: Results,
+}
+
+/// A notable game that reached the explored position
+#[derive(Debug, Clone, PartialEq)]
+pub struct TopGame {
+    /// Players with their ratings
+    pub white: String,
+    pub black: String,
+    pub result: &'static str,
+    /// Move the game went on with, in SAN
+    pub san: String,
+}
+
+/// What a database knows about a position
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PositionStats {
+    /// Results of every game that reached the position
+    pub results: Results,
+    /// Moves played from it, the most played first
+    pub moves: Vec<ExplorerMove>,
+    /// Example games, best rated first (online databases only)
+    pub top_games: Vec<TopGame>,
+}
+
+/// Games reaching a position and the moves they went on with
+#[derive(Debug, Default)]
+struct Node {
+    results: Results,
+    moves: Vec<(Move, Results)>,
+}
+
+/// The positions of the mainlines of a set of games, for looking up which
+/// moves were played from each
+#[derive(Debug, Default)]
+pub struct OpeningTree {
+    nodes: HashMap<u64, Node>,
+    games: usize,
+}
+
+impl OpeningTree {
+    pub fn new(games: &[Game]) -> Self {
+        let mut tree = Self {
+            nodes: HashMap::new(),
+            games: games.len(),
+        };
+        for game in games {
+            tree.add(game);
+        }
+        tree
+    }
+
+    /// Number of games in the tree
+    pub fn games(&self) -> usize {
+        self.games
+    }
+
+    /// Count a game once at every position of its mainline, with the move
+    /// that followed (a position the game came back to counts its first move)
+    fn add(&mut self, game: &Game) {
+        let result = game.header("Result");
+        let mut seen = HashSet::new();
+        let mut position = game.initial_position().clone();
+        let mut moves = game.moves().iter();
+        loop {
+            let next = moves.next();
+            let key = Game::position_key(&position);
+            if seen.insert(key) {
+                let node = self.nodes.entry(key).or_default();
+                node.results.add(result);
+                if let Some(m) = next {
+                    match node.moves.iter_mut().find(|(played, _)| played == m) {
+                        Some((_, results)) => results.add(result),
+                        None => {
+                            let mut results = Results::default();
+                            results.add(result);
+                            node.moves.push((m.clone(), results));
+                        }
+                    }
+                }
+            }
+            let Some(m) = next else {
+                break;
+            };
+            position.play_unchecked(m);
+        }
+    }
+
+    /// The games that reached a position, None when no game did
+    pub fn lookup(&self, position: &Chess) -> Option<PositionStats> {
+        let node = self.nodes.get(&Game::position_key(position))?;
+        let mut moves: Vec<ExplorerMove> = node
+            .moves
+            .iter()
+            .map(|(m, results)| ExplorerMove {
+                san: San::from_move(position, m).to_string(),
+                uci: m.to_uci(position.castles().mode()).to_string(),
+                results: *results,
+            })
+            .collect();
+        moves.sort_by_key(|m| std::cmp::Reverse(m.results.games()));
+        Some(PositionStats {
+            results: node.results,
+            moves,
+            top_games: Vec::new(),
+        })
+    }
+}
@@ -0,0 +1,161 @@
+//! Files picked from inside the TUI: paths typed in the input bar, with `~`
+//! expansion and Tab completion, and the PGN files of the game browser
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use crate::chess::first_headers;
+
+/// Replace a leading `~` with the home directory
+pub fn expand_home(path: &str) -> String {
+    let rest = match path.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => rest,
+        _ => return path.to_string(),
+    };
+    match dirs::home_dir() {
+        Some(home) => format!("{}{}", home.to_string_lossy(), rest),
+        None => path.to_string(),
+    }
+}
+
+/// Paths a partly typed path could be completed to, sorted, with a `/`
+/// after directories. The typed directory part is kept as typed, `~`
+/// included. Hidden files are only offered once a `.` is typed.
+pub fn complete_path(partial: &str) -> Vec<String> {
+    let (dir, prefix) = match partial.rfind('/') {
+        Some(i) => partial.split_at(i + 1),
+        None => ("", partial),
+    };
+    let search = match dir {
+        "" => ".".to_string(),
+        dir => expand_home(dir),
+    };
+    let Ok(entries) = fs::read_dir(&search) else {
+        return Vec::new();
+    };
+
+    let mut matches: Vec<String> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let is_dir = Path::new(&search).join(&name).is_dir();
+            Some(format!("{}{}{}", dir, name, if is_dir { "/" } else { "" }))
+        })
+        .collect();
+    matches.sort();
+    matches
+}
+
+/// Longest start shared by all the strings
+pub fn common_prefix(strings: &[String]) -> String {
+    let Some(first) = strings.first() else {
+        return String::new();
+    };
+    let mut len = first.len();
+    for s in &strings[1..] {
+        len = first
+            .char_indices()
+            .zip(s.chars())
+            .take_while(|((_, a), b)| a == b)
+            .last()
+            .map_or(0, |((i, a), _)| i + a.len_utf8())
+            .min(len);
+    }
+    first[..len].to_string()
+}
+
+/// A PGN file found by the game browser
+#[derive(Debug, Clone, PartialEq)]
+pub struct PgnFile {
+    pub path: PathBuf,
+    /// Path relative to the browsed directory
+    pub name: String,
+    /// Players and result of the first game
+    pub preview: String,
+}
+
+/// Bytes read from each file for its preview
+const PREVIEW_BYTES: u64 = 16 * 1024;
+
+/// Most files listed, so a huge directory tree doesn't stall the screen
+const MAX_PGN_FILES: usize = 1000;
+
+/// Find the PGN files in a directory and its sub-directories, sorted by path
+pub fn pgn_files(dir: &Path) -> Result<Vec<PgnFile>> {
+    let mut paths = Vec::new();
+    let mut dirs = vec![dir.to_path_buf()];
+    while let Some(current) = dirs.pop() {
+        if paths.len() >= MAX_PGN_FILES {
+            break;
+        }
+        let entries = fs::read_dir(&current)
+            .with_context(|| format!("Failed to read directory {}", current.display()))?;
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let hidden = entry.file_name().to_string_lossy().starts_with('.');
+            if hidden {
+                continue;
+            }
+            if path.is_dir() {
+                dirs.push(path);
+            } else if path
+                .extension()
+                .is_some_and(|e| e.eq_ignore_ascii_case("pgn"))
+            {
+                paths.push(path);
+            }
+            if paths.len() >= MAX_PGN_FILES {
+                break;
+            }
+        }
+    }
+    paths.sort();
+
+    Ok(paths
+        .into_iter()
+        .map(|path| {
+            let name = path
+                .strip_prefix(dir)
+                .unwrap_or(&path)
+                .to_string_lossy()
+                .to_string();
+            let preview = pgn_preview(&path);
+            PgnFile {
+                path,
+                name,
+                preview,
+            }
+        })
+        .collect())
+}
+
+/// Describe the first game of a PGN file from its headers
+fn pgn_preview(path: &Path) -> String {
+    let mut start = Vec::new();
+    let read = fs::File::open(path).and_then(|f| f.take(PREVIEW_BYTES).read_to_end(&mut start));
+    if read.is_err() {
+        return "unreadable".to_string();
+    }
+    let headers = first_headers(&String::from_utf8_lossy(&start));
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|(k, _)| k == name)
+            .map(|(_, v)| v.as_str())
+            .filter(|v| !v.is_empty() && *v != "?")
+    };
+    let players = match (header("White"), header("Black")) {
+        (None, None) => "no players".to_string(),
+        (white, black) => format!("{} - {}", white.unwrap_or("?"), black.unwrap_or("?")),
+    };
+    let mut preview = format!("{}, {}", players, header("Result").unwrap_or("*"));
+    if let Some(event) = header("Event") {
+        preview.push_str(&format!(" ({})", event));
+    }
+    preview
+}
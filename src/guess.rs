@@ -0,0 +1,104 @@
+use shakmaty::Color;
+
+use crate::review::win_percent;
+
+/// Search depth used to score guesses that differ from the game move
+pub const GUESS_DEPTH: u32 = 14;
+/// Points for guessing the game move exactly
+pub const MATCH_POINTS: u32 = 10;
+/// Points for a different move the engine rates as highly as the game move;
+/// every 2.5 percentage points of winning chances lost costs one of them
+const GOOD_POINTS: f64 = 8.0;
+
+/// Points for a guess that isn't the game move, from both moves' scores
+/// (centipawns, side to move's view)
+pub fn guess_points(guess_cp: i32, actual_cp: i32) -> u32 {
+    let loss = (win_percent(actual_cp) - win_percent(guess_cp)).max(0.0);
+    (GOOD_POINTS - loss / 2.5).round().max(0.0) as u32
+}
+
+/// Outcome of one guess
+#[derive(Debug, Clone)]
+pub struct GuessResult {
+    /// Guessed move in SAN
+    pub guess: String,
+    /// Game move in numbered SAN, e.g. "12... Nc6"
+    pub actual: String,
+    pub matched: bool,
+    /// Engine scores of the guess and the game move, when they differ
+    pub scores: Option<(i32, i32)>,
+    pub points: u32,
+}
+
+/// Where the current guess is at
+#[derive(Debug, Clone, PartialEq)]
+pub enum GuessPhase {
+    /// Waiting for the user's guess
+    Asking,
+    /// The engine is scoring the game move, then the guess
+    Checking {
+        /// Guessed move (UCI and SAN)
+        guess: (String, String),
+        /// Game move (UCI and SAN)
+        actual: (String, String),
+        /// Score of the game move once known
+        actual_cp: Option<i32>,
+    },
+}
+
+/// Guess-the-move session over the loaded game
+#[derive(Debug, Clone)]
+pub struct GuessSession {
+    /// Side whose moves are guessed
+    pub side: Color,
+    /// FEN of the position being guessed, to notice when the game is changed
+    pub fen: String,
+    pub phase: GuessPhase,
+    /// The last scored guess
+    pub last: Option<GuessResult>,
+    /// The opponent's reply played since the last guess, in SAN
+    pub reply: Option<String>,
+    pub points: u32,
+    pub guesses: usize,
+    pub matched: usize,
+    /// Latest score of the running search
+    pub search: Option<i32>,
+}
+
+impl GuessSession {
+    pub fn new(side: Color) -> Self {
+        Self {
+            side,
+            fen: String::new(),
+            phase: GuessPhase::Asking,
+            last: None,
+            reply: None,
+            points: 0,
+            guesses: 0,
+            matched: 0,
+            search: None,
+        }
+    }
+
+    /// Check if the engine is busy scoring a guess
+    pub fn is_checking(&self) -> bool {
+        matches!(self.phase, GuessPhase::Checking { .. })
+    }
+
+    /// Highest score possible for the guesses made so far
+    pub fn max_points(&self) -> u32 {
+        self.guesses as u32 * MATCH_POINTS
+    }
+
+    /// Record a scored guess
+    pub fn record(&mut self, result: GuessResult) {
+        self.guesses += 1;
+        if result.matched {
+            self.matched += 1;
+        }
+        self.points += result.points;
+        self.last = Some(result);
+        self.phase = GuessPhase::Asking;
+        self.search = None;
+    }
+}
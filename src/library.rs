@@ -0,0 +1,115 @@
+/// An instructive position in the built-in library
+#[derive(Debug, Clone, PartialEq)]
+pub struct LibraryEntry {
+    pub category: &'static str,
+    pub name: &'static str,
+    pub fen: &'static str,
+    /// The task, or what the position shows
+    pub note: &'static str,
+}
+
+/// Built-in positions, grouped by category
+pub const LIBRARY: &[LibraryEntry] = &[
+    LibraryEntry {
+        category: "Endgames",
+        name: "King and pawn: the opposition",
+        fen: "4k3/8/4K3/4P3/8/8/8/8 w - - 0 1",
+        note: "White wins whoever is to move",
+    },
+    LibraryEntry {
+        category: "Endgames",
+        name: "Lucena position",
+        fen: "1K1k4/1P6/8/8/8/8/r7/2R5 w - - 0 1",
+        note: "White to play and win by building a bridge",
+    },
+    LibraryEntry {
+        category: "Endgames",
+        name: "Philidor position",
+        fen: "4k3/8/r7/4PK2/8/8/8/1R6 b - - 0 1",
+        note: "Black to play and draw: hold the third rank",
+    },
+    LibraryEntry {
+        category: "Endgames",
+        name: "Rook pawn and wrong bishop",
+        fen: "7k/8/6K1/7P/8/8/4B3/8 b - - 0 1",
+        note: "Black draws by staying in the corner",
+    },
+    LibraryEntry {
+        category: "Endgames",
+        name: "Bishop and knight mate",
+        fen: "8/8/8/4k3/8/8/8/1NB1K3 w - - 0 1",
+        note: "White to play and mate in the corner of the bishop's colour",
+    },
+    LibraryEntry {
+        category: "Endgames",
+        name: "Pawn breakthrough",
+        fen: "7k/ppp5/8/PPP5/8/8/8/7K w - - 0 1",
+        note: "White to play and win: 1. b6!",
+    },
+    LibraryEntry {
+        category: "Studies",
+        name: "Reti, 1921",
+        fen: "7K/8/k1P5/7p/8/8/8/8 w - - 0 1",
+        note: "White to play and draw",
+    },
+    LibraryEntry {
+        category: "Studies",
+        name: "Saavedra, 1895",
+        fen: "8/8/1KP5/3r4/8/8/8/k7 w - - 0 1",
+        note: "White to play and win",
+    },
+    LibraryEntry {
+        category: "Studies",
+        name: "Lasker and Reichhelm, 1901",
+        fen: "8/k7/3p4/p2P1p2/P2P1P2/8/8/K7 w - - 0 1",
+        note: "White to play and win with corresponding squares",
+    },
+    LibraryEntry {
+        category: "Tactics",
+        name: "Legal's mate",
+        fen: "rn1qkbnr/ppp2p1p/3p2p1/4N3/2B1P3/2N5/PPPP1PPP/R1BbK2R w KQkq - 0 6",
+        note: "White to play and mate in 2",
+    },
+    LibraryEntry {
+        category: "Tactics",
+        name: "Reti vs Tartakower, Vienna 1910",
+        fen: "rnb1kb1r/pp3ppp/2p5/4q3/4n3/3Q4/PPPB1PPP/2KR1BNR w kq - 0 9",
+        note: "White to play and mate in 3",
+    },
+    LibraryEntry {
+        category: "Tactics",
+        name: "Morphy's Opera Game, Paris 1858",
+        fen: "4kb1r/p2n1ppp/4q3/4p1B1/4P3/1Q6/PPP2PPP/2KR4 w k - 0 16",
+        note: "White to play and mate in 2",
+    },
+    LibraryEntry {
+        category: "Tactics",
+        name: "The Evergreen Game, Berlin 1852",
+        fen: "1r2k1r1/pbppnp1p/1bn2P2/8/Q7/B1PB1q2/P4PPP/3RR1K1 w - - 0 20",
+        note: "White to play and mate in 5",
+    },
+    LibraryEntry {
+        category: "Tactics",
+        name: "The Immortal Game, London 1851",
+        fen: "rnb1k1nr/p2p1ppp/8/1pbN1N1P/4PBP1/3P1Q2/PqP5/R4KR1 w kq - 0 18",
+        note: "White to play: both rooks can go",
+    },
+    LibraryEntry {
+        category: "Test positions",
+        name: "Kiwipete",
+        fen: "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+        note: "Move generator test: castling, en passant and pins",
+    },
+    LibraryEntry {
+        category: "Test positions",
+        name: "Rook endgame perft",
+        fen: "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+        note: "Move generator test: discovered checks and en passant",
+    },
+    LibraryEntry {
+        category: "Test positions",
+        name: "Bratko-Kopec 1",
+        fen: "1k1r4/pp1b1R2/3q2pp/4p3/2B5/4Q3/PPP2B2/2K5 b - - 0 1",
+        note: "Black to play: the best move is Qd1+",
+    },
+];
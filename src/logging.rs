@@ -0,0 +1,43 @@
+//! Log file in the state directory. The screen belongs to the UI, so
+//! warnings and engine traffic go to `logs/lazychess.<date>.log` instead,
+//! rotated daily.
+
+use anyhow::{anyhow, Context, Result};
+use std::fs;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::EnvFilter;
+
+use crate::config::LogConfig;
+use crate::state;
+
+/// Daily log files kept before the oldest is deleted
+const MAX_LOG_FILES: usize = 7;
+
+/// Start logging at the configured level. Lines are written as they're
+/// logged, so a panic's message makes it into the file before the exit.
+pub fn init(config: &LogConfig) -> Result<()> {
+    if config.level == "off" {
+        return Ok(());
+    }
+    let filter = EnvFilter::try_new(&config.level)
+        .map_err(|e| anyhow!("Invalid log level {:?}: {}", config.level, e))?;
+    let Some(dir) = state::dir().map(|p| p.join("logs")) else {
+        return Ok(());
+    };
+    fs::create_dir_all(&dir)
+        .with_context(|| format!("Failed to create log directory {:?}", dir))?;
+
+    let appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix("lazychess")
+        .filename_suffix("log")
+        .max_log_files(MAX_LOG_FILES)
+        .build(&dir)
+        .with_context(|| format!("Failed to open log file in {:?}", dir))?;
+    tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(appender)
+        .with_ansi(false)
+        .init();
+    Ok(())
+}
@@ -1,22 +1,51 @@
 mod app;
 mod chess;
 mod config;
+mod control;
+mod drill;
 mod engine;
+mod events;
+mod explorer;
+mod files;
+mod guess;
+mod library;
+mod logging;
+mod mates;
+mod notify;
+mod online;
+mod openings;
+mod play;
+mod power;
+mod puzzle;
+mod quiz;
+mod repertoire;
+mod review;
+mod script;
+mod sound;
+mod state;
+mod transpositions;
 mod ui;
 
 use anyhow::Result;
 use clap::Parser;
 use crossterm::{
-    event::{self, DisableMouseCapture, EnableMouseCapture, Event},
+    cursor::Show,
+    event::{
+        DisableFocusChange, DisableMouseCapture, EnableFocusChange, EnableMouseCapture, Event,
+    },
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
 use ratatui::{backend::CrosstermBackend, Terminal};
-use std::io;
-use std::time::Duration;
+use std::io::{self, IsTerminal, Write};
+use std::time::{Duration, Instant};
 
 use app::App;
 use config::Config;
+use events::{AppEvent, Events};
+
+/// Shortest time between redraws
+const FRAME_TIME: Duration = Duration::from_millis(30);
 
 #[derive(Parser, Debug)]
 #[command(name = "lazychess")]
@@ -34,6 +63,10 @@ struct Args {
     #[arg(short, long)]
     pgn: Option<String>,
 
+    /// Load the FEN or PGN on the clipboard
+    #[arg(long, conflicts_with_all = ["fen", "pgn"])]
+    from_clipboard: bool,
+
     /// Search depth for analysis
     #[arg(short, long)]
     depth: Option<u32>,
@@ -46,16 +79,32 @@ struct Args {
     #[arg(short, long)]
     stockfish: Option<String>,
 
-    /// Piece style: "nerd" (default), "unicode", or "ascii"
-    #[arg(long, default_value = "nerd")]
-    pieces: String,
+    /// Piece style: "nerd", "unicode", "ascii" or "blocks" (default: the
+    /// configured style)
+    #[arg(long)]
+    pieces: Option<String>,
+
+    /// Accept JSON commands on a Unix socket path or tcp://host:port
+    #[arg(long)]
+    control: Option<String>,
+
+    /// Read FEN strings from stdin, one per line, and analyse each as it
+    /// arrives
+    #[arg(long)]
+    stdin_fen: bool,
+
+    /// Config profile to use: config.<name>.toml in the config directory,
+    /// started from the default config when it doesn't exist yet
+    #[arg(long)]
+    profile: Option<String>,
 }
 
 fn main() -> Result<()> {
     let args = Args::parse();
 
     // Load configuration
-    let mut config = Config::load()?;
+    let mut config = Config::load(args.profile.as_deref())?;
+    config.apply_env()?;
 
     // Override config with command-line arguments
     if let Some(depth) = args.depth {
@@ -67,52 +116,133 @@ fn main() -> Result<()> {
     if let Some(stockfish) = args.stockfish {
         config.engine.path = Some(stockfish);
     }
-    config.ui.piece_style = args.pieces;
+    if let Some(pieces) = args.pieces {
+        config.ui.piece_style = pieces;
+    }
+    if let Some(control) = args.control {
+        config.control.listen = Some(control);
+    }
+
+    logging::init(&config.log)?;
+    tracing::info!("lazychess {} starting", env!("CARGO_PKG_VERSION"));
+
+    if args.stdin_fen && io::stdin().is_terminal() {
+        anyhow::bail!("--stdin-fen needs positions piped to stdin");
+    }
+
+    // Put the terminal back before a panic message is printed, so it's
+    // readable and the shell still works
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        tracing::error!("{}", info);
+        let _ = restore_terminal();
+        default_hook(info);
+        // A panic on a background thread would leave the app running
+        // without a usable screen
+        std::process::exit(101);
+    }));
 
     // Setup terminal
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
+    let mouse_capture = config.ui.mouse_capture;
+    let mut terminal = match setup_terminal(mouse_capture) {
+        Ok(terminal) => terminal,
+        Err(e) => {
+            let _ = restore_terminal();
+            return Err(e.into());
+        }
+    };
 
     // Create app
-    let mut app = App::new(config)?;
-
-    // Load initial position if specified
-    if let Some(fen) = args.fen {
-        if let Err(e) = app.game.load_fen(&fen) {
-            app.input.set_error(format!("Invalid FEN: {}", e));
-        } else {
-            app.start_analysis()?;
+    let events = Events::new();
+    // Kept until exit, when the socket file is removed
+    let _control = match config.control.listen {
+        Some(ref address) => match control::listen(&config.control, address, events.waker()) {
+            Ok(socket) => Some(socket),
+            Err(e) => {
+                restore_terminal()?;
+                return Err(e);
+            }
+        },
+        None => None,
+    };
+    let mut app = match App::new(config, events.waker()) {
+        Ok(app) => app,
+        Err(e) => {
+            restore_terminal()?;
+            return Err(e);
+        }
+    };
+    if args.stdin_fen {
+        events::read_stdin_fens(events.waker());
+    }
+
+    // Load initial position if specified. The terminal is set up by now,
+    // so a failure is shown rather than returned.
+    let loaded = if let Some(fen) = args.fen {
+        match app.game.load_fen(&fen) {
+            Ok(_) => app.start_analysis(),
+            Err(e) => {
+                app.input.set_error(format!("Invalid FEN: {}", e));
+                Ok(())
+            }
         }
     } else if let Some(pgn_path) = args.pgn {
-        let pgn_content = std::fs::read_to_string(&pgn_path)?;
-        // The app will parse this on first tick or we can do it manually
-        app.input.pgn_buffer = pgn_content.lines().map(String::from).collect();
-        // We'd need to call finish_pgn_input but it's private, so let's handle differently
+        app.load_pgn_file(&pgn_path)
+    } else if args.from_clipboard {
+        app.load_clipboard()
+    } else {
+        Ok(())
+    };
+    if let Err(e) = loaded {
+        tracing::error!("{:#}", e);
+        app.input.set_error(format!("{:#}", e));
     }
 
     // Main loop
-    let result = run_app(&mut terminal, &mut app);
+    let result = run_app(&mut terminal, &mut app, &events, mouse_capture);
+    if let Err(ref e) = result {
+        tracing::error!("{:#}", e);
+    }
 
     // Restore terminal
+    restore_terminal()?;
+
+    result
+}
+
+/// Enter raw mode and the alternate screen
+fn setup_terminal(mouse_capture: bool) -> io::Result<Terminal<CrosstermBackend<io::Stdout>>> {
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen, EnableFocusChange)?;
+    if mouse_capture {
+        execute!(stdout, EnableMouseCapture)?;
+    }
+    Terminal::new(CrosstermBackend::new(stdout))
+}
+
+/// Leave raw mode and the alternate screen and show the cursor again
+fn restore_terminal() -> io::Result<()> {
     disable_raw_mode()?;
     execute!(
-        terminal.backend_mut(),
+        io::stdout(),
         LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
-    terminal.show_cursor()?;
-
-    result
+        DisableMouseCapture,
+        DisableFocusChange,
+        Show
+    )
 }
 
-fn run_app(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App) -> Result<()> {
+fn run_app(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    app: &mut App,
+    events: &Events,
+    mut mouse_capture: bool,
+) -> Result<()> {
+    // Idle ticks leave the screen as it is, so only events and animations
+    // call for a redraw
+    let mut dirty = true;
     loop {
-        // Render
-        terminal.draw(|f| app.render(f))?;
-
         // Process engine events
         app.tick()?;
 
@@ -121,11 +251,78 @@ fn run_app(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App)
             return Ok(());
         }
 
-        // Poll for events with timeout (allows engine updates)
-        if event::poll(Duration::from_millis(50))? {
-            if let Event::Key(key) = event::read()? {
-                app.handle_key_event(key)?;
+        // Hand the mouse to the terminal (for selecting text) or take it back
+        if app.mouse_capture() != mouse_capture {
+            mouse_capture = app.mouse_capture();
+            if mouse_capture {
+                execute!(terminal.backend_mut(), EnableMouseCapture)?;
+            } else {
+                execute!(terminal.backend_mut(), DisableMouseCapture)?;
+            }
+        }
+
+        // Render
+        if dirty || app.animating() {
+            terminal.draw(|f| app.render(f))?;
+
+            // Draw the board image for terminals with a graphics protocol
+            if let Some(graphics) = app.board_graphics() {
+                let backend = terminal.backend_mut();
+                write!(backend, "{}", graphics)?;
+                backend.flush()?;
+            }
+        }
+        dirty = false;
+        let drawn = Instant::now();
+
+        // Sleep until there's input or engine output, or it's time to tick
+        // (clocks count down between events)
+        if let Some(event) = events.next(app.tick_interval()) {
+            handle_event(terminal, app, event)?;
+            dirty = true;
+        }
+
+        // Engine output can stream in faster than it's worth redrawing, so
+        // gather events until the next frame is due
+        while let Some(wait) = FRAME_TIME.checked_sub(drawn.elapsed()) {
+            match events.next(wait) {
+                Some(event) => {
+                    handle_event(terminal, app, event)?;
+                    dirty = true;
+                }
+                None => break,
             }
         }
+        while let Some(event) = events.try_next() {
+            handle_event(terminal, app, event)?;
+            dirty = true;
+        }
+    }
+}
+
+fn handle_event(
+    terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    app: &mut App,
+    event: AppEvent,
+) -> Result<()> {
+    match event {
+        AppEvent::Terminal(Event::Key(key)) => app.handle_key_event(key)?,
+        AppEvent::Terminal(Event::FocusLost) => app.focus_lost()?,
+        AppEvent::Terminal(Event::FocusGained) => app.focus_gained()?,
+        AppEvent::Terminal(Event::Resize(_, _)) => {
+            // Lay the screen out for the new size right away
+            terminal.autoresize()?;
+            app.resized();
+        }
+        AppEvent::Control(request) => {
+            let reply = app
+                .control(&request.command)
+                .unwrap_or_else(|e| control::error_reply(format!("{:#}", e)));
+            let _ = request.reply.send(reply);
+        }
+        AppEvent::StdinFen(fen) => app.stdin_fen(fen.as_deref())?,
+        // Engine output is picked up by the next tick
+        AppEvent::Terminal(_) | AppEvent::Wake => {}
     }
+    Ok(())
 }
@@ -0,0 +1,185 @@
+/// Search depth used to verify a mating move
+pub const MATE_DEPTH: u32 = 18;
+
+/// Checkmate patterns, by short name and full name
+pub const MATE_PATTERNS: &[(&str, &str)] = &[
+    ("backrank", "Back rank mate"),
+    ("smothered", "Smothered mate"),
+    ("arabian", "Arabian mate"),
+    ("anastasia", "Anastasia's mate"),
+    ("boden", "Boden's mate"),
+    ("epaulette", "Epaulette mate"),
+    ("ladder", "Ladder mate"),
+];
+
+/// A built-in mate-in-N exercise
+#[derive(Debug, Clone, PartialEq)]
+pub struct MateExercise {
+    /// Short name of the pattern
+    pub pattern: &'static str,
+    /// Starting position; the side to move delivers mate
+    pub fen: &'static str,
+    pub mate_in: u32,
+}
+
+/// Built-in exercises, easiest first within each pattern
+pub const MATE_EXERCISES: &[MateExercise] = &[
+    MateExercise {
+        pattern: "backrank",
+        fen: "6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1",
+        mate_in: 1,
+    },
+    MateExercise {
+        pattern: "backrank",
+        fen: "2r3k1/5ppp/8/8/8/8/5PPP/2R1R1K1 w - - 0 1",
+        mate_in: 1,
+    },
+    MateExercise {
+        pattern: "smothered",
+        fen: "6rk/6pp/8/6N1/8/8/8/6K1 w - - 0 1",
+        mate_in: 1,
+    },
+    MateExercise {
+        pattern: "smothered",
+        fen: "2r4k/6pp/7N/3Q4/8/8/8/6K1 w - - 0 1",
+        mate_in: 2,
+    },
+    MateExercise {
+        pattern: "arabian",
+        fen: "7k/R7/5N2/8/8/8/8/6K1 w - - 0 1",
+        mate_in: 1,
+    },
+    MateExercise {
+        pattern: "anastasia",
+        fen: "5r2/4Nppk/8/8/8/8/6K1/3R4 w - - 0 1",
+        mate_in: 1,
+    },
+    MateExercise {
+        pattern: "anastasia",
+        fen: "5r1k/4Nppp/8/7Q/8/4R3/6PP/6K1 w - - 0 1",
+        mate_in: 2,
+    },
+    MateExercise {
+        pattern: "boden",
+        fen: "2kr4/p2p4/8/8/5B2/8/4B3/6K1 w - - 0 1",
+        mate_in: 1,
+    },
+    MateExercise {
+        pattern: "boden",
+        fen: "2kr4/pp1n4/2n5/8/5B2/8/4B3/2Q3K1 w - - 0 1",
+        mate_in: 2,
+    },
+    MateExercise {
+        pattern: "epaulette",
+        fen: "3rkr2/8/8/8/8/1Q6/8/6K1 w - - 0 1",
+        mate_in: 1,
+    },
+    MateExercise {
+        pattern: "ladder",
+        fen: "6k1/8/8/8/8/8/R7/1R4K1 w - - 0 1",
+        mate_in: 2,
+    },
+];
+
+/// Full name of a pattern
+pub fn pattern_name(pattern: &str) -> &'static str {
+    MATE_PATTERNS
+        .iter()
+        .find(|(key, _)| *key == pattern)
+        .map_or("Checkmate", |(_, name)| name)
+}
+
+/// Where the current exercise is at
+#[derive(Debug, Clone, PartialEq)]
+pub enum MatePhase {
+    /// Waiting for the user's move
+    Asking,
+    /// The engine is verifying a move: first with its own search of the
+    /// position, then (when it prefers another move) the move on its own
+    Checking {
+        /// Move being checked (UCI and SAN), None when giving up
+        answer: Option<(String, String)>,
+        /// Engine's best move (UCI) once known
+        best: Option<String>,
+    },
+    Solved,
+    /// A move that doesn't mate in time was tried (or the exercise given up)
+    Failed {
+        /// A mating move in UCI and SAN
+        uci: String,
+        san: String,
+    },
+}
+
+/// Checkmate exercises being served
+#[derive(Debug, Clone)]
+pub struct MateSession {
+    /// Exercises not played yet
+    pub queue: Vec<&'static MateExercise>,
+    pub exercise: &'static MateExercise,
+    /// Moves left to deliver mate in
+    pub moves_left: u32,
+    pub phase: MatePhase,
+    /// FEN of the position to move in, to notice when the game is changed
+    pub fen: String,
+    pub solved: usize,
+    pub played: usize,
+    /// Mate score (for the side to move) and line of the running search
+    pub search: Option<(Option<i32>, Vec<String>)>,
+}
+
+impl MateSession {
+    /// Serve the exercises for a pattern (or all), returning None when there
+    /// are none
+    pub fn new(pattern: Option<&'static str>) -> Option<Self> {
+        let mut queue: Vec<&'static MateExercise> = MATE_EXERCISES
+            .iter()
+            .filter(|e| pattern.is_none_or(|p| e.pattern == p))
+            .collect();
+        // Served from the back
+        queue.reverse();
+        let exercise = queue.pop()?;
+        Some(Self {
+            queue,
+            exercise,
+            moves_left: exercise.mate_in,
+            phase: MatePhase::Asking,
+            fen: String::new(),
+            solved: 0,
+            played: 0,
+            search: None,
+        })
+    }
+
+    /// Check if the engine is busy verifying a move
+    pub fn is_checking(&self) -> bool {
+        matches!(self.phase, MatePhase::Checking { .. })
+    }
+
+    /// Check if the current exercise is over
+    pub fn is_done(&self) -> bool {
+        matches!(self.phase, MatePhase::Solved | MatePhase::Failed { .. })
+    }
+
+    /// Move on to the next exercise; returns false after the last
+    pub fn advance(&mut self) -> bool {
+        let Some(exercise) = self.queue.pop() else {
+            return false;
+        };
+        self.exercise = exercise;
+        self.moves_left = exercise.mate_in;
+        self.phase = MatePhase::Asking;
+        self.search = None;
+        true
+    }
+
+    /// Mark the exercise over
+    pub fn finish(&mut self, phase: MatePhase) {
+        self.played += 1;
+        if phase == MatePhase::Solved {
+            self.solved += 1;
+        }
+        self.phase = phase;
+        self.search = None;
+    }
+}
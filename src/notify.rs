@@ -0,0 +1,19 @@
+//! Desktop notifications for long jobs finishing in the background
+
+use notify_rust::Notification;
+
+/// Show a desktop notification without waiting for the notification
+/// service. Failures (no service running, headless sessions) are only
+/// logged.
+pub fn send(summary: &str, body: &str) {
+    let mut notification = Notification::new();
+    notification
+        .summary(summary)
+        .body(body)
+        .appname("lazychess");
+    std::thread::spawn(move || {
+        if let Err(e) = notification.show() {
+            tracing::debug!("Failed to show notification: {}", e);
+        }
+    });
+}
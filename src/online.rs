@@ -0,0 +1,413 @@
+//! Online databases: lookups of the current position on web services, run
+//! on a background thread that wakes the main loop with the answer
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+use shakmaty::{Chess, Position};
+use std::collections::HashMap;
+use std::process::{Command, Stdio};
+use std::sync::mpsc::{self, Receiver, TryRecvError};
+use std::thread;
+use std::time::Duration;
+
+use crate::config::OnlineConfig;
+use crate::events::Waker;
+use crate::explorer::{ExplorerMove, PositionStats, Results, TopGame};
+
+/// Longest wait for a service to answer
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Example games asked of the masters explorer
+const TOP_GAMES: usize = 4;
+
+/// Most pieces the online tablebase has positions with
+const TABLEBASE_PIECES: usize = 7;
+
+/// Answers by FEN, or why the lookup failed
+pub type Answers<T> = HashMap<String, std::result::Result<T, String>>;
+
+/// A lookup running in the background, for the position with this FEN
+pub struct Query<T> {
+    pub fen: String,
+    rx: Receiver<Result<T>>,
+}
+
+impl<T: Send + 'static> Query<T> {
+    pub fn spawn(
+        fen: &str,
+        waker: &Waker,
+        lookup: impl FnOnce() -> Result<T> + Send + 'static,
+    ) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let waker = waker.clone();
+        thread::spawn(move || {
+            let _ = tx.send(lookup());
+            waker.wake();
+        });
+        Self {
+            fen: fen.to_string(),
+            rx,
+        }
+    }
+
+    /// The answer, once it has arrived
+    pub fn poll(&self) -> Option<Result<T>> {
+        match self.rx.try_recv() {
+            Ok(result) => Some(result),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => Some(Err(anyhow!("Lookup stopped"))),
+        }
+    }
+}
+
+/// Store the answer of a finished lookup
+pub fn collect<T: Send + 'static>(query: &mut Option<Query<T>>, answers: &mut Answers<T>) {
+    let Some(result) = query.as_ref().and_then(Query::poll) else {
+        return;
+    };
+    if let Some(query) = query.take() {
+        answers.insert(query.fen, result.map_err(|e| format!("{:#}", e)));
+    }
+}
+
+/// GET a JSON document
+fn get_json<T: for<'de> Deserialize<'de>>(
+    url: &str,
+    query: &[(&str, &str)],
+    token: Option<&str>,
+) -> Result<T> {
+    let mut request = ureq::get(url).timeout(TIMEOUT);
+    for (name, value) in query {
+        request = request.query(name, value);
+    }
+    if let Some(token) = token {
+        request = request.set("Authorization", &format!("Bearer {}", token));
+    }
+    read_json(request.call(), url)
+}
+
+/// The JSON document a service answered with, or why there's none
+fn read_json<T: for<'de> Deserialize<'de>>(
+    response: std::result::Result<ureq::Response, ureq::Error>,
+    url: &str,
+) -> Result<T> {
+    let response = match response {
+        Ok(response) => response,
+        Err(ureq::Error::Status(429, _)) => bail!("Too many requests, try again in a minute"),
+        Err(ureq::Error::Status(401, _)) => {
+            bail!("Not authorized (set lichess_token under [online])")
+        }
+        Err(e) => return Err(e).with_context(|| format!("Failed to query {}", url)),
+    };
+    response
+        .into_json()
+        .with_context(|| format!("Unexpected answer from {}", url))
+}
+
+#[derive(Deserialize)]
+struct ExplorerAnswer {
+    white: usize,
+    draws: usize,
+    black: usize,
+    #[serde(default)]
+    moves: Vec<ExplorerAnswerMove>,
+    #[serde(default, rename = "topGames")]
+    top_games: Vec<ExplorerAnswerGame>,
+}
+
+#[derive(Deserialize)]
+struct ExplorerAnswerMove {
+    uci: String,
+    san: String,
+    white: usize,
+    draws: usize,
+    black: usize,
+}
+
+#[derive(Deserialize)]
+struct ExplorerAnswerGame {
+    uci: String,
+    winner: Option<String>,
+    white: ExplorerAnswerPlayer,
+    black: ExplorerAnswerPlayer,
+}
+
+#[derive(Deserialize)]
+struct ExplorerAnswerPlayer {
+    name: String,
+    rating: Option<u32>,
+}
+
+impl ExplorerAnswerPlayer {
+    /// Surname and rating, e.g. "Carlsen 2882" for "Carlsen, M."
+    fn label(&self) -> String {
+        let surname = self.name.split(',').next().unwrap_or_default().trim();
+        match self.rating {
+            Some(rating) => format!("{} {}", surname, rating),
+            None => surname.to_string(),
+        }
+    }
+}
+
+/// Master games that reached a position, from the Lichess opening explorer
+pub fn masters(config: &OnlineConfig, fen: &str) -> Result<PositionStats> {
+    let top_games = TOP_GAMES.to_string();
+    let answer: ExplorerAnswer = get_json(
+        &config.masters_url,
+        &[("fen", fen), ("topGames", &top_games)],
+        config.lichess_token.as_deref(),
+    )?;
+
+    let moves: Vec<ExplorerMove> = answer
+        .moves
+        .into_iter()
+        .map(|m| ExplorerMove {
+            san: m.san,
+            uci: m.uci,
+            results: Results::new(m.white, m.draws, m.black),
+        })
+        .collect();
+    let top_games = answer
+        .top_games
+        .into_iter()
+        .map(|game| TopGame {
+            white: game.white.label(),
+            black: game.black.label(),
+            result: match game.winner.as_deref() {
+                Some("white") => "1-0",
+                Some("black") => "0-1",
+                _ => "½-½",
+            },
+            san: moves
+                .iter()
+                .find(|m| m.uci == game.uci)
+                .map_or(game.uci, |m| m.san.clone()),
+        })
+        .collect();
+    Ok(PositionStats {
+        results: Results::new(answer.white, answer.draws, answer.black),
+        moves,
+        top_games,
+    })
+}
+
+/// Outcome of a tablebase position for the side to move
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Win,
+    /// A win the 50-move rule turns into a draw
+    CursedWin,
+    Draw,
+    /// A loss the 50-move rule saves
+    BlessedLoss,
+    Loss,
+    Unknown,
+}
+
+impl Outcome {
+    /// Read a Lichess tablebase category. Wins and losses that are only
+    /// uncertain in their DTZ count as wins and losses.
+    fn from_category(category: &str) -> Self {
+        match category {
+            "win" | "syzygy-win" | "maybe-win" => Self::Win,
+            "cursed-win" => Self::CursedWin,
+            "draw" => Self::Draw,
+            "blessed-loss" => Self::BlessedLoss,
+            "loss" | "syzygy-loss" | "maybe-loss" => Self::Loss,
+            _ => Self::Unknown,
+        }
+    }
+
+    /// The same outcome for the other side
+    fn flip(self) -> Self {
+        match self {
+            Self::Win => Self::Loss,
+            Self::CursedWin => Self::BlessedLoss,
+            Self::BlessedLoss => Self::CursedWin,
+            Self::Loss => Self::Win,
+            other => other,
+        }
+    }
+}
+
+/// A tablebase position: its outcome for the side to move, with the
+/// distances to zeroing (DTZ) and mate (DTM) when known
+#[derive(Debug, Clone, PartialEq)]
+pub struct TablebaseEntry {
+    pub outcome: Outcome,
+    pub dtz: Option<i32>,
+    pub dtm: Option<i32>,
+    /// Legal moves, the best first
+    pub moves: Vec<TablebaseMove>,
+}
+
+/// A move from a tablebase position
+#[derive(Debug, Clone, PartialEq)]
+pub struct TablebaseMove {
+    pub san: String,
+    /// Outcome for the side playing it
+    pub outcome: Outcome,
+}
+
+#[derive(Deserialize)]
+struct TablebaseAnswer {
+    category: String,
+    dtz: Option<i32>,
+    dtm: Option<i32>,
+    #[serde(default)]
+    moves: Vec<TablebaseAnswerMove>,
+}
+
+#[derive(Deserialize)]
+struct TablebaseAnswerMove {
+    san: String,
+    category: String,
+}
+
+/// Whether the online tablebase has a position: few enough pieces and no
+/// castling rights
+pub fn in_tablebase(position: &Chess) -> bool {
+    position.board().occupied().count() <= TABLEBASE_PIECES && !position.castles().any()
+}
+
+/// Look up a position in the Lichess tablebase
+pub fn tablebase(config: &OnlineConfig, fen: &str) -> Result<TablebaseEntry> {
+    let answer: TablebaseAnswer = get_json(&config.tablebase_url, &[("fen", fen)], None)?;
+    Ok(TablebaseEntry {
+        outcome: Outcome::from_category(&answer.category),
+        dtz: answer.dtz,
+        dtm: answer.dtm,
+        moves: answer
+            .moves
+            .into_iter()
+            .map(|m| TablebaseMove {
+                san: m.san,
+                // Categories of moves are for the side to move after them
+                outcome: Outcome::from_category(&m.category).flip(),
+            })
+            .collect(),
+    })
+}
+
+/// Lichess analysis board set up with a position, seen from Black when
+/// `flipped`
+pub fn analysis_url(config: &OnlineConfig, fen: &str, chess960: bool, flipped: bool) -> String {
+    let variant = if chess960 { "chess960" } else { "standard" };
+    let mut url = format!(
+        "{}/analysis/{}/{}",
+        config.lichess_url.trim_end_matches('/'),
+        variant,
+        fen.replace(' ', "_")
+    );
+    if flipped {
+        url.push_str("?color=black");
+    }
+    url
+}
+
+#[derive(Deserialize)]
+struct ImportAnswer {
+    url: String,
+}
+
+/// Import a game to Lichess, to the account of the token when one is set,
+/// giving the URL of the imported game
+pub fn import_game(config: &OnlineConfig, pgn: &str) -> Result<String> {
+    let url = format!("{}/api/import", config.lichess_url.trim_end_matches('/'));
+    let mut request = ureq::post(&url).timeout(TIMEOUT);
+    if let Some(token) = &config.lichess_token {
+        request = request.set("Authorization", &format!("Bearer {}", token));
+    }
+    let answer: ImportAnswer = read_json(request.send_form(&[("pgn", pgn)]), &url)?;
+    Ok(answer.url)
+}
+
+/// Open a URL in the default browser, or the program in $BROWSER when set
+pub fn open_in_browser(url: &str) -> Result<()> {
+    let mut command = match std::env::var("BROWSER") {
+        Ok(browser) if !browser.is_empty() => Command::new(browser),
+        _ if cfg!(target_os = "macos") => Command::new("open"),
+        _ if cfg!(windows) => {
+            let mut command = Command::new("cmd");
+            command.args(["/C", "start", ""]);
+            command
+        }
+        _ => Command::new("xdg-open"),
+    };
+    let mut child = command
+        .arg(url)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .context("Failed to start a browser (set $BROWSER)")?;
+    // Reap the process without holding up the UI
+    thread::spawn(move || child.wait());
+    Ok(())
+}
+
+/// Moves shown from the cloud database
+const CLOUD_MOVES: usize = 3;
+
+/// Scores from this far from the cloud database's mate score are mates,
+/// counted in plies
+const CLOUD_MATE: i32 = 30000;
+const CLOUD_MATE_PLIES: i32 = 1000;
+
+/// A move scored by the cloud database, for the side to move
+#[derive(Debug, Clone, PartialEq)]
+pub struct CloudMove {
+    pub san: String,
+    pub cp: Option<i32>,
+    pub mate: Option<i32>,
+}
+
+#[derive(Deserialize)]
+struct CloudAnswer {
+    status: String,
+    #[serde(default)]
+    moves: Vec<CloudAnswerMove>,
+}
+
+#[derive(Deserialize)]
+struct CloudAnswerMove {
+    san: String,
+    score: i32,
+}
+
+/// Best moves for a position from the chessdb.cn cloud database, empty
+/// when it doesn't know the position
+pub fn cloud(config: &OnlineConfig, fen: &str) -> Result<Vec<CloudMove>> {
+    let answer: CloudAnswer = get_json(
+        &config.cloud_url,
+        &[("action", "queryall"), ("board", fen), ("json", "1")],
+        None,
+    )?;
+    match answer.status.as_str() {
+        "ok" => {}
+        "unknown" | "checkmate" | "stalemate" => return Ok(Vec::new()),
+        status => bail!("chessdb.cn: {}", status),
+    }
+    Ok(answer
+        .moves
+        .into_iter()
+        .take(CLOUD_MOVES)
+        .map(|m| {
+            let plies = CLOUD_MATE - m.score.abs();
+            if plies < CLOUD_MATE_PLIES {
+                let mate = (plies + 1) / 2;
+                CloudMove {
+                    san: m.san,
+                    cp: None,
+                    mate: Some(if m.score > 0 { mate } else { -mate }),
+                }
+            } else {
+                CloudMove {
+                    san: m.san,
+                    cp: Some(m.score),
+                    mate: None,
+                }
+            }
+        })
+        .collect())
+}
@@ -0,0 +1,89 @@
+//! Opening names: a built-in table of ECO codes, names and move orders,
+//! looked up by position so transposed move orders are named too
+
+use shakmaty::fen::Epd;
+use shakmaty::san::SanPlus;
+use shakmaty::{Chess, EnPassantMode, Position};
+use std::collections::HashMap;
+use std::sync::OnceLock;
+
+use crate::chess::Game;
+
+/// A named opening
+#[derive(Debug, Clone, PartialEq)]
+pub struct Opening {
+    pub eco: &'static str,
+    pub name: &'static str,
+}
+
+/// ECO code, name and moves (SAN from the starting position) of each
+/// opening, tab-separated after a header line
+const OPENINGS_TSV: &str = include_str!("openings.tsv");
+
+/// Openings by the position their moves reach, and the longest line in plies
+struct Table {
+    openings: HashMap<String, Opening>,
+    max_plies: usize,
+}
+
+fn table() -> &'static Table {
+    static TABLE: OnceLock<Table> = OnceLock::new();
+    TABLE.get_or_init(|| {
+        let mut openings = HashMap::new();
+        let mut max_plies = 0;
+        for line in OPENINGS_TSV.lines().skip(1) {
+            let mut fields = line.split('\t');
+            let (Some(eco), Some(name), Some(moves)) =
+                (fields.next(), fields.next(), fields.next())
+            else {
+                continue;
+            };
+            let mut pos = Chess::default();
+            let mut plies = 0;
+            for san in moves.split_whitespace() {
+                let Some(m) = san
+                    .parse::<SanPlus>()
+                    .ok()
+                    .and_then(|san| san.san.to_move(&pos).ok())
+                else {
+                    tracing::warn!("Bad move {} in opening {}", san, name);
+                    break;
+                };
+                pos.play_unchecked(&m);
+                plies += 1;
+            }
+            max_plies = max_plies.max(plies);
+            // The first name given for a position wins
+            openings.entry(key(&pos)).or_insert(Opening { eco, name });
+        }
+        Table {
+            openings,
+            max_plies,
+        }
+    })
+}
+
+/// Position key that ignores the move counters
+fn key(pos: &Chess) -> String {
+    Epd::from_position(pos.clone(), EnPassantMode::Legal).to_string()
+}
+
+/// Name the opening played up to the current position: the last named
+/// position along the way, so the name stays once the game leaves the book.
+/// Games that don't start from the standard position have none.
+pub fn opening(game: &Game) -> Option<&'static Opening> {
+    if !game.is_standard_start() {
+        return None;
+    }
+    let table = table();
+    let plies = game.current_index().min(table.max_plies);
+    let mut pos = game.initial_position().clone();
+    let mut found = None;
+    for m in &game.moves()[..plies] {
+        pos.play_unchecked(m);
+        if let Some(opening) = table.openings.get(&key(&pos)) {
+            found = Some(opening);
+        }
+    }
+    found
+}
@@ -0,0 +1,124 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use shakmaty::Color;
+
+use crate::chess::{Clock, Game, TimeControl};
+use crate::state;
+
+/// Lowest Elo the adaptive sparring partner drops to
+const MIN_SPARRING_ELO: u32 = 1320;
+/// Highest Elo the adaptive sparring partner climbs to
+const MAX_SPARRING_ELO: u32 = 3190;
+/// Number of recent games that make up the running score
+const SPARRING_WINDOW: usize = 5;
+
+/// State of a game being played against the engine
+#[derive(Debug, Clone)]
+pub struct PlaySession {
+    /// Side the user plays
+    pub user_color: Color,
+    /// Time control in use (None = untimed)
+    pub time_control: Option<TimeControl>,
+    /// Game clock (None = untimed)
+    pub clock: Option<Clock>,
+    /// Is the engine searching for its move?
+    pub engine_thinking: bool,
+    /// Number of moves in the game when the clock was last pressed
+    pub last_ply: usize,
+    /// PGN result once the game has finished
+    pub result: Option<String>,
+    /// Move (UCI) the engine is pondering on while the user thinks
+    pub ponder_move: Option<String>,
+}
+
+impl PlaySession {
+    /// Start a session for the current game position
+    pub fn new(user_color: Color, time_control: Option<TimeControl>, game: &Game) -> Self {
+        Self {
+            user_color,
+            time_control,
+            clock: time_control.map(Clock::new),
+            engine_thinking: false,
+            last_ply: game.moves().len(),
+            result: None,
+            ponder_move: None,
+        }
+    }
+
+    /// Check if it is the engine's turn to move in the game
+    pub fn is_engine_turn(&self, game: &Game) -> bool {
+        game.is_at_end() && !game.is_game_over() && game.turn() != self.user_color
+    }
+
+    /// Describe the session for status messages
+    pub fn describe(&self) -> String {
+        let side = if self.user_color == Color::White {
+            "White"
+        } else {
+            "Black"
+        };
+        match self.time_control {
+            Some(tc) => format!("Playing {} vs engine ({})", side, tc),
+            None => format!("Playing {} vs engine (untimed)", side),
+        }
+    }
+}
+
+/// Results against the adaptive sparring engine, persisted in the state dir
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SparringRecord {
+    /// Current engine Elo
+    pub elo: u32,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    /// Scores of the most recent games (1 = win, 0.5 = draw, 0 = loss)
+    pub recent: Vec<f32>,
+}
+
+impl Default for SparringRecord {
+    fn default() -> Self {
+        Self {
+            elo: 1500,
+            wins: 0,
+            draws: 0,
+            losses: 0,
+            recent: Vec::new(),
+        }
+    }
+}
+
+impl SparringRecord {
+    /// Load the record, or start a fresh one
+    pub fn load() -> Self {
+        state::load("sparring.toml")
+    }
+
+    /// Save the record to the state dir
+    pub fn save(&self) -> Result<()> {
+        state::save("sparring.toml", self)
+    }
+
+    /// Record a game result (from the user's perspective) and retune the Elo
+    /// so the running score drifts back toward 50%
+    pub fn record(&mut self, score: f32) {
+        if score >= 1.0 {
+            self.wins += 1;
+        } else if score <= 0.0 {
+            self.losses += 1;
+        } else {
+            self.draws += 1;
+        }
+
+        self.recent.push(score);
+        if self.recent.len() > SPARRING_WINDOW {
+            self.recent.remove(0);
+        }
+
+        let running = self.recent.iter().sum::<f32>() / self.recent.len() as f32;
+        let step = ((running - 0.5) * 200.0).round() as i32;
+        self.elo =
+            (self.elo as i32 + step).clamp(MIN_SPARRING_ELO as i32, MAX_SPARRING_ELO as i32) as u32;
+    }
+}
@@ -0,0 +1,88 @@
+//! Power source detection, used to go easy on the CPU while unplugged
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+/// Check whether the machine is currently running on battery power
+///
+/// Returns false when it can't be determined (desktops, unsupported platforms).
+pub fn on_battery() -> bool {
+    if cfg!(target_os = "linux") {
+        linux_on_battery(Path::new("/sys/class/power_supply"))
+    } else if cfg!(target_os = "macos") {
+        Command::new("pmset")
+            .args(["-g", "batt"])
+            .output()
+            .map(|out| String::from_utf8_lossy(&out.stdout).contains("Battery Power"))
+            .unwrap_or(false)
+    } else {
+        false
+    }
+}
+
+/// On battery when no mains adapter is online but a battery is present.
+/// Wireless mice, keyboards and the like report their own batteries with
+/// scope `Device`; those don't power the machine.
+fn linux_on_battery(supplies: &Path) -> bool {
+    let Ok(entries) = fs::read_dir(supplies) else {
+        return false;
+    };
+
+    let mut has_battery = false;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let scope = fs::read_to_string(path.join("scope")).unwrap_or_default();
+        if scope.trim() == "Device" {
+            continue;
+        }
+        let kind = fs::read_to_string(path.join("type")).unwrap_or_default();
+        match kind.trim() {
+            "Mains" | "USB" => {
+                let online = fs::read_to_string(path.join("online")).unwrap_or_default();
+                if online.trim() == "1" {
+                    return false;
+                }
+            }
+            "Battery" => has_battery = true,
+            _ => {}
+        }
+    }
+    has_battery
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn supply(dir: &Path, name: &str, files: &[(&str, &str)]) {
+        let path = dir.join(name);
+        fs::create_dir_all(&path).unwrap();
+        for (file, text) in files {
+            fs::write(path.join(file), format!("{}\n", text)).unwrap();
+        }
+    }
+
+    #[test]
+    fn device_batteries_are_ignored() {
+        let dir = std::env::temp_dir().join(format!("lazychess-power-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+
+        // A desktop with a wireless mouse
+        supply(
+            &dir,
+            "hidpp_battery_0",
+            &[("type", "Battery"), ("scope", "Device")],
+        );
+        assert!(!linux_on_battery(&dir));
+
+        // A laptop, unplugged and then plugged in
+        supply(&dir, "BAT0", &[("type", "Battery")]);
+        supply(&dir, "AC", &[("type", "Mains"), ("online", "0")]);
+        assert!(linux_on_battery(&dir));
+        supply(&dir, "AC", &[("type", "Mains"), ("online", "1")]);
+        assert!(!linux_on_battery(&dir));
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
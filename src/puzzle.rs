@@ -0,0 +1,358 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use shakmaty::uci::UciMove;
+use shakmaty::{Chess, Color, Move, Position};
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::hash::{BuildHasher, Hasher};
+use std::io::{BufRead, BufReader};
+use std::time::{Duration, Instant};
+
+use crate::state;
+
+/// Puzzles drawn from the database in one pass over it
+pub const PUZZLE_BATCH: usize = 20;
+/// Puzzles drawn for a rush, more than anyone solves in the time
+pub const RUSH_PUZZLES: usize = 100;
+/// Default length of a rush
+pub const RUSH_MINUTES: u32 = 3;
+/// Rating range of rush puzzles, which are served easiest first
+pub const RUSH_MIN_RATING: u32 = 600;
+pub const RUSH_MAX_RATING: u32 = 2400;
+/// Wrong moves that end a rush
+pub const RUSH_STRIKES: u32 = 3;
+/// Puzzle rating of a fresh record
+const START_RATING: u32 = 1500;
+/// Rating points at stake per puzzle
+const RATING_K: f64 = 32.0;
+/// Recently played puzzles remembered so they aren't served again
+const RECENT_PUZZLES: usize = 500;
+
+/// A puzzle from the Lichess puzzle database
+#[derive(Debug, Clone, PartialEq)]
+pub struct Puzzle {
+    pub id: String,
+    /// Position before the opponent's first move
+    pub fen: String,
+    /// Solution line in UCI, starting with the opponent's move
+    pub moves: Vec<String>,
+    pub rating: u32,
+    pub themes: Vec<String>,
+}
+
+impl Puzzle {
+    /// Parse a line of the Lichess puzzle CSV: PuzzleId, FEN, Moves, Rating,
+    /// RatingDeviation, Popularity, NbPlays, Themes, GameUrl, OpeningTags
+    pub fn parse(line: &str) -> Option<Self> {
+        let fields: Vec<&str> = line.split(',').collect();
+        if fields.len() < 8 {
+            return None;
+        }
+        let moves: Vec<String> = fields[2].split_whitespace().map(String::from).collect();
+        if moves.len() < 2 {
+            return None;
+        }
+        Some(Self {
+            id: fields[0].to_string(),
+            fen: fields[1].to_string(),
+            moves,
+            // Fails on the header line
+            rating: fields[3].parse().ok()?,
+            themes: fields[7].split_whitespace().map(String::from).collect(),
+        })
+    }
+
+    /// Solution move at a step of the line, for the position it's played from
+    pub fn move_at(&self, step: usize, position: &Chess) -> Option<Move> {
+        let uci: UciMove = self.moves.get(step)?.parse().ok()?;
+        uci.to_move(position).ok()
+    }
+}
+
+/// Which puzzles to serve
+#[derive(Debug, Clone, PartialEq)]
+pub struct PuzzleFilter {
+    /// Lichess theme tag, e.g. "fork" or "mateIn2"
+    pub theme: Option<String>,
+    pub min_rating: u32,
+    pub max_rating: u32,
+}
+
+impl PuzzleFilter {
+    fn matches(&self, puzzle: &Puzzle) -> bool {
+        (self.min_rating..=self.max_rating).contains(&puzzle.rating)
+            && self
+                .theme
+                .as_ref()
+                .is_none_or(|theme| puzzle.themes.iter().any(|t| t.eq_ignore_ascii_case(theme)))
+    }
+
+    /// Describe the filter for status messages
+    pub fn describe(&self) -> String {
+        match self.theme {
+            Some(ref theme) => format!("{} {}-{}", theme, self.min_rating, self.max_rating),
+            None => format!("{}-{}", self.min_rating, self.max_rating),
+        }
+    }
+}
+
+/// Draw a random batch of up to `count` matching puzzles in one pass over
+/// the database, leaving out the given puzzle ids
+pub fn sample_puzzles(
+    path: &str,
+    filter: &PuzzleFilter,
+    skip: &[String],
+    count: usize,
+) -> Result<Vec<Puzzle>> {
+    let file =
+        File::open(path).with_context(|| format!("Failed to open puzzle database {}", path))?;
+    // Checked for every line of a database of millions
+    let skip: HashSet<&str> = skip.iter().map(String::as_str).collect();
+    let mut rng = RandomState::new().build_hasher().finish() | 1;
+    let mut batch = Vec::new();
+    let mut matched = 0usize;
+
+    for line in BufReader::new(file).lines() {
+        let line = line.context("Failed to read puzzle database")?;
+        let Some(puzzle) = Puzzle::parse(&line) else {
+            continue;
+        };
+        if !filter.matches(&puzzle) || skip.contains(puzzle.id.as_str()) {
+            continue;
+        }
+
+        // Reservoir sampling, so every matching puzzle is equally likely
+        matched += 1;
+        if batch.len() < count {
+            batch.push(puzzle);
+        } else {
+            rng ^= rng << 13;
+            rng ^= rng >> 7;
+            rng ^= rng << 17;
+            let slot = (rng % matched as u64) as usize;
+            if slot < count {
+                batch[slot] = puzzle;
+            }
+        }
+    }
+    Ok(batch)
+}
+
+/// Progress through the current puzzle
+#[derive(Debug, Clone, PartialEq)]
+pub enum PuzzleState {
+    Solving,
+    Solved,
+    /// A wrong move was tried (or the puzzle given up)
+    Failed {
+        /// Expected move in UCI and SAN
+        uci: String,
+        san: String,
+    },
+}
+
+/// A timed run of puzzles with a limited number of mistakes
+#[derive(Debug, Clone)]
+pub struct Rush {
+    pub minutes: u32,
+    pub deadline: Instant,
+    pub solved: u32,
+    pub strikes: u32,
+    /// High score for this length before the rush started
+    pub best: u32,
+    /// Set once the time or the strikes run out
+    pub finished: bool,
+}
+
+impl Rush {
+    pub fn new(minutes: u32, best: u32) -> Self {
+        Self {
+            minutes,
+            deadline: Instant::now() + Duration::from_secs(minutes as u64 * 60),
+            solved: 0,
+            strikes: 0,
+            best,
+            finished: false,
+        }
+    }
+
+    /// Time left in the rush
+    pub fn remaining(&self) -> Duration {
+        if self.finished {
+            return Duration::ZERO;
+        }
+        self.deadline.saturating_duration_since(Instant::now())
+    }
+}
+
+/// Puzzles being served from the database
+#[derive(Debug, Clone)]
+pub struct PuzzleSession {
+    pub filter: PuzzleFilter,
+    /// Follow the puzzle rating when drawing new batches, rather than a
+    /// range given by the user
+    pub auto_range: bool,
+    /// Puzzles drawn but not played yet
+    pub queue: Vec<Puzzle>,
+    pub puzzle: Puzzle,
+    /// Index into the solution of the next move to find
+    pub step: usize,
+    pub state: PuzzleState,
+    /// Side the user plays
+    pub side: Color,
+    /// FEN of the current puzzle position, to notice when the game is changed
+    pub fen: String,
+    /// The user's puzzle rating
+    pub rating: u32,
+    /// Puzzle rating before and after the current puzzle, once it's over
+    pub rating_change: Option<(u32, u32)>,
+    pub solved: usize,
+    pub played: usize,
+    /// Puzzle rush in progress (or just finished)
+    pub rush: Option<Rush>,
+}
+
+impl PuzzleSession {
+    /// Start serving from a batch of puzzles (which must not be empty)
+    pub fn new(
+        filter: PuzzleFilter,
+        auto_range: bool,
+        rating: u32,
+        rush: Option<Rush>,
+        mut queue: Vec<Puzzle>,
+    ) -> Self {
+        if rush.is_some() {
+            Self::order_for_rush(&mut queue);
+        }
+        let puzzle = queue.pop().expect("puzzle batch is empty");
+        Self {
+            filter,
+            auto_range,
+            queue,
+            puzzle,
+            step: 1,
+            state: PuzzleState::Solving,
+            side: Color::White,
+            fen: String::new(),
+            rating,
+            rating_change: None,
+            solved: 0,
+            played: 0,
+            rush,
+        }
+    }
+
+    /// Sort a batch so the easiest puzzle is served (popped) first
+    fn order_for_rush(batch: &mut [Puzzle]) {
+        batch.sort_by_key(|p| std::cmp::Reverse(p.rating));
+    }
+
+    /// Queue a fresh batch of puzzles
+    pub fn refill(&mut self, mut batch: Vec<Puzzle>) {
+        if self.rush.is_some() {
+            Self::order_for_rush(&mut batch);
+        }
+        self.queue = batch;
+    }
+
+    /// Move on to the next queued puzzle; returns false when the queue is empty
+    pub fn advance(&mut self) -> bool {
+        let Some(puzzle) = self.queue.pop() else {
+            return false;
+        };
+        self.puzzle = puzzle;
+        self.step = 1;
+        self.state = PuzzleState::Solving;
+        self.rating_change = None;
+        true
+    }
+
+    /// Check a move against the solution at the current step. On the last
+    /// move any checkmate counts, as on Lichess.
+    pub fn is_solution(&self, position: &Chess, m: &Move) -> bool {
+        if self.puzzle.move_at(self.step, position).as_ref() == Some(m) {
+            return true;
+        }
+        self.step + 1 == self.puzzle.moves.len()
+            && position
+                .clone()
+                .play(m)
+                .is_ok_and(|after| after.is_checkmate())
+    }
+}
+
+/// Local puzzle rating and results, persisted in the state dir
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PuzzleRecord {
+    pub rating: u32,
+    pub solved: u32,
+    pub failed: u32,
+    /// Ids of the most recently played puzzles
+    #[serde(default)]
+    pub recent: Vec<String>,
+    /// Best puzzle rush scores by length in minutes
+    #[serde(default)]
+    pub rush_best: BTreeMap<String, u32>,
+}
+
+impl Default for PuzzleRecord {
+    fn default() -> Self {
+        Self {
+            rating: START_RATING,
+            solved: 0,
+            failed: 0,
+            recent: Vec::new(),
+            rush_best: BTreeMap::new(),
+        }
+    }
+}
+
+impl PuzzleRecord {
+    /// Load the record, or start a fresh one
+    pub fn load() -> Self {
+        state::load("puzzles.toml")
+    }
+
+    /// Save the record to the state dir
+    pub fn save(&self) -> Result<()> {
+        state::save("puzzles.toml", self)
+    }
+
+    /// Best score for rushes of the given length
+    pub fn rush_best(&self, minutes: u32) -> u32 {
+        self.rush_best
+            .get(&minutes.to_string())
+            .copied()
+            .unwrap_or(0)
+    }
+
+    /// Record a rush score; returns true for a new high score
+    pub fn record_rush(&mut self, minutes: u32, solved: u32) -> bool {
+        if solved <= self.rush_best(minutes) {
+            return false;
+        }
+        self.rush_best.insert(minutes.to_string(), solved);
+        true
+    }
+
+    /// Record a puzzle result and update the rating as an Elo game against
+    /// the puzzle
+    pub fn record(&mut self, puzzle: &Puzzle, solved: bool) {
+        if solved {
+            self.solved += 1;
+        } else {
+            self.failed += 1;
+        }
+        self.recent.push(puzzle.id.clone());
+        if self.recent.len() > RECENT_PUZZLES {
+            self.recent.remove(0);
+        }
+
+        let expected =
+            1.0 / (1.0 + 10f64.powf((puzzle.rating as f64 - self.rating as f64) / 400.0));
+        let score = if solved { 1.0 } else { 0.0 };
+        let rating = self.rating as f64 + RATING_K * (score - expected);
+        self.rating = rating.round().clamp(400.0, 3500.0) as u32;
+    }
+}
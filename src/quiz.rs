@@ -0,0 +1,118 @@
+use shakmaty::Color;
+
+use crate::chess::Eval;
+use crate::review::win_percent;
+
+/// Search depth used to check quiz answers
+pub const QUIZ_DEPTH: u32 = 16;
+/// Largest drop in winning chances (percentage points) an answer may
+/// have against the engine's move and still count as correct
+const QUIZ_TOLERANCE: f64 = 10.0;
+
+/// A position where a better move than the one played has to be found
+#[derive(Debug, Clone)]
+pub struct QuizItem {
+    /// Position index in the game (the blunder was played from here)
+    pub index: usize,
+    /// FEN of the position, to notice when the game is changed underneath
+    pub fen: String,
+    /// Side to move
+    pub turn: Color,
+    /// The move that was played, in SAN
+    pub played: String,
+    /// Evaluations (White's view) before and after the move that was played
+    pub before: Eval,
+    pub after: Eval,
+}
+
+/// Where the current quiz position is at
+#[derive(Debug, Clone, PartialEq)]
+pub enum QuizPhase {
+    /// Waiting for the user's move
+    Asking,
+    /// The engine is looking for the best move, then scoring the answer
+    Checking {
+        /// User's move (UCI and SAN); None when they gave up
+        answer: Option<(String, String)>,
+        /// Engine's best move (UCI) and score once known
+        best: Option<(String, i32)>,
+    },
+    /// The answer has been checked
+    Revealed {
+        /// User's move in SAN and its score
+        answer: Option<(String, i32)>,
+        /// Engine's best move in SAN and its score
+        best: (String, i32),
+        /// Best move in UCI, for the board arrow
+        best_uci: String,
+        correct: bool,
+    },
+}
+
+/// Blunder quiz over positions from a reviewed game
+#[derive(Debug, Clone)]
+pub struct Quiz {
+    pub items: Vec<QuizItem>,
+    /// Index of the current item
+    pub current: usize,
+    pub phase: QuizPhase,
+    /// Items answered correctly so far
+    pub correct: usize,
+    /// Items answered (or given up) so far
+    pub answered: usize,
+    /// Latest score and best move of the running check search
+    pub search: Option<(i32, String)>,
+}
+
+impl Quiz {
+    pub fn new(items: Vec<QuizItem>) -> Self {
+        Self {
+            items,
+            current: 0,
+            phase: QuizPhase::Asking,
+            correct: 0,
+            answered: 0,
+            search: None,
+        }
+    }
+
+    /// The current quiz item
+    pub fn item(&self) -> &QuizItem {
+        &self.items[self.current]
+    }
+
+    /// Check if the engine is busy checking an answer
+    pub fn is_checking(&self) -> bool {
+        matches!(self.phase, QuizPhase::Checking { .. })
+    }
+
+    /// Move on to the next item; returns false when the quiz is over
+    pub fn advance(&mut self) -> bool {
+        self.phase = QuizPhase::Asking;
+        self.search = None;
+        if self.current + 1 < self.items.len() {
+            self.current += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Record the verdict for the current item
+    pub fn reveal(&mut self, answer: Option<(String, i32)>, best: (String, i32), best_uci: String) {
+        // Scores are from the point of view of the side to move
+        let correct = answer
+            .as_ref()
+            .is_some_and(|(_, cp)| win_percent(best.1) - win_percent(*cp) < QUIZ_TOLERANCE);
+        self.answered += 1;
+        if correct {
+            self.correct += 1;
+        }
+        self.phase = QuizPhase::Revealed {
+            answer,
+            best,
+            best_uci,
+            correct,
+        };
+    }
+}
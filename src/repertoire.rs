@@ -0,0 +1,79 @@
+use anyhow::{Context, Result};
+use shakmaty::fen::Epd;
+use shakmaty::san::San;
+use shakmaty::{Chess, Color, EnPassantMode, Move, Position};
+use std::collections::HashMap;
+use std::fs;
+
+use crate::chess::{tree_moves, Game};
+
+/// Key of a position in the repertoire, so transpositions match
+fn position_key(position: &Chess) -> String {
+    Epd::from_position(position.clone(), EnPassantMode::Legal).to_string()
+}
+
+/// An opening repertoire for one side, read from a PGN file with variations
+#[derive(Debug, Clone)]
+pub struct Repertoire {
+    /// Side the repertoire is played with
+    pub side: Color,
+    /// Repertoire moves by position
+    moves: HashMap<String, Vec<Move>>,
+}
+
+/// Where a game first leaves the repertoire
+#[derive(Debug, Clone, PartialEq)]
+pub struct Deviation {
+    /// Index of the position the move was played from
+    pub index: usize,
+    /// Whether the user (rather than the opponent) left the repertoire
+    pub by_user: bool,
+    /// The move played, numbered, e.g. "7. Bc4"
+    pub played: String,
+    /// Repertoire moves in the position, in SAN
+    pub expected: Vec<String>,
+}
+
+impl Repertoire {
+    /// Read every game and variation of a PGN file
+    pub fn load(path: &str, side: Color) -> Result<Self> {
+        let pgn = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read repertoire {}", path))?;
+        let mut moves: HashMap<String, Vec<Move>> = HashMap::new();
+        for (position, m) in tree_moves(&pgn)? {
+            let known = moves.entry(position_key(&position)).or_default();
+            if !known.contains(&m) {
+                known.push(m);
+            }
+        }
+        Ok(Self { side, moves })
+    }
+
+    /// Number of positions with repertoire moves
+    pub fn positions(&self) -> usize {
+        self.moves.len()
+    }
+
+    /// Find the first move of the game played from a repertoire position
+    /// that isn't in the repertoire. Returns None when the game stays in the
+    /// repertoire until either runs out.
+    pub fn deviation(&self, game: &Game) -> Option<Deviation> {
+        let mut position = game.initial_position().clone();
+        for (index, m) in game.moves().iter().enumerate() {
+            let known = self.moves.get(&position_key(&position))?;
+            if !known.contains(m) {
+                return Some(Deviation {
+                    index,
+                    by_user: position.turn() == self.side,
+                    played: game.numbered_san(index)?,
+                    expected: known
+                        .iter()
+                        .map(|m| San::from_move(&position, m).to_string())
+                        .collect(),
+                });
+            }
+            position.play_unchecked(m);
+        }
+        None
+    }
+}
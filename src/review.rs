@@ -0,0 +1,430 @@
+use anyhow::Result;
+use shakmaty::fen::Fen;
+use shakmaty::{Color, EnPassantMode, Position};
+use std::collections::VecDeque;
+
+use crate::chess::{Eval, Game};
+use crate::engine::{Engine, EngineEvent};
+
+/// Search depth for each position of a game review
+pub const REVIEW_DEPTH: u32 = 16;
+
+/// Most critical moments offered for navigation
+const CRITICAL_MOMENTS: usize = 5;
+/// Smallest swing in winning chances (percentage points) that counts as critical
+const CRITICAL_SWING: f64 = 10.0;
+
+/// Drops in winning chances (percentage points) classed as inaccuracy,
+/// mistake and blunder, as on Lichess
+const INACCURACY_DROP: f64 = 10.0;
+const MISTAKE_DROP: f64 = 20.0;
+const BLUNDER_DROP: f64 = 30.0;
+
+/// How much a move gave away, as the review classes it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MoveClass {
+    Good,
+    Inaccuracy,
+    Mistake,
+    Blunder,
+}
+
+impl MoveClass {
+    /// Class a move from the evals (White's view, centipawns) before and
+    /// after it, by the mover's drop in winning chances
+    pub fn of(before: i32, after: i32, mover: Color) -> Self {
+        let drop = win_percent(before) - win_percent(after);
+        match if mover == Color::White { drop } else { -drop } {
+            d if d >= BLUNDER_DROP => MoveClass::Blunder,
+            d if d >= MISTAKE_DROP => MoveClass::Mistake,
+            d if d >= INACCURACY_DROP => MoveClass::Inaccuracy,
+            _ => MoveClass::Good,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            MoveClass::Good => "Good move",
+            MoveClass::Inaccuracy => "Inaccuracy",
+            MoveClass::Mistake => "Mistake",
+            MoveClass::Blunder => "Blunder",
+        }
+    }
+
+    /// Annotation glyph, as in PGN
+    pub fn glyph(self) -> &'static str {
+        match self {
+            MoveClass::Good => "",
+            MoveClass::Inaccuracy => "?!",
+            MoveClass::Mistake => "?",
+            MoveClass::Blunder => "??",
+        }
+    }
+}
+
+/// Evaluation of one reviewed position
+#[derive(Debug, Clone)]
+pub struct ReviewResult {
+    /// Position index in the game (0 = initial position)
+    pub index: usize,
+    /// FEN of the position, to check the game hasn't changed since
+    pub fen: String,
+    /// Evaluation from White's point of view (None for checkmate)
+    pub eval: Option<Eval>,
+}
+
+/// Background analysis of every position of a game with its own engine
+pub struct Review {
+    engine: Engine,
+    depth: u32,
+    /// Positions still to analyse: index, FEN and side to move
+    queue: VecDeque<(usize, String, Color)>,
+    /// Position being analysed
+    current: Option<(usize, String, Color)>,
+    /// Latest score (side to move) of the current search
+    score: (Option<i32>, Option<i32>),
+    /// Number of positions in the review
+    total: usize,
+}
+
+impl Review {
+    /// Queue every position of the game on a dedicated engine
+    pub fn start(mut engine: Engine, game: &Game, depth: u32) -> Result<Self> {
+        engine.set_option("MultiPV", "1")?;
+        engine.new_game()?;
+
+        let queue: VecDeque<_> = (0..=game.moves().len())
+            .filter_map(|index| {
+                let position = game.position_at(index)?;
+                let turn = position.turn();
+                let fen = Fen::from_position(position, EnPassantMode::Legal).to_string();
+                Some((index, fen, turn))
+            })
+            .collect();
+
+        let mut review = Self {
+            engine,
+            depth,
+            total: queue.len(),
+            queue,
+            current: None,
+            score: (None, None),
+        };
+        review.next()?;
+        Ok(review)
+    }
+
+    /// Start searching the next queued position
+    fn next(&mut self) -> Result<()> {
+        self.current = self.queue.pop_front();
+        self.score = (None, None);
+        if let Some((_, ref fen, _)) = self.current {
+            self.engine.set_position(Some(fen), &[])?;
+            self.engine.go_depth(self.depth)?;
+        }
+        Ok(())
+    }
+
+    /// Positions finished and the total
+    pub fn progress(&self) -> (usize, usize) {
+        let pending = self.queue.len() + usize::from(self.current.is_some());
+        (self.total - pending, self.total)
+    }
+
+    /// Check if every position has been analysed
+    pub fn is_done(&self) -> bool {
+        self.current.is_none()
+    }
+
+    /// Collect finished positions and move on to the next ones
+    pub fn poll(&mut self) -> Result<Vec<ReviewResult>> {
+        let mut results = Vec::new();
+        while let Some(event) = self.engine.try_recv() {
+            match event {
+                EngineEvent::Info(info)
+                    if info.multipv.unwrap_or(1) == 1
+                        && (info.score_cp.is_some() || info.score_mate.is_some()) =>
+                {
+                    self.score = (info.score_cp, info.score_mate);
+                }
+                EngineEvent::BestMove(_) => {
+                    if let Some((index, fen, turn)) = self.current.take() {
+                        // Scores are relative to the side to move
+                        let sign = if turn == Color::White { 1 } else { -1 };
+                        let eval = match self.score {
+                            // Mate 0: the side to move is already checkmated,
+                            // which `game_evals` accounts for
+                            (_, Some(0)) => None,
+                            (_, Some(mate)) => Some(Eval::Mate(mate * sign)),
+                            (Some(cp), None) => Some(Eval::Cp(cp * sign)),
+                            (None, None) => None,
+                        };
+                        results.push(ReviewResult { index, fen, eval });
+                    }
+                    self.next()?;
+                }
+                _ => {}
+            }
+        }
+        Ok(results)
+    }
+
+    /// Shut down the review engine
+    pub fn quit(mut self) {
+        let _ = self.engine.stop();
+        let _ = self.engine.quit();
+    }
+}
+
+/// Centipawns a checkmated final position counts as, past any mate score
+const MATED_CP: i32 = 10_000;
+
+/// Evals (White's view) of every position of a game, with a final
+/// checkmate, which engines give no score, counted as won outright
+pub fn game_evals(game: &Game) -> Vec<Option<Eval>> {
+    let mut evals: Vec<_> = game.annotations().iter().map(|a| a.eval).collect();
+    let last = game.position_at(game.moves().len());
+    if let (Some(eval @ None), Some(position)) = (evals.last_mut(), last) {
+        if position.is_checkmate() {
+            *eval = Some(Eval::Cp(position.turn().fold_wb(-MATED_CP, MATED_CP)));
+        }
+    }
+    evals
+}
+
+/// Winning chances (0-100) for White from a centipawn eval, using
+/// Lichess's logistic model
+pub fn win_percent(cp: i32) -> f64 {
+    50.0 + 50.0 * (2.0 / (1.0 + (-0.00368208 * cp as f64).exp()) - 1.0)
+}
+
+/// Position indices reached by the moves that swung the game the most,
+/// in game order
+pub fn critical_moments(evals: &[Option<Eval>]) -> Vec<usize> {
+    let mut swings: Vec<(usize, f64)> = evals
+        .windows(2)
+        .enumerate()
+        .filter_map(|(i, pair)| {
+            let (before, after) = (pair[0]?, pair[1]?);
+            let swing = (win_percent(after.to_cp()) - win_percent(before.to_cp())).abs();
+            (swing >= CRITICAL_SWING).then_some((i + 1, swing))
+        })
+        .collect();
+    swings.sort_by(|a, b| b.1.total_cmp(&a.1));
+    swings.truncate(CRITICAL_MOMENTS);
+
+    let mut moments: Vec<usize> = swings.into_iter().map(|(index, _)| index).collect();
+    moments.sort_unstable();
+    moments
+}
+
+/// Review statistics for one side
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PlayerSummary {
+    /// Lichess-style accuracy (0-100)
+    pub accuracy: f64,
+    /// Average centipawn loss
+    pub acpl: u32,
+    /// Moves with an eval before and after
+    pub moves: usize,
+    pub inaccuracies: usize,
+    pub mistakes: usize,
+    pub blunders: usize,
+}
+
+/// Fewest moves a side needs before a rating estimate means anything
+const MIN_RATED_MOVES: usize = 8;
+
+impl PlayerSummary {
+    /// Very rough Elo performance estimate from the ACPL, lowered further by
+    /// the share of mistakes and blunders
+    pub fn estimated_elo(&self) -> Option<u32> {
+        if self.moves < MIN_RATED_MOVES {
+            return None;
+        }
+        let moves = self.moves as f64;
+        let elo = 3100.0 * (-0.01 * self.acpl as f64).exp()
+            - 400.0 * self.blunders as f64 / moves
+            - 150.0 * self.mistakes as f64 / moves;
+        // Round to 50 so it doesn't look more precise than it is
+        Some(((elo.clamp(400.0, 3200.0) / 50.0).round() * 50.0) as u32)
+    }
+}
+
+/// Accuracy and centipawn loss of both sides over a game
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GameSummary {
+    pub white: PlayerSummary,
+    pub black: PlayerSummary,
+}
+
+/// Running totals for one side while summarizing a game
+#[derive(Default)]
+struct SideTally {
+    /// Accuracy and volatility weight of each move
+    accuracies: Vec<(f64, f64)>,
+    total_loss: i64,
+    summary: PlayerSummary,
+}
+
+impl SideTally {
+    /// Combine the weighted and harmonic means of the move accuracies
+    fn finish(self) -> PlayerSummary {
+        let mut summary = self.summary;
+        if summary.moves == 0 {
+            return summary;
+        }
+        let weighted = self.accuracies.iter().map(|(a, w)| a * w).sum::<f64>()
+            / self.accuracies.iter().map(|(_, w)| w).sum::<f64>();
+        let harmonic = self.accuracies.len() as f64
+            / self
+                .accuracies
+                .iter()
+                .map(|(a, _)| 1.0 / a.max(1.0))
+                .sum::<f64>();
+        summary.accuracy = (weighted + harmonic) / 2.0;
+        summary.acpl = (self.total_loss as f64 / summary.moves as f64).round() as u32;
+        summary
+    }
+}
+
+/// Accuracy of a single move from the mover's drop in winning chances
+fn move_accuracy(win_drop: f64) -> f64 {
+    // Lichess's fit, plus its bonus for imperfect analysis
+    (103.1668 * (-0.04354 * win_drop).exp() - 3.1669 + 1.0).clamp(0.0, 100.0)
+}
+
+/// Standard deviation of a set of winning chances
+fn std_dev(values: &[f64]) -> f64 {
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+    let variance = values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / values.len() as f64;
+    variance.sqrt()
+}
+
+/// Compute per-player accuracy and ACPL from the evals (White's view) of
+/// every position, as Lichess does. `first_turn` is the side to move in the
+/// first position. Returns None unless every position has an eval.
+pub fn summarize(evals: &[Option<Eval>], first_turn: Color) -> Option<GameSummary> {
+    let cps: Vec<i32> = evals
+        .iter()
+        .map(|e| e.map(|e| e.to_cp()))
+        .collect::<Option<_>>()?;
+    if cps.len() < 2 {
+        return None;
+    }
+    let wins: Vec<f64> = cps.iter().map(|&cp| win_percent(cp)).collect();
+
+    // Volatile stretches of the game weigh more in the weighted mean
+    let window = (wins.len() / 10).clamp(2, 8);
+    let weights: Vec<f64> = (0..wins.len() - 1)
+        .map(|i| {
+            let start = (i + 1).saturating_sub(window - 1).min(wins.len() - window);
+            std_dev(&wins[start..start + window]).clamp(0.5, 12.0)
+        })
+        .collect();
+
+    let mut sides: [SideTally; 2] = Default::default();
+    for i in 0..wins.len() - 1 {
+        let white_moved = (i % 2 == 0) == (first_turn == Color::White);
+        let sign = if white_moved { 1.0 } else { -1.0 };
+        let win_drop = ((wins[i] - wins[i + 1]) * sign).max(0.0);
+        let cp_loss = ((cps[i].clamp(-1000, 1000) - cps[i + 1].clamp(-1000, 1000)) as f64 * sign)
+            .max(0.0) as i64;
+
+        let side = &mut sides[usize::from(!white_moved)];
+        side.accuracies.push((move_accuracy(win_drop), weights[i]));
+        side.total_loss += cp_loss;
+        side.summary.moves += 1;
+        let mover = if white_moved {
+            Color::White
+        } else {
+            Color::Black
+        };
+        match MoveClass::of(cps[i], cps[i + 1], mover) {
+            MoveClass::Blunder => side.summary.blunders += 1,
+            MoveClass::Mistake => side.summary.mistakes += 1,
+            MoveClass::Inaccuracy => side.summary.inaccuracies += 1,
+            MoveClass::Good => {}
+        }
+    }
+
+    let [white, black] = sides;
+    Some(GameSummary {
+        white: white.finish(),
+        black: black.finish(),
+    })
+}
+
+/// Position indices the given side blundered from (None = either side)
+pub fn blunders(evals: &[Option<Eval>], first_turn: Color, side: Option<Color>) -> Vec<usize> {
+    evals
+        .windows(2)
+        .enumerate()
+        .filter_map(|(i, pair)| {
+            let (before, after) = (pair[0]?, pair[1]?);
+            let mover = if i % 2 == 0 { first_turn } else { !first_turn };
+            let class = MoveClass::of(before.to_cp(), after.to_cp(), mover);
+            (class == MoveClass::Blunder && side.is_none_or(|s| s == mover)).then_some(i)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A game with engine-like evals on every position but the last, as a
+    /// review leaves it
+    fn reviewed(pgn: &str, cps: &[i32], mates: &[(usize, i32)]) -> Game {
+        let mut game = Game::from_pgn(pgn).unwrap();
+        for (index, &cp) in cps.iter().enumerate() {
+            game.annotation_at_mut(index).unwrap().eval = Some(Eval::Cp(cp));
+        }
+        for &(index, mate) in mates {
+            game.annotation_at_mut(index).unwrap().eval = Some(Eval::Mate(mate));
+        }
+        game
+    }
+
+    #[test]
+    fn game_ending_in_mate_is_summarized() {
+        // Fool's mate: Black mates, White is checkmated in the final position
+        let game = reviewed("1. f3 e5 2. g4 Qh4# 0-1", &[20, -50, -40], &[(3, -1)]);
+        let evals = game_evals(&game);
+        assert_eq!(evals.len(), 5);
+        assert_eq!(evals[4], Some(Eval::Cp(-MATED_CP)));
+
+        let summary = summarize(&evals, Color::White).unwrap();
+        assert_eq!(summary.white.moves, 2);
+        assert_eq!(summary.black.moves, 2);
+        // 2. g4 walked into mate; Qh4# lost nothing
+        assert_eq!(summary.white.blunders, 1);
+        assert_eq!(summary.black.blunders, 0);
+        assert!(summary.black.accuracy > summary.white.accuracy);
+    }
+
+    #[test]
+    fn mate_by_white_counts_for_white() {
+        let game = reviewed(
+            "1. e4 e5 2. Qh5 Nc6 3. Bc4 Nf6 4. Qxf7# 1-0",
+            &[30, 30, 20, 20, 10, 15],
+            &[(6, 1)],
+        );
+        let evals = game_evals(&game);
+        assert_eq!(evals[7], Some(Eval::Cp(MATED_CP)));
+        let summary = summarize(&evals, Color::White).unwrap();
+        assert_eq!(summary.white.blunders, 0);
+        assert_eq!(summary.black.blunders, 1);
+    }
+
+    #[test]
+    fn unreviewed_positions_are_not_filled_in() {
+        // A missing eval before the end still leaves the game unsummarized
+        let mut game = reviewed("1. f3 e5 2. g4 Qh4# 0-1", &[20, -50, -40], &[(3, -1)]);
+        game.annotation_at_mut(1).unwrap().eval = None;
+        assert!(summarize(&game_evals(&game), Color::White).is_none());
+        // And a final position that isn't mate keeps its missing eval
+        let game = reviewed("1. e4 e5 *", &[20, 30], &[]);
+        assert_eq!(game_evals(&game)[2], None);
+    }
+}
@@ -0,0 +1,169 @@
+//! User scripts: a Rhai file whose hook functions are called on game events,
+//! with functions to look at the game and queue moves and commands
+
+use anyhow::{anyhow, Context, Result};
+use rhai::{Array, CallFnOptions, Dynamic, Engine, Map, Scope, AST};
+use std::cell::RefCell;
+use std::fs;
+use std::rc::Rc;
+
+/// Operations a hook may run before it's stopped, so a runaway script can't
+/// freeze the screen
+const MAX_OPERATIONS: u64 = 1_000_000;
+
+/// Hook functions a script can define
+pub const HOOKS: &[&str] = &["on_move", "on_analysis", "on_game_loaded"];
+
+/// The game as scripts see it, and what they asked for
+#[derive(Debug, Default)]
+struct ScriptState {
+    fen: String,
+    /// Moves of the game in SAN
+    moves: Vec<String>,
+    /// Index of the position shown
+    ply: usize,
+    /// Lines to enter in the input bar: moves and `:commands`
+    actions: Vec<String>,
+    /// Messages for the status line
+    messages: Vec<String>,
+}
+
+/// What the app should do after a hook ran
+#[derive(Debug, Default)]
+pub struct ScriptOutput {
+    pub actions: Vec<String>,
+    pub messages: Vec<String>,
+}
+
+/// A loaded script
+pub struct Script {
+    path: String,
+    engine: Engine,
+    ast: AST,
+    scope: Scope<'static>,
+    state: Rc<RefCell<ScriptState>>,
+    /// Depth of the analysis when `on_analysis` was last called
+    pub analysis_depth: Option<u32>,
+}
+
+impl Script {
+    /// Compile a script file and run its top level
+    pub fn load(path: &str) -> Result<Self> {
+        let source =
+            fs::read_to_string(path).with_context(|| format!("Failed to read script {}", path))?;
+        let state = Rc::new(RefCell::new(ScriptState::default()));
+        let mut engine = Engine::new();
+        engine.set_max_operations(MAX_OPERATIONS);
+
+        // Printing would draw over the screen, so it goes to the status line
+        let printed = state.clone();
+        engine.on_print(move |text| printed.borrow_mut().messages.push(text.to_string()));
+        let debugged = state.clone();
+        engine.on_debug(move |text, _, _| debugged.borrow_mut().messages.push(text.to_string()));
+
+        let s = state.clone();
+        engine.register_fn("fen", move || s.borrow().fen.clone());
+        let s = state.clone();
+        engine.register_fn("moves", move || -> Array {
+            s.borrow()
+                .moves
+                .iter()
+                .cloned()
+                .map(Dynamic::from)
+                .collect()
+        });
+        let s = state.clone();
+        engine.register_fn("ply", move || s.borrow().ply as i64);
+        let s = state.clone();
+        engine.register_fn("play", move |san: &str| {
+            s.borrow_mut().actions.push(san.to_string())
+        });
+        let s = state.clone();
+        engine.register_fn("command", move |command: &str| {
+            let command = command.trim_start_matches(':');
+            s.borrow_mut().actions.push(format!(":{}", command));
+        });
+        let s = state.clone();
+        engine.register_fn("message", move |text: &str| {
+            s.borrow_mut().messages.push(text.to_string())
+        });
+
+        let ast = engine
+            .compile(&source)
+            .map_err(|e| anyhow!("Script error in {}: {}", path, e))?;
+        let mut scope = Scope::new();
+        engine
+            .run_ast_with_scope(&mut scope, &ast)
+            .map_err(|e| anyhow!("Script error in {}: {}", path, e))?;
+
+        Ok(Self {
+            path: path.to_string(),
+            engine,
+            ast,
+            scope,
+            state,
+            analysis_depth: None,
+        })
+    }
+
+    /// Path the script was loaded from
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Check if the script defines a hook
+    pub fn has_hook(&self, hook: &str) -> bool {
+        self.ast.iter_functions().any(|f| f.name == hook)
+    }
+
+    /// Update the game the script functions see
+    pub fn set_game(&mut self, fen: String, moves: Vec<String>, ply: usize) {
+        let mut state = self.state.borrow_mut();
+        state.fen = fen;
+        state.moves = moves;
+        state.ply = ply;
+    }
+
+    /// Call a hook if the script defines it, returning what it asked for
+    pub fn call(&mut self, hook: &str, args: Vec<Dynamic>) -> Result<ScriptOutput> {
+        if !self.has_hook(hook) {
+            return Ok(ScriptOutput::default());
+        }
+        // The top level already ran when the script was loaded
+        let options = CallFnOptions::new().eval_ast(false);
+        let result = self.engine.call_fn_with_options::<Dynamic>(
+            options,
+            &mut self.scope,
+            &self.ast,
+            hook,
+            args,
+        );
+        let mut state = self.state.borrow_mut();
+        let output = ScriptOutput {
+            actions: std::mem::take(&mut state.actions),
+            messages: std::mem::take(&mut state.messages),
+        };
+        match result {
+            Ok(_) => Ok(output),
+            Err(e) => Err(anyhow!("Script error in {}: {}", hook, e)),
+        }
+    }
+}
+
+/// Analysis line as a script map: `depth`, `score_cp` and `score_mate`
+/// (`()` when not given) and `pv` in SAN
+pub fn analysis_map(
+    depth: Option<u32>,
+    score_cp: Option<i32>,
+    score_mate: Option<i32>,
+    pv: &[String],
+) -> Map {
+    let optional = |value: Option<i64>| value.map_or(Dynamic::UNIT, Dynamic::from);
+    let mut map = Map::new();
+    map.insert("depth".into(), optional(depth.map(i64::from)));
+    map.insert("score_cp".into(), optional(score_cp.map(i64::from)));
+    map.insert("score_mate".into(), optional(score_mate.map(i64::from)));
+    let pv: Array = pv.iter().cloned().map(Dynamic::from).collect();
+    map.insert("pv".into(), pv.into());
+    map
+}
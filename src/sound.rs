@@ -0,0 +1,53 @@
+//! Audio feedback: the terminal bell or a user command on game events
+
+use anyhow::{Context, Result};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Events that can make a sound
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SoundEvent {
+    /// A move was played on the board
+    Move,
+    /// A move gave check
+    Check,
+    /// A move ended the game
+    GameOver,
+    /// Analysis reached its target depth
+    AnalysisDone,
+}
+
+/// Carry out a configured sound action: "bell" rings the terminal bell,
+/// anything else is run as a shell command, and "" does nothing
+pub fn play(action: &str) -> Result<()> {
+    match action.trim() {
+        "" => Ok(()),
+        "bell" => {
+            let mut stdout = std::io::stdout();
+            stdout.write_all(b"\x07")?;
+            stdout.flush()?;
+            Ok(())
+        }
+        command => {
+            let mut shell = if cfg!(windows) {
+                let mut shell = Command::new("cmd");
+                shell.arg("/C");
+                shell
+            } else {
+                let mut shell = Command::new("sh");
+                shell.arg("-c");
+                shell
+            };
+            let mut child = shell
+                .arg(command)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .with_context(|| format!("Failed to run sound command: {}", command))?;
+            // Reap the process without holding up the UI
+            std::thread::spawn(move || child.wait());
+            Ok(())
+        }
+    }
+}
@@ -0,0 +1,65 @@
+//! Persistent application state: records, stats, logs and anything else
+//! lazychess keeps between runs that isn't configuration. Everything lives
+//! in one directory, `$XDG_STATE_HOME/lazychess` (or the platform's local
+//! data directory), overridable with `LAZYCHESS_STATE_DIR`.
+
+use anyhow::{Context, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fs;
+use std::path::PathBuf;
+
+/// Environment variable that moves the state directory elsewhere
+const STATE_DIR_VAR: &str = "LAZYCHESS_STATE_DIR";
+
+/// Get the state directory
+pub fn dir() -> Option<PathBuf> {
+    if cfg!(test) {
+        return Some(test_dir().join("state"));
+    }
+    if let Some(dir) = std::env::var_os(STATE_DIR_VAR).filter(|d| !d.is_empty()) {
+        return Some(PathBuf::from(dir));
+    }
+    dirs::state_dir()
+        .or_else(dirs::data_local_dir)
+        .map(|p| p.join("lazychess"))
+}
+
+/// Scratch directory standing in for the user's config and state while
+/// tests run
+pub fn test_dir() -> PathBuf {
+    std::env::temp_dir().join(format!("lazychess-test-{}", std::process::id()))
+}
+
+/// Get the path of a file in the state directory
+pub fn path(name: &str) -> Option<PathBuf> {
+    dir().map(|p| p.join(name))
+}
+
+/// Load a TOML file from the state directory, or start fresh when it's
+/// missing or unreadable
+pub fn load<T: DeserializeOwned + Default>(name: &str) -> T {
+    path(name)
+        .and_then(|p| fs::read_to_string(p).ok())
+        .and_then(|s| toml::from_str(&s).ok())
+        .unwrap_or_default()
+}
+
+/// Save a TOML file to the state directory. The file is replaced in one
+/// step, so a crash mid-write can't leave half of it behind.
+pub fn save<T: Serialize>(name: &str, value: &T) -> Result<()> {
+    let contents = toml::to_string_pretty(value).context("Failed to serialize state")?;
+    write(name, contents.as_bytes())
+}
+
+/// Write a file to the state directory, replacing it in one step
+pub fn write(name: &str, contents: &[u8]) -> Result<()> {
+    let path = path(name).context("Could not determine state directory")?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)
+            .with_context(|| format!("Failed to create state directory {:?}", parent))?;
+    }
+    let temp = path.with_extension("tmp");
+    fs::write(&temp, contents).with_context(|| format!("Failed to write {:?}", temp))?;
+    fs::rename(&temp, &path).with_context(|| format!("Failed to write {:?}", path))
+}
@@ -0,0 +1,71 @@
+//! Transpositions: positions of the game that another move order reaches
+//! too, in its variations or in the other games of the loaded PGN
+
+use shakmaty::Position;
+use std::collections::{HashMap, HashSet};
+
+use crate::chess::{tree_moves, Game};
+
+/// The positions each position is reached from, across a set of games
+#[derive(Debug, Default)]
+pub struct Arrivals {
+    /// Keys of the positions before each position, with the game's index
+    from: HashMap<u64, Vec<(u64, usize)>>,
+}
+
+impl Arrivals {
+    /// Index the mainlines and variations of some games
+    pub fn new(games: &[Game]) -> Self {
+        let mut arrivals = Self::default();
+        for (index, game) in games.iter().enumerate() {
+            arrivals.add(game, index);
+        }
+        arrivals
+    }
+
+    fn add(&mut self, game: &Game, index: usize) {
+        let Ok(moves) = tree_moves(&game.to_pgn()) else {
+            return;
+        };
+        for (from, m) in moves {
+            let arrival = (Game::position_key(&from), index);
+            let mut to = from;
+            to.play_unchecked(&m);
+            let arrivals = self.from.entry(Game::position_key(&to)).or_default();
+            if !arrivals.contains(&arrival) {
+                arrivals.push(arrival);
+            }
+        }
+    }
+
+    /// Whether a position is reached from another position than `from`,
+    /// leaving out one game
+    fn reached_otherwise(&self, to: u64, from: u64, skip: Option<usize>) -> bool {
+        self.from.get(&to).is_some_and(|arrivals| {
+            arrivals
+                .iter()
+                .any(|&(other, game)| other != from && Some(game) != skip)
+        })
+    }
+}
+
+/// Indices of the positions in a game's mainline that another move order
+/// reaches too: in the game itself, its variations, or the other loaded
+/// games indexed in `others` (where the game itself is number `skip`)
+pub fn transpositions(game: &Game, others: &Arrivals, skip: Option<usize>) -> HashSet<usize> {
+    let mut own = Arrivals::default();
+    own.add(game, 0);
+
+    let mut found = HashSet::new();
+    let mut position = game.initial_position().clone();
+    let mut from = Game::position_key(&position);
+    for (i, m) in game.moves().iter().enumerate() {
+        position.play_unchecked(m);
+        let to = Game::position_key(&position);
+        if own.reached_otherwise(to, from, None) || others.reached_otherwise(to, from, skip) {
+            found.insert(i + 1);
+        }
+        from = to;
+    }
+    found
+}
@@ -5,9 +5,20 @@ use ratatui::{
     text::{Line, Span},
     widgets::{Block, Borders, Paragraph, Widget},
 };
-use shakmaty::{san::San, uci::UciMove, Chess, Position};
+use shakmaty::zobrist::{Zobrist64, ZobristHash};
+use shakmaty::{san::San, uci::UciMove, Chess, EnPassantMode, Position};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
 
-use crate::engine::{format_nodes, format_score, AnalysisInfo};
+use super::Palette;
+use crate::engine::{format_nodes, format_score, format_wdl, AnalysisInfo};
+
+/// Depth a new search must reach before it replaces the previous lines
+const WARM_DEPTH: u32 = 8;
+
+/// SAN of the PVs converted from one position
+type SanLines = HashMap<Vec<String>, Rc<[String]>>;
 
 /// Analysis information for display
 #[derive(Debug, Clone, Default)]
@@ -26,6 +37,19 @@ pub struct AnalysisState {
     pub nps: Option<u64>,
     /// Hash usage
     pub hashfull: Option<u32>,
+    /// Tablebase hits
+    pub tbhits: Option<u64>,
+    /// Root move the engine is currently examining (UCI)
+    pub currmove: Option<String>,
+    /// Index of the current root move (1-indexed)
+    pub currmovenumber: Option<u32>,
+    /// Position being analysed
+    pub position: Option<Chess>,
+    /// Lines of an earlier search and the position they belong to, shown
+    /// dimmed while the current search warms up
+    pub previous: Option<(Chess, Vec<AnalysisInfo>)>,
+    /// PVs already converted to SAN, by position and PV
+    san_cache: RefCell<HashMap<Zobrist64, SanLines>>,
 }
 
 impl AnalysisState {
@@ -38,7 +62,55 @@ impl AnalysisState {
             nodes: None,
             nps: None,
             hashfull: None,
+            tbhits: None,
+            currmove: None,
+            currmovenumber: None,
+            position: None,
+            previous: None,
+            san_cache: RefCell::default(),
+        }
+    }
+
+    /// Check if the current search is deep enough to be shown on its own
+    pub fn is_warm(&self) -> bool {
+        self.lines
+            .first()
+            .and_then(|l| l.depth)
+            .is_some_and(|d| d >= WARM_DEPTH.min(self.target_depth))
+    }
+
+    /// Reset for a search of a new position, keeping the last warmed-up
+    /// lines around until the new search catches up
+    pub fn restart(&mut self, position: Chess) {
+        let previous = match self.position.take() {
+            Some(old) if self.is_warm() => Some((old, std::mem::take(&mut self.lines))),
+            _ => self.previous.take(),
+        };
+        // Only the previous lines' conversions are still of use
+        let mut san_cache = self.san_cache.take();
+        self.clear();
+        if let Some((ref old, _)) = previous {
+            let key = old.zobrist_hash(EnPassantMode::Legal);
+            if let Some(lines) = san_cache.remove(&key) {
+                self.san_cache.get_mut().insert(key, lines);
+            }
         }
+        self.previous = previous;
+        self.position = Some(position);
+    }
+
+    /// SAN of a PV from a position, converted on first use
+    pub fn san(&self, position: &Chess, pv: &[String]) -> Rc<[String]> {
+        let mut san_cache = self.san_cache.borrow_mut();
+        let lines = san_cache
+            .entry(position.zobrist_hash(EnPassantMode::Legal))
+            .or_default();
+        if let Some(san) = lines.get(pv) {
+            return san.clone();
+        }
+        let san: Rc<[String]> = uci_to_san(position, pv).into();
+        lines.insert(pv.to_vec(), san.clone());
+        san
     }
 
     /// Update with new analysis info
@@ -53,31 +125,116 @@ impl AnalysisState {
         if info.hashfull.is_some() {
             self.hashfull = info.hashfull;
         }
+        if info.tbhits.is_some() {
+            self.tbhits = info.tbhits;
+        }
+        if info.currmove.is_some() {
+            self.currmove = info.currmove.clone();
+            self.currmovenumber = info.currmovenumber;
+        }
 
         // Update the appropriate line based on MultiPV
         let line_idx = info.multipv.unwrap_or(1).saturating_sub(1) as usize;
 
-        // Only update if we have a PV (principal variation)
-        if !info.pv.is_empty() {
+        // Only update if we have a PV (principal variation), and never replace a
+        // line with a shallower one (a deepened search starts again from depth 1)
+        let shallower = self.lines.get(line_idx).and_then(|l| l.depth) > info.depth;
+        if !info.pv.is_empty() && !shallower {
             // Ensure we have enough slots
             while self.lines.len() <= line_idx {
                 self.lines.push(AnalysisInfo::default());
             }
             self.lines[line_idx] = info;
+            if self.is_warm() {
+                self.previous = None;
+            }
         }
     }
 
+    /// Has the search finished at its target depth?
+    pub fn is_done(&self) -> bool {
+        !self.is_running
+            && !self.is_paused
+            && self
+                .lines
+                .first()
+                .and_then(|l| l.depth)
+                .is_some_and(|d| d >= self.target_depth)
+    }
+
     /// Clear analysis state
     pub fn clear(&mut self) {
         self.lines.clear();
         self.nodes = None;
         self.nps = None;
         self.hashfull = None;
+        self.tbhits = None;
+        self.currmove = None;
+        self.currmovenumber = None;
+        self.position = None;
+        self.previous = None;
+        self.san_cache.get_mut().clear();
+    }
+}
+
+/// Format a PV as SAN with move numbers, e.g. "12... Nf6 13. Bg5 Be7"
+pub fn format_pv(position: &Chess, pv: &[String]) -> String {
+    number_moves(position, &uci_to_san(position, pv))
+}
+
+/// Number SAN moves played from a position
+fn number_moves(position: &Chess, san_moves: &[String]) -> String {
+    let mut fullmove = position.fullmoves().get();
+    let mut white_to_move = position.turn() == shakmaty::Color::White;
+    let mut out = Vec::new();
+
+    for (i, san) in san_moves.iter().enumerate() {
+        if white_to_move {
+            out.push(format!("{}. {}", fullmove, san));
+        } else if i == 0 {
+            out.push(format!("{}... {}", fullmove, san));
+        } else {
+            out.push(san.clone());
+        }
+        if !white_to_move {
+            fullmove += 1;
+        }
+        white_to_move = !white_to_move;
+    }
+
+    out.join(" ")
+}
+
+/// Split text into lines of at most `width` characters at word boundaries
+pub(super) fn wrap_words(text: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut current = String::new();
+    for word in text.split_whitespace() {
+        if !current.is_empty() && current.len() + 1 + word.len() > width {
+            lines.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push(' ');
+        }
+        current.push_str(word);
+    }
+    if !current.is_empty() {
+        lines.push(current);
+    }
+    lines
+}
+
+/// Grey out lines belonging to an earlier search
+fn dim_lines(lines: &mut [Line]) {
+    for line in lines {
+        for span in line.spans.iter_mut() {
+            span.style = Style::default().fg(Color::DarkGray);
+        }
     }
 }
 
 /// Convert a list of UCI move strings to SAN notation given a starting position
-fn uci_to_san(position: &Chess, uci_moves: &[String]) -> Vec<String> {
+pub fn uci_to_san(position: &Chess, uci_moves: &[String]) -> Vec<String> {
     let mut pos = position.clone();
     let mut san_moves = Vec::new();
 
@@ -112,6 +269,28 @@ pub struct AnalysisWidget<'a> {
     position: &'a Chess,
     multipv: u32,
     eval_only: bool,
+    /// Panel title (engine name) when comparing engines
+    title: Option<String>,
+    /// Highlight the best move because another engine prefers a different one
+    disagree: bool,
+    /// Show scores from White's point of view instead of the side to move
+    white_perspective: bool,
+    /// Line under the selection cursor
+    selected: Option<usize>,
+    /// Show the selected line's full PV
+    expanded: bool,
+    /// Opponent's threat (formatted line), shown under the evaluation
+    threat: Option<String>,
+    /// Online tablebase verdict, shown under the evaluation
+    tablebase: Option<String>,
+    /// Cloud database moves, shown under the evaluation
+    cloud: Option<String>,
+    /// Lines are restricted to user-chosen candidate moves
+    candidates: bool,
+    /// Show just the evaluation and best line on one row, without a border
+    compact: bool,
+    /// Colors for good and bad scores
+    palette: Palette,
 }
 
 impl<'a> AnalysisWidget<'a> {
@@ -126,21 +305,173 @@ impl<'a> AnalysisWidget<'a> {
             position,
             multipv,
             eval_only,
+            title: None,
+            disagree: false,
+            white_perspective: false,
+            selected: None,
+            expanded: false,
+            threat: None,
+            tablebase: None,
+            cloud: None,
+            candidates: false,
+            compact: false,
+            palette: Palette::Standard,
+        }
+    }
+
+    /// Color scores and warnings with the given palette
+    pub fn palette(mut self, palette: Palette) -> Self {
+        self.palette = palette;
+        self
+    }
+
+    /// Collapse the panel to one row for small terminals
+    pub fn compact(mut self, compact: bool) -> Self {
+        self.compact = compact;
+        self
+    }
+
+    /// Label the lines as the user's candidate moves
+    pub fn candidates(mut self, candidates: bool) -> Self {
+        self.candidates = candidates;
+        self
+    }
+
+    /// Show what the opponent threatens
+    pub fn threat(mut self, threat: Option<String>) -> Self {
+        self.threat = threat;
+        self
+    }
+
+    /// Show the online tablebase's verdict
+    pub fn tablebase(mut self, tablebase: Option<String>) -> Self {
+        self.tablebase = tablebase;
+        self
+    }
+
+    /// Show the cloud database's best moves
+    pub fn cloud(mut self, cloud: Option<String>) -> Self {
+        self.cloud = cloud;
+        self
+    }
+
+    /// Highlight a line, optionally showing its full PV
+    pub fn selected(mut self, selected: Option<usize>, expanded: bool) -> Self {
+        self.selected = selected;
+        self.expanded = expanded;
+        self
+    }
+
+    /// Show scores from White's point of view instead of the side to move
+    pub fn white_perspective(mut self, white_perspective: bool) -> Self {
+        self.white_perspective = white_perspective;
+        self
+    }
+
+    /// Should engine scores (side-to-move relative) be negated for display?
+    fn negate(&self, position: &Chess) -> bool {
+        self.white_perspective && position.turn() == shakmaty::Color::Black
+    }
+
+    /// Score of a line as (cp, mate) in the display perspective
+    fn score(&self, info: &AnalysisInfo, position: &Chess) -> (Option<i32>, Option<i32>) {
+        if self.negate(position) {
+            (info.score_cp.map(|c| -c), info.score_mate.map(|m| -m))
+        } else {
+            (info.score_cp, info.score_mate)
         }
     }
+
+    /// Score of a line, colored by who it favors
+    fn score_span(&self, info: &AnalysisInfo, position: &Chess) -> Span<'static> {
+        let (score_cp, score_mate) = self.score(info, position);
+        let score_color = if score_mate.is_some() {
+            Color::Yellow
+        } else if let Some(cp) = score_cp {
+            if cp > 100 {
+                self.palette.good()
+            } else if cp < -100 {
+                self.palette.bad()
+            } else {
+                Color::White
+            }
+        } else {
+            Color::White
+        };
+        Span::styled(
+            format_score(score_cp, score_mate),
+            Style::default()
+                .fg(score_color)
+                .add_modifier(Modifier::BOLD),
+        )
+    }
+
+    /// One row: evaluation, depth and the best line
+    fn render_compact(&self, area: Rect, buf: &mut Buffer) {
+        let mut line = Line::from(Span::styled("Eval: ", Style::default().fg(Color::DarkGray)));
+        match self.state.lines.first() {
+            Some(info) => {
+                line.push_span(self.score_span(info, self.position));
+                line.push_span(Span::styled(
+                    format!("  d{}  ", info.depth.unwrap_or(0)),
+                    Style::default().fg(Color::DarkGray),
+                ));
+                line.push_span(Span::styled(
+                    format_pv(self.position, &info.pv),
+                    Style::default().fg(Color::White),
+                ));
+            }
+            None => line.push_span(Span::styled("---", Style::default().fg(Color::DarkGray))),
+        }
+        if self.state.is_paused {
+            line.push_span(Span::styled(
+                " (paused)",
+                Style::default().fg(Color::DarkGray),
+            ));
+        }
+        Paragraph::new(line).render(area, buf);
+    }
+
+    /// Set the panel title, replacing "Analysis"
+    pub fn title(mut self, title: Option<String>) -> Self {
+        self.title = title;
+        self
+    }
+
+    /// Mark the best line as disputed by a second engine
+    pub fn disagree(mut self, disagree: bool) -> Self {
+        self.disagree = disagree;
+        self
+    }
 }
 
 impl Widget for AnalysisWidget<'_> {
     fn render(self, area: Rect, buf: &mut Buffer) {
+        if self.compact {
+            self.render_compact(area, buf);
+            return;
+        }
+
+        let name = self.title.as_deref().unwrap_or("Analysis");
         let status = if self.state.is_paused {
-            " Analysis (PAUSED) "
+            format!(" {} (PAUSED) ", name)
         } else if self.state.is_running {
-            " Analysis "
+            format!(" {} ", name)
+        } else if self.state.is_done() {
+            format!(" {} (done, + to go deeper) ", name)
         } else {
-            " Analysis (stopped) "
+            format!(" {} (stopped) ", name)
         };
 
-        let block = Block::default().borders(Borders::ALL).title(status);
+        let mut block = Block::default().borders(Borders::ALL).title(status);
+        if self.disagree {
+            block = block.title(Span::styled(
+                " disagree ",
+                Style::default()
+                    .fg(self.palette.bad())
+                    .add_modifier(Modifier::BOLD),
+            ));
+        }
 
         let inner = block.inner(area);
         block.render(area, buf);
@@ -153,44 +484,73 @@ impl Widget for AnalysisWidget<'_> {
 
         if !self.eval_only {
             // Depth and status line
-            let current_depth = self.state.lines.first().and_then(|l| l.depth).unwrap_or(0);
+            let first = self.state.lines.first();
+            let current_depth = first.and_then(|l| l.depth).unwrap_or(0);
 
-            let depth_line = Line::from(vec![
+            let mut depth_line = Line::from(vec![
                 Span::styled("Depth: ", Style::default().fg(Color::DarkGray)),
                 Span::styled(
                     format!("{}/{}", current_depth, self.state.target_depth),
                     Style::default().fg(Color::White),
                 ),
             ]);
+            if let Some(seldepth) = first.and_then(|l| l.seldepth) {
+                depth_line.push_span(Span::styled(
+                    format!(" (sel {})", seldepth),
+                    Style::default().fg(Color::DarkGray),
+                ));
+            }
+
+            // Root move being examined right now
+            if let (Some(currmove), true) = (&self.state.currmove, self.state.is_running) {
+                let san = self
+                    .state
+                    .san(self.position, std::slice::from_ref(currmove))
+                    .first()
+                    .cloned()
+                    .unwrap_or_else(|| currmove.clone());
+                let progress = match self.state.currmovenumber {
+                    Some(n) => format!(" {}/{}", n, self.position.legal_moves().len()),
+                    None => String::new(),
+                };
+                depth_line.push_span(Span::styled(
+                    "  Searching: ",
+                    Style::default().fg(Color::DarkGray),
+                ));
+                depth_line.push_span(Span::styled(
+                    format!("{}{}", san, progress),
+                    Style::default().fg(Color::White),
+                ));
+            }
             lines.push(depth_line);
         }
 
-        // Main evaluation (from first line)
-        if let Some(first_line) = self.state.lines.first() {
-            let score = format_score(first_line.score_cp, first_line.score_mate);
-            let score_color = if first_line.score_mate.is_some() {
-                Color::Yellow
-            } else if let Some(cp) = first_line.score_cp {
-                if cp > 100 {
-                    Color::Green
-                } else if cp < -100 {
-                    Color::Red
-                } else {
-                    Color::White
-                }
-            } else {
-                Color::White
-            };
+        // Until the new search warms up, keep showing the previous lines dimmed
+        let (shown, position, dim) = match self.state.previous {
+            Some((ref position, ref lines)) if !self.state.is_warm() => {
+                (lines.as_slice(), position, true)
+            }
+            _ => (self.state.lines.as_slice(), self.position, false),
+        };
+        let first_lines = lines.len();
 
-            let eval_line = Line::from(vec![
+        // Main evaluation (from first line)
+        if let Some(first_line) = shown.first() {
+            let mut eval_line = Line::from(vec![
                 Span::styled("Eval: ", Style::default().fg(Color::DarkGray)),
-                Span::styled(
-                    score,
-                    Style::default()
-                        .fg(score_color)
-                        .add_modifier(Modifier::BOLD),
-                ),
+                self.score_span(first_line, position),
             ]);
+            if let Some((win, draw, loss)) = first_line.wdl {
+                let wdl = if self.negate(position) {
+                    (loss, draw, win)
+                } else {
+                    (win, draw, loss)
+                };
+                eval_line.push_span(Span::styled(
+                    format!("  {}", format_wdl(wdl)),
+                    Style::default().fg(Color::Gray),
+                ));
+            }
             lines.push(eval_line);
         } else {
             lines.push(Line::from(vec![
@@ -199,7 +559,31 @@ impl Widget for AnalysisWidget<'_> {
             ]));
         }
 
+        if let Some(ref threat) = self.threat {
+            lines.push(Line::from(vec![
+                Span::styled("Threat: ", Style::default().fg(Color::DarkGray)),
+                Span::styled(threat.clone(), Style::default().fg(self.palette.bad())),
+            ]));
+        }
+
+        if let Some(ref tablebase) = self.tablebase {
+            lines.push(Line::from(vec![
+                Span::styled("Tablebase: ", Style::default().fg(Color::DarkGray)),
+                Span::styled(tablebase.clone(), Style::default().fg(Color::Cyan)),
+            ]));
+        }
+
+        if let Some(ref cloud) = self.cloud {
+            lines.push(Line::from(vec![
+                Span::styled("Cloud: ", Style::default().fg(Color::DarkGray)),
+                Span::styled(cloud.clone(), Style::default().fg(Color::Cyan)),
+            ]));
+        }
+
         if self.eval_only {
+            if dim {
+                dim_lines(&mut lines[first_lines..]);
+            }
             let paragraph = Paragraph::new(lines);
             paragraph.render(inner, buf);
             return;
@@ -209,22 +593,21 @@ impl Widget for AnalysisWidget<'_> {
 
         // Best lines header
         lines.push(Line::from(Span::styled(
-            "Best lines:",
+            if self.candidates {
+                "Candidate moves:"
+            } else {
+                "Best lines:"
+            },
             Style::default().fg(Color::Cyan),
         )));
 
         // Show each PV line
-        for (idx, info) in self
-            .state
-            .lines
-            .iter()
-            .take(self.multipv as usize)
-            .enumerate()
-        {
-            let score = format_score(info.score_cp, info.score_mate);
+        for (idx, info) in shown.iter().take(self.multipv as usize).enumerate() {
+            let (score_cp, score_mate) = self.score(info, position);
+            let score = format_score(score_cp, score_mate);
 
             // Convert UCI moves to SAN notation
-            let san_moves = uci_to_san(self.position, &info.pv);
+            let san_moves = self.state.san(position, &info.pv);
 
             // Format PV moves (show first few moves)
             let pv_str: String = san_moves
@@ -240,19 +623,41 @@ impl Widget for AnalysisWidget<'_> {
                 pv_str
             };
 
-            let line = Line::from(vec![
+            let pv_style = if self.disagree && idx == 0 && !dim {
+                Style::default()
+                    .fg(self.palette.bad())
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(Color::White)
+            };
+            let is_selected = self.selected == Some(idx);
+            let mut line = Line::from(vec![
                 Span::styled(
                     format!("{}. ", idx + 1),
                     Style::default().fg(Color::DarkGray),
                 ),
                 Span::styled(format!("{:>6} ", score), Style::default().fg(Color::Yellow)),
-                Span::styled(pv_display, Style::default().fg(Color::White)),
+                Span::styled(pv_display, pv_style),
             ]);
+            if is_selected {
+                line = line.style(Style::default().bg(Color::Rgb(50, 50, 70)));
+            }
             lines.push(line);
+
+            if is_selected && self.expanded {
+                let full = number_moves(position, &san_moves);
+                let width = (inner.width as usize).saturating_sub(3).max(10);
+                for chunk in wrap_words(&full, width) {
+                    lines.push(Line::from(Span::styled(
+                        format!("   {}", chunk),
+                        Style::default().fg(Color::Gray),
+                    )));
+                }
+            }
         }
 
         // Fill empty lines if we don't have enough PVs yet
-        for idx in self.state.lines.len()..self.multipv as usize {
+        for idx in shown.len()..self.multipv as usize {
             lines.push(Line::from(vec![
                 Span::styled(
                     format!("{}. ", idx + 1),
@@ -262,6 +667,10 @@ impl Widget for AnalysisWidget<'_> {
             ]));
         }
 
+        if dim {
+            dim_lines(&mut lines[first_lines..]);
+        }
+
         lines.push(Line::from(""));
 
         // Stats line
@@ -276,22 +685,34 @@ impl Widget for AnalysisWidget<'_> {
             .map(format_nodes)
             .unwrap_or_else(|| "---".to_string());
 
-        let stats_line = Line::from(vec![
+        let mut stats_line = Line::from(vec![
             Span::styled("Nodes: ", Style::default().fg(Color::DarkGray)),
             Span::styled(format!("{} ", nodes_str), Style::default().fg(Color::White)),
             Span::styled("NPS: ", Style::default().fg(Color::DarkGray)),
             Span::styled(nps_str, Style::default().fg(Color::White)),
         ]);
+        if let Some(tbhits) = self.state.tbhits.filter(|&n| n > 0) {
+            stats_line.push_span(Span::styled(" TB: ", Style::default().fg(Color::DarkGray)));
+            stats_line.push_span(Span::styled(
+                format_nodes(tbhits),
+                Style::default().fg(Color::Green),
+            ));
+        }
         lines.push(stats_line);
 
         // Hash usage
         if let Some(hashfull) = self.state.hashfull {
             let hash_percent = hashfull as f64 / 10.0;
+            let hash_color = if hashfull >= 950 {
+                Color::Yellow
+            } else {
+                Color::White
+            };
             let hash_line = Line::from(vec![
                 Span::styled("Hash: ", Style::default().fg(Color::DarkGray)),
                 Span::styled(
                     format!("{:.1}%", hash_percent),
-                    Style::default().fg(Color::White),
+                    Style::default().fg(hash_color),
                 ),
             ]);
             lines.push(hash_line);
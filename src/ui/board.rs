@@ -1,13 +1,16 @@
 use ratatui::{
-    buffer::Buffer,
+    buffer::{Buffer, Cell},
     layout::Rect,
-    style::{Color, Style},
+    style::{Color, Modifier, Style},
+    text::Line,
     widgets::{Block, Borders, Widget},
 };
-use shakmaty::{File, Move, Piece, Rank, Role, Square};
+use shakmaty::{Bitboard, File, Move, Piece, Position, Rank, Role, Square};
+use std::cell::RefCell;
 
-use crate::chess::{piece_to_char, Game, PieceStyle};
-use crate::config::UiConfig;
+use crate::chess::{piece_to_char, Arrow, Game, MarkColor, PieceStyle};
+use crate::config::{PiecesConfig, UiConfig};
+use super::{BoardArrow, BoardImage, BoardSquares, Palette};
 
 /// Quarter-block pixel patterns for pieces (6 rows × 10 cols)
 /// Each piece is defined at 2x resolution for smoother rendering
@@ -101,6 +104,40 @@ fn quadrants_to_char(tl: bool, tr: bool, bl: bool, br: bool) -> char {
     }
 }
 
+/// What a square's cells are drawn from
+type SquareKey = (Color, Option<Piece>, Option<(char, MarkColor)>);
+
+/// Where and how the squares were drawn; any change redraws them all
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct CacheLayout {
+    origin: (u16, u16),
+    cell_size: (u16, u16),
+    flipped: bool,
+    piece_style: PieceStyle,
+    graphics: bool,
+    half_blocks: bool,
+}
+
+/// Cells of each square from the last render, so squares that haven't
+/// changed are copied instead of drawn again
+#[derive(Debug, Default)]
+pub struct BoardCache {
+    layout: Option<CacheLayout>,
+    glyphs: Option<PiecesConfig>,
+    squares: Vec<Option<(SquareKey, Vec<Cell>)>>,
+}
+
+impl BoardCache {
+    /// Forget the cached squares, keeping them only if the layout and glyphs
+    /// are the same
+    fn validate(&mut self, layout: CacheLayout, glyphs: Option<&PiecesConfig>) {
+        if self.layout != Some(layout) || self.glyphs.as_ref() != glyphs {
+            self.layout = Some(layout);
+            self.glyphs = glyphs.cloned();
+            self.squares = vec![None; 64];
+        }
+    }
+}
 
 /// Chess board widget
 pub struct BoardWidget<'a> {
@@ -108,21 +145,340 @@ pub struct BoardWidget<'a> {
     config: &'a UiConfig,
     last_move: Option<&'a Move>,
     piece_style: PieceStyle,
+    /// Custom glyphs replacing the piece style's
+    glyphs: Option<&'a PiecesConfig>,
+    /// Highlight colors
+    palette: Palette,
+    /// Squares of hanging pieces to mark (empty when the overlay is off)
+    hanging: Bitboard,
+    /// Square tints from arrows and circles, indexed by square
+    marks: [Option<MarkColor>; 64],
+    /// Arrow glyphs drawn along arrow paths, indexed by square
+    arrow_glyphs: [Option<(char, MarkColor)>; 64],
+    /// Arrows of the annotation and any extra ones
+    arrows: Vec<Arrow>,
     /// Show captured pieces inside the board pane
     show_captured: bool,
+    /// Centipawns lost by the best move to each square (heatmap mode)
+    heatmap: [Option<i32>; 64],
+    /// Leave the squares blank for an image drawn over them
+    graphics: bool,
+    /// Draw piece sprites with half-block characters when there's room
+    half_blocks: bool,
+    /// Squares from the last render to reuse
+    cache: Option<&'a RefCell<BoardCache>>,
 }
 
 impl<'a> BoardWidget<'a> {
     pub fn new(game: &'a Game, config: &'a UiConfig) -> Self {
+        let (marks, arrow_glyphs) = Self::collect_marks(game, &[], config.flip_board);
         Self {
             game,
             config,
             last_move: game.last_move(),
-            piece_style: config.get_piece_style(),
+            // Letters tell the sides apart without color
+            piece_style: if config.screen_reader {
+                PieceStyle::Ascii
+            } else {
+                config.get_piece_style()
+            },
+            glyphs: None,
+            palette: config.get_palette(),
+            hanging: if config.show_hanging {
+                game.hanging_pieces()
+            } else {
+                Bitboard::EMPTY
+            },
+            marks,
+            arrow_glyphs,
+            arrows: game.annotation().arrows.clone(),
             show_captured: true,
+            heatmap: [None; 64],
+            graphics: false,
+            half_blocks: false,
+            cache: None,
         }
     }
 
+    /// Reuse the cells of squares that are unchanged since the last render
+    pub fn cache(mut self, cache: &'a RefCell<BoardCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Leave the squares blank so a graphics protocol can draw the board
+    pub fn graphics(mut self, graphics: bool) -> Self {
+        self.graphics = graphics;
+        self
+    }
+
+    /// Draw pieces with custom glyphs where they're set
+    pub fn glyphs(mut self, glyphs: &'a PiecesConfig) -> Self {
+        self.glyphs = Some(glyphs);
+        self
+    }
+
+    /// Text for a piece: its custom glyph, or the style's character
+    fn piece_text(&self, piece: Piece, style: PieceStyle) -> String {
+        self.glyphs
+            .and_then(|glyphs| glyphs.glyph(piece))
+            .map_or_else(|| piece_to_char(piece, style).to_string(), str::to_string)
+    }
+
+    /// Show the captured pieces above and below the board
+    pub fn show_captured(mut self, show_captured: bool) -> Self {
+        self.show_captured = show_captured;
+        self
+    }
+
+    /// Draw the pieces as sprites of half-block pixels, as large as the
+    /// board pane allows
+    pub fn half_blocks(mut self, half_blocks: bool) -> Self {
+        self.half_blocks = half_blocks;
+        self
+    }
+
+    /// Columns and rows around the squares for coordinates and the captured
+    /// pieces
+    fn chrome(&self) -> (u16, u16) {
+        let coords = u16::from(self.config.show_coordinates);
+        let captured = u16::from(self.show_captured);
+        (3 * coords, coords + 2 * captured)
+    }
+
+    /// Check if squares of a size fit an inner area
+    fn fits(&self, inner: Rect, (width, height): (u16, u16)) -> bool {
+        let (extra_width, extra_height) = self.chrome();
+        8 * width + extra_width <= inner.width && 8 * height + extra_height <= inner.height
+    }
+
+    /// Half-block square size in use for an inner area, or None when there's
+    /// no room for a readable one. Each cell holds two pixels stacked, so a
+    /// square of `w`×`w/2` cells is `w` pixels on each side.
+    fn half_block_size(&self, inner: Rect) -> Option<(u16, u16)> {
+        if !self.half_blocks || self.graphics {
+            return None;
+        }
+        (8..=16)
+            .rev()
+            .step_by(2)
+            .map(|w| (w, w / 2))
+            .find(|&size| self.fits(inner, size))
+    }
+
+    /// Character cells per square: the size set in the config, or the
+    /// largest one that fits. When only one side is set, the other is the
+    /// one that would fit.
+    fn cell_size(&self, inner: Rect) -> (u16, u16) {
+        if let Some(size) = self.half_block_size(inner) {
+            return size;
+        }
+        // Block pieces take 5×3 cells
+        let blocks = self.piece_style == PieceStyle::Blocks && !self.graphics;
+        let (width, height) = (self.config.cell_width, self.config.cell_height);
+        if width > 0 && height > 0 {
+            return if blocks {
+                (width.max(5), height.max(3))
+            } else {
+                (width, height)
+            };
+        }
+
+        // Cells are about twice as tall as wide, so twice as many columns as
+        // rows look square: 4×2 for text pieces and 7×4 for blocks
+        let sizes: Vec<(u16, u16)> = if blocks {
+            (3..=8).map(|h| (2 * h - 1, h)).collect()
+        } else {
+            std::iter::once((3, 1)).chain((2..=8).map(|h| (2 * h, h))).collect()
+        };
+        let (fit_width, fit_height) = sizes
+            .iter()
+            .rev()
+            .find(|&&size| self.fits(inner, size))
+            .copied()
+            .unwrap_or(sizes[0]);
+        match (width, height) {
+            (0, 0) => (fit_width, fit_height),
+            (0, height) => (fit_width, if blocks { height.max(3) } else { height }),
+            (width, _) => (if blocks { width.max(5) } else { width }, fit_height),
+        }
+    }
+
+    /// Rows needed by the board pane and the status line under it, in at
+    /// most the given area
+    pub fn pane_height(&self, area: Rect) -> u16 {
+        let mut inner = Block::default().borders(Borders::ALL).inner(area);
+        inner.height = inner.height.saturating_sub(1);
+        let (_, cell_height) = self.cell_size(inner);
+        8 * cell_height + self.chrome().1 + 3
+    }
+
+    /// Columns needed by the board pane, in at most the given area (which
+    /// includes the status line, as for `pane_height`)
+    pub fn pane_width(&self, area: Rect) -> u16 {
+        let mut inner = Block::default().borders(Borders::ALL).inner(area);
+        inner.height = inner.height.saturating_sub(1);
+        let (cell_width, _) = self.cell_size(inner);
+        8 * cell_width + self.chrome().0 + 2
+    }
+
+    /// Cells covered by the squares when the board is rendered in an area
+    pub fn squares_area(&self, area: Rect) -> Option<Rect> {
+        let inner = Block::default().borders(Borders::ALL).inner(area);
+        let (cell_width, cell_height) = self.cell_size(inner);
+        if 8 * cell_width > inner.width || 8 * cell_height > inner.height {
+            return None;
+        }
+        let board_width = 8 * cell_width;
+        let coord_width = if self.config.show_coordinates { 3 } else { 0 };
+        let captured_height = if self.show_captured { 1 } else { 0 };
+        let total_width = board_width + coord_width;
+        let x = inner.x + (inner.width.saturating_sub(total_width)) / 2 + coord_width;
+        let y = inner.y + captured_height;
+        Some(Rect::new(x, y, board_width, 8 * cell_height).intersection(inner))
+    }
+
+    /// Background color and piece of each square as shown, row by row from
+    /// the top left
+    pub fn squares(&self) -> BoardSquares {
+        let mut squares = [(Color::Reset, None); 64];
+        for (index, entry) in squares.iter_mut().enumerate() {
+            let square = self.display_square(index);
+            *entry = (self.square_bg(square), self.game.piece_at(square));
+        }
+        squares
+    }
+
+    /// The squares without highlights or marks
+    pub fn plain_squares(&self) -> BoardSquares {
+        let mut squares = [(Color::Reset, None); 64];
+        for (index, entry) in squares.iter_mut().enumerate() {
+            let square = self.display_square(index);
+            let color = self.get_square_color(square.file(), square.rank());
+            *entry = (color, self.game.piece_at(square));
+        }
+        squares
+    }
+
+    /// The arrows for drawing over an image of the squares
+    pub fn image_arrows(&self) -> Vec<BoardArrow> {
+        let index = |square: Square| {
+            let (file, rank) = (square.file() as usize, square.rank() as usize);
+            if self.config.flip_board {
+                rank * 8 + 7 - file
+            } else {
+                (7 - rank) * 8 + file
+            }
+        };
+        self.arrows
+            .iter()
+            .map(|arrow| BoardArrow {
+                from: index(arrow.from),
+                to: index(arrow.to),
+                color: self.palette.mark_fg(arrow.color),
+            })
+            .collect()
+    }
+
+    /// Square shown at an index of the squares, row by row from the top left
+    fn display_square(&self, index: usize) -> Square {
+        let (row, col) = (index / 8, index % 8);
+        let (file, rank) = if self.config.flip_board {
+            (File::new(7 - col as u32), Rank::new(row as u32))
+        } else {
+            (File::new(col as u32), Rank::new(7 - row as u32))
+        };
+        Square::from_coords(file, rank)
+    }
+
+    /// Background color of a square, with any overlay
+    fn square_bg(&self, square: Square) -> Color {
+        let (file, rank) = (square.file(), square.rank());
+        let light = Self::is_light(file, rank);
+        if self.hanging.contains(square) {
+            self.palette.hanging(light)
+        } else if let Some(mark) = self.marks[square as usize] {
+            self.palette.mark(mark, light)
+        } else if self.is_checked_king(square) {
+            self.palette.check(light)
+        } else if let Some(loss) = self.heatmap[square as usize] {
+            self.palette.heatmap(loss, light)
+        } else if self.is_highlighted(square) {
+            self.palette.last_move(light)
+        } else {
+            self.get_square_color(file, rank)
+        }
+    }
+
+    /// Tint move destinations by how much the best move there loses
+    pub fn heatmap(mut self, heatmap: [Option<i32>; 64]) -> Self {
+        self.heatmap = heatmap;
+        self
+    }
+
+    /// Draw additional arrows (e.g. an engine threat) on top of the annotations
+    pub fn extra_arrows(mut self, arrows: &[Arrow]) -> Self {
+        let (marks, arrow_glyphs) =
+            Self::collect_marks(self.game, arrows, self.config.flip_board);
+        self.marks = marks;
+        self.arrow_glyphs = arrow_glyphs;
+        self.arrows = self.game.annotation().arrows.clone();
+        self.arrows.extend_from_slice(arrows);
+        self
+    }
+
+    /// Work out square tints and path glyphs for the current position's arrows and circles
+    #[allow(clippy::type_complexity)]
+    fn collect_marks(
+        game: &Game,
+        extra_arrows: &[Arrow],
+        flipped: bool,
+    ) -> ([Option<MarkColor>; 64], [Option<(char, MarkColor)>; 64]) {
+        let mut marks = [None; 64];
+        let mut glyphs = [None; 64];
+        let annotation = game.annotation();
+
+        for circle in &annotation.circles {
+            marks[circle.square as usize] = Some(circle.color);
+        }
+
+        for arrow in annotation.arrows.iter().chain(extra_arrows) {
+            marks[arrow.from as usize] = Some(arrow.color);
+            marks[arrow.to as usize] = Some(arrow.color);
+
+            let df = arrow.to.file() as i32 - arrow.from.file() as i32;
+            let dr = arrow.to.rank() as i32 - arrow.from.rank() as i32;
+
+            // Screen direction: right and up are positive
+            let (dx, dy) = if flipped { (-df, -dr) } else { (df, dr) };
+            let glyph = match (dx.signum(), dy.signum()) {
+                (1, 0) => '→',
+                (-1, 0) => '←',
+                (0, 1) => '↑',
+                (0, -1) => '↓',
+                (1, 1) => '↗',
+                (-1, 1) => '↖',
+                (1, -1) => '↘',
+                _ => '↙',
+            };
+
+            // Walk straight and diagonal lines; knight arrows only mark the ends
+            let steps = df.abs().max(dr.abs());
+            if df == 0 || dr == 0 || df.abs() == dr.abs() {
+                for step in 1..=steps {
+                    let file = arrow.from.file() as i32 + df.signum() * step;
+                    let rank = arrow.from.rank() as i32 + dr.signum() * step;
+                    let sq = Square::from_coords(File::new(file as u32), Rank::new(rank as u32));
+                    glyphs[sq as usize] = Some((glyph, arrow.color));
+                }
+            } else {
+                glyphs[arrow.to as usize] = Some((glyph, arrow.color));
+            }
+        }
+
+        (marks, glyphs)
+    }
+
     fn render_captured_pieces(&self, area: Rect, buf: &mut Buffer, show_white_captures: bool) {
         if area.width < 2 || area.height < 1 {
             return;
@@ -163,6 +519,21 @@ impl<'a> BoardWidget<'a> {
         ];
         let mut display_parts: Vec<(String, Color)> = Vec::new();
 
+        // Use Unicode for captured pieces display (blocks don't work inline)
+        let style_for_captured = if self.piece_style == PieceStyle::Blocks {
+            PieceStyle::Unicode
+        } else {
+            self.piece_style
+        };
+        let piece_part = |piece: Piece| {
+            let fg_color = if piece.color == shakmaty::Color::White {
+                Color::White
+            } else {
+                Color::DarkGray
+            };
+            (self.piece_text(piece, style_for_captured), fg_color)
+        };
+
         for role in piece_order {
             let count = pieces.iter().filter(|&&r| r == role).count();
             if count > 0 {
@@ -171,36 +542,42 @@ impl<'a> BoardWidget<'a> {
                 } else {
                     shakmaty::Color::White
                 };
-                let piece = Piece { color, role };
-                // Use Unicode for captured pieces display (blocks don't work inline)
-                let style_for_captured = if self.piece_style == PieceStyle::Blocks {
-                    PieceStyle::Unicode
-                } else {
-                    self.piece_style
-                };
-                let piece_char = piece_to_char(piece, style_for_captured);
-
-                let fg_color = if piece.color == shakmaty::Color::White {
-                    Color::White
-                } else {
-                    Color::DarkGray
-                };
 
                 // Show piece repeated by count
                 for _ in 0..count {
-                    display_parts.push((format!("{}", piece_char), fg_color));
+                    display_parts.push(piece_part(Piece { color, role }));
                 }
             }
         }
 
+        // Then this side's promotions as the pawn and what it became, so
+        // the advantage (which gains the piece but loses the pawn) adds up
+        let (white_promoted, black_promoted) = self.game.promoted_pieces();
+        let (promoted, color) = if show_white_captures {
+            (&white_promoted, shakmaty::Color::White)
+        } else {
+            (&black_promoted, shakmaty::Color::Black)
+        };
+        for &role in promoted {
+            if !display_parts.is_empty() {
+                display_parts.push((" ".to_string(), Color::DarkGray));
+            }
+            display_parts.push(piece_part(Piece {
+                color,
+                role: Role::Pawn,
+            }));
+            display_parts.push(("→".to_string(), Color::DarkGray));
+            display_parts.push(piece_part(Piece { color, role }));
+        }
+
         // Render horizontally
         let mut x = area.x;
         for (text, fg_color) in &display_parts {
             if x >= area.x + area.width {
                 break;
             }
-            buf.set_string(x, area.y, text, Style::default().fg(*fg_color));
-            x += text.chars().count() as u16;
+            let width = (area.x + area.width - x) as usize;
+            (x, _) = buf.set_stringn(x, area.y, text, width, Style::default().fg(*fg_color));
         }
 
         // Show material advantage at the end if this side is ahead
@@ -210,7 +587,7 @@ impl<'a> BoardWidget<'a> {
                     x,
                     area.y,
                     format!("+{}", adv),
-                    Style::default().fg(Color::Green),
+                    Style::default().fg(self.palette.good()),
                 );
             }
         }
@@ -225,13 +602,8 @@ impl<'a> BoardWidget<'a> {
         }
     }
 
-    fn get_highlight_color(&self, file: File, rank: Rank) -> Color {
-        let is_light = (file as u8 + rank as u8) % 2 == 1;
-        if is_light {
-            Color::Rgb(205, 210, 106) // Light highlight
-        } else {
-            Color::Rgb(170, 162, 58) // Dark highlight
-        }
+    fn is_light(file: File, rank: Rank) -> bool {
+        (file as u8 + rank as u8) % 2 == 1
     }
 
     fn is_highlighted(&self, square: Square) -> bool {
@@ -246,6 +618,13 @@ impl<'a> BoardWidget<'a> {
         }
         false
     }
+
+    fn is_checked_king(&self, square: Square) -> bool {
+        let position = self.game.position();
+        self.config.highlight_last_move
+            && position.is_check()
+            && position.board().king_of(position.turn()) == Some(square)
+    }
 }
 
 impl Widget for BoardWidget<'_> {
@@ -255,20 +634,14 @@ impl Widget for BoardWidget<'_> {
         let inner = block.inner(area);
         block.render(area, buf);
 
-        if inner.width < 26 || inner.height < 10 {
+        // Calculate cell size based on piece style
+        // The 5×3 piece pattern is centered within the cell
+        let (cell_width, cell_height) = self.cell_size(inner);
+        if 8 * cell_width > inner.width || 8 * cell_height > inner.height {
             // Not enough space to render board
             return;
         }
 
-        // Calculate cell size based on piece style
-        // Blocks mode uses 7×4 cells (visually ~square since chars are ~2:1 tall:wide)
-        // The 5×3 piece pattern is centered within the cell
-        let (cell_width, cell_height) = if self.piece_style == PieceStyle::Blocks {
-            (7u16, 4u16)
-        } else {
-            (4u16, 2u16)
-        };
-
         // Piece pattern dimensions for centering (blocks mode only)
         let piece_width = 5u16;
         let piece_height = 3u16;
@@ -309,6 +682,45 @@ impl Widget for BoardWidget<'_> {
             (0..8).map(|f| File::new(f)).collect()
         };
 
+        // Only draw the squares that changed since the cached render
+        let half_block_size = self.half_block_size(inner);
+        let keys: Vec<SquareKey> = Square::ALL
+            .iter()
+            .map(|&square| {
+                (
+                    self.square_bg(square),
+                    self.game.piece_at(square),
+                    self.arrow_glyphs[square as usize],
+                )
+            })
+            .collect();
+        let mut cache = self.cache.map(|cache| cache.borrow_mut());
+        let dirty: Vec<bool> = match cache.as_deref_mut() {
+            Some(cache) => {
+                cache.validate(
+                    CacheLayout {
+                        origin: (start_x, start_y),
+                        cell_size: (cell_width, cell_height),
+                        flipped: self.config.flip_board,
+                        piece_style: self.piece_style,
+                        graphics: self.graphics,
+                        half_blocks: half_block_size.is_some(),
+                    },
+                    self.glyphs,
+                );
+                keys.iter()
+                    .zip(&cache.squares)
+                    .map(|(key, cached)| cached.as_ref().is_none_or(|(k, _)| k != key))
+                    .collect()
+            }
+            None => vec![true; 64],
+        };
+
+        // Half-block boards are drawn from an image of the whole board
+        let image = half_block_size
+            .filter(|_| dirty.contains(&true))
+            .map(|(width, _)| BoardImage::with_square_size(&self.squares(), width as usize));
+
         // Render the board
         for (row_idx, &rank) in ranks.iter().enumerate() {
             for row_line in 0..cell_height {
@@ -332,20 +744,58 @@ impl Widget for BoardWidget<'_> {
                 for (col_idx, &file) in files.iter().enumerate() {
                     let x = start_x + (col_idx as u16 * cell_width);
                     let square = Square::from_coords(file, rank);
-
-                    // Determine background color
-                    let bg_color = if self.is_highlighted(square) {
-                        self.get_highlight_color(file, rank)
-                    } else {
-                        self.get_square_color(file, rank)
-                    };
-
-                    let style = Style::default().bg(bg_color);
+                    if !dirty[square as usize] {
+                        continue;
+                    }
 
                     // Fill the entire cell with background color first
+                    // (or leave it blank for the board image)
                     let blank_cell: String = " ".repeat(cell_width as usize);
+                    if self.graphics {
+                        buf.set_string(x, y, &blank_cell, Style::default());
+                        continue;
+                    }
+                    let bg_color = self.square_bg(square);
+                    let style = Style::default().bg(bg_color);
                     buf.set_string(x, y, &blank_cell, style);
 
+                    // Each half-block cell shows two image pixels: the upper
+                    // one as the foreground of ▀ and the lower one behind it
+                    if let Some(image) = &image {
+                        let size = image.size as usize;
+                        let pixel = |px: usize, py: usize| {
+                            let offset = (py * size + px) * 3;
+                            let rgb = &image.pixels[offset..offset + 3];
+                            Color::Rgb(rgb[0], rgb[1], rgb[2])
+                        };
+                        for col in 0..cell_width {
+                            let px = (col_idx as u16 * cell_width + col) as usize;
+                            let py = (row_idx as u16 * cell_height + row_line) as usize * 2;
+                            let cell_style = Style::default().fg(pixel(px, py)).bg(pixel(px, py + 1));
+                            buf.set_string(x + col, y, "▀", cell_style);
+                        }
+                    }
+
+                    // Draw arrow paths through empty squares
+                    if let Some((glyph, mark)) = self.arrow_glyphs[square as usize] {
+                        if row_line == cell_height / 2 && self.game.piece_at(square).is_none() {
+                            let glyph_style = Style::default()
+                                .fg(self.palette.mark_fg(mark))
+                                .bg(bg_color)
+                                .add_modifier(Modifier::BOLD);
+                            buf.set_string(
+                                x + (cell_width - 1) / 2,
+                                y,
+                                glyph.to_string(),
+                                glyph_style,
+                            );
+                        }
+                    }
+
+                    if half_block_size.is_some() {
+                        continue;
+                    }
+
                     // Get piece at square and render it
                     if let Some(piece) = self.game.piece_at(square) {
                         if self.piece_style == PieceStyle::Blocks {
@@ -388,7 +838,8 @@ impl Widget for BoardWidget<'_> {
                         } else {
                             // Render single-character piece (only on middle row)
                             if row_line == cell_height / 2 {
-                                let piece_char = piece_to_char(piece, self.piece_style);
+                                let piece_text = self.piece_text(piece, self.piece_style);
+                                let width = Line::raw(piece_text.as_str()).width() as u16;
 
                                 // Determine piece color
                                 let fg_color = if piece.color == shakmaty::Color::White {
@@ -400,7 +851,13 @@ impl Widget for BoardWidget<'_> {
                                 let piece_style = Style::default().fg(fg_color).bg(bg_color);
 
                                 // Render piece centered in cell (position 1 of 0-3)
-                                buf.set_string(x + 1, y, format!("{}", piece_char), piece_style);
+                                buf.set_stringn(
+                                    x + cell_width.saturating_sub(width) / 2,
+                                    y,
+                                    &piece_text,
+                                    cell_width as usize,
+                                    piece_style,
+                                );
                             }
                         }
                     }
@@ -408,6 +865,27 @@ impl Widget for BoardWidget<'_> {
             }
         }
 
+        // Copy the unchanged squares from the cache, and cache the new ones
+        if let Some(cache) = cache.as_deref_mut() {
+            for (row_idx, &rank) in ranks.iter().enumerate() {
+                for (col_idx, &file) in files.iter().enumerate() {
+                    let square = Square::from_coords(file, rank) as usize;
+                    let x = start_x + col_idx as u16 * cell_width;
+                    let y = start_y + row_idx as u16 * cell_height;
+                    let positions =
+                        (y..y + cell_height).flat_map(|y| (x..x + cell_width).map(move |x| (x, y)));
+                    if dirty[square] {
+                        let cells = positions.map(|position| buf[position].clone()).collect();
+                        cache.squares[square] = Some((keys[square], cells));
+                    } else if let Some((_, cells)) = &cache.squares[square] {
+                        for (position, cell) in positions.zip(cells) {
+                            buf[position] = cell.clone();
+                        }
+                    }
+                }
+            }
+        }
+
         // Render file coordinates
         if self.config.show_coordinates {
             let y = start_y + board_height;
@@ -443,16 +921,56 @@ impl Widget for BoardWidget<'_> {
 /// Widget showing whose turn and game status
 pub struct StatusWidget<'a> {
     game: &'a Game,
+    /// Eval change (centipawns) for the side that made the last move
+    eval_delta: Option<i32>,
+    /// Colors for the eval change
+    palette: Palette,
+    /// Text shown instead of the status, for screen readers
+    announcement: Option<String>,
 }
 
 impl<'a> StatusWidget<'a> {
     pub fn new(game: &'a Game) -> Self {
-        Self { game }
+        Self {
+            game,
+            eval_delta: None,
+            palette: Palette::Standard,
+            announcement: None,
+        }
+    }
+
+    /// Show an announcement of the last move and position instead
+    pub fn announcement(mut self, announcement: Option<String>) -> Self {
+        self.announcement = announcement;
+        self
+    }
+
+    /// Color the eval change with the given palette
+    pub fn palette(mut self, palette: Palette) -> Self {
+        self.palette = palette;
+        self
+    }
+
+    /// Show how the last move changed the evaluation for the side that played it
+    pub fn eval_delta(mut self, eval_delta: Option<i32>) -> Self {
+        self.eval_delta = eval_delta;
+        self
     }
 }
 
 impl Widget for StatusWidget<'_> {
     fn render(self, area: Rect, buf: &mut Buffer) {
+        if let Some(announcement) = &self.announcement {
+            buf.set_stringn(
+                area.x + 1,
+                area.y,
+                announcement,
+                area.width.saturating_sub(1) as usize,
+                Style::default().fg(Color::White),
+            );
+            return;
+        }
+
         let turn = self.game.turn();
         let turn_str = if turn == shakmaty::Color::White {
             "White"
@@ -476,12 +994,8 @@ impl Widget for StatusWidget<'_> {
             format!("{} to move", turn_str)
         };
 
-        // Use nerd font chess king for turn indicator
-        let turn_indicator = if turn == shakmaty::Color::White {
-            "\u{f43f}" // nf-fa-chess_king
-        } else {
-            "\u{f43f}" // same icon, different context
-        };
+        // Use nerd font chess king for turn indicator (same icon, colored by side)
+        let turn_indicator = "\u{f43f}"; // nf-fa-chess_king
 
         let indicator_color = if turn == shakmaty::Color::White {
             Color::White
@@ -501,5 +1015,80 @@ impl Widget for StatusWidget<'_> {
             &status,
             Style::default().fg(Color::White),
         );
+
+        let mut used = 3 + status.chars().count() as u16;
+        if let Some(delta) = self.eval_delta {
+            let (arrow, color) = if delta <= -100 {
+                ("⇓", self.palette.bad())
+            } else if delta <= -30 {
+                ("⇓", Color::Yellow)
+            } else if delta >= 30 {
+                ("⇑", self.palette.good())
+            } else {
+                ("=", Color::DarkGray)
+            };
+            let text = format!("{} {:.1}", arrow, delta.abs() as f64 / 100.0);
+            buf.set_string(area.x + used + 2, area.y, &text, Style::default().fg(color));
+            used += 2 + text.chars().count() as u16;
+        }
+
+        // Draw counters on the right: the halfmove clock once it starts to
+        // matter and the repetition count once a position repeats, in yellow
+        // when a draw can be claimed (just that when the rest doesn't fit)
+        let halfmoves = self.game.halfmoves();
+        let repetitions = self.game.repetitions();
+        let mut counters = Vec::new();
+        if halfmoves >= 20 {
+            counters.push(format!("50-move {}/100", halfmoves));
+        }
+        if repetitions >= 2 {
+            counters.push(format!("rep {}", repetitions));
+        }
+        let claimable = self.game.draw_claim().is_some();
+        if claimable {
+            counters.push("draw claimable".to_string());
+        }
+        let color = if claimable {
+            Color::Yellow
+        } else {
+            Color::DarkGray
+        };
+        let room = area.width.saturating_sub(used + 3) as usize;
+        let mut text = counters.join(" · ");
+        if text.chars().count() > room {
+            text = counters.pop().unwrap_or_default();
+        }
+        let width = text.chars().count() as u16;
+        if width > 0 && width as usize <= room {
+            buf.set_string(
+                area.x + area.width - width - 1,
+                area.y,
+                &text,
+                Style::default().fg(color),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn one_cell_side_set_fits_the_other() {
+        let game = Game::new();
+        let inner = Rect::new(0, 0, 200, 100);
+        let mut config = UiConfig::default();
+        assert_eq!(BoardWidget::new(&game, &config).cell_size(inner), (16, 8));
+
+        config.cell_width = 6;
+        assert_eq!(BoardWidget::new(&game, &config).cell_size(inner), (6, 8));
+
+        config.cell_width = 0;
+        config.cell_height = 3;
+        assert_eq!(BoardWidget::new(&game, &config).cell_size(inner), (16, 3));
+
+        config.cell_width = 6;
+        assert_eq!(BoardWidget::new(&game, &config).cell_size(inner), (6, 3));
     }
 }
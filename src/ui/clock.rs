@@ -0,0 +1,76 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    widgets::Widget,
+};
+use std::time::Duration;
+
+use crate::chess::{format_clock, Clock};
+
+/// Remaining time below which a clock is shown in red
+const LOW_TIME: Duration = Duration::from_secs(10);
+
+/// Dual chess clock display
+pub struct ClockWidget<'a> {
+    clock: &'a Clock,
+}
+
+impl<'a> ClockWidget<'a> {
+    pub fn new(clock: &'a Clock) -> Self {
+        Self { clock }
+    }
+
+    fn side_style(&self, color: shakmaty::Color) -> Style {
+        let remaining = self.clock.remaining(color);
+        let fg = if self.clock.flagged() == Some(color) || remaining < LOW_TIME {
+            Color::Red
+        } else {
+            Color::White
+        };
+
+        if self.clock.running() == Some(color) {
+            Style::default()
+                .fg(fg)
+                .bg(Color::DarkGray)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().fg(fg)
+        }
+    }
+
+    fn format_side(&self, color: shakmaty::Color) -> String {
+        let remaining = self.clock.remaining(color);
+        // Show tenths when time is short
+        let time = if remaining < LOW_TIME {
+            format!("{:.1}", remaining.as_secs_f64())
+        } else {
+            format_clock(remaining)
+        };
+        let flag = if self.clock.flagged() == Some(color) {
+            " ⚑"
+        } else {
+            ""
+        };
+        let name = if color == shakmaty::Color::White {
+            "White"
+        } else {
+            "Black"
+        };
+        format!(" {} {}{} ", name, time, flag)
+    }
+}
+
+impl Widget for ClockWidget<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let white = self.format_side(shakmaty::Color::White);
+        let black = self.format_side(shakmaty::Color::Black);
+
+        let mut x = area.x;
+        buf.set_string(x, area.y, &white, self.side_style(shakmaty::Color::White));
+        x += white.chars().count() as u16 + 1;
+        if x < area.x + area.width {
+            buf.set_string(x, area.y, &black, self.side_style(shakmaty::Color::Black));
+        }
+    }
+}
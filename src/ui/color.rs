@@ -0,0 +1,244 @@
+use ratatui::{buffer::Buffer, style::Color};
+
+use crate::chess::MarkColor;
+
+/// Colors the terminal can show
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorDepth {
+    /// 24-bit RGB
+    TrueColor,
+    /// The xterm 256-color palette
+    Ansi256,
+    /// The 16 basic ANSI colors
+    Ansi16,
+}
+
+/// Board colors with hand-picked stand-ins: RGB, 256-color index and basic
+/// color. The basic colors keep light and dark squares apart and leave both
+/// white and black pieces readable.
+const CURATED: &[((u8, u8, u8), u8, Color)] = &[
+    // Light and dark squares
+    ((240, 217, 181), 223, Color::Yellow),
+    ((181, 136, 99), 137, Color::Green),
+    // Last move
+    ((205, 210, 106), 186, Color::LightYellow),
+    ((170, 162, 58), 142, Color::LightGreen),
+    // Hanging pieces
+    ((236, 126, 106), 209, Color::LightRed),
+    ((204, 88, 70), 167, Color::Red),
+];
+
+/// The basic colors with xterm's default RGB values, to match against
+const ANSI16: [(Color, (u8, u8, u8)); 16] = [
+    (Color::Black, (0, 0, 0)),
+    (Color::Red, (205, 0, 0)),
+    (Color::Green, (0, 205, 0)),
+    (Color::Yellow, (205, 205, 0)),
+    (Color::Blue, (0, 0, 238)),
+    (Color::Magenta, (205, 0, 205)),
+    (Color::Cyan, (0, 205, 205)),
+    (Color::Gray, (229, 229, 229)),
+    (Color::DarkGray, (127, 127, 127)),
+    (Color::LightRed, (255, 0, 0)),
+    (Color::LightGreen, (0, 255, 0)),
+    (Color::LightYellow, (255, 255, 0)),
+    (Color::LightBlue, (92, 92, 255)),
+    (Color::LightMagenta, (255, 0, 255)),
+    (Color::LightCyan, (0, 255, 255)),
+    (Color::White, (255, 255, 255)),
+];
+
+/// Levels of each channel in the 6×6×6 color cube of the 256-color palette
+const CUBE_LEVELS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+fn distance((r1, g1, b1): (u8, u8, u8), (r2, g2, b2): (u8, u8, u8)) -> u32 {
+    let d = |a: u8, b: u8| (a as i32 - b as i32).unsigned_abs();
+    d(r1, r2).pow(2) + d(g1, g2).pow(2) + d(b1, b2).pow(2)
+}
+
+/// Nearest color in the 256-color palette's cube and gray ramp
+fn nearest_256(rgb: (u8, u8, u8)) -> u8 {
+    let level = |c: u8| {
+        (0..6)
+            .min_by_key(|&i| (CUBE_LEVELS[i] as i32 - c as i32).abs())
+            .unwrap_or(0)
+    };
+    let (r, g, b) = (level(rgb.0), level(rgb.1), level(rgb.2));
+    let cube = (CUBE_LEVELS[r], CUBE_LEVELS[g], CUBE_LEVELS[b]);
+    let cube_index = 16 + 36 * r as u8 + 6 * g as u8 + b as u8;
+
+    // Grays run from 8 to 238 in steps of 10
+    let average = (rgb.0 as u32 + rgb.1 as u32 + rgb.2 as u32) / 3;
+    let step = (average.saturating_sub(3) / 10).min(23) as u8;
+    let gray_level = 8 + 10 * step;
+    let gray = (gray_level, gray_level, gray_level);
+
+    if distance(rgb, gray) < distance(rgb, cube) {
+        232 + step
+    } else {
+        cube_index
+    }
+}
+
+impl ColorDepth {
+    /// Guess what the terminal supports from its environment
+    pub fn detect() -> Self {
+        let var = |name: &str| std::env::var(name).unwrap_or_default();
+        if matches!(var("COLORTERM").as_str(), "truecolor" | "24bit") {
+            return Self::TrueColor;
+        }
+        let term = var("TERM");
+        if term.contains("256color") {
+            Self::Ansi256
+        } else if term.is_empty() || term.contains("direct") {
+            // No TERM on Windows, where the console has true color
+            Self::TrueColor
+        } else {
+            Self::Ansi16
+        }
+    }
+
+    /// The closest color this depth can show
+    pub fn convert(self, color: Color) -> Color {
+        let Color::Rgb(r, g, b) = color else {
+            return color;
+        };
+        let rgb = (r, g, b);
+        let curated = CURATED.iter().find(|(c, _, _)| *c == rgb);
+        match self {
+            Self::TrueColor => color,
+            Self::Ansi256 => Color::Indexed(curated.map_or_else(|| nearest_256(rgb), |c| c.1)),
+            Self::Ansi16 => curated.map_or_else(
+                || {
+                    ANSI16
+                        .iter()
+                        .min_by_key(|(_, c)| distance(rgb, *c))
+                        .map_or(Color::Reset, |(color, _)| *color)
+                },
+                |c| c.2,
+            ),
+        }
+    }
+
+    /// Replace every RGB color of a drawn frame with its closest match
+    pub fn apply(self, buf: &mut Buffer) {
+        if self == Self::TrueColor {
+            return;
+        }
+        for cell in buf.content.iter_mut() {
+            cell.fg = self.convert(cell.fg);
+            cell.bg = self.convert(cell.bg);
+        }
+    }
+}
+
+/// Highlight colors. Besides the standard red and green ones there are
+/// schemes for red-green color blindness built on the Okabe-Ito colors:
+/// blue for good and orange for bad.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Palette {
+    #[default]
+    Standard,
+    Deuteranopia,
+    Protanopia,
+}
+
+/// Pick between the light and dark square version of a tint
+fn tint(light: bool, on_light: (u8, u8, u8), on_dark: (u8, u8, u8)) -> Color {
+    let (r, g, b) = if light { on_light } else { on_dark };
+    Color::Rgb(r, g, b)
+}
+
+impl Palette {
+    /// Look up a palette by name
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "standard" | "default" => Some(Self::Standard),
+            "deuteranopia" | "deutan" => Some(Self::Deuteranopia),
+            "protanopia" | "protan" => Some(Self::Protanopia),
+            _ => None,
+        }
+    }
+
+    /// Last move squares
+    pub fn last_move(self, light: bool) -> Color {
+        match self {
+            Self::Standard => tint(light, (205, 210, 106), (170, 162, 58)),
+            Self::Deuteranopia | Self::Protanopia => tint(light, (150, 190, 230), (100, 150, 205)),
+        }
+    }
+
+    /// King in check
+    pub fn check(self, light: bool) -> Color {
+        match self {
+            Self::Standard => tint(light, (240, 90, 75), (215, 55, 45)),
+            Self::Deuteranopia => tint(light, (240, 150, 60), (220, 120, 20)),
+            Self::Protanopia => tint(light, (245, 190, 70), (230, 160, 30)),
+        }
+    }
+
+    /// Hanging pieces
+    pub fn hanging(self, light: bool) -> Color {
+        match self {
+            Self::Standard => tint(light, (236, 126, 106), (204, 88, 70)),
+            Self::Deuteranopia | Self::Protanopia => tint(light, (225, 150, 190), (195, 110, 160)),
+        }
+    }
+
+    /// Square marks
+    pub fn mark(self, mark: MarkColor, light: bool) -> Color {
+        match (self, mark) {
+            (Self::Standard, MarkColor::Green) => tint(light, (150, 200, 120), (105, 160, 75)),
+            (Self::Standard, MarkColor::Red) => tint(light, (225, 140, 140), (190, 95, 95)),
+            (_, MarkColor::Yellow) => tint(light, (245, 200, 100), (215, 165, 55)),
+            (_, MarkColor::Blue) => tint(light, (140, 175, 225), (95, 130, 190)),
+            (_, MarkColor::Green) => tint(light, (110, 200, 170), (55, 165, 130)),
+            (Self::Deuteranopia, MarkColor::Red) => tint(light, (235, 150, 100), (210, 110, 50)),
+            (Self::Protanopia, MarkColor::Red) => tint(light, (245, 180, 90), (225, 150, 40)),
+        }
+    }
+
+    /// Arrows
+    pub fn mark_fg(self, mark: MarkColor) -> Color {
+        match (self, mark) {
+            (Self::Standard, MarkColor::Green) => Color::Rgb(21, 120, 27),
+            (Self::Standard, MarkColor::Red) => Color::Rgb(136, 32, 32),
+            (_, MarkColor::Yellow) => Color::Rgb(160, 95, 0),
+            (_, MarkColor::Blue) => Color::Rgb(0, 48, 136),
+            (_, MarkColor::Green) => Color::Rgb(0, 110, 85),
+            (Self::Deuteranopia, MarkColor::Red) => Color::Rgb(170, 60, 0),
+            (Self::Protanopia, MarkColor::Red) => Color::Rgb(190, 105, 0),
+        }
+    }
+
+    /// Heatmap tint for a move losing `loss` centipawns, blending from the
+    /// good color to the bad one at 300cp
+    pub fn heatmap(self, loss: i32, light: bool) -> Color {
+        let (good, bad) = match self {
+            Self::Standard => ((90.0, 170.0, 80.0), (200.0, 70.0, 60.0)),
+            Self::Deuteranopia => ((70.0, 130.0, 200.0), (230.0, 120.0, 30.0)),
+            Self::Protanopia => ((70.0, 130.0, 200.0), (240.0, 170.0, 30.0)),
+        };
+        let t = loss.clamp(0, 300) as f32 / 300.0;
+        let lift = if light { 30.0 } else { 0.0 };
+        let mix = |good: f32, bad: f32| (good + (bad - good) * t + lift).min(255.0) as u8;
+        Color::Rgb(mix(good.0, bad.0), mix(good.1, bad.1), mix(good.2, bad.2))
+    }
+
+    /// Text for good news: winning scores, improvements
+    pub fn good(self) -> Color {
+        match self {
+            Self::Standard => Color::Green,
+            Self::Deuteranopia | Self::Protanopia => Color::Rgb(86, 180, 233),
+        }
+    }
+
+    /// Text for bad news: losing scores, blunders, threats
+    pub fn bad(self) -> Color {
+        match self {
+            Self::Standard => Color::Red,
+            Self::Deuteranopia => Color::Rgb(240, 120, 40),
+            Self::Protanopia => Color::Rgb(230, 159, 0),
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph, Widget, Wrap},
+};
+use shakmaty::Position;
+
+use super::Palette;
+use crate::chess::Game;
+use crate::drill::{DrillGoal, DrillSession, HOLD_MOVES};
+
+/// Endgame drill panel, shown in place of the analysis
+pub struct DrillWidget<'a> {
+    session: &'a DrillSession,
+    game: &'a Game,
+    palette: Palette,
+}
+
+impl<'a> DrillWidget<'a> {
+    pub fn new(session: &'a DrillSession, game: &'a Game) -> Self {
+        Self {
+            session,
+            game,
+            palette: Palette::Standard,
+        }
+    }
+
+    /// Color the outcome with the given palette
+    pub fn palette(mut self, palette: Palette) -> Self {
+        self.palette = palette;
+        self
+    }
+}
+
+impl Widget for DrillWidget<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let session = self.session;
+        let drill = session.drill;
+        let title = format!(" Endgame drill: {} ", drill.name);
+        let block = Block::default().borders(Borders::ALL).title(title);
+
+        let dim = Style::default().fg(Color::DarkGray);
+        let side = if session.side == shakmaty::Color::White {
+            "White"
+        } else {
+            "Black"
+        };
+        let goal = match drill.goal {
+            DrillGoal::Win => format!("Win as {} against the engine", side),
+            DrillGoal::Draw => format!("Hold the draw as {} for {} moves", side, HOLD_MOVES),
+        };
+        let mut lines = vec![Line::from(goal)];
+
+        lines.push(Line::from(vec![
+            Span::styled("Moves: ", dim),
+            Span::raw(session.moves_played(self.game).to_string()),
+            Span::styled("  fifty-move count: ", dim),
+            Span::raw(self.game.position().halfmoves().to_string()),
+        ]));
+
+        if let Some(ref end) = session.end {
+            let (text, color) = if end.success {
+                (format!("Success! {}", end.reason), self.palette.good())
+            } else {
+                (format!("Failed: {}", end.reason), self.palette.bad())
+            };
+            lines.push(Line::from(""));
+            lines.push(Line::styled(
+                text,
+                Style::default().fg(color).add_modifier(Modifier::BOLD),
+            ));
+            lines.push(Line::styled(
+                ":drill retry to go again, :drill stop to end",
+                dim,
+            ));
+        }
+
+        let streak = &session.streak;
+        lines.push(Line::from(""));
+        lines.push(Line::styled(
+            format!(
+                "Streak {} (best {}), {}/{} successful",
+                streak.current, streak.best, streak.successes, streak.attempts
+            ),
+            dim,
+        ));
+
+        Paragraph::new(lines)
+            .block(block)
+            .wrap(Wrap { trim: true })
+            .render(area, buf);
+    }
+}
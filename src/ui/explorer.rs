@@ -0,0 +1,164 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph, Widget},
+};
+
+use super::analysis::wrap_words;
+use super::Palette;
+use crate::explorer::{PositionStats, Results};
+
+/// Opening explorer panel, shown in place of the move list: the moves
+/// played from the current position with their frequencies and scores
+pub struct ExplorerWidget<'a> {
+    title: &'a str,
+    stats: Option<&'a PositionStats>,
+    /// Side to move, whose score is shown
+    turn: shakmaty::Color,
+    selected: usize,
+    /// Shown instead of the statistics, e.g. while they're looked up
+    note: Option<&'a str>,
+    palette: Palette,
+}
+
+impl<'a> ExplorerWidget<'a> {
+    pub fn new(
+        title: &'a str,
+        stats: Option<&'a PositionStats>,
+        turn: shakmaty::Color,
+        selected: usize,
+    ) -> Self {
+        Self {
+            title,
+            stats,
+            turn,
+            selected,
+            note: None,
+            palette: Palette::Standard,
+        }
+    }
+
+    /// Color wins and losses with the given palette
+    pub fn palette(mut self, palette: Palette) -> Self {
+        self.palette = palette;
+        self
+    }
+
+    pub fn note(mut self, note: Option<&'a str>) -> Self {
+        self.note = note;
+        self
+    }
+
+    /// Score and wins/draws/losses of the side to move
+    fn results_spans(&self, results: &Results) -> [Span<'static>; 4] {
+        let score = match results.score(self.turn) {
+            Some(score) => format!("{:>3}%", score),
+            None => "   -".to_string(),
+        };
+        [
+            Span::styled(score, Style::default().fg(Color::Yellow)),
+            Span::styled(
+                format!("  +{}", results.wins(self.turn)),
+                Style::default().fg(self.palette.good()),
+            ),
+            Span::styled(
+                format!(" ={}", results.draws),
+                Style::default().fg(Color::Gray),
+            ),
+            Span::styled(
+                format!(" -{}", results.losses(self.turn)),
+                Style::default().fg(self.palette.bad()),
+            ),
+        ]
+    }
+}
+
+impl Widget for ExplorerWidget<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(format!(" {} ", self.title));
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let dim = Style::default().fg(Color::DarkGray);
+        let mut lines = Vec::new();
+        match (self.note, self.stats) {
+            (Some(note), _) => {
+                for line in wrap_words(note, inner.width as usize) {
+                    lines.push(Line::styled(line, dim));
+                }
+            }
+            (None, Some(stats)) => {
+                let side = match self.turn {
+                    shakmaty::Color::White => "White",
+                    shakmaty::Color::Black => "Black",
+                };
+                let games = stats.results.games();
+                lines.push(Line::from(vec![
+                    Span::raw(format!(
+                        "{} game{}, scores for ",
+                        games,
+                        if games == 1 { "" } else { "s" }
+                    )),
+                    Span::styled(side, Style::default().add_modifier(Modifier::BOLD)),
+                ]));
+                lines.push(Line::from(""));
+
+                if stats.moves.is_empty() {
+                    lines.push(Line::styled("No moves played from here", dim));
+                }
+                // Scroll so the selected move stays in view above the top
+                // games and the footer
+                let top_games = match stats.top_games.len() {
+                    0 => 0,
+                    n => n + 2,
+                };
+                let visible = (inner.height as usize).saturating_sub(5 + top_games);
+                let scroll = (self.selected + 1).saturating_sub(visible);
+                for (i, m) in stats.moves.iter().enumerate().skip(scroll).take(visible) {
+                    let style = if i == self.selected {
+                        Style::default()
+                            .fg(Color::Yellow)
+                            .add_modifier(Modifier::BOLD)
+                    } else {
+                        Style::default().fg(Color::White)
+                    };
+                    let marker = if i == self.selected { ">" } else { " " };
+                    let mut spans = vec![
+                        Span::styled(format!("{}{:<7}", marker, m.san), style),
+                        Span::raw(format!("{:>5} ", m.results.games())),
+                    ];
+                    spans.extend(self.results_spans(&m.results));
+                    lines.push(Line::from(spans));
+                }
+
+                if !stats.top_games.is_empty() {
+                    lines.push(Line::from(""));
+                    lines.push(Line::styled("Top games", dim));
+                }
+                for game in &stats.top_games {
+                    lines.push(Line::from(vec![
+                        Span::styled(
+                            format!("{:<4}", game.result),
+                            Style::default().fg(Color::Yellow),
+                        ),
+                        Span::raw(format!(" {} - {}", game.white, game.black)),
+                        Span::styled(
+                            format!("  {}", game.san),
+                            Style::default().fg(self.palette.good()),
+                        ),
+                    ]));
+                }
+            }
+            (None, None) => lines.push(Line::styled("No games reached this position", dim)),
+        }
+
+        lines.push(Line::from(""));
+        lines.push(Line::styled("Enter play, ← back, Esc close", dim));
+        lines.push(Line::styled("Tab: my games or masters", dim));
+        Paragraph::new(lines).render(inner, buf);
+    }
+}
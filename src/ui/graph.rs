@@ -0,0 +1,159 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Style},
+    widgets::{Block, Borders, Widget},
+};
+
+use crate::chess::Game;
+
+/// Eval clamp for the graph in centipawns (beyond this the bar is full)
+const GRAPH_CLAMP_CP: f64 = 500.0;
+
+/// Partial block characters, from 1/8 to 8/8 filled (bottom-up)
+const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Evaluation graph over the whole game
+pub struct EvalGraphWidget<'a> {
+    game: &'a Game,
+    /// Draw Black's advantage upward instead of White's
+    black_up: bool,
+    /// Positions reviewed so far and the total, while a review runs
+    progress: Option<(usize, usize)>,
+    /// Leave out evals after the current position
+    hide_future: bool,
+}
+
+impl<'a> EvalGraphWidget<'a> {
+    pub fn new(game: &'a Game) -> Self {
+        Self {
+            game,
+            black_up: false,
+            progress: None,
+            hide_future: false,
+        }
+    }
+
+    /// Draw Black's advantage upward instead of White's
+    pub fn black_up(mut self, black_up: bool) -> Self {
+        self.black_up = black_up;
+        self
+    }
+
+    /// Leave out evals after the current position (for guess the move)
+    pub fn hide_future(mut self, hide_future: bool) -> Self {
+        self.hide_future = hide_future;
+        self
+    }
+
+    /// Show the progress of a running game review in the title
+    pub fn progress(mut self, progress: Option<(usize, usize)>) -> Self {
+        self.progress = progress;
+        self
+    }
+}
+
+impl Widget for EvalGraphWidget<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let side = if self.black_up { " (Black)" } else { "" };
+        let title = match self.progress {
+            Some((done, total)) => format!(" Eval{} - reviewing {}/{} ", side, done, total),
+            None => format!(" Eval{} ", side),
+        };
+        let block = Block::default().borders(Borders::ALL).title(title);
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        if inner.width < 2 || inner.height < 2 {
+            return;
+        }
+
+        let evals: Vec<Option<i32>> = self
+            .game
+            .annotations()
+            .iter()
+            .enumerate()
+            .map(|(ply, a)| {
+                a.eval
+                    .filter(|_| !self.hide_future || ply <= self.game.current_index())
+                    .map(|e| if self.black_up { -e.to_cp() } else { e.to_cp() })
+            })
+            .collect();
+        if evals.is_empty() {
+            return;
+        }
+
+        // Vertical resolution is in eighths of a cell; bars grow from the midline
+        let half_height = (inner.height / 2).max(1) as f64;
+        let mid_y = (inner.y + inner.height / 2) as f64;
+        let current = self.game.current_index();
+        let (up_color, down_color) = if self.black_up {
+            (Color::DarkGray, Color::White)
+        } else {
+            (Color::White, Color::DarkGray)
+        };
+
+        // One column per ply, or several plies per column for long games
+        let columns = (inner.width as usize).min(evals.len());
+        for col in 0..columns {
+            let ply = col * evals.len() / columns;
+            let x = inner.x + col as u16;
+            let is_current =
+                ply == current || (ply < current && (col + 1) * evals.len() / columns > current);
+
+            if is_current {
+                for y in inner.y..inner.y + inner.height {
+                    buf.set_string(x, y, " ", Style::default().bg(Color::Rgb(60, 60, 90)));
+                }
+            }
+
+            let Some(cp) = evals[ply] else {
+                continue;
+            };
+            let ratio = (cp as f64 / GRAPH_CLAMP_CP).clamp(-1.0, 1.0);
+            let eighths = (ratio.abs() * half_height * 8.0).round() as usize;
+            let bg = if is_current {
+                Color::Rgb(60, 60, 90)
+            } else {
+                Color::Reset
+            };
+
+            if ratio >= 0.0 {
+                // The favoured side's advantage grows upward from the midline
+                let mut y = mid_y - 1.0;
+                let mut left = eighths;
+                while left > 0 && y >= inner.y as f64 {
+                    let ch = BLOCKS[left.min(8) - 1];
+                    buf.set_string(
+                        x,
+                        y as u16,
+                        ch.to_string(),
+                        Style::default().fg(up_color).bg(bg),
+                    );
+                    left = left.saturating_sub(8);
+                    y -= 1.0;
+                }
+            } else {
+                // The other side's advantage grows downward (only coarse
+                // upper blocks exist, so partial cells round to ▔ or ▀)
+                let mut y = mid_y;
+                let mut left = eighths;
+                while left > 0 && y < (inner.y + inner.height) as f64 {
+                    let ch = match left.min(8) {
+                        1..=2 => '▔',
+                        3..=5 => '▀',
+                        _ => '█',
+                    };
+                    buf.set_string(
+                        x,
+                        y as u16,
+                        ch.to_string(),
+                        Style::default().fg(down_color).bg(bg),
+                    );
+                    left = left.saturating_sub(8);
+                    y += 1.0;
+                }
+            }
+        }
+    }
+}
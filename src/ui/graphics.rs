@@ -0,0 +1,682 @@
+use anyhow::{Context, Result};
+use ratatui::{layout::Rect, style::Color};
+use shakmaty::{Piece, Role};
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// How the board squares are drawn
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoardRenderer {
+    /// Text cells (the piece style decides the glyphs)
+    Text,
+    /// An image through the kitty graphics protocol
+    Kitty,
+    /// An image through iTerm2's inline image protocol
+    Iterm2,
+    /// An image in sixel graphics
+    Sixel,
+    /// Text cells with piece sprites drawn in half-block characters
+    HalfBlock,
+}
+
+impl BoardRenderer {
+    /// Pick a graphics protocol the terminal is known to support, falling
+    /// back to text
+    pub fn detect() -> Self {
+        let var = |name: &str| std::env::var(name).unwrap_or_default();
+        // Multiplexers don't pass images through
+        if !var("TMUX").is_empty() || var("TERM").starts_with("screen") {
+            return Self::Text;
+        }
+        if !var("KITTY_WINDOW_ID").is_empty()
+            || matches!(var("TERM").as_str(), "xterm-kitty" | "xterm-ghostty")
+        {
+            return Self::Kitty;
+        }
+        if matches!(var("TERM_PROGRAM").as_str(), "iTerm.app" | "WezTerm") {
+            return Self::Iterm2;
+        }
+        if var("TERM").starts_with("mlterm") || var("TERM").starts_with("foot") {
+            return Self::Sixel;
+        }
+        Self::Text
+    }
+
+    /// Check if the board is drawn as an image
+    pub fn is_graphics(self) -> bool {
+        !matches!(self, Self::Text | Self::HalfBlock)
+    }
+}
+
+/// The board as shown: background color and piece of each square, row by
+/// row from the top left
+pub type BoardSquares = [(Color, Option<Piece>); 64];
+
+/// Piece sprites on a 16x16 grid; `#` is the body of the piece
+const PAWN_SPRITE: [&str; 16] = [
+    "................",
+    "................",
+    "................",
+    "......####......",
+    ".....######.....",
+    ".....######.....",
+    "......####......",
+    ".....######.....",
+    "......####......",
+    "......####......",
+    ".....######.....",
+    "....########....",
+    "...##########...",
+    "...##########...",
+    "................",
+    "................",
+];
+
+const KNIGHT_SPRITE: [&str; 16] = [
+    "................",
+    ".......#.#......",
+    "......######....",
+    ".....########...",
+    "....##.#######..",
+    "...###########..",
+    "..####..######..",
+    "..###..#######..",
+    ".......#######..",
+    "......#######...",
+    ".....#######....",
+    "....########....",
+    "...##########...",
+    "...##########...",
+    "................",
+    "................",
+];
+
+const BISHOP_SPRITE: [&str; 16] = [
+    ".......##.......",
+    ".......##.......",
+    "......####......",
+    ".....###..#.....",
+    "....###..###....",
+    "....##..####....",
+    "....########....",
+    ".....######.....",
+    "......####......",
+    "....########....",
+    "......####......",
+    ".....######.....",
+    "...##########...",
+    "...##########...",
+    "................",
+    "................",
+];
+
+const ROOK_SPRITE: [&str; 16] = [
+    "................",
+    "................",
+    "...##.####.##...",
+    "...##.####.##...",
+    "...##########...",
+    "....########....",
+    ".....######.....",
+    ".....######.....",
+    ".....######.....",
+    ".....######.....",
+    ".....######.....",
+    "....########....",
+    "...##########...",
+    "...##########...",
+    "................",
+    "................",
+];
+
+const QUEEN_SPRITE: [&str; 16] = [
+    "................",
+    "..#...#..#...#..",
+    "..#..##..##..#..",
+    "..##.##..##.##..",
+    "..############..",
+    "...##########...",
+    "...##########...",
+    "....########....",
+    "....########....",
+    ".....######.....",
+    ".....######.....",
+    "....########....",
+    "...##########...",
+    "...##########...",
+    "................",
+    "................",
+];
+
+const KING_SPRITE: [&str; 16] = [
+    ".......##.......",
+    "......####......",
+    ".......##.......",
+    "...###.##.###...",
+    "..############..",
+    "..############..",
+    "..############..",
+    "...##########...",
+    "....########....",
+    ".....######.....",
+    ".....######.....",
+    "....########....",
+    "...##########...",
+    "...##########...",
+    "................",
+    "................",
+];
+
+fn sprite(role: Role) -> &'static [&'static str; 16] {
+    match role {
+        Role::Pawn => &PAWN_SPRITE,
+        Role::Knight => &KNIGHT_SPRITE,
+        Role::Bishop => &BISHOP_SPRITE,
+        Role::Rook => &ROOK_SPRITE,
+        Role::Queen => &QUEEN_SPRITE,
+        Role::King => &KING_SPRITE,
+    }
+}
+
+/// Sprite pixel: body, outline (next to the body) or transparent
+#[derive(Clone, Copy, PartialEq)]
+enum SpritePixel {
+    Body,
+    Outline,
+    Clear,
+}
+
+fn sprite_pixel(role: Role, x: usize, y: usize) -> SpritePixel {
+    let rows = sprite(role);
+    let body = |x: i32, y: i32| {
+        (0..16).contains(&x)
+            && (0..16).contains(&y)
+            && rows[y as usize].as_bytes()[x as usize] == b'#'
+    };
+    let (x, y) = (x as i32, y as i32);
+    if body(x, y) {
+        SpritePixel::Body
+    } else if [(-1, 0), (1, 0), (0, -1), (0, 1)]
+        .iter()
+        .any(|(dx, dy)| body(x + dx, y + dy))
+    {
+        SpritePixel::Outline
+    } else {
+        SpritePixel::Clear
+    }
+}
+
+/// Sprite pixel of a sprite scaled to `span` pixels, from the sprite pixels
+/// it covers
+fn scaled_pixel(role: Role, x: usize, y: usize, span: usize) -> SpritePixel {
+    let cover = |i: usize| i * 16 / span..((i + 1) * 16).div_ceil(span);
+    let (mut body, mut outline, mut total) = (0, 0, 0);
+    for sy in cover(y) {
+        for sx in cover(x) {
+            match sprite_pixel(role, sx, sy) {
+                SpritePixel::Body => body += 1,
+                SpritePixel::Outline => outline += 1,
+                SpritePixel::Clear => {}
+            }
+            total += 1;
+        }
+    }
+    if body * 2 >= total {
+        SpritePixel::Body
+    } else if (body + outline) * 2 > total {
+        SpritePixel::Outline
+    } else {
+        SpritePixel::Clear
+    }
+}
+
+fn rgb(color: Color) -> [u8; 3] {
+    match color {
+        Color::Rgb(r, g, b) => [r, g, b],
+        _ => [128, 128, 128],
+    }
+}
+
+/// The board drawn as an RGB image
+pub struct BoardImage {
+    /// Width and height in pixels
+    pub size: u32,
+    /// RGB bytes, row by row
+    pub pixels: Vec<u8>,
+}
+
+impl BoardImage {
+    /// Draw the squares into an image of about `size` pixels square
+    pub fn render(squares: &BoardSquares, size: u32) -> Self {
+        Self::with_square_size(squares, (size / 8).max(16) as usize)
+    }
+
+    /// Draw the squares `square` pixels wide each
+    pub fn with_square_size(squares: &BoardSquares, square: usize) -> Self {
+        let size = square * 8;
+        let mut pixels = vec![0u8; size * size * 3];
+
+        for (index, &(bg, piece)) in squares.iter().enumerate() {
+            let (left, top) = ((index % 8) * square, (index / 8) * square);
+            let background = rgb(bg);
+            // The sprite fills the square with a small margin
+            let margin = square / 16;
+            let span = square - 2 * margin;
+            for y in 0..square {
+                for x in 0..square {
+                    let sprite_px = match piece {
+                        Some(piece)
+                            if (margin..margin + span).contains(&x)
+                                && (margin..margin + span).contains(&y) =>
+                        {
+                            let (sx, sy) = (x - margin, y - margin);
+                            Some((piece, scaled_pixel(piece.role, sx, sy, span)))
+                        }
+                        _ => None,
+                    };
+                    let color = match sprite_px {
+                        Some((piece, SpritePixel::Body)) => match piece.color {
+                            shakmaty::Color::White => [250, 250, 250],
+                            shakmaty::Color::Black => [30, 30, 30],
+                        },
+                        Some((piece, SpritePixel::Outline)) => match piece.color {
+                            shakmaty::Color::White => [20, 20, 20],
+                            shakmaty::Color::Black => [200, 200, 200],
+                        },
+                        _ => background,
+                    };
+                    let offset = ((top + y) * size + left + x) * 3;
+                    pixels[offset..offset + 3].copy_from_slice(&color);
+                }
+            }
+        }
+
+        Self {
+            size: size as u32,
+            pixels,
+        }
+    }
+
+    /// Encode the image as PNG
+    pub fn to_png(&self) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        let mut encoder = png::Encoder::new(&mut data, self.size, self.size);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&self.pixels))
+            .context("Failed to encode board image")?;
+        Ok(data)
+    }
+
+    /// Draw arrows over the squares, `square` pixels wide each
+    pub fn draw_arrows(&mut self, arrows: &[BoardArrow], square: usize) {
+        let size = self.size as usize;
+        for arrow in arrows {
+            let Some(outline) = arrow.outline(square as f32) else {
+                continue;
+            };
+            let color = rgb(arrow.color);
+            for y in 0..size {
+                for x in 0..size {
+                    if !inside(&outline, (x as f32 + 0.5, y as f32 + 0.5)) {
+                        continue;
+                    }
+                    let offset = (y * size + x) * 3;
+                    for (channel, &value) in self.pixels[offset..offset + 3].iter_mut().zip(&color)
+                    {
+                        *channel = ((*channel as u32 * 2 + value as u32 * 8) / 10) as u8;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// An arrow between squares of the board as shown, indexed row by row from
+/// the top left
+#[derive(Debug, Clone, Copy)]
+pub struct BoardArrow {
+    pub from: usize,
+    pub to: usize,
+    pub color: Color,
+}
+
+impl BoardArrow {
+    /// Corners of the arrow from the middle of one square to the middle of
+    /// the other, or None when they're the same square
+    fn outline(&self, square: f32) -> Option<[(f32, f32); 7]> {
+        let center = |index: usize| {
+            (
+                ((index % 8) as f32 + 0.5) * square,
+                ((index / 8) as f32 + 0.5) * square,
+            )
+        };
+        let (start, tip) = (center(self.from), center(self.to));
+        let (dx, dy) = (tip.0 - start.0, tip.1 - start.1);
+        let length = (dx * dx + dy * dy).sqrt();
+        if length < 1.0 {
+            return None;
+        }
+        let (ux, uy) = (dx / length, dy / length);
+        let (nx, ny) = (-uy, ux);
+        let (shaft, head, head_length) = (square * 0.1, square * 0.25, square * 0.4);
+        let base = (tip.0 - ux * head_length, tip.1 - uy * head_length);
+        let side = |(x, y): (f32, f32), w: f32| (x + nx * w, y + ny * w);
+        Some([
+            side(start, shaft),
+            side(base, shaft),
+            side(base, head),
+            tip,
+            side(base, -head),
+            side(base, -shaft),
+            side(start, -shaft),
+        ])
+    }
+}
+
+/// Check if a point is inside a polygon (even-odd rule)
+fn inside(polygon: &[(f32, f32)], (x, y): (f32, f32)) -> bool {
+    let mut result = false;
+    let mut previous = polygon[polygon.len() - 1];
+    for &point in polygon {
+        if (point.1 > y) != (previous.1 > y)
+            && x < (previous.0 - point.0) * (y - point.1) / (previous.1 - point.1) + point.0
+        {
+            result = !result;
+        }
+        previous = point;
+    }
+    result
+}
+
+fn hex(color: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", color[0], color[1], color[2])
+}
+
+/// SVG path of a sprite's body or outline pixels, in sprite pixels
+fn sprite_path(role: Role, kind: SpritePixel) -> String {
+    let mut path = String::new();
+    for y in 0..16 {
+        let mut x = 0;
+        while x < 16 {
+            if sprite_pixel(role, x, y) != kind {
+                x += 1;
+                continue;
+            }
+            let start = x;
+            while x < 16 && sprite_pixel(role, x, y) == kind {
+                x += 1;
+            }
+            path.push_str(&format!("M{} {}h{}v1h-{}z", start, y, x - start, x - start));
+        }
+    }
+    path
+}
+
+/// The board as an SVG document with the sprites as the piece set, `square`
+/// pixels wide per square
+pub fn board_svg(squares: &BoardSquares, arrows: &[BoardArrow], square: usize) -> String {
+    let size = square * 8;
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" xmlns:xlink=\"http://www.w3.org/1999/xlink\" \
+         width=\"{size}\" height=\"{size}\" viewBox=\"0 0 {size} {size}\">\n"
+    );
+
+    // Each piece once, used on every square it stands on
+    svg.push_str("<defs>\n");
+    for role in Role::ALL {
+        let (body, outline) = (
+            sprite_path(role, SpritePixel::Body),
+            sprite_path(role, SpritePixel::Outline),
+        );
+        for (side, body_color, outline_color) in [
+            ('w', [250, 250, 250], [20, 20, 20]),
+            ('b', [30, 30, 30], [200, 200, 200]),
+        ] {
+            svg.push_str(&format!(
+                "<g id=\"{side}{}\"><path fill=\"{}\" d=\"{outline}\"/><path fill=\"{}\" d=\"{body}\"/></g>\n",
+                role.upper_char(),
+                hex(outline_color),
+                hex(body_color),
+            ));
+        }
+    }
+    svg.push_str("</defs>\n");
+
+    let margin = square / 16;
+    let scale = (square - 2 * margin) as f32 / 16.0;
+    for (index, &(bg, piece)) in squares.iter().enumerate() {
+        let (left, top) = ((index % 8) * square, (index / 8) * square);
+        svg.push_str(&format!(
+            "<rect x=\"{left}\" y=\"{top}\" width=\"{square}\" height=\"{square}\" fill=\"{}\"/>\n",
+            hex(rgb(bg))
+        ));
+        if let Some(piece) = piece {
+            svg.push_str(&format!(
+                "<use xlink:href=\"#{}{}\" transform=\"translate({} {}) scale({})\"/>\n",
+                piece.color.fold_wb('w', 'b'),
+                piece.role.upper_char(),
+                left + margin,
+                top + margin,
+                scale
+            ));
+        }
+    }
+
+    for arrow in arrows {
+        let Some(outline) = arrow.outline(square as f32) else {
+            continue;
+        };
+        let points: Vec<String> = outline
+            .iter()
+            .map(|(x, y)| format!("{:.1},{:.1}", x, y))
+            .collect();
+        svg.push_str(&format!(
+            "<polygon points=\"{}\" fill=\"{}\" fill-opacity=\"0.8\"/>\n",
+            points.join(" "),
+            hex(rgb(arrow.color))
+        ));
+    }
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Encode boards as an animated GIF that loops, each shown for its duration
+pub fn board_gif(frames: &[(BoardImage, Duration)]) -> Result<Vec<u8>> {
+    let size = frames.first().map_or(0, |(image, _)| image.size) as u16;
+    let mut data = Vec::new();
+    {
+        let mut encoder =
+            gif::Encoder::new(&mut data, size, size, &[]).context("Failed to start GIF")?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .context("Failed to start GIF")?;
+        for (image, duration) in frames {
+            // The boards have few colors, so they keep them exactly
+            let mut frame = gif::Frame::from_rgb_speed(size, size, &image.pixels, 10);
+            frame.delay = (duration.as_millis() / 10).min(u16::MAX as u128) as u16;
+            encoder
+                .write_frame(&frame)
+                .context("Failed to encode GIF frame")?;
+        }
+    }
+    Ok(data)
+}
+
+/// Image id used for the board with the kitty protocol
+const KITTY_IMAGE_ID: u32 = 7;
+
+/// Kitty graphics escape sequence showing the image over the cells of an area
+pub fn kitty_sequence(image: &BoardImage, area: Rect, fit_height: bool) -> String {
+    let data = base64(&image.pixels);
+    let mut out = kitty_delete_sequence();
+    out.push_str(&format!("\x1b7\x1b[{};{}H", area.y + 1, area.x + 1));
+
+    // Data goes in chunks of at most 4096 bytes
+    let chunks: Vec<&[u8]> = data.as_bytes().chunks(4096).collect();
+    for (i, chunk) in chunks.iter().enumerate() {
+        let more = u8::from(i + 1 < chunks.len());
+        let chunk = std::str::from_utf8(chunk).unwrap_or_default();
+        if i == 0 {
+            // Scale to the area's width or height, keeping the aspect ratio
+            let fit = if fit_height {
+                format!("r={}", area.height)
+            } else {
+                format!("c={}", area.width)
+            };
+            out.push_str(&format!(
+                "\x1b_Ga=T,f=24,s={},v={},i={},{},C=1,q=2,m={};{}\x1b\\",
+                image.size, image.size, KITTY_IMAGE_ID, fit, more, chunk
+            ));
+        } else {
+            out.push_str(&format!("\x1b_Gm={};{}\x1b\\", more, chunk));
+        }
+    }
+    out.push_str("\x1b8");
+    out
+}
+
+/// Kitty graphics escape sequence removing the board image
+pub fn kitty_delete_sequence() -> String {
+    format!("\x1b_Ga=d,d=I,i={},q=2\x1b\\", KITTY_IMAGE_ID)
+}
+
+/// iTerm2 inline image escape sequence showing the image over an area
+pub fn iterm2_sequence(image: &BoardImage, area: Rect) -> Result<String> {
+    let png = image.to_png()?;
+    Ok(format!(
+        "\x1b7\x1b[{};{}H\x1b]1337;File=inline=1;size={};width={};height={};preserveAspectRatio=1:{}\x07\x1b8",
+        area.y + 1,
+        area.x + 1,
+        png.len(),
+        area.width,
+        area.height,
+        base64(&png)
+    ))
+}
+
+/// Sixel escape sequence showing the image at the top left of an area. Sixel
+/// images aren't scaled, so the image should be sized in terminal pixels.
+pub fn sixel_sequence(image: &BoardImage, area: Rect) -> String {
+    let size = image.size as usize;
+    let pixels: Vec<[u8; 3]> = image
+        .pixels
+        .chunks(3)
+        .map(|px| [px[0], px[1], px[2]])
+        .collect();
+
+    // Sixel palettes hold 256 colors; past that, snap to a 6x6x6 cube
+    let mut palette: Vec<[u8; 3]> = Vec::new();
+    let mut indices: HashMap<[u8; 3], usize> = HashMap::new();
+    for px in &pixels {
+        if !indices.contains_key(px) {
+            indices.insert(*px, palette.len());
+            palette.push(*px);
+        }
+    }
+    let pixels = if palette.len() > 256 {
+        let snap = |c: u8| ((c as u32 * 5 + 127) / 255 * 51) as u8;
+        let pixels: Vec<[u8; 3]> = pixels.iter().map(|px| px.map(snap)).collect();
+        palette.clear();
+        indices.clear();
+        for px in &pixels {
+            if !indices.contains_key(px) {
+                indices.insert(*px, palette.len());
+                palette.push(*px);
+            }
+        }
+        pixels
+    } else {
+        pixels
+    };
+
+    let mut out = format!("\x1b7\x1b[{};{}H", area.y + 1, area.x + 1);
+    out.push_str(&format!("\x1bP0;1;0q\"1;1;{};{}", size, size));
+    for (index, color) in palette.iter().enumerate() {
+        // Palette channels are percentages
+        let [r, g, b] = color.map(|c| c as u32 * 100 / 255);
+        out.push_str(&format!("#{};2;{};{};{}", index, r, g, b));
+    }
+
+    // Each band covers six rows; every color in the band is drawn over it
+    // in turn, returning to the band's start with `$`
+    for band in (0..size).step_by(6) {
+        let rows = band..(band + 6).min(size);
+        let mut colors: Vec<usize> = rows
+            .clone()
+            .flat_map(|y| pixels[y * size..(y + 1) * size].iter())
+            .map(|px| indices[px])
+            .collect();
+        colors.sort_unstable();
+        colors.dedup();
+
+        for (i, &color) in colors.iter().enumerate() {
+            if i > 0 {
+                out.push('$');
+            }
+            out.push_str(&format!("#{}", color));
+            let sixels = (0..size).map(|x| {
+                let bits = rows
+                    .clone()
+                    .enumerate()
+                    .filter(|&(_, y)| indices[&pixels[y * size + x]] == color)
+                    .fold(0u8, |bits, (bit, _)| bits | 1 << bit);
+                (b'?' + bits) as char
+            });
+            push_runs(&mut out, sixels);
+        }
+        out.push('-');
+    }
+    out.push_str("\x1b\\\x1b8");
+    out
+}
+
+/// Append sixel characters, run-length encoding repeats as `!<count><char>`
+fn push_runs(out: &mut String, sixels: impl Iterator<Item = char>) {
+    let mut run: Option<(char, usize)> = None;
+    let flush = |out: &mut String, (c, count): (char, usize)| {
+        if count > 3 {
+            out.push_str(&format!("!{}{}", count, c));
+        } else {
+            out.extend(std::iter::repeat_n(c, count));
+        }
+    };
+    for c in sixels {
+        run = match run {
+            Some((prev, count)) if prev == c => Some((c, count + 1)),
+            Some(prev) => {
+                flush(out, prev);
+                Some((c, 1))
+            }
+            None => Some((c, 1)),
+        };
+    }
+    if let Some(run) = run {
+        flush(out, run);
+    }
+}
+
+/// Standard base64 with padding
+fn base64(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::with_capacity(data.len().div_ceil(3) * 4);
+    for chunk in data.chunks(3) {
+        let bytes = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (bytes[0] as u32) << 16 | (bytes[1] as u32) << 8 | bytes[2] as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
@@ -0,0 +1,114 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph, Widget, Wrap},
+};
+
+use super::Palette;
+use crate::engine::format_cp;
+use crate::guess::{GuessPhase, GuessSession};
+
+/// Guess-the-move panel, shown in place of the analysis
+pub struct GuessWidget<'a> {
+    session: &'a GuessSession,
+    palette: Palette,
+}
+
+impl<'a> GuessWidget<'a> {
+    pub fn new(session: &'a GuessSession) -> Self {
+        Self {
+            session,
+            palette: Palette::Standard,
+        }
+    }
+
+    /// Color matches and misses with the given palette
+    pub fn palette(mut self, palette: Palette) -> Self {
+        self.palette = palette;
+        self
+    }
+}
+
+impl Widget for GuessWidget<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let session = self.session;
+        let side = if session.side == shakmaty::Color::White {
+            "White"
+        } else {
+            "Black"
+        };
+        let title = format!(
+            " Guess the move ({}) - {}/{} pts, {}/{} matched ",
+            side,
+            session.points,
+            session.max_points(),
+            session.matched,
+            session.guesses
+        );
+        let block = Block::default().borders(Borders::ALL).title(title);
+
+        let dim = Style::default().fg(Color::DarkGray);
+        let mut lines = Vec::new();
+
+        if let Some(ref last) = session.last {
+            let mut spans = vec![Span::styled("Game: ", dim), Span::raw(last.actual.clone())];
+            if last.matched {
+                spans.push(Span::styled(
+                    format!("  matched! +{}", last.points),
+                    Style::default()
+                        .fg(self.palette.good())
+                        .add_modifier(Modifier::BOLD),
+                ));
+            } else {
+                let (guess_cp, actual_cp) = last.scores.unwrap_or_default();
+                spans.push(Span::styled(format!(" ({})", format_cp(actual_cp)), dim));
+                spans.push(Span::raw(format!(
+                    "  you: {} ({})",
+                    last.guess,
+                    format_cp(guess_cp)
+                )));
+                let color = if last.points > 0 {
+                    Color::Yellow
+                } else {
+                    self.palette.bad()
+                };
+                spans.push(Span::styled(
+                    format!("  +{}", last.points),
+                    Style::default().fg(color).add_modifier(Modifier::BOLD),
+                ));
+            }
+            lines.push(Line::from(spans));
+        }
+        if let Some(ref reply) = session.reply {
+            lines.push(Line::from(vec![
+                Span::styled("Reply: ", dim),
+                Span::raw(reply.clone()),
+            ]));
+        }
+        if !lines.is_empty() {
+            lines.push(Line::from(""));
+        }
+
+        match &session.phase {
+            GuessPhase::Asking => {
+                lines.push(Line::from(format!(
+                    "{} to move. Type your guess for the game move",
+                    side
+                )));
+            }
+            GuessPhase::Checking { guess, .. } => {
+                lines.push(Line::styled(
+                    format!("Scoring {}...", guess.1),
+                    Style::default().fg(Color::Yellow),
+                ));
+            }
+        }
+
+        Paragraph::new(lines)
+            .block(block)
+            .wrap(Wrap { trim: true })
+            .render(area, buf);
+    }
+}
@@ -3,9 +3,15 @@ use ratatui::{
     layout::{Alignment, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Clear, Paragraph, Widget},
+    widgets::{Block, Borders, Clear, Paragraph, Widget, Wrap},
 };
 
+use crate::config::EngineConfig;
+use crate::engine::StaticEval;
+use crate::files::PgnFile;
+use crate::library::LIBRARY;
+use crate::review::{GameSummary, PlayerSummary};
+
 /// Help popup widget
 pub struct HelpPopup;
 
@@ -48,6 +54,14 @@ impl Widget for HelpPopup {
                     ("→, l", "Next move"),
                     ("Home", "Go to start"),
                     ("End", "Go to latest position"),
+                    ("], [", "Next / previous critical moment"),
+                    ("}, {", "Next / previous capture"),
+                    ("), (", "Next / previous check"),
+                    (">, <", "Next / previous pawn break"),
+                    (
+                        "Space, :autoplay [secs]",
+                        "Play through the game (Space, ,/., Esc)",
+                    ),
                 ],
             ),
             (
@@ -55,27 +69,189 @@ impl Widget for HelpPopup {
                 vec![
                     ("p", "Pause/resume analysis"),
                     ("d", "Change search depth"),
+                    (
+                        "+, :deepen [N]",
+                        "Search a finished analysis 5 (or N) plies deeper",
+                    ),
                     ("m", "Change MultiPV (number of lines)"),
                     ("1-9", "Play move from analysis line N"),
+                    ("Up/Down, j/k", "Select an analysis line"),
+                    ("Enter", "Play the selected line's move (Esc clears)"),
+                    ("o", "Expand the selected line's full PV"),
+                    ("C", "Copy the selected line"),
+                    ("t, :threat", "Show the opponent's threat (null move)"),
+                    (
+                        ":tablebase [on|off]",
+                        "Look up endgames in the online tablebase",
+                    ),
+                    (":cloud [on|off]", "Show chessdb.cn cloud evaluations"),
+                    (":pass", "Null move for analysis (again to undo)"),
+                    ("H, :heatmap", "Color squares by each move's evaluation"),
+                    (
+                        "v, :static-eval",
+                        "Show the engine's static evaluation breakdown",
+                    ),
+                    (
+                        ":candidates <moves>",
+                        "Analyse only these moves (clear to reset)",
+                    ),
+                    ("E, :engine [path]", "Switch to another UCI engine"),
+                    (
+                        ":compare <path>",
+                        "Analyse with a second engine side by side",
+                    ),
+                    (":compare stop", "Stop the comparison engine"),
+                    ("U, :uci-log", "Show raw UCI traffic with the engine"),
+                    (
+                        ":review [depth]",
+                        "Analyse the whole game in the background",
+                    ),
+                    (":review stop", "Stop the background review"),
+                    (":summary", "Show accuracy and ACPL of a reviewed game"),
+                    (":clear-hash", "Clear the engine's hash table"),
+                    ("D, :double-hash", "Double the engine's hash size"),
+                ],
+            ),
+            (
+                "Training",
+                vec![
+                    (
+                        ":quiz [white|black]",
+                        "Find better moves for the reviewed game's blunders",
+                    ),
+                    (":quiz skip, :quiz stop", "Reveal the answer / end the quiz"),
+                    (
+                        ":guess [white|black]",
+                        "Guess the loaded game's moves from here on",
+                    ),
+                    (":guess stop", "End guess the move"),
+                    (
+                        ":puzzle [theme] [min-max]",
+                        "Solve Lichess database puzzles",
+                    ),
+                    (
+                        ":puzzle rush [3|5] [theme]",
+                        "Timed puzzle rush, three strikes",
+                    ),
+                    (":puzzle file <path>", "Set the puzzle database CSV"),
+                    (":puzzle skip, :puzzle stop", "Give up / end puzzles"),
+                    (":mates [pattern]", "Mate-in-N exercises by pattern"),
+                    (
+                        ":mates skip, :mates stop",
+                        "Show a solution / end exercises",
+                    ),
+                    (
+                        ":drill [name]",
+                        "Endgame drill vs the engine (lists drills)",
+                    ),
+                    (":drill retry, :drill stop", "Restart / end the drill"),
+                    (
+                        ":repertoire white|black <path>",
+                        "Load an opening repertoire PGN",
+                    ),
+                    (":repertoire", "Show where the game left the repertoire"),
+                    (":repertoire jump", "Go where the game left the repertoire"),
+                    ("n", "Next quiz position / puzzle / exercise once answered"),
                 ],
             ),
             (
                 "Import/Export",
                 vec![
                     ("i", "Import FEN or PGN"),
+                    ("L, :library", "Load a position from the built-in library"),
                     (":fen <FEN>", "Load position from FEN"),
+                    (":reset", "Start a new game"),
                     (":pgn", "Enter PGN input mode"),
+                    (":open <path>", "Load a PGN or FEN file (Tab completes)"),
+                    (
+                        "Ctrl+O, :browse [dir]",
+                        "Open a PGN file from the games directory",
+                    ),
+                    (":game [n|next|prev]", "Switch games of a multi-game PGN"),
+                    (
+                        "X, :explorer [games|masters]",
+                        "Opening explorer: loaded games or masters",
+                    ),
+                    ("W, :lichess [pgn]", "Open position (or game) on Lichess"),
                     ("y", "Copy current FEN to clipboard"),
+                    ("Y", "Copy game PGN to clipboard"),
+                    (":diagram [ascii]", "Copy a text diagram of the board"),
+                    (":export <path>", "Save game as PGN"),
+                    (":export-image <path>", "Save the board as PNG or SVG"),
+                    (
+                        ":export-gif <path> [12-20]",
+                        "Save the game as an animated GIF",
+                    ),
+                    ("G, :headers", "Edit the game's PGN headers"),
                 ],
             ),
             (
                 "Display",
                 vec![
-                    ("f", "Flip board"),
+                    ("f, :flip", "Flip board"),
                     ("e", "Toggle eval-only analysis"),
+                    ("x", "Toggle hanging piece overlay"),
+                    ("w", "Toggle White / side-to-move score perspective"),
+                    ("P", "Cycle piece style (Nerd Font, Unicode, ASCII, blocks)"),
+                    ("M", "Toggle the move list in the compact layout"),
+                    ("z", "Toggle zen mode (board only)"),
+                    (
+                        ":layout [stacked|side]",
+                        "Analysis under or beside the board",
+                    ),
+                    ("V", "Cycle through layouts and layout presets"),
+                    (
+                        ":screen-reader [on|off]",
+                        "Announce moves in words, letter pieces",
+                    ),
+                    (":board", "Read the board rank by rank"),
+                    (
+                        "T / :mouse [on|off]",
+                        "Toggle mouse capture (off to select text)",
+                    ),
+                    ("F / :show-fen [on|off]", "Show the FEN above the input bar"),
+                    ("A", "Moves: mainline only / full annotations"),
+                    (
+                        ":show-variations|comments|nags",
+                        "Show or hide one kind in the Moves panel",
+                    ),
+                    (
+                        ":script [path|reload|off]",
+                        "Load, reload or unload the user script",
+                    ),
+                    (":state-dir", "Show where records and logs are kept"),
+                    (
+                        ":set[!] <key> [value]",
+                        "Show or change a setting (! saves it now)",
+                    ),
                     ("?", "Toggle this help"),
                 ],
             ),
+            (
+                "Play",
+                vec![
+                    (
+                        ":play [side] [tc]",
+                        "Play the engine (e.g. :play black 5+3)",
+                    ),
+                    (":play stop", "Return to analysis"),
+                    (":resign", "Resign the current game"),
+                    (":claim", "Claim a draw by repetition or 50 moves"),
+                    (
+                        ":chess960 [n] [play ...]",
+                        "Random or numbered Chess960 start position",
+                    ),
+                    ("S, :strength", "Set engine skill level / Elo for play"),
+                ],
+            ),
+            (
+                "Annotations",
+                vec![
+                    (":arrow e2e4 [c]", "Toggle arrow (green/red/yellow/blue)"),
+                    (":circle e4 [c]", "Toggle circled square"),
+                    (":clear-marks", "Remove marks from this position"),
+                ],
+            ),
             (
                 "General",
                 vec![
@@ -99,7 +275,11 @@ impl Widget for HelpPopup {
 
             for (key, desc) in shortcuts {
                 lines.push(Line::from(vec![
-                    Span::styled(format!("  {:12}", key), Style::default().fg(Color::Yellow)),
+                    // Longer keys still get a space before the description
+                    Span::styled(
+                        format!("  {:18}", format!("{} ", key)),
+                        Style::default().fg(Color::Yellow),
+                    ),
                     Span::styled(desc, Style::default().fg(Color::White)),
                 ]));
             }
@@ -223,6 +403,588 @@ impl Widget for MultiPVPopup {
     }
 }
 
+/// Play strength input popup
+pub struct StrengthPopup {
+    current: String,
+    input: String,
+}
+
+impl StrengthPopup {
+    pub fn new(config: &EngineConfig, input: &str) -> Self {
+        Self {
+            current: config.strength_label(),
+            input: input.to_string(),
+        }
+    }
+}
+
+impl Widget for StrengthPopup {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        Clear.render(area, buf);
+
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(" Play Strength ")
+            .title_alignment(Alignment::Center)
+            .style(Style::default().bg(Color::DarkGray));
+
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let lines = vec![
+            Line::from(vec![
+                Span::styled("Current: ", Style::default().fg(Color::DarkGray)),
+                Span::styled(self.current, Style::default().fg(Color::White)),
+            ]),
+            Line::from(""),
+            Line::from(vec![
+                Span::styled("Skill (0-20) or Elo: ", Style::default().fg(Color::Cyan)),
+                Span::styled(&self.input, Style::default().fg(Color::White)),
+                Span::styled("_", Style::default().fg(Color::White).bg(Color::White)),
+            ]),
+            Line::from(""),
+            Line::from(Span::styled(
+                "Empty for full strength, Esc to cancel",
+                Style::default().fg(Color::DarkGray),
+            )),
+        ];
+
+        let paragraph = Paragraph::new(lines);
+        paragraph.render(inner, buf);
+    }
+}
+
+/// Engine picker popup
+pub struct EnginePopup<'a> {
+    candidates: &'a [String],
+    selected: usize,
+}
+
+impl<'a> EnginePopup<'a> {
+    pub fn new(candidates: &'a [String], selected: usize) -> Self {
+        Self {
+            candidates,
+            selected,
+        }
+    }
+}
+
+impl Widget for EnginePopup<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        Clear.render(area, buf);
+
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(" Engine ")
+            .title_alignment(Alignment::Center)
+            .style(Style::default().bg(Color::DarkGray));
+
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let mut lines: Vec<Line> = self
+            .candidates
+            .iter()
+            .enumerate()
+            .map(|(i, path)| {
+                if i == self.selected {
+                    Line::from(Span::styled(
+                        format!("> {}", path),
+                        Style::default()
+                            .fg(Color::Yellow)
+                            .add_modifier(Modifier::BOLD),
+                    ))
+                } else {
+                    Line::from(Span::styled(
+                        format!("  {}", path),
+                        Style::default().fg(Color::White),
+                    ))
+                }
+            })
+            .collect();
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(
+            "Enter to switch, Esc to cancel",
+            Style::default().fg(Color::DarkGray),
+        )));
+
+        let paragraph = Paragraph::new(lines);
+        paragraph.render(inner, buf);
+    }
+}
+
+/// Game browser popup: the PGN files of the games directory, each with
+/// the players and result of its first game
+pub struct GamesPopup<'a> {
+    pgn_files: &'a [PgnFile],
+    selected: usize,
+}
+
+impl<'a> GamesPopup<'a> {
+    pub fn new(pgn_files: &'a [PgnFile], selected: usize) -> Self {
+        Self {
+            pgn_files,
+            selected,
+        }
+    }
+}
+
+impl Widget for GamesPopup<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        Clear.render(area, buf);
+
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(format!(" Games ({}) ", self.pgn_files.len()))
+            .title_alignment(Alignment::Center)
+            .style(Style::default().bg(Color::DarkGray));
+
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let footer = vec![
+            Line::from(""),
+            Line::from(Span::styled(
+                "Enter to open, Esc to cancel",
+                Style::default().fg(Color::DarkGray),
+            )),
+        ];
+        let visible = (inner.height as usize).saturating_sub(footer.len());
+        let scroll = (self.selected + 1).saturating_sub(visible);
+
+        let mut lines: Vec<Line> = self
+            .pgn_files
+            .iter()
+            .enumerate()
+            .skip(scroll)
+            .take(visible)
+            .map(|(i, file)| {
+                let name = if i == self.selected {
+                    Span::styled(
+                        format!("> {}", file.name),
+                        Style::default()
+                            .fg(Color::Yellow)
+                            .add_modifier(Modifier::BOLD),
+                    )
+                } else {
+                    Span::styled(
+                        format!("  {}", file.name),
+                        Style::default().fg(Color::White),
+                    )
+                };
+                Line::from(vec![
+                    name,
+                    Span::styled(
+                        format!("  {}", file.preview),
+                        Style::default().fg(Color::Green),
+                    ),
+                ])
+            })
+            .collect();
+        lines.extend(footer);
+
+        Paragraph::new(lines).render(inner, buf);
+    }
+}
+
+/// PGN header editor popup
+pub struct HeadersPopup<'a> {
+    rows: &'a [(String, String)],
+    selected: usize,
+    /// Text being typed for the selected row
+    input: Option<&'a str>,
+}
+
+impl<'a> HeadersPopup<'a> {
+    pub fn new(rows: &'a [(String, String)], selected: usize, input: Option<&'a str>) -> Self {
+        Self {
+            rows,
+            selected,
+            input,
+        }
+    }
+}
+
+impl Widget for HeadersPopup<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        Clear.render(area, buf);
+
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(" Game headers ")
+            .title_alignment(Alignment::Center)
+            .style(Style::default().bg(Color::DarkGray));
+
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let width = self.rows.iter().map(|(k, _)| k.len()).max().unwrap_or(0);
+        let new_tag = (String::new(), String::new());
+        let mut lines: Vec<Line> = Vec::new();
+        for (i, (name, value)) in self.rows.iter().chain([&new_tag]).enumerate() {
+            let selected = i == self.selected;
+            let name = if i == self.rows.len() {
+                "+ new tag".to_string()
+            } else {
+                format!("{:width$}", name)
+            };
+            let marker = if selected { "> " } else { "  " };
+            let name_style = if selected {
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(Color::Cyan)
+            };
+            let mut spans = vec![Span::styled(format!("{}{}  ", marker, name), name_style)];
+            match self.input.filter(|_| selected) {
+                Some(input) => {
+                    spans.push(Span::styled(input, Style::default().fg(Color::White)));
+                    spans.push(Span::styled(
+                        "_",
+                        Style::default().fg(Color::White).bg(Color::White),
+                    ));
+                }
+                None => spans.push(Span::styled(value, Style::default().fg(Color::White))),
+            }
+            lines.push(Line::from(spans));
+        }
+
+        let hint = match self.input {
+            Some(_) if self.selected == self.rows.len() => {
+                "Type the name and value, Enter to add, Esc to cancel"
+            }
+            Some(_) => "Enter to save (empty removes the tag), Esc to cancel",
+            None => "Enter to edit, d to remove, Esc to close",
+        };
+        let footer = vec![
+            Line::from(""),
+            Line::from(Span::styled(hint, Style::default().fg(Color::DarkGray))),
+        ];
+        let visible = (inner.height as usize).saturating_sub(footer.len());
+        let scroll = (self.selected + 1).saturating_sub(visible);
+        let mut lines: Vec<Line> = lines.into_iter().skip(scroll).take(visible).collect();
+        lines.extend(footer);
+
+        Paragraph::new(lines).render(inner, buf);
+    }
+}
+
+/// Position library popup
+pub struct LibraryPopup {
+    selected: usize,
+}
+
+impl LibraryPopup {
+    pub fn new(selected: usize) -> Self {
+        Self { selected }
+    }
+}
+
+impl Widget for LibraryPopup {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        Clear.render(area, buf);
+
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(" Position library ")
+            .title_alignment(Alignment::Center)
+            .style(Style::default().bg(Color::DarkGray));
+
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let mut lines: Vec<Line> = Vec::new();
+        let mut selected_line = 0;
+        let mut category = "";
+        for (i, entry) in LIBRARY.iter().enumerate() {
+            if entry.category != category {
+                category = entry.category;
+                if !lines.is_empty() {
+                    lines.push(Line::from(""));
+                }
+                lines.push(Line::from(Span::styled(
+                    category,
+                    Style::default()
+                        .fg(Color::Cyan)
+                        .add_modifier(Modifier::BOLD),
+                )));
+            }
+            if i == self.selected {
+                selected_line = lines.len();
+                lines.push(Line::from(Span::styled(
+                    format!("> {}", entry.name),
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD),
+                )));
+            } else {
+                lines.push(Line::from(Span::styled(
+                    format!("  {}", entry.name),
+                    Style::default().fg(Color::White),
+                )));
+            }
+        }
+
+        // The selected entry's note and the keys stay at the bottom
+        let footer = vec![
+            Line::from(""),
+            Line::from(Span::styled(
+                LIBRARY.get(self.selected).map_or("", |e| e.note),
+                Style::default().fg(Color::Green),
+            )),
+            Line::from(Span::styled(
+                "Enter to load, Esc to cancel",
+                Style::default().fg(Color::DarkGray),
+            )),
+        ];
+        let visible = (inner.height as usize).saturating_sub(footer.len());
+        let scroll = (selected_line + 1).saturating_sub(visible);
+        let mut lines: Vec<Line> = lines.into_iter().skip(scroll).take(visible).collect();
+        lines.extend(footer);
+
+        Paragraph::new(lines).render(inner, buf);
+    }
+}
+
+/// Static evaluation breakdown popup
+pub struct StaticEvalPopup<'a> {
+    eval: Option<&'a StaticEval>,
+    /// Number of lines scrolled down from the top
+    scroll: usize,
+}
+
+impl<'a> StaticEvalPopup<'a> {
+    pub fn new(eval: Option<&'a StaticEval>, scroll: usize) -> Self {
+        Self { eval, scroll }
+    }
+}
+
+impl Widget for StaticEvalPopup<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        Clear.render(area, buf);
+
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(" Static Evaluation ")
+            .title_bottom(Line::from(" ↑/↓ scroll, Esc close ").centered())
+            .title_alignment(Alignment::Center)
+            .style(Style::default().bg(Color::DarkGray));
+
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let eval = match self.eval {
+            Some(eval) if !eval.lines.is_empty() || !eval.summary.is_empty() => eval,
+            _ => {
+                Paragraph::new(Span::styled(
+                    "Waiting for the engine...",
+                    Style::default().fg(Color::Gray),
+                ))
+                .render(inner, buf);
+                return;
+            }
+        };
+
+        let mut lines: Vec<Line> = eval
+            .summary
+            .iter()
+            .map(|s| {
+                Line::from(Span::styled(
+                    s.as_str(),
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD),
+                ))
+            })
+            .collect();
+        lines.push(Line::from(""));
+
+        if eval.terms.is_empty() {
+            // No classical term table (NNUE-only engines), show the trace as printed
+            lines.extend(
+                eval.lines.iter().map(|l| {
+                    Line::from(Span::styled(l.as_str(), Style::default().fg(Color::White)))
+                }),
+            );
+        } else {
+            lines.push(Line::from(Span::styled(
+                format!(
+                    "{:>14} {:>13} {:>13} {:>13}",
+                    "Term", "White", "Black", "Total"
+                ),
+                Style::default().fg(Color::Cyan),
+            )));
+            for term in &eval.terms {
+                let style = if term.name == "Total" {
+                    Style::default()
+                        .fg(Color::White)
+                        .add_modifier(Modifier::BOLD)
+                } else {
+                    Style::default().fg(Color::White)
+                };
+                lines.push(Line::from(vec![
+                    Span::styled(
+                        format!("{:>14} ", term.name),
+                        Style::default().fg(Color::Cyan),
+                    ),
+                    Span::styled(
+                        format!("{:>13} {:>13} {:>13}", term.white, term.black, term.total),
+                        style,
+                    ),
+                ]));
+            }
+        }
+
+        let scroll = self.scroll.min(lines.len().saturating_sub(1));
+        Paragraph::new(lines)
+            .scroll((scroll as u16, 0))
+            .render(inner, buf);
+    }
+}
+
+/// Accuracy and centipawn loss summary after a game review
+pub struct SummaryPopup<'a> {
+    summary: &'a GameSummary,
+    white: &'a str,
+    black: &'a str,
+    /// Moves played during analysis that lost eval, with the best move
+    deltas: &'a [String],
+}
+
+impl<'a> SummaryPopup<'a> {
+    pub fn new(summary: &'a GameSummary, white: &'a str, black: &'a str) -> Self {
+        Self {
+            summary,
+            white,
+            black,
+            deltas: &[],
+        }
+    }
+
+    pub fn deltas(mut self, deltas: &'a [String]) -> Self {
+        self.deltas = deltas;
+        self
+    }
+}
+
+impl Widget for SummaryPopup<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        Clear.render(area, buf);
+
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(" Game Summary ")
+            .title_alignment(Alignment::Center)
+            .style(Style::default().bg(Color::DarkGray));
+
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let label = |text: &'static str| Span::styled(text, Style::default().fg(Color::Gray));
+        let row = |name: &'static str, value: fn(&PlayerSummary) -> String| {
+            Line::from(vec![
+                label(name),
+                Span::styled(
+                    format!("{:>12}", value(&self.summary.white)),
+                    Style::default().fg(Color::White),
+                ),
+                Span::styled(
+                    format!("{:>12}", value(&self.summary.black)),
+                    Style::default().fg(Color::White),
+                ),
+            ])
+        };
+
+        let mut lines = vec![
+            Line::from(vec![
+                Span::raw(format!("{:14}", "")),
+                Span::styled(
+                    format!("{:>12.12}", self.white),
+                    Style::default()
+                        .fg(Color::Cyan)
+                        .add_modifier(Modifier::BOLD),
+                ),
+                Span::styled(
+                    format!("{:>12.12}", self.black),
+                    Style::default()
+                        .fg(Color::Cyan)
+                        .add_modifier(Modifier::BOLD),
+                ),
+            ]),
+            Line::from(""),
+            row("Accuracy      ", |p| format!("{:.1}%", p.accuracy)),
+            row("Avg. cp loss  ", |p| p.acpl.to_string()),
+            row("Inaccuracies  ", |p| p.inaccuracies.to_string()),
+            row("Mistakes      ", |p| p.mistakes.to_string()),
+            row("Blunders      ", |p| p.blunders.to_string()),
+            row("Est. rating   ", |p| {
+                p.estimated_elo()
+                    .map(|elo| format!("~{}", elo))
+                    .unwrap_or_else(|| "-".to_string())
+            }),
+            Line::from(""),
+        ];
+        if !self.deltas.is_empty() {
+            lines.push(Line::from(label("Played during analysis")));
+            for delta in self.deltas {
+                lines.push(Line::from(Span::styled(
+                    format!("  {}", delta),
+                    Style::default().fg(Color::White),
+                )));
+            }
+            lines.push(Line::from(""));
+        }
+        lines.push(Line::from(Span::styled(
+            "Esc to close",
+            Style::default().fg(Color::DarkGray),
+        )));
+
+        Paragraph::new(lines).render(inner, buf);
+    }
+}
+
+/// The board read out rank by rank, for screen readers
+pub struct BoardTextPopup<'a> {
+    lines: &'a [String],
+}
+
+impl<'a> BoardTextPopup<'a> {
+    pub fn new(lines: &'a [String]) -> Self {
+        Self { lines }
+    }
+}
+
+impl Widget for BoardTextPopup<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        Clear.render(area, buf);
+
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(" Board ")
+            .title_alignment(Alignment::Center)
+            .style(Style::default().bg(Color::DarkGray));
+
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let mut lines: Vec<Line> = self
+            .lines
+            .iter()
+            .map(|l| Line::from(Span::styled(l.as_str(), Style::default().fg(Color::White))))
+            .collect();
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(
+            "Esc to close",
+            Style::default().fg(Color::Gray),
+        )));
+
+        Paragraph::new(lines)
+            .wrap(Wrap { trim: false })
+            .render(inner, buf);
+    }
+}
+
 /// Import popup for FEN/PGN selection
 pub struct ImportPopup;
 
@@ -100,6 +100,12 @@ impl InputState {
         self.clear_messages();
     }
 
+    /// Replace the buffer content, with the cursor at its end
+    pub fn set_buffer(&mut self, text: String) {
+        self.cursor = text.len();
+        self.buffer = text;
+    }
+
     /// Take the current buffer content
     pub fn take(&mut self) -> String {
         self.cursor = 0;
@@ -108,7 +114,9 @@ impl InputState {
 
     /// Set error message
     pub fn set_error(&mut self, msg: impl Into<String>) {
-        self.error = Some(msg.into());
+        let msg = msg.into();
+        tracing::info!("{}", msg);
+        self.error = Some(msg);
         self.message = None;
     }
 
@@ -0,0 +1,160 @@
+use anyhow::{bail, Context, Result};
+use ratatui::{
+    buffer::Buffer,
+    layout::{Alignment, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Paragraph, Widget, Wrap},
+};
+
+/// Arrangement of the panels around the board
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScreenLayout {
+    /// Board over the analysis, with the move list on the right
+    Stacked,
+    /// Board on the left, with the analysis over the move list in a tall
+    /// column on the right (for wide terminals)
+    Side,
+}
+
+impl ScreenLayout {
+    /// Parse a layout name from the config or a command
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "stacked" | "vertical" => Some(Self::Stacked),
+            "side" | "horizontal" => Some(Self::Side),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Stacked => "stacked",
+            Self::Side => "side",
+        }
+    }
+}
+
+/// A panel shown next to the board
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Panel {
+    /// Engine analysis (or the training panel)
+    Analysis,
+    Moves,
+    /// Eval graph, once the game has evaluations
+    Graph,
+}
+
+impl Panel {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "analysis" => Some(Self::Analysis),
+            "moves" => Some(Self::Moves),
+            "graph" => Some(Self::Graph),
+            _ => None,
+        }
+    }
+}
+
+/// Which panels are shown, in which order and at what size
+#[derive(Debug, Clone, PartialEq)]
+pub struct PanelLayout {
+    pub arrangement: ScreenLayout,
+    /// Panels in order, with a fixed size in rows (columns for the move list
+    /// in the stacked arrangement) or None for the default
+    pub panels: Vec<(Panel, Option<u16>)>,
+}
+
+impl PanelLayout {
+    /// One of the built-in layouts, with every panel
+    pub fn builtin(arrangement: ScreenLayout) -> Self {
+        let panels = match arrangement {
+            ScreenLayout::Stacked => [Panel::Analysis, Panel::Graph, Panel::Moves],
+            ScreenLayout::Side => [Panel::Analysis, Panel::Moves, Panel::Graph],
+        };
+        Self {
+            arrangement,
+            panels: panels.into_iter().map(|p| (p, None)).collect(),
+        }
+    }
+
+    /// Read a layout preset from the config: an arrangement name and panels
+    /// like "analysis" or "moves:30"
+    pub fn parse(arrangement: &str, panels: &[String]) -> Result<Self> {
+        let arrangement = ScreenLayout::from_name(arrangement)
+            .with_context(|| format!("Unknown arrangement {}", arrangement))?;
+        let mut parsed = Vec::new();
+        for spec in panels {
+            let (name, size) = match spec.split_once(':') {
+                Some((name, size)) => {
+                    let size = size
+                        .trim()
+                        .parse::<u16>()
+                        .with_context(|| format!("Bad panel size in {}", spec))?;
+                    (name.trim(), Some(size))
+                }
+                None => (spec.trim(), None),
+            };
+            let Some(panel) = Panel::from_name(name) else {
+                bail!("Unknown panel {} (analysis, moves or graph)", name);
+            };
+            parsed.push((panel, size));
+        }
+        Ok(Self {
+            arrangement,
+            panels: parsed,
+        })
+    }
+
+    /// Size setting of a panel: None when it's hidden
+    pub fn panel(&self, panel: Panel) -> Option<Option<u16>> {
+        self.panels
+            .iter()
+            .find(|(p, _)| *p == panel)
+            .map(|(_, size)| *size)
+    }
+}
+
+/// Shown instead of the screen when the terminal is too small for it
+pub struct TooSmallWidget {
+    min_width: u16,
+    min_height: u16,
+}
+
+impl TooSmallWidget {
+    pub fn new(min_width: u16, min_height: u16) -> Self {
+        Self {
+            min_width,
+            min_height,
+        }
+    }
+}
+
+impl Widget for TooSmallWidget {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let lines = vec![
+            Line::from(Span::styled(
+                "Terminal too small",
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            )),
+            Line::from(format!(
+                "{}x{}, need {}x{}",
+                area.width, area.height, self.min_width, self.min_height
+            )),
+        ];
+        // Vertically centered
+        let height = (lines.len() as u16).min(area.height);
+        let area = Rect::new(
+            area.x,
+            area.y + (area.height - height) / 2,
+            area.width,
+            height,
+        );
+        Paragraph::new(lines)
+            .alignment(Alignment::Center)
+            .wrap(Wrap { trim: true })
+            .render(area, buf);
+    }
+}
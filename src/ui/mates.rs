@@ -0,0 +1,108 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph, Widget, Wrap},
+};
+
+use super::Palette;
+use crate::mates::{pattern_name, MatePhase, MateSession};
+
+/// Checkmate pattern panel, shown in place of the analysis
+pub struct MateWidget<'a> {
+    session: &'a MateSession,
+    palette: Palette,
+}
+
+impl<'a> MateWidget<'a> {
+    pub fn new(session: &'a MateSession) -> Self {
+        Self {
+            session,
+            palette: Palette::Standard,
+        }
+    }
+
+    /// Color found and missed moves with the given palette
+    pub fn palette(mut self, palette: Palette) -> Self {
+        self.palette = palette;
+        self
+    }
+}
+
+impl Widget for MateWidget<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let session = self.session;
+        let exercise = session.exercise;
+        let title = format!(
+            " {} - mate in {} ",
+            pattern_name(exercise.pattern),
+            exercise.mate_in
+        );
+        let block = Block::default().borders(Borders::ALL).title(title);
+
+        let dim = Style::default().fg(Color::DarkGray);
+        let mut lines = Vec::new();
+
+        match &session.phase {
+            MatePhase::Asking if session.moves_left < exercise.mate_in => {
+                lines.push(Line::styled(
+                    format!("Correct, now mate in {}", session.moves_left),
+                    Style::default().fg(self.palette.good()),
+                ));
+            }
+            MatePhase::Asking => {
+                lines.push(Line::from(format!(
+                    "Find the mate in {}",
+                    session.moves_left
+                )));
+            }
+            MatePhase::Checking { answer, .. } => {
+                let text = match answer {
+                    Some((_, san)) => format!("Checking {}...", san),
+                    None => "Finding the solution...".to_string(),
+                };
+                lines.push(Line::styled(text, Style::default().fg(Color::Yellow)));
+            }
+            MatePhase::Solved => {
+                lines.push(Line::styled(
+                    "Checkmate!",
+                    Style::default()
+                        .fg(self.palette.good())
+                        .add_modifier(Modifier::BOLD),
+                ));
+            }
+            MatePhase::Failed { san, .. } => {
+                lines.push(Line::from(vec![
+                    Span::styled(
+                        "Missed. ",
+                        Style::default()
+                            .fg(self.palette.bad())
+                            .add_modifier(Modifier::BOLD),
+                    ),
+                    Span::raw("The solution was "),
+                    Span::styled(san.clone(), Style::default().fg(self.palette.good())),
+                ]));
+            }
+        }
+        if session.is_done() {
+            lines.push(Line::from(""));
+            lines.push(Line::styled("Press n for the next exercise", dim));
+        }
+
+        lines.push(Line::styled(
+            format!(
+                "Solved {}/{}, {} more to go",
+                session.solved,
+                session.played,
+                session.queue.len()
+            ),
+            dim,
+        ));
+
+        Paragraph::new(lines)
+            .block(block)
+            .wrap(Wrap { trim: true })
+            .render(area, buf);
+    }
+}
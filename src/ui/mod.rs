@@ -1,11 +1,37 @@
 mod analysis;
 mod board;
+mod clock;
+mod color;
+mod drill;
+mod explorer;
+mod graph;
+mod graphics;
+mod guess;
 mod help;
 mod input;
+mod layout;
+mod mates;
 mod moves;
+mod puzzle;
+mod quiz;
+mod replay;
+mod uci_log;
 
 pub use analysis::*;
 pub use board::*;
+pub use clock::*;
+pub use color::*;
+pub use drill::*;
+pub use explorer::*;
+pub use graph::*;
+pub use graphics::*;
+pub use guess::*;
 pub use help::*;
 pub use input::*;
+pub use layout::*;
+pub use mates::*;
 pub use moves::*;
+pub use puzzle::*;
+pub use quiz::*;
+pub use replay::*;
+pub use uci_log::*;
@@ -5,14 +5,28 @@ use ratatui::{
     text::{Line, Span},
     widgets::{Block, Borders, Paragraph, Widget},
 };
+use std::collections::HashSet;
 
-use crate::chess::Game;
+use super::analysis::wrap_words;
+use crate::chess::{format_clock, nag_glyph, Eval, Game};
 
 /// Move history widget
 pub struct MovesWidget<'a> {
     game: &'a Game,
     /// Scroll offset for the move list
     _scroll_offset: usize,
+    /// Leave out moves after the current position
+    hide_future: bool,
+    /// Index of the position whose move is flagged
+    flagged: Option<usize>,
+    /// Show the variations given after moves
+    variations: bool,
+    /// Show comments after moves
+    comments: bool,
+    /// Show NAGs such as `!?` after moves
+    nags: bool,
+    /// Indices of the positions reached by transposing moves
+    transpositions: Option<&'a HashSet<usize>>,
 }
 
 impl<'a> MovesWidget<'a> {
@@ -20,10 +34,123 @@ impl<'a> MovesWidget<'a> {
         Self {
             game,
             _scroll_offset: scroll_offset,
+            hide_future: false,
+            flagged: None,
+            variations: true,
+            comments: true,
+            nags: true,
+            transpositions: None,
         }
     }
+
+    /// Leave out moves after the current position (for guess the move)
+    pub fn hide_future(mut self, hide_future: bool) -> Self {
+        self.hide_future = hide_future;
+        self
+    }
+
+    /// Flag the move played from the position at an index (where the game
+    /// left the repertoire)
+    pub fn flag(mut self, flagged: Option<usize>) -> Self {
+        self.flagged = flagged;
+        self
+    }
+
+    /// Show or hide variations
+    pub fn variations(mut self, variations: bool) -> Self {
+        self.variations = variations;
+        self
+    }
+
+    /// Show or hide comments
+    pub fn comments(mut self, comments: bool) -> Self {
+        self.comments = comments;
+        self
+    }
+
+    /// Show or hide NAGs
+    pub fn nags(mut self, nags: bool) -> Self {
+        self.nags = nags;
+        self
+    }
+
+    /// Mark the moves into positions that another move order reaches too
+    pub fn transpositions(mut self, transpositions: &'a HashSet<usize>) -> Self {
+        self.transpositions = Some(transpositions);
+        self
+    }
+
+    /// Comment and variation lines shown under the move into a position,
+    /// wrapped to a width and indented to the move column
+    fn notes(&self, idx: usize, width: usize) -> Vec<Line<'static>> {
+        let Some(annotation) = self.game.annotations().get(idx) else {
+            return Vec::new();
+        };
+        let width = width.saturating_sub(NOTE_INDENT.len()).max(1);
+        let mut lines = Vec::new();
+        let mut push = |text: &str, color: Color| {
+            for line in wrap_words(text, width) {
+                lines.push(Line::from(Span::styled(
+                    format!("{}{}", NOTE_INDENT, line),
+                    Style::default().fg(color),
+                )));
+            }
+        };
+        if let Some(comment) = annotation.comment.as_ref().filter(|_| self.comments) {
+            push(comment, Color::Green);
+        }
+        if self.variations {
+            for variation in &annotation.variations {
+                push(&self.variation_text(variation), Color::Cyan);
+            }
+        }
+        lines
+    }
+
+    /// A variation in parentheses, without the comments or NAGs that are
+    /// hidden
+    fn variation_text(&self, variation: &str) -> String {
+        let mut text = String::from("(");
+        let mut in_comment = false;
+        for word in variation.split_whitespace() {
+            if word == "{" {
+                in_comment = true;
+            }
+            let shown = if in_comment {
+                self.comments
+            } else {
+                self.nags || !word.starts_with('$')
+            };
+            let word = if in_comment || self.nags {
+                word
+            } else {
+                word.trim_end_matches(['!', '?'])
+            };
+            if word == "}" {
+                in_comment = false;
+            }
+            if !shown {
+                continue;
+            }
+            if !text.ends_with('(') && word != ")" {
+                text.push(' ');
+            }
+            match word.strip_prefix('$').and_then(|n| n.parse().ok()) {
+                Some(nag) if !in_comment => text.push_str(&nag_glyph(nag)),
+                _ => text.push_str(word),
+            }
+        }
+        text.push(')');
+        text
+    }
 }
 
+/// Mark after a move into a position another move order reaches too
+const TRANSPOSITION_MARK: &str = "⇄";
+
+/// Indent of comment and variation lines, to line up with the moves
+const NOTE_INDENT: &str = "     ";
+
 impl Widget for MovesWidget<'_> {
     fn render(self, area: Rect, buf: &mut Buffer) {
         let block = Block::default().borders(Borders::ALL).title(" Moves ");
@@ -37,48 +164,161 @@ impl Widget for MovesWidget<'_> {
 
         let formatted_moves = self.game.formatted_moves();
         let current_idx = self.game.current_index();
+        let annotations = self.game.annotations();
+        let show_clocks = self.game.has_clocks();
+        let show_evals = self.game.has_evals();
 
-        let mut lines: Vec<Line> = Vec::new();
-
-        // Track which move is highlighted
-        let mut move_counter = 0;
+        // Clock time left after the move at the given position index
+        let clock_span = |idx: usize| -> Span {
+            let clock = annotations
+                .get(idx)
+                .and_then(|a| a.clock)
+                .map(format_clock)
+                .unwrap_or_default();
+            Span::styled(
+                format!("{:>7} ", clock),
+                Style::default().fg(Color::DarkGray),
+            )
+        };
 
-        for (move_num, white_move, black_move) in &formatted_moves {
-            let mut spans: Vec<Span> = Vec::new();
+        // Evaluation (White's view) of the position after the move at the index
+        let eval_span = |idx: usize| -> Span {
+            let eval = match annotations.get(idx).and_then(|a| a.eval) {
+                Some(Eval::Cp(cp)) => format!("{:+.2}", cp as f64 / 100.0),
+                Some(Eval::Mate(m)) => format!("#{}", m),
+                None => String::new(),
+            };
+            Span::styled(format!("{:>6} ", eval), Style::default().fg(Color::Gray))
+        };
 
-            // Move number
-            spans.push(Span::styled(
-                format!("{:>3}. ", move_num),
-                Style::default().fg(Color::DarkGray),
-            ));
+        // Style of a move other than the current one, by the index of the
+        // position after it
+        let move_style = |idx: usize| -> Style {
+            if self.flagged.is_some_and(|flagged| flagged + 1 == idx) {
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::UNDERLINED)
+            } else {
+                Style::default().fg(Color::White)
+            }
+        };
 
-            // White's move
-            move_counter += 1;
-            let white_style = if move_counter == current_idx {
+        // A move, with its NAGs, highlighted if it leads to the current
+        // position, and marked when it transposes
+        let move_spans = |idx: usize, san: &str| -> [Span; 2] {
+            let mut text = san.to_string();
+            if self.nags {
+                if let Some(annotation) = annotations.get(idx) {
+                    text.extend(annotation.nags.iter().map(|&nag| nag_glyph(nag)));
+                }
+            }
+            let style = if idx == current_idx {
                 Style::default()
                     .fg(Color::Black)
                     .bg(Color::White)
                     .add_modifier(Modifier::BOLD)
             } else {
-                Style::default().fg(Color::White)
+                move_style(idx)
+            };
+            let transposes = self.transpositions.is_some_and(|t| t.contains(&idx));
+            let marker = if transposes { TRANSPOSITION_MARK } else { "" };
+            let pad = 7usize.saturating_sub(text.chars().count() + marker.chars().count());
+            [
+                Span::styled(text, style),
+                Span::styled(
+                    format!("{}{}", marker, " ".repeat(pad)),
+                    Style::default().fg(Color::Magenta),
+                ),
+            ]
+        };
+
+        // Blank space where White's move, clock and eval would be, for a
+        // Black move continued after notes
+        let mut white_gap = " ".repeat(7);
+        if show_clocks {
+            white_gap.push_str(&" ".repeat(8));
+        }
+        if show_evals {
+            white_gap.push_str(&" ".repeat(7));
+        }
+
+        let width = inner.width as usize;
+        let mut lines: Vec<Line> = Vec::new();
+        // Line of the current move, to keep in view
+        let mut current_line = 0;
+
+        // Track which move is highlighted
+        let mut move_counter = 0;
+
+        let last_shown = if self.hide_future {
+            current_idx
+        } else {
+            usize::MAX
+        };
+
+        for (move_num, white_move, black_move) in &formatted_moves {
+            if move_counter >= last_shown {
+                break;
+            }
+            let number = |dots: &str| {
+                Span::styled(
+                    format!("{:>3}{} ", move_num, dots),
+                    Style::default().fg(Color::DarkGray),
+                )
             };
-            spans.push(Span::styled(format!("{:<7}", white_move), white_style));
+            let mut spans: Vec<Span> = vec![number(".")];
+
+            // White's move
+            move_counter += 1;
+            if move_counter == current_idx {
+                current_line = lines.len();
+            }
+            spans.extend(move_spans(move_counter, white_move));
+            if show_clocks {
+                spans.push(clock_span(move_counter));
+            }
+            if show_evals {
+                spans.push(eval_span(move_counter));
+            }
+
+            // Comments and variations break the line, and Black's move
+            // carries on under them
+            let notes = self.notes(move_counter, width);
+            let black_move = black_move.as_ref().filter(|_| move_counter < last_shown);
+            if !notes.is_empty() {
+                lines.push(Line::from(std::mem::take(&mut spans)));
+                lines.extend(notes);
+                if black_move.is_some() {
+                    spans.push(number("..."));
+                    spans.push(Span::raw(white_gap.clone()));
+                }
+            }
 
             // Black's move (if any)
             if let Some(black) = black_move {
                 move_counter += 1;
-                let black_style = if move_counter == current_idx {
-                    Style::default()
-                        .fg(Color::Black)
-                        .bg(Color::White)
-                        .add_modifier(Modifier::BOLD)
-                } else {
-                    Style::default().fg(Color::White)
-                };
-                spans.push(Span::styled(format!("{:<7}", black), black_style));
+                if move_counter == current_idx {
+                    current_line = lines.len();
+                }
+                spans.extend(move_spans(move_counter, black));
+                if show_clocks {
+                    spans.push(clock_span(move_counter));
+                }
+                if show_evals {
+                    spans.push(eval_span(move_counter));
+                }
+                lines.push(Line::from(spans));
+                lines.extend(self.notes(move_counter, width));
+            } else if !spans.is_empty() {
+                lines.push(Line::from(spans));
             }
+        }
 
-            lines.push(Line::from(spans));
+        // Comment on the starting position
+        let start_notes = self.notes(0, width);
+        if !start_notes.is_empty() {
+            current_line += start_notes.len();
+            lines.splice(0..0, start_notes);
         }
 
         // If no moves yet, show a placeholder
@@ -92,16 +332,6 @@ impl Widget for MovesWidget<'_> {
         // Calculate scroll
         let visible_height = inner.height as usize;
         let total_lines = lines.len();
-
-        // Auto-scroll to keep current position visible
-        let current_line = formatted_moves
-            .iter()
-            .take_while(|(num, _, _)| {
-                let white_idx = (*num - 1) * 2 + 1;
-                white_idx < current_idx
-            })
-            .count();
-
         let scroll = if current_line >= visible_height {
             current_line.saturating_sub(visible_height / 2)
         } else {
@@ -0,0 +1,205 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph, Widget, Wrap},
+};
+
+use super::Palette;
+use crate::chess::format_clock;
+use crate::puzzle::{PuzzleSession, PuzzleState, Rush, RUSH_STRIKES};
+
+/// Puzzle panel, shown in place of the analysis
+pub struct PuzzleWidget<'a> {
+    session: &'a PuzzleSession,
+    palette: Palette,
+}
+
+impl<'a> PuzzleWidget<'a> {
+    pub fn new(session: &'a PuzzleSession) -> Self {
+        Self {
+            session,
+            palette: Palette::Standard,
+        }
+    }
+
+    /// Color solved and failed moves with the given palette
+    pub fn palette(mut self, palette: Palette) -> Self {
+        self.palette = palette;
+        self
+    }
+}
+
+impl Widget for PuzzleWidget<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let session = self.session;
+        let puzzle = &session.puzzle;
+        let title = match session.rush {
+            Some(ref rush) => format!(
+                " Puzzle rush ({} min) - {} solved, best {} ",
+                rush.minutes, rush.solved, rush.best
+            ),
+            None => format!(
+                " Puzzle {} ({}) - your rating {} ",
+                puzzle.id, puzzle.rating, session.rating
+            ),
+        };
+        let block = Block::default().borders(Borders::ALL).title(title);
+
+        let dim = Style::default().fg(Color::DarkGray);
+        let side = if session.side == shakmaty::Color::White {
+            "White"
+        } else {
+            "Black"
+        };
+        let mut lines = Vec::new();
+
+        match &session.state {
+            PuzzleState::Solving => {
+                lines.push(Line::from(format!("Find the best move for {}", side)));
+                if session.step > 1 {
+                    lines.push(Line::styled(
+                        "Correct, keep going",
+                        Style::default().fg(self.palette.good()),
+                    ));
+                }
+            }
+            PuzzleState::Solved => {
+                lines.push(Line::styled(
+                    "Solved!",
+                    Style::default()
+                        .fg(self.palette.good())
+                        .add_modifier(Modifier::BOLD),
+                ));
+            }
+            PuzzleState::Failed { san, .. } => {
+                lines.push(Line::from(vec![
+                    Span::styled(
+                        "Wrong move. ",
+                        Style::default()
+                            .fg(self.palette.bad())
+                            .add_modifier(Modifier::BOLD),
+                    ),
+                    Span::raw("The solution was "),
+                    Span::styled(san.clone(), Style::default().fg(self.palette.good())),
+                ]));
+            }
+        }
+
+        if let Some((before, after)) = session.rating_change {
+            let change = after as i32 - before as i32;
+            let color = if change >= 0 {
+                self.palette.good()
+            } else {
+                self.palette.bad()
+            };
+            lines.push(Line::from(vec![
+                Span::styled("Rating: ", dim),
+                Span::raw(format!("{} -> {} ", before, after)),
+                Span::styled(format!("({:+})", change), Style::default().fg(color)),
+            ]));
+            // Themes would be hints while solving
+            lines.push(Line::from(vec![
+                Span::styled("Themes: ", dim),
+                Span::raw(puzzle.themes.join(", ")),
+            ]));
+            lines.push(Line::from(""));
+            lines.push(Line::styled("Press n for the next puzzle", dim));
+        }
+
+        if let Some(ref rush) = session.rush {
+            if rush.finished {
+                let result = if rush.solved > rush.best {
+                    format!("Rush over: {} solved, a new high score!", rush.solved)
+                } else {
+                    format!("Rush over: {} solved (best {})", rush.solved, rush.best)
+                };
+                lines.push(Line::from(""));
+                lines.push(Line::styled(
+                    result,
+                    Style::default()
+                        .fg(Color::Yellow)
+                        .add_modifier(Modifier::BOLD),
+                ));
+                lines.push(Line::styled(":puzzle rush to go again", dim));
+            }
+            lines.push(Line::styled(
+                format!("Puzzle rating {}", puzzle.rating),
+                dim,
+            ));
+            return Paragraph::new(lines)
+                .block(block)
+                .wrap(Wrap { trim: true })
+                .render(area, buf);
+        }
+
+        lines.push(Line::styled(
+            format!(
+                "Solved {}/{} this session ({})",
+                session.solved,
+                session.played,
+                session.filter.describe()
+            ),
+            dim,
+        ));
+
+        Paragraph::new(lines)
+            .block(block)
+            .wrap(Wrap { trim: true })
+            .render(area, buf);
+    }
+}
+
+/// Puzzle rush countdown, shown in the status bar
+pub struct RushWidget<'a> {
+    rush: &'a Rush,
+    palette: Palette,
+}
+
+impl<'a> RushWidget<'a> {
+    pub fn new(rush: &'a Rush) -> Self {
+        Self {
+            rush,
+            palette: Palette::Standard,
+        }
+    }
+
+    /// Color the score and strikes with the given palette
+    pub fn palette(mut self, palette: Palette) -> Self {
+        self.palette = palette;
+        self
+    }
+}
+
+impl Widget for RushWidget<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let rush = self.rush;
+        let remaining = rush.remaining();
+        let time_style = if rush.finished {
+            Style::default().fg(Color::DarkGray)
+        } else if remaining.as_secs() < 10 {
+            Style::default()
+                .fg(self.palette.bad())
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default().add_modifier(Modifier::BOLD)
+        };
+        let strikes: String = (0..RUSH_STRIKES)
+            .map(|i| if i < rush.strikes { 'x' } else { '.' })
+            .collect();
+
+        let line = Line::from(vec![
+            Span::styled(format!(" {} ", format_clock(remaining)), time_style),
+            Span::styled(
+                format!(" {} solved ", rush.solved),
+                Style::default().fg(self.palette.good()),
+            ),
+            Span::styled(
+                format!(" {} ", strikes),
+                Style::default().fg(self.palette.bad()),
+            ),
+        ]);
+        buf.set_line(area.x, area.y, &line, area.width);
+    }
+}
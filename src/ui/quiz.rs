@@ -0,0 +1,141 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Paragraph, Widget, Wrap},
+};
+
+use super::Palette;
+use crate::chess::Eval;
+use crate::engine::{format_cp, format_score};
+use crate::quiz::{Quiz, QuizPhase};
+
+/// Format an annotation eval (White's view)
+fn format_eval(eval: Eval) -> String {
+    match eval {
+        Eval::Cp(cp) => format_score(Some(cp), None),
+        Eval::Mate(m) => format_score(None, Some(m)),
+    }
+}
+
+/// Blunder quiz panel, shown in place of the analysis
+pub struct QuizWidget<'a> {
+    quiz: &'a Quiz,
+    palette: Palette,
+}
+
+impl<'a> QuizWidget<'a> {
+    pub fn new(quiz: &'a Quiz) -> Self {
+        Self {
+            quiz,
+            palette: Palette::Standard,
+        }
+    }
+
+    /// Color right and wrong answers with the given palette
+    pub fn palette(mut self, palette: Palette) -> Self {
+        self.palette = palette;
+        self
+    }
+}
+
+impl Widget for QuizWidget<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        let quiz = self.quiz;
+        let item = quiz.item();
+        let title = format!(
+            " Blunder quiz {}/{} - score {}/{} ",
+            quiz.current + 1,
+            quiz.items.len(),
+            quiz.correct,
+            quiz.answered
+        );
+        let block = Block::default().borders(Borders::ALL).title(title);
+
+        let dim = Style::default().fg(Color::DarkGray);
+        let side = if item.turn == shakmaty::Color::White {
+            "White"
+        } else {
+            "Black"
+        };
+        let mut lines = vec![
+            Line::from(vec![
+                Span::styled("Played: ", dim),
+                Span::styled(
+                    item.played.clone(),
+                    Style::default()
+                        .fg(self.palette.bad())
+                        .add_modifier(Modifier::BOLD),
+                ),
+                Span::styled(
+                    format!(
+                        " ({} -> {})",
+                        format_eval(item.before),
+                        format_eval(item.after)
+                    ),
+                    dim,
+                ),
+            ]),
+            Line::from(""),
+        ];
+
+        match &quiz.phase {
+            QuizPhase::Asking => {
+                lines.push(Line::from(format!(
+                    "{} to move. Type a better move (:quiz skip to reveal)",
+                    side
+                )));
+            }
+            QuizPhase::Checking { answer, .. } => {
+                let text = match answer {
+                    Some((_, san)) => format!("Checking {}...", san),
+                    None => "Finding the best move...".to_string(),
+                };
+                lines.push(Line::styled(text, Style::default().fg(Color::Yellow)));
+            }
+            QuizPhase::Revealed {
+                answer,
+                best,
+                correct,
+                ..
+            } => {
+                let verdict = match (answer, correct) {
+                    (Some((san, cp)), true) => Span::styled(
+                        format!("Correct! {} ({})", san, format_cp(*cp)),
+                        Style::default()
+                            .fg(self.palette.good())
+                            .add_modifier(Modifier::BOLD),
+                    ),
+                    (Some((san, cp)), false) => Span::styled(
+                        format!("Not quite: {} ({})", san, format_cp(*cp)),
+                        Style::default()
+                            .fg(self.palette.bad())
+                            .add_modifier(Modifier::BOLD),
+                    ),
+                    (None, _) => Span::styled("Skipped", dim),
+                };
+                lines.push(Line::from(verdict));
+                lines.push(Line::from(vec![
+                    Span::styled("Best: ", dim),
+                    Span::styled(
+                        format!("{} ({})", best.0, format_cp(best.1)),
+                        Style::default().fg(self.palette.good()),
+                    ),
+                ]));
+                lines.push(Line::from(""));
+                let next = if quiz.current + 1 < quiz.items.len() {
+                    "Press n for the next position"
+                } else {
+                    "Press n to finish"
+                };
+                lines.push(Line::styled(next, dim));
+            }
+        }
+
+        Paragraph::new(lines)
+            .block(block)
+            .wrap(Wrap { trim: true })
+            .render(area, buf);
+    }
+}
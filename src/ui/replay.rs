@@ -0,0 +1,86 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::{Alignment, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph, Widget},
+};
+
+use super::Palette;
+use crate::review::MoveClass;
+
+/// Note shown over the middle of the board as autoplay plays a reviewed
+/// move: the move, its class and how the eval changed
+pub struct ReplayOverlay<'a> {
+    /// The move with its number, e.g. "14. Nxe5"
+    san: &'a str,
+    class: MoveClass,
+    before: &'a str,
+    after: &'a str,
+    palette: Palette,
+}
+
+impl<'a> ReplayOverlay<'a> {
+    pub fn new(san: &'a str, class: MoveClass, before: &'a str, after: &'a str) -> Self {
+        Self {
+            san,
+            class,
+            before,
+            after,
+            palette: Palette::Standard,
+        }
+    }
+
+    /// Color the move's class with the given palette
+    pub fn palette(mut self, palette: Palette) -> Self {
+        self.palette = palette;
+        self
+    }
+}
+
+impl Widget for ReplayOverlay<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        // The class is spelled out, so mistakes and blunders can share a color
+        let color = match self.class {
+            MoveClass::Good => self.palette.good(),
+            MoveClass::Inaccuracy => Color::Yellow,
+            MoveClass::Mistake | MoveClass::Blunder => self.palette.bad(),
+        };
+        let line = Line::from(vec![
+            Span::styled(
+                format!("{}{} ", self.san, self.class.glyph()),
+                Style::default()
+                    .fg(Color::White)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::styled(
+                self.class.name(),
+                Style::default().fg(color).add_modifier(Modifier::BOLD),
+            ),
+            Span::styled(
+                format!("  {} → {}", self.before, self.after),
+                Style::default().fg(Color::Gray),
+            ),
+        ]);
+
+        // A bordered box just big enough for the line, in the middle
+        let width = (line.width() as u16 + 4).min(area.width);
+        let height = 3.min(area.height);
+        let rect = Rect::new(
+            area.x + (area.width - width) / 2,
+            area.y + (area.height - height) / 2,
+            width,
+            height,
+        );
+        Clear.render(rect, buf);
+        Paragraph::new(line)
+            .alignment(Alignment::Center)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .border_style(Style::default().fg(color))
+                    .style(Style::default().bg(Color::Black)),
+            )
+            .render(rect, buf);
+    }
+}
@@ -0,0 +1,76 @@
+use ratatui::{
+    buffer::Buffer,
+    layout::{Alignment, Rect},
+    style::{Color, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, Clear, Paragraph, Widget},
+};
+
+use crate::engine::{UciDirection, UciLogEntry};
+
+/// Scrollable view of the raw UCI traffic with the engine
+pub struct UciLogWidget<'a> {
+    entries: &'a [UciLogEntry],
+    /// Number of lines scrolled up from the bottom
+    scroll: usize,
+}
+
+impl<'a> UciLogWidget<'a> {
+    pub fn new(entries: &'a [UciLogEntry], scroll: usize) -> Self {
+        Self { entries, scroll }
+    }
+}
+
+impl Widget for UciLogWidget<'_> {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        Clear.render(area, buf);
+
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(" UCI Log ")
+            .title_bottom(Line::from(" ↑/↓ PgUp/PgDn scroll, Esc close ").centered())
+            .title_alignment(Alignment::Center)
+            .style(Style::default().bg(Color::Black));
+
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        if self.entries.is_empty() {
+            Paragraph::new(Span::styled(
+                "No engine traffic yet",
+                Style::default().fg(Color::DarkGray),
+            ))
+            .render(inner, buf);
+            return;
+        }
+
+        // Show the window of lines ending `scroll` lines above the newest
+        let height = inner.height as usize;
+        let end = self
+            .entries
+            .len()
+            .saturating_sub(self.scroll)
+            .max(height.min(self.entries.len()));
+        let start = end.saturating_sub(height);
+
+        let lines: Vec<Line> = self.entries[start..end]
+            .iter()
+            .map(|entry| {
+                let (arrow, color) = match entry.direction {
+                    UciDirection::Sent => (">", Color::Cyan),
+                    UciDirection::Received => ("<", Color::Green),
+                };
+                Line::from(vec![
+                    Span::styled(
+                        format!("{:>9.3} ", entry.elapsed.as_secs_f64()),
+                        Style::default().fg(Color::DarkGray),
+                    ),
+                    Span::styled(format!("{} ", arrow), Style::default().fg(color)),
+                    Span::styled(entry.line.as_str(), Style::default().fg(Color::White)),
+                ])
+            })
+            .collect();
+
+        Paragraph::new(lines).render(inner, buf);
+    }
+}